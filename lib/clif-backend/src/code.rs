@@ -19,19 +19,19 @@ use std::mem;
 use std::sync::{Arc, RwLock};
 use wasmer_runtime_core::error::CompileError;
 use wasmer_runtime_core::{
-    backend::{CacheGen, Token},
+    backend::{CacheGen, CompilerConfig, Token},
     cache::{Artifact, Error as CacheError},
     codegen::*,
     memory::MemoryType,
     module::{ModuleInfo, ModuleInner},
     structures::{Map, TypedIndex},
     types::{
-        FuncIndex, FuncSig, GlobalIndex, LocalFuncIndex, LocalOrImport, MemoryIndex, SigIndex,
-        TableIndex,
+        ElementType, FuncIndex, FuncSig, GlobalIndex, LocalFuncIndex, LocalOrImport, MemoryIndex,
+        SigIndex, TableIndex,
     },
     vm,
 };
-use wasmparser::Type as WpType;
+use wasmparser::{Operator, Type as WpType, TypeOrFuncType};
 
 static BACKEND_ID: &str = "cranelift";
 
@@ -41,6 +41,7 @@ pub struct CraneliftModuleCodeGenerator {
     pub clif_signatures: Map<SigIndex, ir::Signature>,
     function_signatures: Option<Arc<Map<FuncIndex, SigIndex>>>,
     functions: Vec<CraneliftFunctionCodeGenerator>,
+    full_preemption: bool,
 }
 
 impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
@@ -54,6 +55,7 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
             functions: vec![],
             function_signatures: None,
             signatures: None,
+            full_preemption: false,
         }
     }
 
@@ -69,6 +71,11 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
         Ok(())
     }
 
+    fn feed_compiler_config(&mut self, config: &CompilerConfig) -> Result<(), CodegenError> {
+        self.full_preemption = config.full_preemption;
+        Ok(())
+    }
+
     fn next_function(
         &mut self,
         module_info: Arc<RwLock<ModuleInfo>>,
@@ -104,6 +111,7 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
                 clif_signatures: self.clif_signatures.clone(),
             },
             loc,
+            full_preemption: self.full_preemption,
         };
 
         let generate_debug_info = module_info.read().unwrap().generate_debug_info;
@@ -132,6 +140,10 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
 
         declare_wasm_parameters(&mut builder, entry_block);
 
+        if func_env.full_preemption {
+            emit_interrupt_checkpoint(&mut builder, pointer_type(self));
+        }
+
         // Set up the translation state with a single pushed control block representing the whole
         // function and its return values.
         let exit_block = builder.create_block();
@@ -192,6 +204,9 @@ impl ModuleCodeGenerator<CraneliftFunctionCodeGenerator, Caller, CodegenError>
     }
 
     fn feed_signatures(&mut self, signatures: Map<SigIndex, FuncSig>) -> Result<(), CodegenError> {
+        if signatures.iter().any(|(_, sig)| sig.returns().len() > 1) {
+            return Err(multi_value_unsupported());
+        }
         self.signatures = Some(Arc::new(signatures));
         let call_conv = self.isa.frontend_config().default_call_conv;
         for (_sig_idx, func_sig) in self.signatures.as_ref().unwrap().iter() {
@@ -258,6 +273,8 @@ pub struct CraneliftFunctionCodeGenerator {
     func_env: FunctionEnvironment,
     /// Where the function lives in the Wasm module as a span of bytes
     loc: WasmSpan,
+    /// Whether to emit interrupt checkpoints at function and loop headers.
+    full_preemption: bool,
 }
 
 pub struct FunctionEnvironment {
@@ -573,11 +590,16 @@ impl FuncEnvironment for FunctionEnvironment {
             readonly: false,
         });
 
+        let element_size = match description.element {
+            ElementType::Anyfunc => vm::Anyfunc::size() as u64,
+            ElementType::ExternRef => mem::size_of::<u64>() as u64,
+        };
+
         Ok(func.create_table(ir::TableData {
             base_gv: table_base,
             min_size: (description.minimum as u64).into(),
             bound_gv: table_count,
-            element_size: (vm::Anyfunc::size() as u64).into(),
+            element_size: element_size.into(),
             index_type: ir::types::I32,
         }))
     }
@@ -959,139 +981,262 @@ impl FuncEnvironment for FunctionEnvironment {
     }
     fn translate_memory_copy(
         &mut self,
-        _pos: FuncCursor,
-        _clif_mem_index: cranelift_wasm::MemoryIndex,
+        pos: FuncCursor,
+        clif_mem_index: cranelift_wasm::MemoryIndex,
         _heap: ir::Heap,
-        _dst: ir::Value,
-        _src: ir::Value,
-        _len: ir::Value,
+        dst: ir::Value,
+        src: ir::Value,
+        len: ir::Value,
     ) -> cranelift_wasm::WasmResult<()> {
-        unimplemented!("memory.copy not yet implemented");
+        self.call_memory_vmcall(
+            pos,
+            clif_mem_index,
+            call_names::MEMORY_COPY,
+            &[dst, src, len],
+        );
+        Ok(())
     }
 
     fn translate_memory_fill(
         &mut self,
-        _pos: FuncCursor,
-        _clif_mem_index: cranelift_wasm::MemoryIndex,
+        pos: FuncCursor,
+        clif_mem_index: cranelift_wasm::MemoryIndex,
         _heap: ir::Heap,
-        _dst: ir::Value,
-        _val: ir::Value,
-        _len: ir::Value,
+        dst: ir::Value,
+        val: ir::Value,
+        len: ir::Value,
     ) -> cranelift_wasm::WasmResult<()> {
-        unimplemented!("memory.fill not yet implemented");
+        self.call_memory_vmcall(
+            pos,
+            clif_mem_index,
+            call_names::MEMORY_FILL,
+            &[dst, val, len],
+        );
+        Ok(())
     }
 
     fn translate_memory_init(
         &mut self,
-        _pos: FuncCursor,
-        _clif_mem_index: cranelift_wasm::MemoryIndex,
+        mut pos: FuncCursor,
+        clif_mem_index: cranelift_wasm::MemoryIndex,
         _heap: ir::Heap,
-        _seg_index: u32,
-        _dst: ir::Value,
-        _src: ir::Value,
-        _len: ir::Value,
+        seg_index: u32,
+        dst: ir::Value,
+        src: ir::Value,
+        len: ir::Value,
     ) -> cranelift_wasm::WasmResult<()> {
-        unimplemented!("memory.init not yet implemented");
+        let const_seg_index = pos.ins().iconst(ir::types::I32, seg_index as i64);
+        self.call_memory_vmcall(
+            pos,
+            clif_mem_index,
+            call_names::MEMORY_INIT,
+            &[const_seg_index, dst, src, len],
+        );
+        Ok(())
     }
 
     fn translate_data_drop(
         &mut self,
-        _pos: FuncCursor,
-        _seg_index: u32,
+        mut pos: FuncCursor,
+        seg_index: u32,
     ) -> cranelift_wasm::WasmResult<()> {
-        unimplemented!("data.drop not yet implemented");
+        let const_seg_index = pos.ins().iconst(ir::types::I32, seg_index as i64);
+        self.call_vmcall(
+            pos,
+            call_names::LOCAL_NAMESPACE,
+            call_names::DATA_DROP,
+            &[const_seg_index],
+            None,
+        );
+        Ok(())
     }
 
     fn translate_table_size(
         &mut self,
-        _pos: FuncCursor,
-        _index: cranelift_wasm::TableIndex,
+        pos: FuncCursor,
+        clif_table_index: cranelift_wasm::TableIndex,
         _table: ir::Table,
     ) -> cranelift_wasm::WasmResult<ir::Value> {
-        unimplemented!("table.size not yet implemented");
+        let table_index: TableIndex = Converter(clif_table_index).into();
+        Ok(self
+            .call_table_vmcall(
+                pos,
+                table_index,
+                call_names::TABLE_SIZE,
+                &[],
+                Some(ir::types::I32),
+            )
+            .unwrap())
     }
 
     fn translate_table_copy(
         &mut self,
-        _pos: FuncCursor,
-        _dst_table_index: cranelift_wasm::TableIndex,
+        mut pos: FuncCursor,
+        dst_table_index: cranelift_wasm::TableIndex,
         _dst_table: ir::Table,
-        _src_table_index: cranelift_wasm::TableIndex,
+        src_table_index: cranelift_wasm::TableIndex,
         _src_table: ir::Table,
-        _dst: ir::Value,
-        _src: ir::Value,
-        _len: ir::Value,
+        dst: ir::Value,
+        src: ir::Value,
+        len: ir::Value,
     ) -> cranelift_wasm::WasmResult<()> {
-        unimplemented!("table.copy not yet implemented");
+        // Either table may be local or imported, so `table_copy` takes module-wide indices.
+        let const_dst_table_index = pos
+            .ins()
+            .iconst(ir::types::I32, dst_table_index.index() as i64);
+        let const_src_table_index = pos
+            .ins()
+            .iconst(ir::types::I32, src_table_index.index() as i64);
+        self.call_vmcall(
+            pos,
+            call_names::LOCAL_NAMESPACE,
+            call_names::TABLE_COPY,
+            &[const_dst_table_index, const_src_table_index, dst, src, len],
+            None,
+        );
+        Ok(())
     }
 
     fn translate_table_init(
         &mut self,
-        _pos: FuncCursor,
-        _seg_index: u32,
-        _table_index: cranelift_wasm::TableIndex,
+        mut pos: FuncCursor,
+        seg_index: u32,
+        table_index: cranelift_wasm::TableIndex,
         _table: ir::Table,
-        _dst: ir::Value,
-        _src: ir::Value,
-        _len: ir::Value,
+        dst: ir::Value,
+        src: ir::Value,
+        len: ir::Value,
     ) -> cranelift_wasm::WasmResult<()> {
-        unimplemented!("table.init not yet implemented");
+        let const_table_index = pos.ins().iconst(ir::types::I32, table_index.index() as i64);
+        let const_seg_index = pos.ins().iconst(ir::types::I32, seg_index as i64);
+        self.call_vmcall(
+            pos,
+            call_names::LOCAL_NAMESPACE,
+            call_names::TABLE_INIT,
+            &[const_table_index, const_seg_index, dst, src, len],
+            None,
+        );
+        Ok(())
     }
 
     fn translate_elem_drop(
         &mut self,
-        _pos: FuncCursor,
-        _seg_index: u32,
+        mut pos: FuncCursor,
+        seg_index: u32,
     ) -> cranelift_wasm::WasmResult<()> {
-        unimplemented!("elem.drop not yet implemented");
+        let const_seg_index = pos.ins().iconst(ir::types::I32, seg_index as i64);
+        self.call_vmcall(
+            pos,
+            call_names::LOCAL_NAMESPACE,
+            call_names::ELEM_DROP,
+            &[const_seg_index],
+            None,
+        );
+        Ok(())
     }
 
     fn translate_table_grow(
         &mut self,
-        _pos: FuncCursor,
-        _table_index: u32,
-        _delta: ir::Value,
-        _init_value: ir::Value,
+        pos: FuncCursor,
+        table_index: u32,
+        delta: ir::Value,
+        init_value: ir::Value,
     ) -> cranelift_wasm::WasmResult<ir::Value> {
-        unimplemented!("table.grow not yet implemented");
+        Ok(self
+            .call_table_vmcall(
+                pos,
+                TableIndex::new(table_index as usize),
+                call_names::TABLE_GROW,
+                &[delta, init_value],
+                Some(ir::types::I32),
+            )
+            .unwrap())
     }
 
     fn translate_table_get(
         &mut self,
-        _pos: FuncCursor,
-        _table_index: u32,
-        _index: ir::Value,
+        pos: FuncCursor,
+        table_index: u32,
+        index: ir::Value,
     ) -> cranelift_wasm::WasmResult<ir::Value> {
-        unimplemented!("table.get not yet implemented");
+        let reference_type = self.reference_type();
+        Ok(self
+            .call_table_vmcall(
+                pos,
+                TableIndex::new(table_index as usize),
+                call_names::TABLE_GET,
+                &[index],
+                Some(reference_type),
+            )
+            .unwrap())
     }
 
     fn translate_table_set(
         &mut self,
-        _pos: FuncCursor,
-        _table_index: u32,
-        _value: ir::Value,
-        _index: ir::Value,
+        pos: FuncCursor,
+        table_index: u32,
+        value: ir::Value,
+        index: ir::Value,
     ) -> cranelift_wasm::WasmResult<()> {
-        unimplemented!("table.set not yet implemented");
+        self.call_table_vmcall(
+            pos,
+            TableIndex::new(table_index as usize),
+            call_names::TABLE_SET,
+            &[index, value],
+            None,
+        );
+        Ok(())
     }
 
     fn translate_table_fill(
         &mut self,
-        _pos: FuncCursor,
-        _table_index: u32,
-        _dst: ir::Value,
-        _val: ir::Value,
-        _len: ir::Value,
+        pos: FuncCursor,
+        table_index: u32,
+        dst: ir::Value,
+        val: ir::Value,
+        len: ir::Value,
     ) -> cranelift_wasm::WasmResult<()> {
-        unimplemented!("table.fill not yet implemented");
+        self.call_table_vmcall(
+            pos,
+            TableIndex::new(table_index as usize),
+            call_names::TABLE_FILL,
+            &[dst, val, len],
+            None,
+        );
+        Ok(())
     }
 
     fn translate_ref_func(
         &mut self,
-        _pos: FuncCursor,
-        _func_index: u32,
+        mut pos: FuncCursor,
+        func_index: u32,
     ) -> cranelift_wasm::WasmResult<ir::Value> {
-        unimplemented!("ref.func not yet implemented");
+        let signature = pos.func.import_signature(ir::Signature {
+            call_conv: self.target_config().default_call_conv,
+            params: vec![
+                ir::AbiParam::special(self.pointer_type(), ir::ArgumentPurpose::VMContext),
+                ir::AbiParam::new(ir::types::I32),
+            ],
+            returns: vec![ir::AbiParam::new(self.reference_type())],
+        });
+
+        let name = ir::ExternalName::user(call_names::LOCAL_NAMESPACE, call_names::FUNC_REF);
+
+        let func_ref_func = pos.func.import_function(ir::ExtFuncData {
+            name,
+            signature,
+            colocated: false,
+        });
+
+        let const_func_index = pos.ins().iconst(ir::types::I32, func_index as i64);
+        let vmctx = pos
+            .func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .expect("missing vmctx parameter");
+
+        let call_inst = pos.ins().call(func_ref_func, &[vmctx, const_func_index]);
+
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
     }
 
     fn translate_custom_global_get(
@@ -1113,6 +1258,107 @@ impl FuncEnvironment for FunctionEnvironment {
 }
 
 impl FunctionEnvironment {
+    /// Generates a call to the table vmcall `name_index` of the table at `table_index`.
+    ///
+    /// The vmcall is passed the vmctx and the local or imported table index, then `args`.
+    fn call_table_vmcall(
+        &mut self,
+        mut pos: FuncCursor,
+        table_index: TableIndex,
+        name_index: u32,
+        args: &[ir::Value],
+        ret: Option<ir::Type>,
+    ) -> Option<ir::Value> {
+        let (namespace, table_index) =
+            match table_index.local_or_import(&self.module_info.read().unwrap()) {
+                LocalOrImport::Local(local_table_index) => {
+                    (call_names::LOCAL_NAMESPACE, local_table_index.index())
+                }
+                LocalOrImport::Import(import_table_index) => {
+                    (call_names::IMPORT_NAMESPACE, import_table_index.index())
+                }
+            };
+
+        let const_table_index = pos.ins().iconst(ir::types::I32, table_index as i64);
+        let mut call_args = vec![const_table_index];
+        call_args.extend_from_slice(args);
+
+        self.call_vmcall(pos, namespace, name_index, &call_args, ret)
+    }
+
+    /// Generates a call to the memory vmcall `name_index` of the memory at `clif_mem_index`.
+    ///
+    /// The vmcall is passed the vmctx and the local or imported memory index, then `args`.
+    fn call_memory_vmcall(
+        &mut self,
+        mut pos: FuncCursor,
+        clif_mem_index: cranelift_wasm::MemoryIndex,
+        name_index: u32,
+        args: &[ir::Value],
+    ) {
+        let mem_index: MemoryIndex = Converter(clif_mem_index).into();
+        let (namespace, mem_index) =
+            match mem_index.local_or_import(&self.module_info.read().unwrap()) {
+                LocalOrImport::Local(local_mem_index) => {
+                    (call_names::LOCAL_NAMESPACE, local_mem_index.index())
+                }
+                LocalOrImport::Import(import_mem_index) => {
+                    (call_names::IMPORT_NAMESPACE, import_mem_index.index())
+                }
+            };
+
+        let const_mem_index = pos.ins().iconst(ir::types::I32, mem_index as i64);
+        let mut call_args = vec![const_mem_index];
+        call_args.extend_from_slice(args);
+
+        self.call_vmcall(pos, namespace, name_index, &call_args, None);
+    }
+
+    /// Generates a call to the vmcall `name_index` in `namespace`, passing the vmctx then `args`.
+    fn call_vmcall(
+        &mut self,
+        mut pos: FuncCursor,
+        namespace: u32,
+        name_index: u32,
+        args: &[ir::Value],
+        ret: Option<ir::Type>,
+    ) -> Option<ir::Value> {
+        let mut params = vec![ir::AbiParam::special(
+            self.pointer_type(),
+            ir::ArgumentPurpose::VMContext,
+        )];
+        params.extend(
+            args.iter()
+                .map(|arg| ir::AbiParam::new(pos.func.dfg.value_type(*arg))),
+        );
+
+        let signature = pos.func.import_signature(ir::Signature {
+            call_conv: self.target_config().default_call_conv,
+            params,
+            returns: ret.into_iter().map(ir::AbiParam::new).collect(),
+        });
+
+        let name = ir::ExternalName::user(namespace, name_index);
+
+        let vmcall_func = pos.func.import_function(ir::ExtFuncData {
+            name,
+            signature,
+            colocated: false,
+        });
+
+        let vmctx = pos
+            .func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .expect("missing vmctx parameter");
+
+        let mut call_args = vec![vmctx];
+        call_args.extend_from_slice(args);
+
+        let call_inst = pos.ins().call(vmcall_func, &call_args);
+
+        pos.func.dfg.inst_results(call_inst).first().cloned()
+    }
+
     pub fn get_func_type(
         &self,
         func_index: cranelift_wasm::FuncIndex,
@@ -1188,6 +1434,15 @@ impl FunctionCodeGenerator<CodegenError> for CraneliftFunctionCodeGenerator {
             }
         };
 
+        match *op {
+            Operator::Block { ty } | Operator::Loop { ty } | Operator::If { ty } => {
+                if let TypeOrFuncType::FuncType(_) = ty {
+                    return Err(multi_value_unsupported());
+                }
+            }
+            _ => {}
+        }
+
         //let builder = self.builder.as_mut().unwrap();
         //let func_environment = FuncEnv::new();
         //let state = TranslationState::new();
@@ -1213,6 +1468,15 @@ impl FunctionCodeGenerator<CodegenError> for CraneliftFunctionCodeGenerator {
             &mut self.func_env,
         )?;
 
+        if self.full_preemption {
+            if let Operator::Loop { .. } = op {
+                if func_state.reachable() {
+                    let pointer_type = self.func_env.pointer_type();
+                    emit_interrupt_checkpoint(&mut builder, pointer_type);
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -1257,6 +1521,15 @@ pub struct CodegenError {
     pub message: String,
 }
 
+/// The error of a module using the multi-value proposal, which is only supported by the
+/// singlepass and LLVM backends.
+fn multi_value_unsupported() -> CodegenError {
+    CodegenError {
+        message: "the cranelift backend does not support multi-value returns and block types"
+            .to_string(),
+    }
+}
+
 impl CraneliftModuleCodeGenerator {
     /// Return the signature index for the given function index.
     pub fn get_func_type(
@@ -1283,6 +1556,26 @@ impl CraneliftFunctionCodeGenerator {
     }
 }
 
+/// Emits a load from the interrupt signal page referenced by `vm::Ctx`.
+///
+/// The load faults once the page is protected by an `InterruptHandle`, which
+/// the signal handler then reports as `ExceptionCode::Interrupted`.
+fn emit_interrupt_checkpoint(builder: &mut FunctionBuilder, pointer_type: ir::Type) {
+    let vmctx = builder
+        .func
+        .create_global_value(ir::GlobalValueData::VMContext);
+    let vmctx = builder.ins().global_value(pointer_type, vmctx);
+    let signal_mem = builder.ins().load(
+        pointer_type,
+        ir::MemFlags::trusted(),
+        vmctx,
+        vm::Ctx::offset_interrupt_signal_mem() as i32,
+    );
+    builder
+        .ins()
+        .load(ir::types::I8, ir::MemFlags::new(), signal_mem, 0);
+}
+
 /// Creates a signature with VMContext as the last param
 fn generate_signature(
     env: &CraneliftModuleCodeGenerator,
@@ -107,6 +107,8 @@ impl From<Converter<ir::Type>> for Type {
             ir::types::F32 => Type::F32,
             ir::types::F64 => Type::F64,
             ir::types::I32X4 => Type::V128,
+            // Both reference types are lowered to `R64`.
+            ir::types::R64 => Type::ExternRef,
             _ => unimplemented!("unsupported wasm type"),
         }
     }
@@ -120,6 +122,7 @@ impl From<Converter<Type>> for ir::Type {
             Type::F32 => ir::types::F32,
             Type::F64 => ir::types::F64,
             Type::V128 => ir::types::I32X4,
+            Type::FuncRef | Type::ExternRef => ir::types::R64,
         }
     }
 }
@@ -132,6 +135,7 @@ impl From<Converter<Type>> for ir::AbiParam {
             Type::F32 => ir::AbiParam::new(ir::types::F32),
             Type::F64 => ir::AbiParam::new(ir::types::F64),
             Type::V128 => ir::AbiParam::new(ir::types::I32X4),
+            Type::FuncRef | Type::ExternRef => ir::AbiParam::new(ir::types::R64),
         }
     }
 }
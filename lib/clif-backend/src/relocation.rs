@@ -20,6 +20,22 @@ pub mod call_names {
     pub const SHARED_STATIC_MEM_SIZE: u32 = 3;
     pub const DYNAMIC_MEM_GROW: u32 = 4;
     pub const DYNAMIC_MEM_SIZE: u32 = 5;
+
+    pub const TABLE_GROW: u32 = 6;
+    pub const TABLE_SIZE: u32 = 7;
+    pub const TABLE_GET: u32 = 8;
+    pub const TABLE_SET: u32 = 9;
+    pub const TABLE_FILL: u32 = 10;
+
+    pub const FUNC_REF: u32 = 11;
+
+    pub const MEMORY_COPY: u32 = 12;
+    pub const MEMORY_FILL: u32 = 13;
+    pub const MEMORY_INIT: u32 = 14;
+    pub const DATA_DROP: u32 = 15;
+    pub const TABLE_COPY: u32 = 16;
+    pub const TABLE_INIT: u32 = 17;
+    pub const ELEM_DROP: u32 = 18;
 }
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
@@ -73,6 +89,22 @@ pub enum VmCallKind {
 
     DynamicMemoryGrow,
     DynamicMemorySize,
+
+    TableGrow,
+    TableSize,
+    TableGet,
+    TableSet,
+    TableFill,
+
+    FuncRef,
+
+    MemoryCopy,
+    MemoryFill,
+    MemoryInit,
+    DataDrop,
+    TableCopy,
+    TableInit,
+    ElemDrop,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
@@ -146,6 +178,22 @@ impl binemit::RelocSink for RelocSink {
 
                         DYNAMIC_MEM_GROW => VmCallKind::DynamicMemoryGrow,
                         DYNAMIC_MEM_SIZE => VmCallKind::DynamicMemorySize,
+
+                        TABLE_GROW => VmCallKind::TableGrow,
+                        TABLE_SIZE => VmCallKind::TableSize,
+                        TABLE_GET => VmCallKind::TableGet,
+                        TABLE_SET => VmCallKind::TableSet,
+                        TABLE_FILL => VmCallKind::TableFill,
+
+                        FUNC_REF => VmCallKind::FuncRef,
+
+                        MEMORY_COPY => VmCallKind::MemoryCopy,
+                        MEMORY_FILL => VmCallKind::MemoryFill,
+                        MEMORY_INIT => VmCallKind::MemoryInit,
+                        DATA_DROP => VmCallKind::DataDrop,
+                        TABLE_COPY => VmCallKind::TableCopy,
+                        TABLE_INIT => VmCallKind::TableInit,
+                        ELEM_DROP => VmCallKind::ElemDrop,
                         _ => unimplemented!("reloc_external VmCall::Local {}", index),
                     })),
                     IMPORT_NAMESPACE => RelocationType::VmCall(VmCall::Import(match index {
@@ -157,6 +205,16 @@ impl binemit::RelocSink for RelocSink {
 
                         DYNAMIC_MEM_GROW => VmCallKind::DynamicMemoryGrow,
                         DYNAMIC_MEM_SIZE => VmCallKind::DynamicMemorySize,
+
+                        TABLE_GROW => VmCallKind::TableGrow,
+                        TABLE_SIZE => VmCallKind::TableSize,
+                        TABLE_GET => VmCallKind::TableGet,
+                        TABLE_SET => VmCallKind::TableSet,
+                        TABLE_FILL => VmCallKind::TableFill,
+
+                        MEMORY_COPY => VmCallKind::MemoryCopy,
+                        MEMORY_FILL => VmCallKind::MemoryFill,
+                        MEMORY_INIT => VmCallKind::MemoryInit,
                         _ => unimplemented!("reloc_external VmCall::Import {}", index),
                     })),
                     SIG_NAMESPACE => RelocationType::Signature(SigIndex::new(index as usize)),
@@ -403,6 +403,19 @@ impl FuncResolverBuilder {
                             VmCallKind::DynamicMemorySize => {
                                 vmcalls::local_dynamic_memory_size as _
                             }
+                            VmCallKind::TableGrow => vmcalls::local_table_grow as _,
+                            VmCallKind::TableSize => vmcalls::local_table_size as _,
+                            VmCallKind::TableGet => vmcalls::local_table_get as _,
+                            VmCallKind::TableSet => vmcalls::local_table_set as _,
+                            VmCallKind::TableFill => vmcalls::local_table_fill as _,
+                            VmCallKind::FuncRef => vmcalls::func_ref as _,
+                            VmCallKind::MemoryCopy => vmcalls::local_memory_copy as _,
+                            VmCallKind::MemoryFill => vmcalls::local_memory_fill as _,
+                            VmCallKind::MemoryInit => vmcalls::local_memory_init as _,
+                            VmCallKind::DataDrop => vmcalls::data_drop as _,
+                            VmCallKind::TableCopy => vmcalls::table_copy as _,
+                            VmCallKind::TableInit => vmcalls::table_init as _,
+                            VmCallKind::ElemDrop => vmcalls::elem_drop as _,
                         },
                         VmCall::Import(kind) => match kind {
                             VmCallKind::StaticMemoryGrow | VmCallKind::SharedStaticMemoryGrow => {
@@ -417,6 +430,19 @@ impl FuncResolverBuilder {
                             VmCallKind::DynamicMemorySize => {
                                 vmcalls::imported_dynamic_memory_size as _
                             }
+                            VmCallKind::TableGrow => vmcalls::imported_table_grow as _,
+                            VmCallKind::TableSize => vmcalls::imported_table_size as _,
+                            VmCallKind::TableGet => vmcalls::imported_table_get as _,
+                            VmCallKind::TableSet => vmcalls::imported_table_set as _,
+                            VmCallKind::TableFill => vmcalls::imported_table_fill as _,
+                            VmCallKind::FuncRef => vmcalls::func_ref as _,
+                            VmCallKind::MemoryCopy => vmcalls::imported_memory_copy as _,
+                            VmCallKind::MemoryFill => vmcalls::imported_memory_fill as _,
+                            VmCallKind::MemoryInit => vmcalls::imported_memory_init as _,
+                            VmCallKind::DataDrop => vmcalls::data_drop as _,
+                            VmCallKind::TableCopy => vmcalls::table_copy as _,
+                            VmCallKind::TableInit => vmcalls::table_init as _,
+                            VmCallKind::ElemDrop => vmcalls::elem_drop as _,
                         },
                     },
                     RelocationType::Signature(sig_index) => {
@@ -493,6 +519,16 @@ impl FuncResolver {
     pub fn lookup(&self, index: LocalFuncIndex) -> Option<NonNull<vm::Func>> {
         lookup_func(&self.map, &self.memory, index)
     }
+
+    /// The compiled code of all local functions.
+    pub fn code(&self) -> &[u8] {
+        unsafe { self.memory.as_slice() }
+    }
+
+    /// The offsets of the local functions in `code()`.
+    pub fn local_function_offsets(&self) -> Vec<usize> {
+        self.map.values().cloned().collect()
+    }
 }
 
 #[inline]
@@ -6,7 +6,8 @@ use crate::{
 use libc::c_void;
 use std::{any::Any, cell::Cell, ptr::NonNull, sync::Arc};
 use wasmer_runtime_core::{
-    backend::RunnableModule,
+    backend::{ExceptionCode, RunnableModule},
+    error::Trap,
     module::ModuleInfo,
     typed_func::{Trampoline, Wasm},
     types::{LocalFuncIndex, SigIndex},
@@ -31,6 +32,17 @@ thread_local! {
 
 pub struct CallProtError(pub Box<dyn Any + Send>);
 
+/// Where a trap raised by compiled code happened, converted into a
+/// `Trap` once the module is known.
+pub struct TrapLocation {
+    pub code: ExceptionCode,
+    /// The address of the trapping instruction.
+    pub ip: usize,
+    /// The offset in the module's binary of the trapping operator, as
+    /// recorded by `ir::SourceLoc`.
+    pub srcloc: u32,
+}
+
 pub struct Caller {
     handler_data: HandlerData,
     trampolines: Arc<Trampolines>,
@@ -56,6 +68,14 @@ impl RunnableModule for Caller {
         self.resolver.lookup(func_index)
     }
 
+    fn get_code(&self) -> Option<&[u8]> {
+        Some(self.resolver.code())
+    }
+
+    fn get_local_function_offsets(&self) -> Option<Vec<usize>> {
+        Some(self.resolver.local_function_offsets())
+    }
+
     fn get_trampoline(&self, _: &ModuleInfo, sig_index: SigIndex) -> Option<Wasm> {
         unsafe extern "C" fn invoke(
             trampoline: Trampoline,
@@ -79,8 +99,24 @@ impl RunnableModule for Caller {
             let res = call_protected(handler_data, trampoline, ctx, func, args, rets);
 
             match res {
-                Err(err) => {
-                    *error_out = Some(err.0);
+                Err(CallProtError(data)) => {
+                    *error_out = Some(match data.downcast::<TrapLocation>() {
+                        Ok(loc) => {
+                            // The default `SourceLoc` is all ones.
+                            let offset = if loc.srcloc == !0 {
+                                None
+                            } else {
+                                Some(loc.srcloc)
+                            };
+                            Box::new(Trap::from_native_frames(
+                                Some(loc.code),
+                                &*(*ctx).module,
+                                &[loc.ip],
+                                offset,
+                            ))
+                        }
+                        Err(data) => data,
+                    });
                     false
                 }
                 Ok(()) => true,
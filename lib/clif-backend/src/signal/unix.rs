@@ -10,7 +10,7 @@
 //! unless you have memory unsafety elsewhere in your code.
 //!
 use crate::relocation::{TrapCode, TrapData};
-use crate::signal::{CallProtError, HandlerData};
+use crate::signal::{CallProtError, HandlerData, TrapLocation};
 use libc::{c_int, c_void, siginfo_t};
 use nix::sys::signal::{
     sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal, SIGBUS, SIGFPE, SIGILL, SIGSEGV,
@@ -19,6 +19,7 @@ use std::cell::{Cell, UnsafeCell};
 use std::ptr;
 use std::sync::Once;
 use wasmer_runtime_core::backend::ExceptionCode;
+use wasmer_runtime_core::fault::take_interrupt;
 
 extern "C" fn signal_trap_handler(
     signum: ::nix::libc::c_int,
@@ -83,14 +84,10 @@ pub fn call_protected<T>(
             } else {
                 let (faulting_addr, inst_ptr) = CAUGHT_ADDRESSES.with(|cell| cell.get());
 
-                if let Some(TrapData {
-                    trapcode,
-                    srcloc: _,
-                }) = handler_data.lookup(inst_ptr)
-                {
-                    Err(CallProtError(Box::new(match Signal::from_c_int(signum) {
+                if let Some(TrapData { trapcode, srcloc }) = handler_data.lookup(inst_ptr) {
+                    let code = match Signal::from_c_int(signum) {
                         Ok(SIGILL) => match trapcode {
-                            TrapCode::StackOverflow => ExceptionCode::MemoryOutOfBounds,
+                            TrapCode::StackOverflow => ExceptionCode::StackOverflow,
                             TrapCode::HeapOutOfBounds => ExceptionCode::MemoryOutOfBounds,
                             TrapCode::TableOutOfBounds => ExceptionCode::CallIndirectOOB,
                             TrapCode::OutOfBounds => ExceptionCode::MemoryOutOfBounds,
@@ -106,12 +103,20 @@ pub fn call_protected<T>(
                                 )))
                             }
                         },
+                        Ok(SIGSEGV) | Ok(SIGBUS) if take_interrupt(faulting_addr) => {
+                            ExceptionCode::Interrupted
+                        }
                         Ok(SIGSEGV) | Ok(SIGBUS) => ExceptionCode::MemoryOutOfBounds,
                         Ok(SIGFPE) => ExceptionCode::IllegalArithmetic,
                         _ => unimplemented!(
                             "ExceptionCode::Unknown signal:{:?}",
                             Signal::from_c_int(signum)
                         ),
+                    };
+                    Err(CallProtError(Box::new(TrapLocation {
+                        code,
+                        ip: inst_ptr as usize,
+                        srcloc,
                     })))
                 } else {
                     let signal = match Signal::from_c_int(signum) {
@@ -73,7 +73,7 @@ pub fn call_protected(
                 TrapCode::UnreachableCodeReached => ExceptionCode::Unreachable,
                 _ => return Err(CallProtError(Box::new("unknown trap code".to_string()))),
             },
-            EXCEPTION_STACK_OVERFLOW => ExceptionCode::MemoryOutOfBounds,
+            EXCEPTION_STACK_OVERFLOW => ExceptionCode::StackOverflow,
             EXCEPTION_INT_DIVIDE_BY_ZERO | EXCEPTION_INT_OVERFLOW => {
                 ExceptionCode::IllegalArithmetic
             }
@@ -60,6 +60,7 @@ enum WasmTrapType {
   CallIndirectOOB = 3,
   IllegalArithmetic = 4,
   MisalignedAtomicAccess = 5,
+  Interrupted = 8,
   Unknown,
 };
 
@@ -121,6 +122,7 @@ private:
 
 struct WasmErrorSink {
   WasmTrapType *trap_out;
+  uintptr_t *trap_ip_out;
   box_any_t *user_error;
 };
 
@@ -200,7 +202,7 @@ private:
 
 struct WasmTrap : UncatchableException {
 public:
-  WasmTrap(WasmTrapType type) : type(type) {}
+  WasmTrap(WasmTrapType type, uintptr_t ip) : type(type), ip(ip) {}
 
   virtual std::string description() const noexcept override {
     std::ostringstream ss;
@@ -211,8 +213,12 @@ public:
 
   WasmTrapType type;
 
+  // Address of the instruction that trapped.
+  uintptr_t ip;
+
   virtual void write_error(WasmErrorSink &out) const noexcept override {
     *out.trap_out = type;
+    *out.trap_ip_out = ip;
   }
 
 private:
@@ -268,8 +274,16 @@ result_t module_load(const uint8_t *mem_ptr, size_t mem_size,
   return RESULT_OK;
 }
 
+// Called from generated code, so the trap is attributed to the caller. The
+// return address points past the call, hence the `- 1`.
 [[noreturn]] void throw_trap(WasmTrapType ty) {
-  unsafe_unwind(new WasmTrap(ty));
+  unsafe_unwind(
+      new WasmTrap(ty, (uintptr_t)__builtin_return_address(0) - 1));
+}
+
+// Called from the signal handler with the faulting instruction.
+[[noreturn]] void throw_trap_at(WasmTrapType ty, uintptr_t ip) {
+  unsafe_unwind(new WasmTrap(ty, ip));
 }
 
 void module_delete(WasmModule *module) { delete module; }
@@ -288,7 +302,8 @@ void module_delete(WasmModule *module) { delete module; }
 
 bool cxx_invoke_trampoline(trampoline_t trampoline, void *ctx, void *func,
                        void *params, void *results, WasmTrapType *trap_out,
-                       box_any_t *user_error, void *invoke_env) noexcept {
+                       uintptr_t *trap_ip_out, box_any_t *user_error,
+                       void *invoke_env) noexcept {
   try {
     catch_unwind([trampoline, ctx, func, params, results]() {
       trampoline(ctx, func, params, results);
@@ -297,6 +312,7 @@ bool cxx_invoke_trampoline(trampoline_t trampoline, void *ctx, void *func,
   } catch (std::unique_ptr<WasmException> &e) {
     WasmErrorSink sink;
     sink.trap_out = trap_out;
+    sink.trap_ip_out = trap_ip_out;
     sink.user_error = user_error;
     e->write_error(sink);
     return false;
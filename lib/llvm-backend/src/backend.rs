@@ -27,6 +27,7 @@ use wasmer_runtime_core::{
         CacheGen, ExceptionCode, RunnableModule,
     },
     cache::Error as CacheError,
+    error::Trap,
     module::ModuleInfo,
     state::ModuleStateMap,
     structures::TypedIndex,
@@ -66,6 +67,7 @@ extern "C" {
         params: *const u64,
         results: *mut u64,
         trap_out: *mut i32,
+        trap_ip_out: *mut usize,
         error_out: *mut Option<Box<dyn Any + Send>>,
         invoke_env: Option<NonNull<c_void>>,
     ) -> bool;
@@ -83,6 +85,7 @@ unsafe extern "C" fn invoke_trampoline(
     invoke_env: Option<NonNull<c_void>>,
 ) -> bool {
     let mut trap_out: i32 = -1;
+    let mut trap_ip: usize = 0;
     let ret = cxx_invoke_trampoline(
         trampoline,
         vmctx_ptr,
@@ -90,20 +93,28 @@ unsafe extern "C" fn invoke_trampoline(
         params,
         results,
         &mut trap_out,
+        &mut trap_ip,
         error_out,
         invoke_env,
     );
     // Translate trap code if an error occurred.
     if !ret && (*error_out).is_none() && trap_out != -1 {
-        *error_out = Some(Box::new(match trap_out {
+        let code = match trap_out {
             0 => ExceptionCode::Unreachable,
             1 => ExceptionCode::IncorrectCallIndirectSignature,
             2 => ExceptionCode::MemoryOutOfBounds,
             3 => ExceptionCode::CallIndirectOOB,
             4 => ExceptionCode::IllegalArithmetic,
             5 => ExceptionCode::MisalignedAtomicAccess,
+            8 => ExceptionCode::Interrupted,
             _ => return ret,
-        }));
+        };
+        *error_out = Some(Box::new(Trap::from_native_frames(
+            Some(code),
+            &*(*vmctx_ptr).module,
+            &[trap_ip],
+            None,
+        )));
     }
     ret
 }
@@ -157,6 +168,31 @@ fn get_callbacks() -> Callbacks {
             fn_name!("vm.memory.grow.static.import") => vmcalls::imported_static_memory_grow as _,
             fn_name!("vm.memory.size.static.import") => vmcalls::imported_static_memory_size as _,
 
+            fn_name!("vm.table.grow.local") => vmcalls::local_table_grow as _,
+            fn_name!("vm.table.size.local") => vmcalls::local_table_size as _,
+            fn_name!("vm.table.get.local") => vmcalls::local_table_get as _,
+            fn_name!("vm.table.set.local") => vmcalls::local_table_set as _,
+            fn_name!("vm.table.fill.local") => vmcalls::local_table_fill as _,
+
+            fn_name!("vm.table.grow.import") => vmcalls::imported_table_grow as _,
+            fn_name!("vm.table.size.import") => vmcalls::imported_table_size as _,
+            fn_name!("vm.table.get.import") => vmcalls::imported_table_get as _,
+            fn_name!("vm.table.set.import") => vmcalls::imported_table_set as _,
+            fn_name!("vm.table.fill.import") => vmcalls::imported_table_fill as _,
+
+            fn_name!("vm.func.ref") => vmcalls::func_ref as _,
+
+            fn_name!("vm.memory.copy.local") => vmcalls::local_memory_copy as _,
+            fn_name!("vm.memory.fill.local") => vmcalls::local_memory_fill as _,
+            fn_name!("vm.memory.init.local") => vmcalls::local_memory_init as _,
+            fn_name!("vm.memory.copy.import") => vmcalls::imported_memory_copy as _,
+            fn_name!("vm.memory.fill.import") => vmcalls::imported_memory_fill as _,
+            fn_name!("vm.memory.init.import") => vmcalls::imported_memory_init as _,
+            fn_name!("vm.data.drop") => vmcalls::data_drop as _,
+            fn_name!("vm.table.copy") => vmcalls::table_copy as _,
+            fn_name!("vm.table.init") => vmcalls::table_init as _,
+            fn_name!("vm.elem.drop") => vmcalls::elem_drop as _,
+
             fn_name!("vm.exception.trap") => throw_trap as _,
             fn_name!("vm.breakpoint") => throw_breakpoint as _,
 
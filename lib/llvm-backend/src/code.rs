@@ -1,7 +1,7 @@
 use crate::{
     backend::LLVMBackend,
-    intrinsics::{tbaa_label, CtxType, GlobalCache, Intrinsics, MemoryCache},
-    read_info::blocktype_to_type,
+    intrinsics::{tbaa_label, type_to_llvm_ptr, CtxType, GlobalCache, Intrinsics, MemoryCache},
+    read_info::blocktype_to_types,
     stackmap::{StackmapEntry, StackmapEntryKind, StackmapRegistry, ValueSemantic},
     state::{ControlFrame, ExtraInfo, IfElseState, State},
     trampolines::generate_trampolines,
@@ -14,11 +14,12 @@ use inkwell::{
     passes::PassManager,
     targets::{CodeModel, InitializationConfig, RelocMode, Target, TargetMachine},
     types::{
-        BasicType, BasicTypeEnum, FloatMathType, FunctionType, IntType, PointerType, VectorType,
+        BasicType, BasicTypeEnum, FloatMathType, FunctionType, IntType, PointerType, StructType,
+        VectorType,
     },
     values::{
-        BasicValue, BasicValueEnum, FloatValue, FunctionValue, IntValue, PhiValue, PointerValue,
-        VectorValue,
+        BasicValue, BasicValueEnum, CallSiteValue, FloatValue, FunctionValue, IntValue, PhiValue,
+        PointerValue, VectorValue,
     },
     AddressSpace, AtomicOrdering, AtomicRMWBinOp, FloatPredicate, IntPredicate, OptimizationLevel,
 };
@@ -37,6 +38,7 @@ use wasmer_runtime_core::{
     codegen::*,
     memory::MemoryType,
     module::{ModuleInfo, ModuleInner},
+    multi_value::{Eightbyte, ResultsLayout},
     parse::{wp_type_to_type, LoadError},
     structures::{Map, TypedIndex},
     types::{
@@ -63,18 +65,145 @@ fn func_sig_to_llvm<'ctx>(
         &[] => intrinsics.void_ty.fn_type(&param_types, false),
         &[single_value] => type_to_llvm(intrinsics, single_value).fn_type(&param_types, false),
         returns @ _ => {
-            let basic_types: Vec<_> = returns
-                .iter()
-                .map(|&ty| type_to_llvm(intrinsics, ty))
-                .collect();
-
-            context
-                .struct_type(&basic_types, false)
-                .fn_type(&param_types, false)
+            let layout = ResultsLayout::new(returns);
+            if layout.in_memory() {
+                // The pointer to the results comes first, and is returned.
+                let param_types: Vec<_> =
+                    std::iter::once(intrinsics.i8_ptr_ty.as_basic_type_enum())
+                        .chain(param_types)
+                        .collect();
+                intrinsics.i8_ptr_ty.fn_type(&param_types, false)
+            } else {
+                eightbytes_type(context, intrinsics, &layout).fn_type(&param_types, false)
+            }
         }
     }
 }
 
+// Returns the type of the eightbytes in which the results laid out by `layout` are
+// returned in registers.
+fn eightbytes_type<'ctx>(
+    context: &'ctx Context,
+    intrinsics: &Intrinsics<'ctx>,
+    layout: &ResultsLayout,
+) -> StructType<'ctx> {
+    let eightbytes: Vec<_> = layout
+        .eightbytes()
+        .into_iter()
+        .map(|class| match class {
+            Eightbyte::Integer => intrinsics.i64_ty.as_basic_type_enum(),
+            Eightbyte::Sse => intrinsics.f64_ty.as_basic_type_enum(),
+        })
+        .collect();
+    context.struct_type(&eightbytes, false)
+}
+
+// Returns the layout of the results of a function of signature `sig`, if it has several.
+pub fn results_layout(sig: &FuncSig) -> Option<ResultsLayout> {
+    if sig.returns().len() > 1 {
+        Some(ResultsLayout::new(sig.returns()))
+    } else {
+        None
+    }
+}
+
+// Returns the index of the vmctx param of a function of signature `sig`, which comes after
+// the pointer to the results if they are returned in memory.
+fn vmctx_param_index(sig: &FuncSig) -> u32 {
+    match results_layout(sig) {
+        Some(ref layout) if layout.in_memory() => 1,
+        _ => 0,
+    }
+}
+
+// Allocates memory for the results laid out by `layout`, and returns an `i8*` to it.
+pub fn build_results_buffer<'ctx>(
+    builder: &Builder<'ctx>,
+    intrinsics: &Intrinsics<'ctx>,
+    layout: &ResultsLayout,
+) -> PointerValue<'ctx> {
+    let buffer = builder.build_alloca(
+        intrinsics
+            .i64_ty
+            .array_type(((layout.size() + 7) / 8) as u32),
+        "results",
+    );
+    builder.build_pointer_cast(buffer, intrinsics.i8_ptr_ty, "results_ptr")
+}
+
+// Returns a pointer to the result at `index` in the results laid out by `layout` at `buffer`.
+fn build_result_ptr<'ctx>(
+    builder: &Builder<'ctx>,
+    intrinsics: &Intrinsics<'ctx>,
+    layout: &ResultsLayout,
+    buffer: PointerValue<'ctx>,
+    index: usize,
+) -> PointerValue<'ctx> {
+    let ptr = unsafe {
+        builder.build_in_bounds_gep(
+            buffer,
+            &[intrinsics
+                .i32_ty
+                .const_int(layout.offsets()[index] as u64, false)],
+            "result_ptr",
+        )
+    };
+    builder.build_pointer_cast(
+        ptr,
+        type_to_llvm_ptr(intrinsics, layout.types()[index]),
+        "typed_result_ptr",
+    )
+}
+
+// Writes `values` to `buffer`, laid out by `layout`.
+fn build_store_results<'ctx>(
+    builder: &Builder<'ctx>,
+    intrinsics: &Intrinsics<'ctx>,
+    layout: &ResultsLayout,
+    buffer: PointerValue<'ctx>,
+    values: &[BasicValueEnum<'ctx>],
+) {
+    for (index, value) in values.iter().enumerate() {
+        let ptr = build_result_ptr(builder, intrinsics, layout, buffer, index);
+        let value = builder.build_bitcast(
+            *value,
+            type_to_llvm(intrinsics, layout.types()[index]),
+            "result",
+        );
+        builder.build_store(ptr, value);
+    }
+}
+
+// Returns the results of `call_site`, a call to a function with the results laid out by
+// `layout`, where `buffer` is the pointer to the results passed to it if they are returned in
+// memory.
+pub fn build_call_results<'ctx>(
+    builder: &Builder<'ctx>,
+    context: &'ctx Context,
+    intrinsics: &Intrinsics<'ctx>,
+    layout: &ResultsLayout,
+    buffer: PointerValue<'ctx>,
+    call_site: CallSiteValue<'ctx>,
+) -> Vec<BasicValueEnum<'ctx>> {
+    if !layout.in_memory() {
+        let eightbytes_ptr = builder.build_pointer_cast(
+            buffer,
+            eightbytes_type(context, intrinsics, layout).ptr_type(AddressSpace::Generic),
+            "eightbytes_ptr",
+        );
+        builder.build_store(
+            eightbytes_ptr,
+            call_site.try_as_basic_value().left().unwrap(),
+        );
+    }
+    (0..layout.types().len())
+        .map(|index| {
+            let ptr = build_result_ptr(builder, intrinsics, layout, buffer, index);
+            builder.build_load(ptr, "result")
+        })
+        .collect()
+}
+
 fn type_to_llvm<'ctx>(intrinsics: &Intrinsics<'ctx>, ty: Type) -> BasicTypeEnum<'ctx> {
     match ty {
         Type::I32 => intrinsics.i32_ty.as_basic_type_enum(),
@@ -82,9 +211,54 @@ fn type_to_llvm<'ctx>(intrinsics: &Intrinsics<'ctx>, ty: Type) -> BasicTypeEnum<
         Type::F32 => intrinsics.f32_ty.as_basic_type_enum(),
         Type::F64 => intrinsics.f64_ty.as_basic_type_enum(),
         Type::V128 => intrinsics.i128_ty.as_basic_type_enum(),
+        Type::FuncRef | Type::ExternRef => intrinsics.i64_ty.as_basic_type_enum(),
     }
 }
 
+// Picks the local or imported variant of a memory vmcall for the memory at `memory`,
+// along with the local or imported memory index to pass to it.
+fn memory_vmcall<'ctx>(
+    info: &ModuleInfo,
+    intrinsics: &Intrinsics<'ctx>,
+    memory: u32,
+    local: FunctionValue<'ctx>,
+    import: FunctionValue<'ctx>,
+) -> (FunctionValue<'ctx>, BasicValueEnum<'ctx>) {
+    let (func_value, index) = match MemoryIndex::new(memory as usize).local_or_import(info) {
+        LocalOrImport::Local(local_memory_index) => (local, local_memory_index.index()),
+        LocalOrImport::Import(import_memory_index) => (import, import_memory_index.index()),
+    };
+    (
+        func_value,
+        intrinsics
+            .i32_ty
+            .const_int(index as u64, false)
+            .as_basic_value_enum(),
+    )
+}
+
+// Picks the local or imported variant of a table vmcall for the table at `table`,
+// along with the local or imported table index to pass to it.
+fn table_vmcall<'ctx>(
+    info: &ModuleInfo,
+    intrinsics: &Intrinsics<'ctx>,
+    table: u32,
+    local: FunctionValue<'ctx>,
+    import: FunctionValue<'ctx>,
+) -> (FunctionValue<'ctx>, BasicValueEnum<'ctx>) {
+    let (func_value, index) = match TableIndex::new(table as usize).local_or_import(info) {
+        LocalOrImport::Local(local_table_index) => (local, local_table_index.index()),
+        LocalOrImport::Import(import_table_index) => (import, import_table_index.index()),
+    };
+    (
+        func_value,
+        intrinsics
+            .i32_ty
+            .const_int(index as u64, false)
+            .as_basic_value_enum(),
+    )
+}
+
 // Create a vector where each lane contains the same value.
 fn splat_vector<'ctx>(
     builder: &Builder<'ctx>,
@@ -965,6 +1139,7 @@ pub struct LLVMModuleCodeGenerator<'ctx> {
     module: ManuallyDrop<Rc<RefCell<Module<'ctx>>>>,
     stackmaps: Rc<RefCell<StackmapRegistry>>,
     track_state: bool,
+    full_preemption: bool,
     target_machine: TargetMachine,
     llvm_callbacks: Option<Rc<RefCell<dyn LLVMCallbacks>>>,
 }
@@ -987,6 +1162,7 @@ pub struct LLVMFunctionCodeGenerator<'ctx> {
     index: usize,
     opcode_offset: usize,
     track_state: bool,
+    full_preemption: bool,
     module: Rc<RefCell<Module<'ctx>>>,
 }
 
@@ -1013,6 +1189,7 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
             Type::F32 => intrinsics.f32_zero.as_basic_value_enum(),
             Type::F64 => intrinsics.f64_zero.as_basic_value_enum(),
             Type::V128 => intrinsics.i128_zero.as_basic_value_enum(),
+            Type::FuncRef | Type::ExternRef => intrinsics.i64_zero.as_basic_value_enum(),
         };
 
         let builder = self.builder.as_ref().unwrap();
@@ -1063,7 +1240,12 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
         cache_builder.position_before(&entry_end_inst);
         let module_info =
             unsafe { ::std::mem::transmute::<&ModuleInfo, &'static ModuleInfo>(module_info) };
-        let ctx = CtxType::new(module_info, &self.function, cache_builder);
+        let ctx_ptr_value = self
+            .function
+            .get_nth_param(vmctx_param_index(&self.func_sig))
+            .unwrap()
+            .into_pointer_value();
+        let ctx = CtxType::new(module_info, ctx_ptr_value, cache_builder);
 
         self.ctx = Some(ctx);
 
@@ -1095,6 +1277,16 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                     ::std::usize::MAX,
                 );
             }
+
+            if self.full_preemption {
+                // Faults if the instance is interrupted.
+                let signal_mem = self.ctx.as_mut().unwrap().signal_mem();
+                let iv = builder.build_store(
+                    signal_mem,
+                    self.context.unwrap().i8_type().const_int(0 as u64, false),
+                );
+                iv.set_volatile(true).unwrap();
+            }
         }
 
         Ok(())
@@ -1114,6 +1306,7 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
         let locals = &self.locals;
         let info = module_info;
         let signatures = &self.signatures;
+        let alloca_builder = self.alloca_builder.as_ref().unwrap();
         let mut ctx = self.ctx.as_mut().unwrap();
 
         let mut opcode_offset: Option<usize> = None;
@@ -1170,6 +1363,34 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                             );
                         }
                     }
+                    InternalEvent::IncrementCounter(idx) => {
+                        if state.reachable {
+                            let idx = idx as usize;
+                            let counter_ptr =
+                                ctx.counter(idx, intrinsics, self.module.clone(), builder);
+                            let count = builder.build_load(counter_ptr, "count").into_int_value();
+                            tbaa_label(
+                                &self.module,
+                                intrinsics,
+                                "counter",
+                                count.as_instruction_value().unwrap(),
+                                Some(idx as u32),
+                            );
+                            let count = builder.build_int_add(
+                                count,
+                                intrinsics.i64_ty.const_int(1, false),
+                                "count",
+                            );
+                            let store = builder.build_store(counter_ptr, count);
+                            tbaa_label(
+                                &self.module,
+                                intrinsics,
+                                "counter",
+                                store,
+                                Some(idx as u32),
+                            );
+                        }
+                    }
                 }
                 return Ok(());
             }
@@ -1212,37 +1433,50 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                 let end_block = context.append_basic_block(function, "end");
                 builder.position_at_end(&end_block);
 
-                let phis = if let Ok(wasmer_ty) = blocktype_to_type(ty) {
-                    let llvm_ty = type_to_llvm(intrinsics, wasmer_ty);
-                    [llvm_ty]
-                        .iter()
-                        .map(|&ty| builder.build_phi(ty, &state.var_name()))
-                        .collect()
-                } else {
-                    SmallVec::new()
-                };
+                let (params, results) = blocktype_to_types(ty, info)?;
+                let phis = results
+                    .iter()
+                    .map(|&ty| builder.build_phi(type_to_llvm(intrinsics, ty), &state.var_name()))
+                    .collect();
 
-                state.push_block(end_block, phis);
+                state.push_block(end_block, phis, params.len());
                 builder.position_at_end(&current_block);
             }
             Operator::Loop { ty } => {
+                let current_block = builder.get_insert_block().ok_or(CodegenError {
+                    message: "not currently in a block".to_string(),
+                })?;
+
                 let loop_body = context.append_basic_block(function, "loop_body");
                 let loop_next = context.append_basic_block(function, "loop_outer");
 
+                let (params, results) = blocktype_to_types(ty, info)?;
+
+                // The params of the loop flow into phis at the top of the body, which
+                // branches back to the loop also feed.
+                let param_values: Vec<_> = state
+                    .popn_save_extra(params.len())?
+                    .into_iter()
+                    .map(|(v, info)| apply_pending_canonicalization(builder, intrinsics, v, info))
+                    .collect();
+
                 builder.build_unconditional_branch(&loop_body);
 
                 builder.position_at_end(&loop_next);
-                let phis = if let Ok(wasmer_ty) = blocktype_to_type(ty) {
-                    let llvm_ty = type_to_llvm(intrinsics, wasmer_ty);
-                    [llvm_ty]
-                        .iter()
-                        .map(|&ty| builder.build_phi(ty, &state.var_name()))
-                        .collect()
-                } else {
-                    SmallVec::new()
-                };
+                let phis = results
+                    .iter()
+                    .map(|&ty| builder.build_phi(type_to_llvm(intrinsics, ty), &state.var_name()))
+                    .collect();
 
                 builder.position_at_end(&loop_body);
+                let body_phis: SmallVec<[PhiValue; 1]> = params
+                    .iter()
+                    .map(|&ty| builder.build_phi(type_to_llvm(intrinsics, ty), &state.var_name()))
+                    .collect();
+                for (phi, value) in body_phis.iter().zip(param_values.iter()) {
+                    phi.add_incoming(&[(value, &current_block)]);
+                    state.push1(phi.as_basic_value());
+                }
 
                 if self.track_state {
                     if let Some(offset) = opcode_offset {
@@ -1273,9 +1507,15 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                             offset,
                         );
                     }
+                } else if self.full_preemption {
+                    // Faults if the instance is interrupted.
+                    let signal_mem = ctx.signal_mem();
+                    let iv = builder
+                        .build_store(signal_mem, context.i8_type().const_int(0 as u64, false));
+                    iv.set_volatile(true).unwrap();
                 }
 
-                state.push_loop(loop_body, loop_next, phis);
+                state.push_loop(loop_body, loop_next, body_phis, phis);
             }
             Operator::Br { relative_depth } => {
                 let frame = state.frame_at_depth(relative_depth)?;
@@ -1284,21 +1524,17 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                     message: "not currently in a block".to_string(),
                 })?;
 
-                let value_len = if frame.is_loop() {
-                    0
-                } else {
-                    frame.phis().len()
-                };
+                let value_len = frame.br_phis().len();
 
                 let values = state.peekn_extra(value_len)?;
                 let values = values.iter().map(|(v, info)| {
                     apply_pending_canonicalization(builder, intrinsics, *v, *info)
                 });
 
-                // For each result of the block we're branching to,
+                // For each value the block we're branching to expects,
                 // pop a value off the value stack and load it into
                 // the corresponding phi.
-                for (phi, value) in frame.phis().iter().zip(values) {
+                for (phi, value) in frame.br_phis().iter().zip(values) {
                     phi.add_incoming(&[(&value, &current_block)]);
                 }
 
@@ -1315,18 +1551,14 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                     message: "not currently in a block".to_string(),
                 })?;
 
-                let value_len = if frame.is_loop() {
-                    0
-                } else {
-                    frame.phis().len()
-                };
+                let value_len = frame.br_phis().len();
 
                 let param_stack = state.peekn_extra(value_len)?;
                 let param_stack = param_stack.iter().map(|(v, info)| {
                     apply_pending_canonicalization(builder, intrinsics, *v, *info)
                 });
 
-                for (phi, value) in frame.phis().iter().zip(param_stack) {
+                for (phi, value) in frame.br_phis().iter().zip(param_stack) {
                     phi.add_incoming(&[(&value, &current_block)]);
                 }
 
@@ -1352,14 +1584,9 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
 
                 let default_frame = state.frame_at_depth(default_depth)?;
 
-                let args = if default_frame.is_loop() {
-                    Vec::new()
-                } else {
-                    let res_len = default_frame.phis().len();
-                    state.peekn(res_len)?
-                };
+                let args = state.peekn(default_frame.br_phis().len())?;
 
-                for (phi, value) in default_frame.phis().iter().zip(args.iter()) {
+                for (phi, value) in default_frame.br_phis().iter().zip(args.iter()) {
                     phi.add_incoming(&[(value, &current_block)]);
                 }
 
@@ -1376,7 +1603,7 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                         let case_index_literal =
                             context.i32_type().const_int(case_index as u64, false);
 
-                        for (phi, value) in frame.phis().iter().zip(args.iter()) {
+                        for (phi, value) in frame.br_phis().iter().zip(args.iter()) {
                             phi.add_incoming(&[(value, &current_block)]);
                         }
 
@@ -1398,24 +1625,24 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                 let if_else_block = context.append_basic_block(function, "if_else");
                 let end_block = context.append_basic_block(function, "if_end");
 
+                let (params, results) = blocktype_to_types(ty, info)?;
+
                 let end_phis = {
                     builder.position_at_end(&end_block);
 
-                    let phis = if let Ok(wasmer_ty) = blocktype_to_type(ty) {
-                        let llvm_ty = type_to_llvm(intrinsics, wasmer_ty);
-                        [llvm_ty]
-                            .iter()
-                            .map(|&ty| builder.build_phi(ty, &state.var_name()))
-                            .collect()
-                    } else {
-                        SmallVec::new()
-                    };
+                    let phis = results
+                        .iter()
+                        .map(|&ty| {
+                            builder.build_phi(type_to_llvm(intrinsics, ty), &state.var_name())
+                        })
+                        .collect();
 
                     builder.position_at_end(&current_block);
                     phis
                 };
 
                 let cond = state.pop1()?;
+                let else_params = state.peekn_extra(params.len())?.iter().cloned().collect();
 
                 let cond_value = builder.build_int_compare(
                     IntPredicate::NE,
@@ -1426,7 +1653,13 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
 
                 builder.build_conditional_branch(cond_value, &if_then_block, &if_else_block);
                 builder.position_at_end(&if_then_block);
-                state.push_if(if_then_block, if_else_block, end_block, end_phis);
+                state.push_if(
+                    if_then_block,
+                    if_else_block,
+                    end_block,
+                    else_params,
+                    end_phis,
+                );
             }
             Operator::Else => {
                 if state.reachable {
@@ -1459,6 +1692,7 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                 *if_else_state = IfElseState::Else;
 
                 builder.position_at_end(if_else_block);
+                state.reset_stack_for_else()?;
                 state.reachable = true;
             }
 
@@ -1483,11 +1717,19 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                     if_else,
                     next,
                     if_else_state,
+                    else_params,
+                    phis,
                     ..
                 } = &frame
                 {
                     if let IfElseState::If = if_else_state {
+                        // Without an `else`, the params of the `if` are its results.
                         builder.position_at_end(if_else);
+                        for (phi, (value, info)) in phis.iter().zip(else_params.iter()) {
+                            let value =
+                                apply_pending_canonicalization(builder, intrinsics, *value, *info);
+                            phi.add_incoming(&[(&value, if_else)]);
+                        }
                         builder.build_unconditional_branch(next);
                     }
                 }
@@ -1527,7 +1769,7 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                 })?;
 
                 let frame = state.outermost_frame()?;
-                for phi in frame.phis().to_vec().iter() {
+                for phi in frame.phis().to_vec().iter().rev() {
                     let (arg, info) = state.pop1_extra()?;
                     let arg = apply_pending_canonicalization(builder, intrinsics, arg, info);
                     phi.add_incoming(&[(&arg, &current_block)]);
@@ -1815,7 +2057,7 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                 }
             }
 
-            Operator::Select => {
+            Operator::Select | Operator::TypedSelect { .. } => {
                 let ((v1, i1), (v2, i2), (cond, _)) = state.pop3_extra()?;
                 // We don't bother canonicalizing 'cond' here because we only
                 // compare it to zero, and that's invariant under
@@ -1863,7 +2105,7 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                 let llvm_sig = signatures[sigindex];
                 let func_sig = &info.signatures[sigindex];
 
-                let (params, func_ptr) = match func_index.local_or_import(info) {
+                let (mut params, func_ptr) = match func_index.local_or_import(info) {
                     LocalOrImport::Local(_) => {
                         let params: Vec<_> = std::iter::once(ctx.basic())
                             .chain(
@@ -1943,6 +2185,19 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                 };
 
                 state.popn(func_sig.params().len())?;
+
+                let results = results_layout(func_sig).map(|layout| {
+                    let buffer = build_results_buffer(alloca_builder, intrinsics, &layout);
+                    (layout, buffer)
+                });
+                match results {
+                    // The pointer to the results comes before vmctx.
+                    Some((ref layout, buffer)) if layout.in_memory() => {
+                        params.insert(0, buffer.as_basic_value_enum())
+                    }
+                    _ => {}
+                }
+
                 if self.track_state {
                     if let Some(offset) = opcode_offset {
                         let mut stackmaps = self.stackmaps.borrow_mut();
@@ -1975,20 +2230,14 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                     }
                 }
 
-                if let Some(basic_value) = call_site.try_as_basic_value().left() {
-                    match func_sig.returns().len() {
-                        1 => state.push1(basic_value),
-                        count @ _ => {
-                            // This is a multi-value return.
-                            let struct_value = basic_value.into_struct_value();
-                            for i in 0..(count as u32) {
-                                let value = builder
-                                    .build_extract_value(struct_value, i, &state.var_name())
-                                    .unwrap();
-                                state.push1(value);
-                            }
-                        }
+                if let Some((layout, buffer)) = results {
+                    for value in
+                        build_call_results(builder, context, intrinsics, &layout, buffer, call_site)
+                    {
+                        state.push1(value);
                     }
+                } else if let Some(basic_value) = call_site.try_as_basic_value().left() {
+                    state.push1(basic_value);
                 }
             }
             Operator::CallIndirect { index, table_index } => {
@@ -2039,6 +2288,13 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                     )
                 };
 
+                // A null context means the function takes the context of its caller.
+                let ctx_ptr = {
+                    let ctx_ptr = ctx_ptr.into_pointer_value();
+                    let is_null = builder.build_is_null(ctx_ptr, "ctx_is_null");
+                    builder.build_select(is_null, ctx.basic(), ctx_ptr.into(), "ctx_ptr")
+                };
+
                 let truncated_table_bounds = builder.build_int_truncate(
                     table_bound,
                     intrinsics.i32_ty,
@@ -2132,7 +2388,21 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
 
                 let pushed_args = state.popn_save_extra(wasmer_fn_sig.params().len())?;
 
-                let args: Vec<_> = std::iter::once(ctx_ptr)
+                let results = results_layout(wasmer_fn_sig).map(|layout| {
+                    let buffer = build_results_buffer(alloca_builder, intrinsics, &layout);
+                    (layout, buffer)
+                });
+
+                // The pointer to the results, if any, comes before vmctx.
+                let results_ptr = match results {
+                    Some((ref layout, buffer)) if layout.in_memory() => {
+                        Some(buffer.as_basic_value_enum())
+                    }
+                    _ => None,
+                };
+                let args: Vec<_> = results_ptr
+                    .into_iter()
+                    .chain(std::iter::once(ctx_ptr))
                     .chain(pushed_args.into_iter().enumerate().map(|(i, (v, info))| {
                         match wasmer_fn_sig.params()[i] {
                             Type::F32 => builder.build_bitcast(
@@ -2206,10 +2476,12 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                         });
                     }
                     _ => {
-                        return Err(CodegenError {
-                            message: "Operator::CallIndirect multi-value returns unimplemented"
-                                .to_string(),
-                        });
+                        let (layout, buffer) = results.unwrap();
+                        for value in build_call_results(
+                            builder, context, intrinsics, &layout, buffer, call_site,
+                        ) {
+                            state.push1(value);
+                        }
                     }
                 }
             }
@@ -8558,6 +8830,233 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                 );
                 state.push1(result.try_as_basic_value().left().unwrap());
             }
+
+            /***************************
+             * Reference types.
+             * https://github.com/WebAssembly/reference-types/blob/master/proposals/reference-types/Overview.md
+             ***************************/
+            Operator::RefNull => {
+                state.push1(intrinsics.i64_zero);
+            }
+            Operator::RefIsNull => {
+                let input = state.pop1()?.into_int_value();
+                let cond = builder.build_int_compare(
+                    IntPredicate::EQ,
+                    input,
+                    intrinsics.i64_zero,
+                    &state.var_name(),
+                );
+                let res = builder.build_int_z_extend(cond, intrinsics.i32_ty, &state.var_name());
+                state.push1(res);
+            }
+            Operator::RefFunc { function_index } => {
+                let function_index_const = intrinsics
+                    .i32_ty
+                    .const_int(function_index as u64, false)
+                    .as_basic_value_enum();
+                let result = builder.build_call(
+                    intrinsics.func_ref,
+                    &[ctx.basic(), function_index_const],
+                    &state.var_name(),
+                );
+                state.push1(result.try_as_basic_value().left().unwrap());
+            }
+            Operator::TableGet { table } => {
+                let (func_value, table_index_const) = table_vmcall(
+                    info,
+                    intrinsics,
+                    table,
+                    intrinsics.table_get_local,
+                    intrinsics.table_get_import,
+                );
+                let index = state.pop1()?;
+                let result = builder.build_call(
+                    func_value,
+                    &[ctx.basic(), table_index_const, index],
+                    &state.var_name(),
+                );
+                state.push1(result.try_as_basic_value().left().unwrap());
+            }
+            Operator::TableSet { table } => {
+                let (func_value, table_index_const) = table_vmcall(
+                    info,
+                    intrinsics,
+                    table,
+                    intrinsics.table_set_local,
+                    intrinsics.table_set_import,
+                );
+                let (index, value) = state.pop2()?;
+                builder.build_call(
+                    func_value,
+                    &[ctx.basic(), table_index_const, index, value],
+                    &state.var_name(),
+                );
+            }
+            Operator::TableGrow { table } => {
+                let (func_value, table_index_const) = table_vmcall(
+                    info,
+                    intrinsics,
+                    table,
+                    intrinsics.table_grow_local,
+                    intrinsics.table_grow_import,
+                );
+                let (init, delta) = state.pop2()?;
+                let result = builder.build_call(
+                    func_value,
+                    &[ctx.basic(), table_index_const, delta, init],
+                    &state.var_name(),
+                );
+                state.push1(result.try_as_basic_value().left().unwrap());
+            }
+            Operator::TableSize { table } => {
+                let (func_value, table_index_const) = table_vmcall(
+                    info,
+                    intrinsics,
+                    table,
+                    intrinsics.table_size_local,
+                    intrinsics.table_size_import,
+                );
+                let result = builder.build_call(
+                    func_value,
+                    &[ctx.basic(), table_index_const],
+                    &state.var_name(),
+                );
+                state.push1(result.try_as_basic_value().left().unwrap());
+            }
+            Operator::TableFill { table } => {
+                let (func_value, table_index_const) = table_vmcall(
+                    info,
+                    intrinsics,
+                    table,
+                    intrinsics.table_fill_local,
+                    intrinsics.table_fill_import,
+                );
+                let ((start, _), (value, _), (len, _)) = state.pop3_extra()?;
+                builder.build_call(
+                    func_value,
+                    &[ctx.basic(), table_index_const, start, value, len],
+                    &state.var_name(),
+                );
+            }
+
+            /***************************
+             * Bulk memory operations.
+             * https://github.com/WebAssembly/bulk-memory-operations/blob/master/proposals/bulk-memory-operations/Overview.md
+             ***************************/
+            Operator::MemoryCopy => {
+                let (func_value, memory_index_const) = memory_vmcall(
+                    info,
+                    intrinsics,
+                    0,
+                    intrinsics.memory_copy_local,
+                    intrinsics.memory_copy_import,
+                );
+                let ((dst, _), (src, _), (len, _)) = state.pop3_extra()?;
+                builder.build_call(
+                    func_value,
+                    &[ctx.basic(), memory_index_const, dst, src, len],
+                    &state.var_name(),
+                );
+            }
+            Operator::MemoryFill => {
+                let (func_value, memory_index_const) = memory_vmcall(
+                    info,
+                    intrinsics,
+                    0,
+                    intrinsics.memory_fill_local,
+                    intrinsics.memory_fill_import,
+                );
+                let ((dst, _), (val, _), (len, _)) = state.pop3_extra()?;
+                builder.build_call(
+                    func_value,
+                    &[ctx.basic(), memory_index_const, dst, val, len],
+                    &state.var_name(),
+                );
+            }
+            Operator::MemoryInit { segment } => {
+                let (func_value, memory_index_const) = memory_vmcall(
+                    info,
+                    intrinsics,
+                    0,
+                    intrinsics.memory_init_local,
+                    intrinsics.memory_init_import,
+                );
+                let segment_const = intrinsics
+                    .i32_ty
+                    .const_int(segment as u64, false)
+                    .as_basic_value_enum();
+                let ((dst, _), (src, _), (len, _)) = state.pop3_extra()?;
+                builder.build_call(
+                    func_value,
+                    &[
+                        ctx.basic(),
+                        memory_index_const,
+                        segment_const,
+                        dst,
+                        src,
+                        len,
+                    ],
+                    &state.var_name(),
+                );
+            }
+            Operator::DataDrop { segment } => {
+                let segment_const = intrinsics
+                    .i32_ty
+                    .const_int(segment as u64, false)
+                    .as_basic_value_enum();
+                builder.build_call(
+                    intrinsics.data_drop,
+                    &[ctx.basic(), segment_const],
+                    &state.var_name(),
+                );
+            }
+            Operator::TableCopy {
+                dst_table,
+                src_table,
+            } => {
+                // Either table may be local or imported, so `table_copy` takes module-wide indices.
+                let dst_table_const = intrinsics
+                    .i32_ty
+                    .const_int(dst_table as u64, false)
+                    .as_basic_value_enum();
+                let src_table_const = intrinsics
+                    .i32_ty
+                    .const_int(src_table as u64, false)
+                    .as_basic_value_enum();
+                let ((dst, _), (src, _), (len, _)) = state.pop3_extra()?;
+                builder.build_call(
+                    intrinsics.table_copy,
+                    &[ctx.basic(), dst_table_const, src_table_const, dst, src, len],
+                    &state.var_name(),
+                );
+            }
+            Operator::TableInit { segment, table } => {
+                let table_const = intrinsics
+                    .i32_ty
+                    .const_int(table as u64, false)
+                    .as_basic_value_enum();
+                let segment_const = intrinsics
+                    .i32_ty
+                    .const_int(segment as u64, false)
+                    .as_basic_value_enum();
+                let ((dst, _), (src, _), (len, _)) = state.pop3_extra()?;
+                builder.build_call(
+                    intrinsics.table_init,
+                    &[ctx.basic(), table_const, segment_const, dst, src, len],
+                    &state.var_name(),
+                );
+            }
+            Operator::ElemDrop { segment } => {
+                let segment_const = intrinsics
+                    .i32_ty
+                    .const_int(segment as u64, false)
+                    .as_basic_value_enum();
+                builder.build_call(
+                    intrinsics.elem_drop,
+                    &[ctx.basic(), segment_const],
+                    &state.var_name(),
+                );
+            }
             _ => {
                 return Err(CodegenError {
                     message: format!("Operator {:?} unimplemented", op),
@@ -8590,10 +9089,36 @@ impl<'ctx> FunctionCodeGenerator<CodegenError> for LLVMFunctionCodeGenerator<'ct
                     "return",
                 )));
             }
-            _ => {
-                return Err(CodegenError {
-                    message: "multi-value returns not yet implemented".to_string(),
-                });
+            results => {
+                let builder = self.builder.as_ref().unwrap();
+                let intrinsics = self.intrinsics.as_ref().unwrap();
+                let layout = results_layout(&self.func_sig).unwrap();
+                let values: Vec<_> = results
+                    .iter()
+                    .map(|(value, info)| {
+                        apply_pending_canonicalization(builder, intrinsics, *value, *info)
+                    })
+                    .collect();
+
+                if layout.in_memory() {
+                    let buffer = self.function.get_nth_param(0).unwrap().into_pointer_value();
+                    build_store_results(builder, intrinsics, &layout, buffer, &values);
+                    builder.build_return(Some(&buffer));
+                } else {
+                    let buffer = build_results_buffer(
+                        self.alloca_builder.as_ref().unwrap(),
+                        intrinsics,
+                        &layout,
+                    );
+                    build_store_results(builder, intrinsics, &layout, buffer, &values);
+                    let eightbytes_ptr = builder.build_pointer_cast(
+                        buffer,
+                        eightbytes_type(self.context.as_ref().unwrap(), intrinsics, &layout)
+                            .ptr_type(AddressSpace::Generic),
+                        "eightbytes_ptr",
+                    );
+                    builder.build_return(Some(&builder.build_load(eightbytes_ptr, "eightbytes")));
+                }
             }
         }
         Ok(())
@@ -8721,6 +9246,7 @@ impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, Cod
             personality_func: ManuallyDrop::new(personality_func),
             stackmaps: Rc::new(RefCell::new(StackmapRegistry::default())),
             track_state: false,
+            full_preemption: false,
             target_machine,
             llvm_callbacks: None,
         }
@@ -8775,14 +9301,14 @@ impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, Cod
             .map(|ty| builder.build_phi(ty, &state.var_name()))
             .collect();
 
-        state.push_block(return_block, phis);
+        state.push_block(return_block, phis, 0);
         builder.position_at_end(&entry_block);
 
         let mut locals = Vec::new();
         locals.extend(
             function
                 .get_param_iter()
-                .skip(1)
+                .skip(1 + vmctx_param_index(&func_sig) as usize)
                 .enumerate()
                 .map(|(index, param)| {
                     let real_ty = func_sig.params()[index];
@@ -8834,6 +9360,7 @@ impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, Cod
             index: local_func_index,
             opcode_offset: 0,
             track_state: self.track_state,
+            full_preemption: self.full_preemption,
             module: (*self.module).clone(),
         };
         self.functions.push(code);
@@ -8943,6 +9470,7 @@ impl<'ctx> ModuleCodeGenerator<LLVMFunctionCodeGenerator<'ctx>, LLVMBackend, Cod
 
     fn feed_compiler_config(&mut self, config: &CompilerConfig) -> Result<(), CodegenError> {
         self.track_state = config.track_state;
+        self.full_preemption = config.full_preemption;
         if let Some(backend_compiler_config) = &config.backend_specific_config {
             if let Some(llvm_config) = backend_compiler_config.get_specific::<LLVMBackendConfig>() {
                 self.llvm_callbacks = llvm_config.callbacks.clone();
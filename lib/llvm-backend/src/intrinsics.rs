@@ -30,13 +30,14 @@ use wasmer_runtime_core::{
     vm::{Ctx, INTERNALS_SIZE},
 };
 
-fn type_to_llvm_ptr<'ctx>(intrinsics: &Intrinsics<'ctx>, ty: Type) -> PointerType<'ctx> {
+pub fn type_to_llvm_ptr<'ctx>(intrinsics: &Intrinsics<'ctx>, ty: Type) -> PointerType<'ctx> {
     match ty {
         Type::I32 => intrinsics.i32_ptr_ty,
         Type::I64 => intrinsics.i64_ptr_ty,
         Type::F32 => intrinsics.f32_ptr_ty,
         Type::F64 => intrinsics.f64_ptr_ty,
         Type::V128 => intrinsics.i128_ptr_ty,
+        Type::FuncRef | Type::ExternRef => intrinsics.i64_ptr_ty,
     }
 }
 
@@ -149,6 +150,30 @@ pub struct Intrinsics<'ctx> {
     pub memory_size_static_import: FunctionValue<'ctx>,
     pub memory_size_shared_import: FunctionValue<'ctx>,
 
+    pub table_grow_local: FunctionValue<'ctx>,
+    pub table_grow_import: FunctionValue<'ctx>,
+    pub table_size_local: FunctionValue<'ctx>,
+    pub table_size_import: FunctionValue<'ctx>,
+    pub table_get_local: FunctionValue<'ctx>,
+    pub table_get_import: FunctionValue<'ctx>,
+    pub table_set_local: FunctionValue<'ctx>,
+    pub table_set_import: FunctionValue<'ctx>,
+    pub table_fill_local: FunctionValue<'ctx>,
+    pub table_fill_import: FunctionValue<'ctx>,
+
+    pub func_ref: FunctionValue<'ctx>,
+
+    pub memory_copy_local: FunctionValue<'ctx>,
+    pub memory_copy_import: FunctionValue<'ctx>,
+    pub memory_fill_local: FunctionValue<'ctx>,
+    pub memory_fill_import: FunctionValue<'ctx>,
+    pub memory_init_local: FunctionValue<'ctx>,
+    pub memory_init_import: FunctionValue<'ctx>,
+    pub data_drop: FunctionValue<'ctx>,
+    pub table_copy: FunctionValue<'ctx>,
+    pub table_init: FunctionValue<'ctx>,
+    pub elem_drop: FunctionValue<'ctx>,
+
     pub throw_trap: FunctionValue<'ctx>,
     pub throw_breakpoint: FunctionValue<'ctx>,
 
@@ -228,6 +253,7 @@ impl<'ctx> Intrinsics<'ctx> {
         let memory_bound_ty = i8_ty;
         let internals_ty = i64_ty;
         let interrupt_signal_mem_ty = i8_ty;
+        let counters_ty = i64_ty;
         let local_function_ty = i8_ptr_ty;
 
         let anyfunc_ty = context.struct_type(
@@ -289,6 +315,9 @@ impl<'ctx> Intrinsics<'ctx> {
                 interrupt_signal_mem_ty
                     .ptr_type(AddressSpace::Generic)
                     .as_basic_type_enum(),
+                counters_ty
+                    .ptr_type(AddressSpace::Generic)
+                    .as_basic_type_enum(),
                 local_function_ty
                     .ptr_type(AddressSpace::Generic)
                     .as_basic_type_enum(),
@@ -319,6 +348,63 @@ impl<'ctx> Intrinsics<'ctx> {
         );
         let ret_i32_take_ctx_i32 =
             i32_ty.fn_type(&[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic], false);
+        let ret_i32_take_ctx_i32_i32_i64 = i32_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i32_ty_basic,
+                i64_ty_basic,
+            ],
+            false,
+        );
+        let ret_i64_take_ctx_i32 =
+            i64_ty.fn_type(&[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic], false);
+        let ret_i64_take_ctx_i32_i32 = i64_ty.fn_type(
+            &[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic, i32_ty_basic],
+            false,
+        );
+        let void_take_ctx_i32_i32_i64 = void_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i32_ty_basic,
+                i64_ty_basic,
+            ],
+            false,
+        );
+        let void_take_ctx_i32_i32_i64_i32 = void_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i32_ty_basic,
+                i64_ty_basic,
+                i32_ty_basic,
+            ],
+            false,
+        );
+        let void_take_ctx_i32 =
+            void_ty.fn_type(&[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic], false);
+        let void_take_ctx_i32_i32_i32_i32 = void_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+            ],
+            false,
+        );
+        let void_take_ctx_i32_i32_i32_i32_i32 = void_ty.fn_type(
+            &[
+                ctx_ptr_ty.as_basic_type_enum(),
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+                i32_ty_basic,
+            ],
+            false,
+        );
 
         let ret_i1_take_i1_i1 = i1_ty.fn_type(&[i1_ty_basic, i1_ty_basic], false);
         let intrinsics = Self {
@@ -508,6 +594,101 @@ impl<'ctx> Intrinsics<'ctx> {
                 ret_i32_take_ctx_i32,
                 None,
             ),
+            table_grow_local: module.add_function(
+                "vm.table.grow.local",
+                ret_i32_take_ctx_i32_i32_i64,
+                None,
+            ),
+            table_grow_import: module.add_function(
+                "vm.table.grow.import",
+                ret_i32_take_ctx_i32_i32_i64,
+                None,
+            ),
+            table_size_local: module.add_function(
+                "vm.table.size.local",
+                ret_i32_take_ctx_i32,
+                None,
+            ),
+            table_size_import: module.add_function(
+                "vm.table.size.import",
+                ret_i32_take_ctx_i32,
+                None,
+            ),
+            table_get_local: module.add_function(
+                "vm.table.get.local",
+                ret_i64_take_ctx_i32_i32,
+                None,
+            ),
+            table_get_import: module.add_function(
+                "vm.table.get.import",
+                ret_i64_take_ctx_i32_i32,
+                None,
+            ),
+            table_set_local: module.add_function(
+                "vm.table.set.local",
+                void_take_ctx_i32_i32_i64,
+                None,
+            ),
+            table_set_import: module.add_function(
+                "vm.table.set.import",
+                void_take_ctx_i32_i32_i64,
+                None,
+            ),
+            table_fill_local: module.add_function(
+                "vm.table.fill.local",
+                void_take_ctx_i32_i32_i64_i32,
+                None,
+            ),
+            table_fill_import: module.add_function(
+                "vm.table.fill.import",
+                void_take_ctx_i32_i32_i64_i32,
+                None,
+            ),
+
+            func_ref: module.add_function("vm.func.ref", ret_i64_take_ctx_i32, None),
+
+            memory_copy_local: module.add_function(
+                "vm.memory.copy.local",
+                void_take_ctx_i32_i32_i32_i32,
+                None,
+            ),
+            memory_copy_import: module.add_function(
+                "vm.memory.copy.import",
+                void_take_ctx_i32_i32_i32_i32,
+                None,
+            ),
+            memory_fill_local: module.add_function(
+                "vm.memory.fill.local",
+                void_take_ctx_i32_i32_i32_i32,
+                None,
+            ),
+            memory_fill_import: module.add_function(
+                "vm.memory.fill.import",
+                void_take_ctx_i32_i32_i32_i32,
+                None,
+            ),
+            memory_init_local: module.add_function(
+                "vm.memory.init.local",
+                void_take_ctx_i32_i32_i32_i32_i32,
+                None,
+            ),
+            memory_init_import: module.add_function(
+                "vm.memory.init.import",
+                void_take_ctx_i32_i32_i32_i32_i32,
+                None,
+            ),
+            data_drop: module.add_function("vm.data.drop", void_take_ctx_i32, None),
+            table_copy: module.add_function(
+                "vm.table.copy",
+                void_take_ctx_i32_i32_i32_i32_i32,
+                None,
+            ),
+            table_init: module.add_function(
+                "vm.table.init",
+                void_take_ctx_i32_i32_i32_i32_i32,
+                None,
+            ),
+            elem_drop: module.add_function("vm.elem.drop", void_take_ctx_i32, None),
             throw_trap: module.add_function(
                 "vm.exception.trap",
                 void_ty.fn_type(&[i32_ty_basic], false),
@@ -552,6 +733,12 @@ impl<'ctx> Intrinsics<'ctx> {
         intrinsics
             .memory_size_shared_import
             .add_attribute(AttributeLoc::Function, readonly);
+        intrinsics
+            .table_size_local
+            .add_attribute(AttributeLoc::Function, readonly);
+        intrinsics
+            .table_size_import
+            .add_attribute(AttributeLoc::Function, readonly);
 
         let noreturn =
             context.create_enum_attribute(Attribute::get_named_enum_kind_id("noreturn"), 0);
@@ -622,11 +809,11 @@ fn offset_to_index(offset: u8) -> u32 {
 impl<'a, 'ctx> CtxType<'a, 'ctx> {
     pub fn new(
         info: &'a ModuleInfo,
-        func_value: &FunctionValue<'ctx>,
+        ctx_ptr_value: PointerValue<'ctx>,
         cache_builder: Builder<'ctx>,
     ) -> CtxType<'a, 'ctx> {
         CtxType {
-            ctx_ptr_value: func_value.get_nth_param(0).unwrap().into_pointer_value(),
+            ctx_ptr_value,
 
             info,
             cache_builder,
@@ -1150,6 +1337,39 @@ impl<'a, 'ctx> CtxType<'a, 'ctx> {
             )
         }
     }
+
+    pub fn counter(
+        &mut self,
+        index: usize,
+        intrinsics: &Intrinsics<'ctx>,
+        module: Rc<RefCell<Module<'ctx>>>,
+        builder: &Builder<'ctx>,
+    ) -> PointerValue<'ctx> {
+        let counters_ptr_ptr = unsafe {
+            builder.build_struct_gep(
+                self.ctx_ptr_value,
+                offset_to_index(Ctx::offset_counters()),
+                "counters_ptr_ptr",
+            )
+        };
+        let counters_ptr = builder
+            .build_load(counters_ptr_ptr, "counters_ptr")
+            .into_pointer_value();
+        tbaa_label(
+            &module,
+            intrinsics,
+            "context_field_ptr_to_counters",
+            counters_ptr.as_instruction_value().unwrap(),
+            None,
+        );
+        unsafe {
+            builder.build_in_bounds_gep(
+                counters_ptr,
+                &[intrinsics.i64_ty.const_int(index as u64, false)],
+                "counter_ptr",
+            )
+        }
+    }
 }
 
 // Given an instruction that operates on memory, mark the access as not aliasing
@@ -8,6 +8,7 @@ use nix::sys::signal::{
     sigaction, SaFlags, SigAction, SigHandler, SigSet, SIGBUS, SIGILL, SIGSEGV,
 };
 use std::ptr;
+use wasmer_runtime_core::fault::{get_fault_info, take_interrupt};
 
 /// `__register_frame` and `__deregister_frame` on macos take a single fde as an
 /// argument, so we need to parse the fde table here.
@@ -48,7 +49,7 @@ pub unsafe fn visit_fde(addr: *mut u8, _size: usize, visitor: extern "C" fn(*mut
 
 extern "C" {
     #[cfg_attr(nightly, unwind(allowed))]
-    fn throw_trap(ty: i32) -> !;
+    fn throw_trap_at(ty: i32, ip: usize) -> !;
 }
 
 pub unsafe fn install_signal_handler() {
@@ -65,8 +66,8 @@ pub unsafe fn install_signal_handler() {
 #[cfg_attr(nightly, unwind(allowed))]
 extern "C" fn signal_trap_handler(
     _signum: ::nix::libc::c_int,
-    _siginfo: *mut siginfo_t,
-    _ucontext: *mut c_void,
+    siginfo: *mut siginfo_t,
+    ucontext: *mut c_void,
 ) {
     unsafe {
         if SigSet::all().thread_unblock().is_err() {
@@ -77,7 +78,13 @@ extern "C" fn signal_trap_handler(
         // was interrupted.
         //
         // This works on macos, not sure about linux.
-        throw_trap(2);
+        let fault = get_fault_info(siginfo as _, ucontext);
+        let ip = fault.ip.get();
+        if take_interrupt(fault.faulting_addr) {
+            // `WasmTrapType::Interrupted`
+            throw_trap_at(8, ip);
+        }
+        throw_trap_at(2, ip);
     }
 }
 
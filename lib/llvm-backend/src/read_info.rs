@@ -1,17 +1,26 @@
 use crate::code::CodegenError;
+use wasmer_runtime_core::module::ModuleInfo;
 use wasmer_runtime_core::parse::wp_type_to_type;
-use wasmer_runtime_core::types::Type;
-use wasmparser::TypeOrFuncType as WpTypeOrFuncType;
+use wasmer_runtime_core::structures::TypedIndex;
+use wasmer_runtime_core::types::{SigIndex, Type};
+use wasmparser::{Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
 
-pub fn blocktype_to_type(ty: WpTypeOrFuncType) -> Result<Type, CodegenError> {
+/// Returns the params and results of a block type.
+pub fn blocktype_to_types(
+    ty: WpTypeOrFuncType,
+    info: &ModuleInfo,
+) -> Result<(Vec<Type>, Vec<Type>), CodegenError> {
     match ty {
-        WpTypeOrFuncType::Type(inner_ty) => Ok(wp_type_to_type(inner_ty)?),
-        _ => {
-            return Err(CodegenError {
-                message:
-                    "the wasmer llvm backend does not yet support the multi-value return extension"
-                        .to_string(),
-            });
+        WpTypeOrFuncType::Type(WpType::EmptyBlockType) => Ok((vec![], vec![])),
+        WpTypeOrFuncType::Type(inner_ty) => Ok((vec![], vec![wp_type_to_type(inner_ty)?])),
+        WpTypeOrFuncType::FuncType(idx) => {
+            let sig = info
+                .signatures
+                .get(SigIndex::new(idx as usize))
+                .ok_or_else(|| CodegenError {
+                    message: format!("invalid block type index {}", idx),
+                })?;
+            Ok((sig.params().to_vec(), sig.returns().to_vec()))
         }
     }
 }
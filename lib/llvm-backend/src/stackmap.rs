@@ -269,6 +269,7 @@ impl StackmapEntry {
             wasm_stack_pop: 0,
             wasm_stack_private_depth: 0,
             wasm_inst_offset: self.opcode_offset,
+            wasm_source_loc: None,
         };
         let diff_id = fsm.diffs.len();
         fsm.diffs.push(diff);
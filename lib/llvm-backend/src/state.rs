@@ -17,6 +17,7 @@ pub enum ControlFrame<'ctx> {
     Loop {
         body: BasicBlock,
         next: BasicBlock,
+        body_phis: SmallVec<[PhiValue<'ctx>; 1]>,
         phis: SmallVec<[PhiValue<'ctx>; 1]>,
         stack_size_snapshot: usize,
     },
@@ -24,6 +25,7 @@ pub enum ControlFrame<'ctx> {
         if_then: BasicBlock,
         if_else: BasicBlock,
         next: BasicBlock,
+        else_params: SmallVec<[(BasicValueEnum<'ctx>, ExtraInfo); 1]>,
         phis: SmallVec<[PhiValue<'ctx>; 1]>,
         stack_size_snapshot: usize,
         if_else_state: IfElseState,
@@ -60,6 +62,15 @@ impl<'ctx> ControlFrame<'ctx> {
         }
     }
 
+    /// Returns the phis receiving the values of branches to this frame: the params of a
+    /// loop, or the results of another block.
+    pub fn br_phis(&self) -> &[PhiValue<'ctx>] {
+        match self {
+            ControlFrame::Loop { ref body_phis, .. } => body_phis.as_slice(),
+            _ => self.phis(),
+        }
+    }
+
     pub fn is_loop(&self) -> bool {
         match self {
             ControlFrame::Loop { .. } => true,
@@ -367,42 +378,74 @@ impl<'ctx> State<'ctx> {
         Ok(())
     }
 
-    pub fn push_block(&mut self, next: BasicBlock, phis: SmallVec<[PhiValue<'ctx>; 1]>) {
+    /// Pushes a block whose `num_params` params are at the top of the value stack.
+    pub fn push_block(
+        &mut self,
+        next: BasicBlock,
+        phis: SmallVec<[PhiValue<'ctx>; 1]>,
+        num_params: usize,
+    ) {
         self.control_stack.push(ControlFrame::Block {
             next,
             phis,
-            stack_size_snapshot: self.stack.len(),
+            stack_size_snapshot: self.stack.len() - num_params,
         });
     }
 
+    /// Pushes a loop whose params, the values of `body_phis`, are at the top of the value
+    /// stack.
     pub fn push_loop(
         &mut self,
         body: BasicBlock,
         next: BasicBlock,
+        body_phis: SmallVec<[PhiValue<'ctx>; 1]>,
         phis: SmallVec<[PhiValue<'ctx>; 1]>,
     ) {
         self.control_stack.push(ControlFrame::Loop {
             body,
             next,
+            stack_size_snapshot: self.stack.len() - body_phis.len(),
+            body_phis,
             phis,
-            stack_size_snapshot: self.stack.len(),
         });
     }
 
+    /// Pushes an `if` whose params, `else_params`, are at the top of the value stack.
     pub fn push_if(
         &mut self,
         if_then: BasicBlock,
         if_else: BasicBlock,
         next: BasicBlock,
+        else_params: SmallVec<[(BasicValueEnum<'ctx>, ExtraInfo); 1]>,
         phis: SmallVec<[PhiValue<'ctx>; 1]>,
     ) {
         self.control_stack.push(ControlFrame::IfElse {
             if_then,
             if_else,
             next,
+            stack_size_snapshot: self.stack.len() - else_params.len(),
+            else_params,
             phis,
-            stack_size_snapshot: self.stack.len(),
             if_else_state: IfElseState::If,
         });
     }
+
+    /// Resets the value stack for the `else` branch of the innermost `if`, which starts with
+    /// the params of the `if`.
+    pub fn reset_stack_for_else(&mut self) -> Result<(), CodegenError> {
+        match self.control_stack.last() {
+            Some(ControlFrame::IfElse {
+                else_params,
+                stack_size_snapshot,
+                ..
+            }) => {
+                self.stack.truncate(*stack_size_snapshot);
+                self.stack.extend(else_params.iter().cloned());
+                Ok(())
+            }
+            _ => Err(CodegenError {
+                message: "reset_stack_for_else: innermost frame is not an if".to_string(),
+            }),
+        }
+    }
 }
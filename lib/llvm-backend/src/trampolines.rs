@@ -1,10 +1,11 @@
+use crate::code::{build_call_results, build_results_buffer, results_layout};
 use crate::intrinsics::Intrinsics;
 use inkwell::{
     builder::Builder,
     context::Context,
     module::{Linkage, Module},
     types::{BasicType, FunctionType},
-    values::FunctionValue,
+    values::{BasicValue, FunctionValue},
     AddressSpace,
 };
 use wasmer_runtime_core::{
@@ -74,9 +75,20 @@ fn generate_trampoline<'ctx>(
         Type::I64 => intrinsics.i64_ptr_ty,
         Type::F64 => intrinsics.f64_ptr_ty,
         Type::V128 => intrinsics.i128_ptr_ty,
+        Type::FuncRef | Type::ExternRef => intrinsics.i64_ptr_ty,
     };
 
-    let mut args_vec = Vec::with_capacity(func_sig.params().len() + 1);
+    let results = results_layout(func_sig).map(|layout| {
+        let buffer = build_results_buffer(builder, intrinsics, &layout);
+        (layout, buffer)
+    });
+
+    let mut args_vec = Vec::with_capacity(func_sig.params().len() + 2);
+    if let Some((ref layout, buffer)) = results {
+        if layout.in_memory() {
+            args_vec.push(buffer.as_basic_value_enum());
+        }
+    }
     args_vec.push(vmctx_ptr);
 
     let mut i = 0;
@@ -111,8 +123,24 @@ fn generate_trampoline<'ctx>(
                 call_site.try_as_basic_value().left().unwrap(),
             );
         }
-        _ => {
-            return Err("trampoline function multi-value returns unimplemented".to_string());
+        returns => {
+            let (layout, buffer) = results.unwrap();
+            let values =
+                build_call_results(builder, context, intrinsics, &layout, buffer, call_site);
+
+            let mut i = 0;
+            for (ret_ty, value) in returns.iter().zip(values) {
+                let index = intrinsics.i32_ty.const_int(i as _, false);
+                let item_pointer =
+                    unsafe { builder.build_in_bounds_gep(returns_ptr, &[index], "ret_ptr") };
+                let typed_item_pointer =
+                    builder.build_pointer_cast(item_pointer, cast_ptr_ty(*ret_ty), "typed_ret_ptr");
+                builder.build_store(typed_item_pointer, value);
+                i = i + 1;
+                if *ret_ty == Type::V128 {
+                    i = i + 1;
+                }
+            }
         }
     }
 
@@ -4,10 +4,12 @@ mod tests {
 
     use wasmer_middleware_common::metering::*;
     use wasmer_runtime_core::codegen::ModuleCodeGenerator;
-    use wasmer_runtime_core::codegen::{MiddlewareChain, StreamingCompiler};
+    use wasmer_runtime_core::codegen::{FunctionMiddleware, MiddlewareChain, StreamingCompiler};
     use wasmer_runtime_core::fault::{pop_code_version, push_code_version};
     use wasmer_runtime_core::state::CodeVersion;
-    use wasmer_runtime_core::{backend::Compiler, compile_with, imports, Func};
+    use wasmer_runtime_core::units::Pages;
+    use wasmer_runtime_core::wasmparser::Operator;
+    use wasmer_runtime_core::{backend::Compiler, compile_with, imports, Func, Instance};
 
     #[cfg(feature = "llvm")]
     use wasmer_llvm_backend::ModuleCodeGenerator as MCG;
@@ -19,14 +21,40 @@ mod tests {
     compile_error!("cranelift does not implement metering yet");
 
     fn get_compiler(limit: u64) -> impl Compiler {
+        get_compiler_with_middleware(move || Metering::new(limit))
+    }
+
+    /// Returns a compiler running the middleware created by `middleware` on each function.
+    pub(crate) fn get_compiler_with_middleware<M, F>(middleware: F) -> impl Compiler
+    where
+        M: FunctionMiddleware + 'static,
+        F: Fn() -> M + 'static,
+    {
         let c: StreamingCompiler<MCG, _, _, _, _> = StreamingCompiler::new(move || {
             let mut chain = MiddlewareChain::new();
-            chain.push(Metering::new(limit));
+            chain.push(middleware());
             chain
         });
         c
     }
 
+    /// Pushes the code version of `instance` if its backend tracks the execution state,
+    /// returning whether it did.
+    pub(crate) fn push_instance_code_version(instance: &Instance) -> bool {
+        if let Some(msm) = instance.module.runnable_module.get_module_state_map() {
+            push_code_version(CodeVersion {
+                baseline: true,
+                msm: msm,
+                base: instance.module.runnable_module.get_code().unwrap().as_ptr() as usize,
+                backend: MCG::backend_id(),
+                runnable_module: instance.module.runnable_module.clone(),
+            });
+            true
+        } else {
+            false
+        }
+    }
+
     #[cfg(not(any(feature = "llvm", feature = "clif", feature = "singlepass")))]
     compile_error!("compiler not specified, activate a compiler via features");
 
@@ -132,7 +160,6 @@ mod tests {
 
     #[test]
     fn test_traps_after_costly_call() {
-        use wasmer_runtime_core::error::RuntimeError;
         let wasm_binary = wat2wasm(WAT).unwrap();
 
         let limit = 100u64;
@@ -165,12 +192,401 @@ mod tests {
         }
 
         let err = result.unwrap_err();
-        assert!(err
-            .0
-            .downcast_ref::<ExecutionLimitExceededError>()
-            .is_some());
+        assert!(err.downcast_ref::<ExecutionLimitExceededError>().is_some());
 
         // verify it used the correct number of points
         assert_eq!(get_points_used(&instance), 109); // Used points will be slightly more than `limit` because of the way we do gas checking.
     }
+
+    #[test]
+    fn test_points_with_cost_function() {
+        let wasm_binary = wat2wasm(WAT).unwrap();
+
+        let compiler = get_compiler_with_middleware(|| Metering::with_cost_function(1000, |_| 2));
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+
+        let import_object = imports! {};
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        set_points_used(&mut instance, 0u64);
+
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+
+        let cv_pushed = push_instance_code_version(&instance);
+
+        let value = add_to.call(3, 4).unwrap();
+        if cv_pushed {
+            pop_code_version().unwrap();
+        }
+
+        assert_eq!(value, 7);
+
+        // Twice the points of `test_points_reduced_after_call`.
+        assert_eq!(get_points_used(&instance), 148);
+    }
+
+    static GROW_WAT: &'static str = r#"
+        (module
+          (memory 1)
+          (func (export "grow") (param i32) (result i32)
+            get_local 0
+            memory.grow))
+        "#;
+
+    #[test]
+    fn test_dynamic_cost_of_memory_grow() {
+        let wasm_binary = wat2wasm(GROW_WAT).unwrap();
+
+        let compiler = get_compiler_with_middleware(|| {
+            Metering::new(100).with_dynamic_cost_function(|op| match *op {
+                Operator::MemoryGrow { .. } => 10,
+                _ => 0,
+            })
+        });
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+
+        let import_object = imports! {};
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        set_points_used(&mut instance, 0u64);
+
+        let grow: Func<i32, i32> = instance.func("grow").unwrap();
+
+        let cv_pushed = push_instance_code_version(&instance);
+
+        let grown = grow.call(2);
+        let points_after_grow = get_points_used(&instance);
+        let too_large = grow.call(20);
+        if cv_pushed {
+            pop_code_version().unwrap();
+        }
+
+        // Two pages at 10 points each, plus a point for each of the 3 operators.
+        assert_eq!(grown.unwrap(), 1);
+        assert_eq!(points_after_grow, 23);
+
+        // The limit is checked before the memory grows.
+        let err = too_large.unwrap_err();
+        assert!(err.downcast_ref::<ExecutionLimitExceededError>().is_some());
+        assert_eq!(instance.context().memory(0).size(), Pages(3));
+    }
+
+    #[test]
+    fn test_dynamic_cost_overflow_traps() {
+        let wasm_binary = wat2wasm(GROW_WAT).unwrap();
+
+        let compiler = get_compiler_with_middleware(|| {
+            Metering::new(u64::max_value()).with_dynamic_cost_function(|op| match *op {
+                Operator::MemoryGrow { .. } => u64::max_value() / 2,
+                _ => 0,
+            })
+        });
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+
+        let import_object = imports! {};
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        set_points_used(&mut instance, 0u64);
+
+        let grow: Func<i32, i32> = instance.func("grow").unwrap();
+
+        let cv_pushed = push_instance_code_version(&instance);
+        let result = grow.call(3);
+        if cv_pushed {
+            pop_code_version().unwrap();
+        }
+
+        // Three pages would cost more points than a `u64` holds instead of wrapping around.
+        let err = result.unwrap_err();
+        assert!(err.downcast_ref::<ExecutionLimitExceededError>().is_some());
+        assert_eq!(instance.context().memory(0).size(), Pages(1));
+    }
+
+    #[test]
+    fn test_limit_set_at_runtime() {
+        let wasm_binary = wat2wasm(WAT).unwrap();
+
+        let compiler = get_compiler(100);
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+
+        let import_object = imports! {};
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        set_points_used(&mut instance, 0u64);
+        assert_eq!(get_limit(&instance), 100);
+        set_limit(&mut instance, 50);
+
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+
+        let cv_pushed = push_instance_code_version(&instance);
+        let result = add_to.call(3, 4);
+        if cv_pushed {
+            pop_code_version().unwrap();
+        }
+
+        // The call uses 74 points, which is within the limit given at compile time only.
+        let err = result.unwrap_err();
+        assert!(err.downcast_ref::<ExecutionLimitExceededError>().is_some());
+    }
+
+    #[cfg(feature = "singlepass")]
+    #[test]
+    fn test_resume_after_limit_exceeded() {
+        use wasmer_runtime_core::{backend::CompilerConfig, compile_with_config};
+
+        let wasm_binary = wat2wasm(WAT).unwrap();
+
+        let compiler = get_compiler_with_middleware(|| Metering::new(100).suspend_on_limit());
+        let module = compile_with_config(
+            &wasm_binary,
+            &compiler,
+            CompilerConfig {
+                track_state: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let import_object = imports! {};
+        let mut instance = module.instantiate(&import_object).unwrap();
+
+        set_points_used(&mut instance, 0u64);
+
+        let add_to: Func<(i32, i32), i32> = instance.func("add_to").unwrap();
+
+        assert!(push_instance_code_version(&instance));
+
+        let err = add_to.call(20, 4).unwrap_err();
+        let suspended = err
+            .downcast::<SuspendedExecution>()
+            .expect("the call was not suspended");
+        assert!(get_points_used(&instance) >= 100);
+
+        set_limit(&mut instance, 1_000_000);
+        let result = unsafe { resume(&mut instance, suspended) };
+        pop_code_version().unwrap();
+
+        // 4 + (0 + 1 + ... + 19)
+        assert_eq!(result.unwrap() as i32, 194);
+    }
+}
+
+#[cfg(all(test, any(feature = "singlepass", feature = "llvm")))]
+mod profiling_tests {
+    use std::sync::{Arc, Mutex};
+    use wabt::wat2wasm;
+
+    use super::tests::get_compiler_with_middleware;
+    use wasmer_middleware_common::profiling::{self, BlockKind, ProfileLayout, Profiling};
+    use wasmer_runtime_core::{compile_with, imports, Func};
+
+    static WAT: &'static str = r#"
+        (module
+          (func $sum (export "sum") (param $n i32) (result i32)
+            (local $i i32) (local $acc i32)
+            block $done
+              loop $next
+                get_local $i
+                get_local $n
+                i32.ge_s
+                br_if $done
+                get_local $i
+                i32.const 2
+                i32.rem_s
+                if
+                  get_local $acc
+                  get_local $i
+                  i32.add
+                  set_local $acc
+                else
+                  get_local $acc
+                  i32.const 1
+                  i32.add
+                  set_local $acc
+                end
+                get_local $i
+                i32.const 1
+                i32.add
+                set_local $i
+                br $next
+              end
+            end
+            get_local $acc)
+          (func $unused (export "unused")))
+        "#;
+
+    #[test]
+    fn test_block_counts() {
+        let wasm_binary = wat2wasm(WAT).unwrap();
+
+        let layout = Arc::new(Mutex::new(ProfileLayout::new()));
+        let compiler = {
+            let layout = Arc::clone(&layout);
+            get_compiler_with_middleware(move || Profiling::new(Arc::clone(&layout)))
+        };
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+        let layout = layout.lock().unwrap();
+
+        let instance = module.instantiate(&imports! {}).unwrap();
+        assert_eq!(instance.counters().len(), layout.counter_count());
+
+        let sum: Func<i32, i32> = instance.func("sum").unwrap();
+        assert_eq!(sum.call(10).unwrap(), 30);
+        assert_eq!(sum.call(4).unwrap(), 6);
+
+        let profile = profiling::collect(&instance, &layout);
+        let sum = &profile.functions[0];
+        assert_eq!(sum.count, 2);
+        let blocks: Vec<(BlockKind, u64)> = sum
+            .blocks
+            .iter()
+            .map(|block| (block.kind, block.count))
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                (BlockKind::Entry, 2),
+                (BlockKind::Loop, 16),
+                (BlockKind::BrIfFallthrough, 14),
+                (BlockKind::Then, 7),
+                (BlockKind::Else, 7),
+                (BlockKind::End, 14),
+                (BlockKind::Unreachable, 0),
+                (BlockKind::End, 0),
+                (BlockKind::End, 2),
+            ]
+        );
+        assert_eq!(profile.functions[1].count, 0);
+        assert_eq!(layout.counter_count(), 9);
+    }
+
+    #[test]
+    fn test_start_function_counted() {
+        let wasm_binary = wat2wasm(
+            r#"
+            (module
+              (func $init)
+              (start $init))
+            "#,
+        )
+        .unwrap();
+
+        let layout = Arc::new(Mutex::new(ProfileLayout::new()));
+        let compiler = {
+            let layout = Arc::clone(&layout);
+            get_compiler_with_middleware(move || Profiling::new(Arc::clone(&layout)))
+        };
+        let module = compile_with(&wasm_binary, &compiler).unwrap();
+        let layout = layout.lock().unwrap();
+
+        // The counters are allocated before the start function runs.
+        let mut instance = module.instantiate(&imports! {}).unwrap();
+        assert_eq!(profiling::collect(&instance, &layout).functions[0].count, 1);
+
+        instance.reset_counters();
+        assert_eq!(profiling::collect(&instance, &layout).functions[0].count, 0);
+    }
+}
+
+#[cfg(all(test, any(feature = "singlepass", feature = "llvm")))]
+mod stack_depth_tests {
+    use wabt::wat2wasm;
+
+    use super::tests::{get_compiler_with_middleware, push_instance_code_version};
+    use wasmer_middleware_common::stack_depth::*;
+    use wasmer_runtime_core::fault::pop_code_version;
+    use wasmer_runtime_core::{backend::Compiler, compile_with, imports, Func};
+
+    fn get_compiler(limit: u64) -> impl Compiler {
+        get_compiler_with_middleware(move || StackDepthLimit::new(limit))
+    }
+
+    static WAT: &'static str = r#"
+        (module
+          (func $rec (export "rec") (param $n i32) (result i32)
+            get_local $n
+            i32.eqz
+            if (result i32)
+              i32.const 0
+            else
+              get_local $n
+              i32.const 1
+              i32.sub
+              call $rec
+              i32.const 1
+              i32.add
+            end)
+          (func $exits (export "exits") (param $k i32) (result i32)
+            block (result i32)
+              i32.const 1
+              get_local $k
+              br_table 0 1 0 0 0 1
+            end
+            drop
+            get_local $k
+            i32.const 2
+            i32.eq
+            if
+              i32.const 2
+              return
+            end
+            i32.const 3
+            get_local $k
+            i32.const 3
+            i32.eq
+            br_if 0
+            drop
+            get_local $k
+            i32.const 4
+            i32.eq
+            if
+              i32.const 4
+              br 1
+            end
+            i32.const 5))
+        "#;
+
+    #[test]
+    fn test_traps_past_limit() {
+        let wasm_binary = wat2wasm(WAT).unwrap();
+        let module = compile_with(&wasm_binary, &get_compiler(100)).unwrap();
+        let instance = module.instantiate(&imports! {}).unwrap();
+
+        let rec: Func<i32, i32> = instance.func("rec").unwrap();
+        assert_eq!(rec.call(99).unwrap(), 99);
+        assert_eq!(get_call_depth(&instance), 0);
+
+        let cv_pushed = push_instance_code_version(&instance);
+        let result = rec.call(100);
+        let depth_after_trap = get_call_depth(&instance);
+        let result_after_trap = rec.call(99);
+        let result_again = rec.call(100);
+        if cv_pushed {
+            pop_code_version().unwrap();
+        }
+
+        let err = result.unwrap_err();
+        assert!(err.downcast_ref::<StackDepthExceededError>().is_some());
+        // The call depth is restored after the trap, so the next calls trap at the same depth.
+        assert_eq!(depth_after_trap, 0);
+        assert_eq!(result_after_trap.unwrap(), 99);
+        assert!(result_again.is_err());
+    }
+
+    #[test]
+    fn test_depth_restored_on_every_exit() {
+        let wasm_binary = wat2wasm(WAT).unwrap();
+        // A single frame is allowed, so any exit leaving the depth behind makes the next
+        // call trap.
+        let module = compile_with(&wasm_binary, &get_compiler(1)).unwrap();
+        let instance = module.instantiate(&imports! {}).unwrap();
+
+        let exits: Func<i32, i32> = instance.func("exits").unwrap();
+        for _ in 0..3 {
+            for &(k, expected) in &[(0, 5), (1, 1), (2, 2), (3, 3), (4, 4), (5, 1), (9, 1)] {
+                assert_eq!(exits.call(k).unwrap(), expected);
+                assert_eq!(get_call_depth(&instance), 0);
+            }
+        }
+    }
 }
@@ -1,11 +1,15 @@
 use wasmer_runtime_core::{
     codegen::{Event, EventSink, FunctionMiddleware, InternalEvent},
+    fault,
     module::ModuleInfo,
+    structures::TypedIndex,
+    types::{FuncIndex, LocalFuncIndex},
     wasmparser::Operator,
 };
 
 pub struct BlockTrace {
     func_idx: usize,
+    func_index: FuncIndex,
     evt_idx: usize,
 }
 
@@ -13,6 +17,7 @@ impl BlockTrace {
     pub fn new() -> BlockTrace {
         BlockTrace {
             func_idx: std::usize::MAX,
+            func_index: FuncIndex::new(0),
             evt_idx: 0,
         }
     }
@@ -23,22 +28,23 @@ impl FunctionMiddleware for BlockTrace {
     fn feed_event<'a, 'b: 'a>(
         &mut self,
         op: Event<'a, 'b>,
-        _module_info: &ModuleInfo,
+        module_info: &ModuleInfo,
         sink: &mut EventSink<'a, 'b>,
         _source_loc: u32,
     ) -> Result<(), Self::Error> {
         match op {
             Event::Internal(InternalEvent::FunctionBegin(_)) => {
                 self.func_idx = self.func_idx.wrapping_add(1);
+                self.func_index = LocalFuncIndex::new(self.func_idx).convert_up(module_info);
                 self.evt_idx = 0;
-                let func_idx = self.func_idx;
+                let (func_idx, func_index) = (self.func_idx, self.func_index);
                 let evt_idx = self.evt_idx;
                 sink.push(op);
                 sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(
                     move |info| {
                         eprintln!(
                             "[BlockTrace] ({}, {}) -> enter_func % {:?}",
-                            func_idx,
+                            func_label(func_idx, func_index),
                             evt_idx,
                             info.fault
                                 .and_then(|x| unsafe { x.read_stack(Some(1)) })
@@ -50,14 +56,14 @@ impl FunctionMiddleware for BlockTrace {
                 ))))
             }
             Event::Wasm(Operator::Call { .. }) => {
-                let func_idx = self.func_idx;
+                let (func_idx, func_index) = (self.func_idx, self.func_index);
                 let evt_idx = self.evt_idx;
                 sink.push(op);
                 sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(
                     move |info| {
                         eprintln!(
                             "[BlockTrace] ({}, {}) -> leave_call % {:?}",
-                            func_idx,
+                            func_label(func_idx, func_index),
                             evt_idx,
                             info.fault
                                 .and_then(|x| unsafe { x.read_stack(Some(1)) })
@@ -69,14 +75,14 @@ impl FunctionMiddleware for BlockTrace {
                 ))))
             }
             Event::Wasm(Operator::Block { .. }) => {
-                let func_idx = self.func_idx;
+                let (func_idx, func_index) = (self.func_idx, self.func_index);
                 let evt_idx = self.evt_idx;
                 sink.push(op);
                 sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(
                     move |info| {
                         eprintln!(
                             "[BlockTrace] ({}, {}) -> block % {:?}",
-                            func_idx,
+                            func_label(func_idx, func_index),
                             evt_idx,
                             info.fault
                                 .and_then(|x| unsafe { x.read_stack(Some(1)) })
@@ -88,14 +94,14 @@ impl FunctionMiddleware for BlockTrace {
                 ))))
             }
             Event::Wasm(Operator::Loop { .. }) => {
-                let func_idx = self.func_idx;
+                let (func_idx, func_index) = (self.func_idx, self.func_index);
                 let evt_idx = self.evt_idx;
                 sink.push(op);
                 sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(
                     move |info| {
                         eprintln!(
                             "[BlockTrace] ({}, {}) -> loop % {:?}",
-                            func_idx,
+                            func_label(func_idx, func_index),
                             evt_idx,
                             info.fault
                                 .and_then(|x| unsafe { x.read_stack(Some(1)) })
@@ -107,14 +113,14 @@ impl FunctionMiddleware for BlockTrace {
                 ))))
             }
             Event::Wasm(Operator::If { .. }) => {
-                let func_idx = self.func_idx;
+                let (func_idx, func_index) = (self.func_idx, self.func_index);
                 let evt_idx = self.evt_idx;
                 sink.push(op);
                 sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(
                     move |info| {
                         eprintln!(
                             "[BlockTrace] ({}, {}) -> if % {:?}",
-                            func_idx,
+                            func_label(func_idx, func_index),
                             evt_idx,
                             info.fault
                                 .and_then(|x| unsafe { x.read_stack(Some(1)) })
@@ -126,14 +132,14 @@ impl FunctionMiddleware for BlockTrace {
                 ))))
             }
             Event::Wasm(Operator::Else { .. }) => {
-                let func_idx = self.func_idx;
+                let (func_idx, func_index) = (self.func_idx, self.func_index);
                 let evt_idx = self.evt_idx;
                 sink.push(op);
                 sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(
                     move |info| {
                         eprintln!(
                             "[BlockTrace] ({}, {}) -> else % {:?}",
-                            func_idx,
+                            func_label(func_idx, func_index),
                             evt_idx,
                             info.fault
                                 .and_then(|x| unsafe { x.read_stack(Some(1)) })
@@ -152,3 +158,12 @@ impl FunctionMiddleware for BlockTrace {
         Ok(())
     }
 }
+
+/// Formats the index of a function with its name from the `name` custom section, which
+/// is only read after the code of the module.
+fn func_label(func_idx: usize, func_index: FuncIndex) -> String {
+    match fault::current_func_name(func_index) {
+        Some(name) => format!("{} <{}>", func_idx, name),
+        None => format!("{}", func_idx),
+    }
+}
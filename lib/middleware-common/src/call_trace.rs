@@ -5,6 +5,8 @@ use std::sync::{
 use wasmer_runtime_core::{
     codegen::{Event, EventSink, FunctionMiddleware, InternalEvent},
     module::ModuleInfo,
+    structures::TypedIndex,
+    types::{FuncIndex, LocalFuncIndex},
 };
 
 pub struct CallTrace {
@@ -24,23 +26,41 @@ impl FunctionMiddleware for CallTrace {
     fn feed_event<'a, 'b: 'a>(
         &mut self,
         op: Event<'a, 'b>,
-        _module_info: &ModuleInfo,
+        module_info: &ModuleInfo,
         sink: &mut EventSink<'a, 'b>,
         _source_loc: u32,
     ) -> Result<(), Self::Error> {
         let counter = self.counter.clone();
 
         match op {
-            Event::Internal(InternalEvent::FunctionBegin(id)) => sink.push(Event::Internal(
-                InternalEvent::Breakpoint(Box::new(move |_| {
-                    let idx = counter.fetch_add(1, Ordering::SeqCst);
-                    eprintln!("[{}] func ({})", idx, id);
-                    Ok(())
-                })),
-            )),
+            Event::Internal(InternalEvent::FunctionBegin(id)) => {
+                let func_index = LocalFuncIndex::new(id as usize).convert_up(module_info);
+                sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(
+                    move |_| {
+                        let idx = counter.fetch_add(1, Ordering::SeqCst);
+                        match func_name(func_index) {
+                            Some(name) => eprintln!("[{}] func ({} <{}>)", idx, id, name),
+                            None => eprintln!("[{}] func ({})", idx, id),
+                        }
+                        Ok(())
+                    },
+                ))))
+            }
             _ => {}
         }
         sink.push(op);
         Ok(())
     }
 }
+
+/// Returns the name of a function from the `name` custom section, which is only read
+/// after the code of the module.
+#[cfg(unix)]
+fn func_name(func_index: FuncIndex) -> Option<String> {
+    wasmer_runtime_core::fault::current_func_name(func_index)
+}
+
+#[cfg(not(unix))]
+fn func_name(_func_index: FuncIndex) -> Option<String> {
+    None
+}
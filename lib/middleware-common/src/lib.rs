@@ -14,3 +14,5 @@
 pub mod block_trace;
 pub mod call_trace;
 pub mod metering;
+pub mod profiling;
+pub mod stack_depth;
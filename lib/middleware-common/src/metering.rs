@@ -1,3 +1,10 @@
+#[cfg(unix)]
+use std::{any::Any, cell::Cell};
+#[cfg(unix)]
+use wasmer_runtime_core::state::{
+    x64::{build_instance_image, invoke_call_return_on_stack},
+    ExecutionStateImage,
+};
 use wasmer_runtime_core::{
     codegen::{Event, EventSink, FunctionMiddleware, InternalEvent},
     module::ModuleInfo,
@@ -8,6 +15,12 @@ use wasmer_runtime_core::{
 
 static INTERNAL_FIELD: InternalField = InternalField::allocate();
 
+/// Holds the limit of points, initialized with the limit given at compile time.
+static LIMIT_FIELD: InternalField = InternalField::allocate();
+
+/// Holds the operand of a dynamically priced operator while its cost is charged.
+static OPERAND_FIELD: InternalField = InternalField::allocate();
+
 /// Metering is a compiler middleware that calculates the cost of WebAssembly instructions at compile
 /// time and will count the cost of executed instructions at runtime. Within the Metering functionality,
 /// this instruction cost is called `points`.
@@ -17,26 +30,272 @@ static INTERNAL_FIELD: InternalField = InternalField::allocate();
 /// trap. Each instance has a `points_used` field which can be used to track points used during
 /// a function call and should be set back to zero after a function call.
 ///
+/// By default every operator costs one point. A cost function can be supplied with
+/// [`Metering::with_cost_function`] to price each operator, and the size dependent part of
+/// `memory.grow`, `table.grow` and the bulk memory operators can be priced with
+/// [`Metering::with_dynamic_cost_function`].
+///
+/// The limit can be changed per instance at runtime with [`set_limit`]. With
+/// [`Metering::suspend_on_limit`], a call exceeding the limit is suspended instead of torn
+/// down, and can be continued with [`resume`] once points have been added.
+///
 /// Each compiler backend with Metering enabled should produce the same cost used at runtime for
 /// the same function calls so we can say that the metering is deterministic.
 ///
+/// [`Metering::with_cost_function`]: #method.with_cost_function
+/// [`Metering::with_dynamic_cost_function`]: #method.with_dynamic_cost_function
+/// [`Metering::suspend_on_limit`]: #method.suspend_on_limit
+/// [`set_limit`]: fn.set_limit.html
+/// [`resume`]: fn.resume.html
 pub struct Metering {
     limit: u64,
     current_block: u64,
+    cost_function: Box<dyn Fn(&Operator) -> u64 + Send + Sync>,
+    dynamic_cost_function: Option<Box<dyn Fn(&Operator) -> u64 + Send + Sync>>,
+    suspend_on_limit: bool,
 }
 
 impl Metering {
     pub fn new(limit: u64) -> Metering {
+        Metering::with_cost_function(limit, |_| 1)
+    }
+
+    /// Creates a Metering middleware which charges `cost_function(op)` points for each
+    /// operator `op`.
+    ///
+    /// # Usage:
+    ///
+    /// ```
+    /// # use wasmer_middleware_common::metering::Metering;
+    /// # use wasmer_runtime_core::wasmparser::Operator;
+    /// let metering = Metering::with_cost_function(1_000_000, |op| match *op {
+    ///     Operator::I32DivS | Operator::I32DivU | Operator::I64DivS | Operator::I64DivU => 10,
+    ///     Operator::Call { .. } | Operator::CallIndirect { .. } => 5,
+    ///     Operator::MemoryGrow { .. } => 100,
+    ///     Operator::Nop => 0,
+    ///     _ => 1,
+    /// });
+    /// ```
+    pub fn with_cost_function<F>(limit: u64, cost_function: F) -> Metering
+    where
+        F: Fn(&Operator) -> u64 + Send + Sync + 'static,
+    {
         Metering {
             limit,
             current_block: 0,
+            cost_function: Box::new(cost_function),
+            dynamic_cost_function: None,
+            suspend_on_limit: false,
         }
     }
+
+    /// Charges `dynamic_cost_function(op)` points per unit of the size operand of
+    /// `memory.grow` and `table.grow` (the delta) and of `memory.copy`, `memory.fill`,
+    /// `memory.init`, `table.copy`, `table.fill` and `table.init` (the length), on top
+    /// of the cost from the cost function. The limit is checked before the operator runs,
+    /// and a charge which does not fit in a `u64` traps as if the limit was exceeded.
+    ///
+    /// # Usage:
+    ///
+    /// ```
+    /// # use wasmer_middleware_common::metering::Metering;
+    /// # use wasmer_runtime_core::wasmparser::Operator;
+    /// // One point per page grown and one point per byte copied or filled.
+    /// let metering = Metering::new(1_000_000).with_dynamic_cost_function(|op| match *op {
+    ///     Operator::MemoryGrow { .. } => 1,
+    ///     Operator::MemoryCopy | Operator::MemoryFill => 1,
+    ///     _ => 0,
+    /// });
+    /// ```
+    pub fn with_dynamic_cost_function<F>(mut self, dynamic_cost_function: F) -> Metering
+    where
+        F: Fn(&Operator) -> u64 + Send + Sync + 'static,
+    {
+        self.dynamic_cost_function = Some(Box::new(dynamic_cost_function));
+        self
+    }
+
+    /// Suspends calls exceeding the limit instead of tearing them down. The call then fails
+    /// with a [`SuspendedExecution`] holding its execution state, which [`resume`] continues
+    /// from the point where the limit was exceeded.
+    ///
+    /// Suspending requires a backend tracking the execution state, such as singlepass
+    /// compiling with `track_state`, and the code version of the module being pushed
+    /// with `push_code_version`. Otherwise the call fails with an
+    /// `ExecutionLimitExceededError` as without this option.
+    ///
+    /// [`SuspendedExecution`]: struct.SuspendedExecution.html
+    /// [`resume`]: fn.resume.html
+    pub fn suspend_on_limit(mut self) -> Metering {
+        self.suspend_on_limit = true;
+        self
+    }
+
+    /// Returns the points charged per unit of the size operand of `op`, if `op` has one.
+    fn dynamic_cost(&self, op: &Operator) -> Option<u64> {
+        match *op {
+            Operator::MemoryGrow { .. }
+            | Operator::MemoryCopy
+            | Operator::MemoryFill
+            | Operator::MemoryInit { .. }
+            | Operator::TableGrow { .. }
+            | Operator::TableCopy { .. }
+            | Operator::TableFill { .. }
+            | Operator::TableInit { .. } => self
+                .dynamic_cost_function
+                .as_ref()
+                .map(|dynamic_cost_function| dynamic_cost_function(op))
+                .filter(|&cost| cost != 0),
+            _ => None,
+        }
+    }
+
+    /// Adds the points of the current block to the points used.
+    fn flush_block<'a, 'b: 'a>(&mut self, sink: &mut EventSink<'a, 'b>) {
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            INTERNAL_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I64Const {
+            value: self.current_block as i64,
+        }));
+        sink.push(Event::WasmOwned(Operator::I64Add));
+        sink.push(Event::Internal(InternalEvent::SetInternal(
+            INTERNAL_FIELD.index() as _,
+        )));
+        self.current_block = 0;
+    }
+
+    /// Adds `cost` points per unit of the `i32` operand on top of the stack to the points
+    /// used, leaving the operand in place. Traps with an `ExecutionLimitExceededError` if
+    /// the charge or the points used would not fit in a `u64`.
+    fn charge_operand<'a, 'b: 'a>(&self, cost: u64, sink: &mut EventSink<'a, 'b>) {
+        sink.push(Event::WasmOwned(Operator::I64ExtendI32U));
+        sink.push(Event::Internal(InternalEvent::SetInternal(
+            OPERAND_FIELD.index() as _,
+        )));
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            OPERAND_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I32WrapI64));
+
+        // The product of two `u32` always fits in a `u64`.
+        if cost > u64::from(u32::max_value()) {
+            sink.push(Event::Internal(InternalEvent::GetInternal(
+                OPERAND_FIELD.index() as _,
+            )));
+            sink.push(Event::WasmOwned(Operator::I64Const {
+                value: (u64::max_value() / cost) as i64,
+            }));
+            sink.push(Event::WasmOwned(Operator::I64GtU));
+            self.trap_if(sink);
+        }
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            OPERAND_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I64Const { value: cost as i64 }));
+        sink.push(Event::WasmOwned(Operator::I64Mul));
+        sink.push(Event::Internal(InternalEvent::SetInternal(
+            OPERAND_FIELD.index() as _,
+        )));
+
+        // The points used must not exceed `u64::MAX - charge`.
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            INTERNAL_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I64Const { value: -1 }));
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            OPERAND_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I64Sub));
+        sink.push(Event::WasmOwned(Operator::I64GtU));
+        self.trap_if(sink);
+
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            INTERNAL_FIELD.index() as _,
+        )));
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            OPERAND_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I64Add));
+        sink.push(Event::Internal(InternalEvent::SetInternal(
+            INTERNAL_FIELD.index() as _,
+        )));
+    }
+
+    /// Traps with an `ExecutionLimitExceededError` if the `i32` on top of the stack is not zero.
+    fn trap_if<'a, 'b: 'a>(&self, sink: &mut EventSink<'a, 'b>) {
+        sink.push(Event::WasmOwned(Operator::If {
+            ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+        }));
+        sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(|_| {
+            Err(Box::new(ExecutionLimitExceededError))
+        }))));
+        sink.push(Event::WasmOwned(Operator::End));
+    }
+
+    /// Traps with an `ExecutionLimitExceededError` if the points used reached the limit.
+    fn check_limit<'a, 'b: 'a>(&self, sink: &mut EventSink<'a, 'b>) {
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            INTERNAL_FIELD.index() as _,
+        )));
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            LIMIT_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I64GeU));
+        sink.push(Event::WasmOwned(Operator::If {
+            ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+        }));
+        let suspend_on_limit = self.suspend_on_limit;
+        sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(
+            move |_info| {
+                #[cfg(unix)]
+                {
+                    if suspend_on_limit {
+                        if let Some(fault) = _info.fault {
+                            let breakpoint = fault.ip.get();
+                            if RESUMING.with(|resuming| resuming.get()) == Some(breakpoint) {
+                                RESUMING.with(|resuming| resuming.set(None));
+                                return Ok(());
+                            }
+                            if let Some(execution_state) = unsafe { fault.read_stack(None) } {
+                                return Err(Box::new(SuspendedExecution {
+                                    execution_state,
+                                    breakpoint,
+                                }));
+                            }
+                        }
+                    }
+                }
+                #[cfg(not(unix))]
+                let _ = suspend_on_limit;
+                Err(Box::new(ExecutionLimitExceededError))
+            },
+        ))));
+        sink.push(Event::WasmOwned(Operator::End));
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
 pub struct ExecutionLimitExceededError;
 
+/// The error of a call suspended by a `Metering` middleware created with `suspend_on_limit`
+/// when the limit was exceeded, holding the execution state to continue the call from.
+#[cfg(unix)]
+#[derive(Clone, Debug)]
+pub struct SuspendedExecution {
+    /// The execution state of the call when the limit was exceeded.
+    pub execution_state: ExecutionStateImage,
+    /// The address of the breakpoint the call was suspended at.
+    breakpoint: usize,
+}
+
+#[cfg(unix)]
+thread_local! {
+    /// The address of the breakpoint a call being resumed was suspended at, which lets the
+    /// call continue when it is executed again.
+    static RESUMING: Cell<Option<usize>> = Cell::new(None);
+}
+
 impl FunctionMiddleware for Metering {
     type Error = String;
     fn feed_event<'a, 'b: 'a>(
@@ -51,7 +310,7 @@ impl FunctionMiddleware for Metering {
                 self.current_block = 0;
             }
             Event::Wasm(&ref op) | Event::WasmOwned(ref op) => {
-                self.current_block += 1;
+                self.current_block += (self.cost_function)(op);
                 match *op {
                     Operator::Loop { .. }
                     | Operator::Block { .. }
@@ -65,17 +324,7 @@ impl FunctionMiddleware for Metering {
                     | Operator::Call { .. }
                     | Operator::CallIndirect { .. }
                     | Operator::Return => {
-                        sink.push(Event::Internal(InternalEvent::GetInternal(
-                            INTERNAL_FIELD.index() as _,
-                        )));
-                        sink.push(Event::WasmOwned(Operator::I64Const {
-                            value: self.current_block as i64,
-                        }));
-                        sink.push(Event::WasmOwned(Operator::I64Add));
-                        sink.push(Event::Internal(InternalEvent::SetInternal(
-                            INTERNAL_FIELD.index() as _,
-                        )));
-                        self.current_block = 0;
+                        self.flush_block(sink);
                     }
                     _ => {}
                 }
@@ -85,29 +334,24 @@ impl FunctionMiddleware for Metering {
                     | Operator::BrIf { .. }
                     | Operator::Call { .. }
                     | Operator::CallIndirect { .. } => {
-                        sink.push(Event::Internal(InternalEvent::GetInternal(
-                            INTERNAL_FIELD.index() as _,
-                        )));
-                        sink.push(Event::WasmOwned(Operator::I64Const {
-                            value: self.limit as i64,
-                        }));
-                        sink.push(Event::WasmOwned(Operator::I64GeU));
-                        sink.push(Event::WasmOwned(Operator::If {
-                            ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
-                        }));
-                        sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(|_| {
-                            Err(Box::new(ExecutionLimitExceededError))
-                        }))));
-                        sink.push(Event::WasmOwned(Operator::End));
+                        self.check_limit(sink);
                     }
                     _ => {}
                 }
+                if let Some(cost) = self.dynamic_cost(op) {
+                    self.charge_operand(cost, sink);
+                    self.check_limit(sink);
+                }
             }
             _ => {}
         }
         sink.push(op);
         Ok(())
     }
+
+    fn initial_internals(&self) -> Vec<(&'static InternalField, u64)> {
+        vec![(&LIMIT_FIELD, self.limit)]
+    }
 }
 
 /// Returns the number of points used by an Instance.
@@ -129,3 +373,58 @@ pub fn get_points_used_ctx(ctx: &Ctx) -> u64 {
 pub fn set_points_used_ctx(ctx: &mut Ctx, value: u64) {
     ctx.set_internal(&INTERNAL_FIELD, value);
 }
+
+/// Returns the limit of points of an Instance.
+pub fn get_limit(instance: &Instance) -> u64 {
+    instance.get_internal(&LIMIT_FIELD)
+}
+
+/// Sets the limit of points of an Instance, replacing the limit given at compile time.
+pub fn set_limit(instance: &mut Instance, limit: u64) {
+    instance.set_internal(&LIMIT_FIELD, limit);
+}
+
+/// Returns the limit of points in a Ctx.
+pub fn get_limit_ctx(ctx: &Ctx) -> u64 {
+    ctx.get_internal(&LIMIT_FIELD)
+}
+
+/// Sets the limit of points in a Ctx, replacing the limit given at compile time.
+pub fn set_limit_ctx(ctx: &mut Ctx, limit: u64) {
+    ctx.set_internal(&LIMIT_FIELD, limit);
+}
+
+/// Continues a call of an Instance suspended when the limit was exceeded, returning the
+/// raw return value of the call. Raise the limit with [`set_limit`] or lower the points
+/// used before resuming, or the call is suspended again at the next check.
+///
+/// # Safety
+///
+/// `suspended` must come from a call of this instance, whose memory and globals are
+/// kept as they are. The code version of the module must be pushed with
+/// `push_code_version` as for the suspended call.
+///
+/// [`set_limit`]: fn.set_limit.html
+#[cfg(unix)]
+pub unsafe fn resume(
+    instance: &mut Instance,
+    suspended: SuspendedExecution,
+) -> Result<u64, Box<dyn Any + Send>> {
+    let runnable_module = instance.module.runnable_module.clone();
+    let msm = runnable_module
+        .get_module_state_map()
+        .expect("the module does not track the execution state");
+    let code_base = runnable_module.get_code().unwrap().as_ptr() as usize;
+    let breakpoints = runnable_module.get_breakpoints();
+
+    let breakpoint = suspended.breakpoint;
+    let mut image = build_instance_image(instance.context_mut(), suspended.execution_state);
+    // The memory of the instance is left as it is.
+    image.memory = None;
+
+    RESUMING.with(|resuming| resuming.set(Some(breakpoint)));
+    let result =
+        invoke_call_return_on_stack(&msm, code_base, image, instance.context_mut(), breakpoints);
+    RESUMING.with(|resuming| resuming.set(None));
+    result
+}
@@ -0,0 +1,314 @@
+//! A reader for the DWARF line tables embedded in a WebAssembly module.
+
+use gimli::{
+    AttributeValue, DebugLine, DebugLineOffset, DebugLineStr, DebugStr, EndianSlice,
+    LineProgramHeader, LittleEndian,
+};
+use std::{cmp::Ordering, collections::HashMap};
+use wasmer_runtime_core::wasmparser::{ModuleReader, SectionCode};
+
+type Reader<'a> = EndianSlice<'a, LittleEndian>;
+
+/// A source line of a WebAssembly module.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation<'a> {
+    pub file: &'a str,
+    pub line: u32,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Row {
+    /// The offset in the code section.
+    address: u32,
+    file: Option<u32>,
+    line: u32,
+    end_sequence: bool,
+}
+
+/// Maps the offsets of a WebAssembly module to source lines, using the line tables of the
+/// `.debug_line` custom section. DWARF versions 2 to 5 are supported.
+#[derive(Clone, Debug)]
+pub struct DebugLineMap {
+    /// The offset in the module of the start of the code section.
+    code_offset: u32,
+    files: Vec<String>,
+    rows: Vec<Row>,
+}
+
+impl DebugLineMap {
+    /// Reads the line tables of a module, returning `None` if the module has no
+    /// `.debug_line` section or if it can't be read.
+    pub fn from_module(wasm: &[u8]) -> Option<DebugLineMap> {
+        let mut code_offset = None;
+        let mut debug_line = None;
+        let mut debug_line_str: &[u8] = &[];
+        let mut debug_str: &[u8] = &[];
+
+        let mut reader = ModuleReader::new(wasm).ok()?;
+        while !reader.eof() {
+            let section = reader.read().ok()?;
+            let range = section.range();
+            let data = &wasm[range.start..range.end];
+            match section.code {
+                SectionCode::Code => code_offset = Some(range.start as u32),
+                SectionCode::Custom { name, .. } => match name {
+                    ".debug_line" => debug_line = Some(data),
+                    ".debug_line_str" => debug_line_str = data,
+                    ".debug_str" => debug_str = data,
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        let mut map = DebugLineMap {
+            code_offset: code_offset?,
+            files: vec![],
+            rows: vec![],
+        };
+        let mut parser = Parser {
+            debug_line_str: DebugLineStr::from(EndianSlice::new(debug_line_str, LittleEndian)),
+            debug_str: DebugStr::new(debug_str, LittleEndian),
+            file_ids: HashMap::new(),
+            map: &mut map,
+        };
+        parser.parse(debug_line?)?;
+
+        // Order the rows by address, the end of a sequence coming before a sequence
+        // starting at the same address.
+        map.rows.sort_by(|a, b| {
+            a.address
+                .cmp(&b.address)
+                .then(b.end_sequence.cmp(&a.end_sequence))
+        });
+        Some(map)
+    }
+
+    /// Returns the source line of the code at `offset` in the module.
+    pub fn lookup(&self, offset: u32) -> Option<SourceLocation<'_>> {
+        let address = offset.checked_sub(self.code_offset)?;
+        let index = self.rows_before(address).checked_sub(1)?;
+        self.location(&self.rows[index])
+    }
+
+    /// Returns the source lines of the code from `start` to `end`, exclusive, in the module.
+    pub fn locations(&self, start: u32, end: u32) -> Vec<SourceLocation<'_>> {
+        let mut locations: Vec<SourceLocation> = self.lookup(start).into_iter().collect();
+        if let (Some(start), Some(end)) = (
+            start.checked_sub(self.code_offset),
+            end.checked_sub(self.code_offset),
+        ) {
+            let first = self.rows_before(start);
+            let last = self.rows_before(end.saturating_sub(1)).max(first);
+            for row in self.rows[first..last].iter() {
+                if let Some(location) = self.location(row) {
+                    if !locations.contains(&location) {
+                        locations.push(location);
+                    }
+                }
+            }
+        }
+        locations
+    }
+
+    /// Returns the number of rows at or before `address`.
+    fn rows_before(&self, address: u32) -> usize {
+        match self
+            .rows
+            .binary_search_by(|row| row.address.cmp(&address).then(Ordering::Less))
+        {
+            Ok(index) | Err(index) => index,
+        }
+    }
+
+    fn location(&self, row: &Row) -> Option<SourceLocation<'_>> {
+        if row.end_sequence || row.line == 0 {
+            return None;
+        }
+        Some(SourceLocation {
+            file: &self.files[row.file? as usize],
+            line: row.line,
+        })
+    }
+}
+
+struct Parser<'a, 'm> {
+    debug_line_str: DebugLineStr<Reader<'a>>,
+    debug_str: DebugStr<Reader<'a>>,
+    file_ids: HashMap<String, u32>,
+    map: &'m mut DebugLineMap,
+}
+
+impl<'a, 'm> Parser<'a, 'm> {
+    /// Reads the line programs of all the units of the `.debug_line` section.
+    fn parse(&mut self, debug_line: &'a [u8]) -> Option<()> {
+        let section = DebugLine::new(debug_line, LittleEndian);
+        let mut offset = 0;
+        while offset < debug_line.len() {
+            // Addresses are 32-bit offsets in the code section.
+            let program = section
+                .program(DebugLineOffset(offset), 4, None, None)
+                .ok()?;
+            let header = program.header();
+            offset = offset
+                .checked_add(header.unit_length())?
+                .checked_add(usize::from(header.format().initial_length_size()))?;
+
+            // Maps the file register to the files of the map.
+            let mut files: HashMap<u64, Option<u32>> = HashMap::new();
+            let mut rows = program.rows();
+            while let Some((header, row)) = rows.next_row().ok()? {
+                let address = row.address();
+                if address > u64::from(std::u32::MAX) {
+                    continue;
+                }
+                let file = match files.get(&row.file_index()) {
+                    Some(&file) => file,
+                    None => {
+                        let file = self.file_id(header, row.file_index());
+                        files.insert(row.file_index(), file);
+                        file
+                    }
+                };
+                let line = row
+                    .line()
+                    .filter(|&line| line <= u64::from(std::u32::MAX))
+                    .unwrap_or(0);
+                self.map.rows.push(Row {
+                    address: address as u32,
+                    file,
+                    line: line as u32,
+                    end_sequence: row.end_sequence(),
+                });
+            }
+        }
+        Some(())
+    }
+
+    /// Returns the index in the map of the file at index `file` of the unit of `header`.
+    fn file_id(&mut self, header: &LineProgramHeader<Reader<'a>>, file: u64) -> Option<u32> {
+        let entry = header.file(file)?;
+        let name = self.attr_str(entry.path_name())?;
+        let directory = entry
+            .directory(header)
+            .and_then(|directory| self.attr_str(directory));
+        let path = match directory {
+            Some(directory) if !directory.is_empty() && !name.starts_with('/') => {
+                format!("{}/{}", directory.trim_end_matches('/'), name)
+            }
+            _ => name.to_string(),
+        };
+        let files = &mut self.map.files;
+        Some(*self.file_ids.entry(path.clone()).or_insert_with(|| {
+            files.push(path);
+            files.len() as u32 - 1
+        }))
+    }
+
+    /// Returns the string of an attribute of a file entry.
+    fn attr_str(&self, value: AttributeValue<Reader<'a>>) -> Option<&'a str> {
+        let string = match value {
+            AttributeValue::String(string) => string,
+            AttributeValue::DebugLineStrRef(offset) => self.debug_line_str.get_str(offset).ok()?,
+            AttributeValue::DebugStrRef(offset) => self.debug_str.get_str(offset).ok()?,
+            _ => return None,
+        };
+        string.to_string().ok()
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use gimli::{
+        DW_LNE_end_sequence, DW_LNE_set_address, DW_LNS_advance_line, DW_LNS_advance_pc,
+        DW_LNS_copy,
+    };
+
+    /// Builds a module with a single function of five `nop`s, at offsets 23 to 27, and a
+    /// `.debug_line` section mapping them to `src/lib.rs`. The code section starts at 20.
+    pub(in crate::profiling) fn sample_module() -> Vec<u8> {
+        let mut program = vec![];
+        // DW_LNE_set_address 3
+        program.extend_from_slice(&[0, 5, DW_LNE_set_address.0, 3, 0, 0, 0]);
+        // Line 3 at address 3.
+        program.extend_from_slice(&[DW_LNS_advance_line.0, 2, DW_LNS_copy.0]);
+        // Line 4 at address 5, as a special opcode: (1 - line_base) + 2 * line_range + opcode_base.
+        program.push((1 + 5) + 2 * 14 + 13);
+        // Line 7 at address 5.
+        program.extend_from_slice(&[
+            DW_LNS_advance_pc.0,
+            2,
+            DW_LNS_advance_line.0,
+            3,
+            DW_LNS_copy.0,
+        ]);
+        // DW_LNE_end_sequence at address 9.
+        program.extend_from_slice(&[DW_LNS_advance_pc.0, 2, 0, 1, DW_LNE_end_sequence.0]);
+
+        let mut header = vec![];
+        // minimum_instruction_length, maximum_operations_per_instruction,
+        // default_is_stmt, line_base, line_range, opcode_base
+        header.extend_from_slice(&[1, 1, 1, (-5i8) as u8, 14, 13]);
+        header.extend_from_slice(&[0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1]);
+        header.extend_from_slice(b"src\0\0");
+        header.extend_from_slice(b"lib.rs\0\x01\0\0\0");
+
+        let mut unit = vec![4, 0];
+        unit.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        unit.extend_from_slice(&header);
+        unit.extend_from_slice(&program);
+        let mut debug_line = (unit.len() as u32).to_le_bytes().to_vec();
+        debug_line.extend_from_slice(&unit);
+
+        let mut module = b"\0asm\x01\0\0\0".to_vec();
+        // type section: () -> ()
+        module.extend_from_slice(&[1, 4, 1, 0x60, 0, 0]);
+        // function section
+        module.extend_from_slice(&[3, 2, 1, 0]);
+        // code section: nop, nop, nop, nop, nop, end
+        module.extend_from_slice(&[10, 9, 1, 7, 0, 1, 1, 1, 1, 1, 0x0b]);
+        let name = b".debug_line";
+        module.push(0);
+        module.push((1 + name.len() + debug_line.len()) as u8);
+        module.push(name.len() as u8);
+        module.extend_from_slice(name);
+        module.extend_from_slice(&debug_line);
+        module
+    }
+
+    #[test]
+    fn test_lookup() {
+        let map = DebugLineMap::from_module(&sample_module()).unwrap();
+        let location = |line| {
+            Some(SourceLocation {
+                file: "src/lib.rs",
+                line,
+            })
+        };
+        assert_eq!(map.lookup(22), None);
+        assert_eq!(map.lookup(23), location(3));
+        assert_eq!(map.lookup(24), location(3));
+        assert_eq!(map.lookup(25), location(4));
+        assert_eq!(map.lookup(28), location(7));
+        assert_eq!(map.lookup(29), None);
+    }
+
+    #[test]
+    fn test_locations() {
+        let map = DebugLineMap::from_module(&sample_module()).unwrap();
+        let lines: Vec<u32> = map
+            .locations(24, 27)
+            .iter()
+            .map(|location| location.line)
+            .collect();
+        assert_eq!(lines, vec![3, 4]);
+    }
+
+    #[test]
+    fn test_no_debug_line() {
+        let module = sample_module();
+        // Cut the `.debug_line` section.
+        assert!(DebugLineMap::from_module(&module[..29]).is_none());
+    }
+}
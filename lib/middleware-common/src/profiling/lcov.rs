@@ -0,0 +1,149 @@
+//! Export of profiles as lcov tracefiles.
+
+use super::{DebugLineMap, Profile};
+use std::{cmp, collections::BTreeMap, fmt::Write};
+
+#[derive(Default)]
+struct SourceFile {
+    /// The line, name and number of calls of each function.
+    functions: Vec<(u32, String, u64)>,
+    /// The number of executions of each line.
+    lines: BTreeMap<u32, u64>,
+}
+
+impl SourceFile {
+    fn hit(&mut self, line: u32, count: u64) {
+        let hits = self.lines.entry(line).or_insert(0);
+        *hits = cmp::max(*hits, count);
+    }
+}
+
+impl Profile {
+    /// Exports the profile as an lcov tracefile.
+    ///
+    /// With a `debug_line` map, each source line of a block is reported with the count of
+    /// the block. Otherwise all blocks are reported in a single file named `module_name`,
+    /// using their offsets in the module as line numbers.
+    pub fn to_lcov(&self, debug_line: Option<&DebugLineMap>, module_name: &str) -> String {
+        let mut files: BTreeMap<String, SourceFile> = BTreeMap::new();
+        for function in &self.functions {
+            let label = function.label();
+            let entry = match function.blocks.first() {
+                Some(entry) => entry,
+                None => continue,
+            };
+            match debug_line {
+                Some(debug_line) => {
+                    if let Some(location) = debug_line.lookup(entry.start) {
+                        files
+                            .entry(location.file.to_string())
+                            .or_default()
+                            .functions
+                            .push((location.line, label, function.count));
+                    }
+                    for block in &function.blocks {
+                        for location in debug_line.locations(block.start, block.end) {
+                            files
+                                .entry(location.file.to_string())
+                                .or_default()
+                                .hit(location.line, block.count);
+                        }
+                    }
+                }
+                None => {
+                    let file = files.entry(module_name.to_string()).or_default();
+                    file.functions.push((entry.start, label, function.count));
+                    for block in &function.blocks {
+                        file.hit(block.start, block.count);
+                    }
+                }
+            }
+        }
+
+        let mut out = String::new();
+        for (path, file) in files.iter() {
+            writeln!(out, "TN:").unwrap();
+            writeln!(out, "SF:{}", path).unwrap();
+            for (line, name, _) in file.functions.iter() {
+                writeln!(out, "FN:{},{}", line, name).unwrap();
+            }
+            for (_, name, count) in file.functions.iter() {
+                writeln!(out, "FNDA:{},{}", count, name).unwrap();
+            }
+            writeln!(out, "FNF:{}", file.functions.len()).unwrap();
+            writeln!(
+                out,
+                "FNH:{}",
+                file.functions
+                    .iter()
+                    .filter(|(_, _, count)| *count > 0)
+                    .count()
+            )
+            .unwrap();
+            for (line, count) in file.lines.iter() {
+                writeln!(out, "DA:{},{}", line, count).unwrap();
+            }
+            writeln!(out, "LF:{}", file.lines.len()).unwrap();
+            writeln!(
+                out,
+                "LH:{}",
+                file.lines.values().filter(|&&count| count > 0).count()
+            )
+            .unwrap();
+            writeln!(out, "end_of_record").unwrap();
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiling::{
+        debug_line::tests::sample_module, tests::sample_profile, BlockKind, BlockLayout,
+        FunctionLayout, ProfileLayout,
+    };
+    use wasmer_runtime_core::{structures::TypedIndex, types::FuncIndex};
+
+    #[test]
+    fn test_to_lcov_without_debug_info() {
+        let profile = sample_profile(&[2, 20, 18, 2, 0]);
+        assert_eq!(
+            profile.to_lcov(None, "sum.wasm"),
+            "TN:\nSF:sum.wasm\nFN:48,sum\nFN:72,func2\nFNDA:2,sum\nFNDA:0,func2\nFNF:2\nFNH:1\n\
+             DA:48,2\nDA:52,20\nDA:64,18\nDA:66,0\nDA:68,2\nDA:72,0\nLF:6\nLH:4\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn test_to_lcov_with_debug_info() {
+        let debug_line = DebugLineMap::from_module(&sample_module()).unwrap();
+        let layout = ProfileLayout {
+            functions: vec![FunctionLayout {
+                func_index: FuncIndex::new(0),
+                blocks: vec![
+                    BlockLayout {
+                        kind: BlockKind::Entry,
+                        start: 23,
+                        end: 27,
+                        counter: Some(0),
+                    },
+                    BlockLayout {
+                        kind: BlockKind::Unreachable,
+                        start: 27,
+                        end: 29,
+                        counter: None,
+                    },
+                ],
+            }],
+            counter_count: 1,
+        };
+        assert_eq!(
+            layout
+                .profile(&[3], |_| None)
+                .to_lcov(Some(&debug_line), "unused"),
+            "TN:\nSF:src/lib.rs\nFN:3,func0\nFNDA:3,func0\nFNF:1\nFNH:1\n\
+             DA:3,3\nDA:4,3\nDA:7,0\nLF:3\nLH:2\nend_of_record\n"
+        );
+    }
+}
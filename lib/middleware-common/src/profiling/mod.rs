@@ -0,0 +1,439 @@
+//! Per-function and per-block execution profiling.
+//!
+//! The [`Profiling`] middleware splits each function into blocks of straight-line code and
+//! increments a counter when a block is entered. Counters live in a buffer owned by the
+//! instance, so counting costs a single memory increment and no call into the host.
+//!
+//! The layout of the counters is recorded in a [`ProfileLayout`] while the module is
+//! compiled, and the counters are allocated when the module is instantiated. Once the
+//! instance has run, [`collect`] pairs the layout with the counters into a [`Profile`],
+//! which can be exported as an lcov tracefile, a pprof profile or folded stacks for
+//! flamegraph tools.
+//!
+//! # Usage:
+//!
+//! ```
+//! # use std::sync::{Arc, Mutex};
+//! # use wasmer_middleware_common::profiling::{self, Profiling, ProfileLayout};
+//! # use wasmer_runtime_core::{codegen::MiddlewareChain, Instance};
+//! let layout = Arc::new(Mutex::new(ProfileLayout::new()));
+//! let middlewares_gen = {
+//!     let layout = Arc::clone(&layout);
+//!     move || {
+//!         let mut middlewares = MiddlewareChain::new();
+//!         middlewares.push(Profiling::new(Arc::clone(&layout)));
+//!         middlewares
+//!     }
+//! };
+//!
+//! // Once the module is compiled with `middlewares_gen` and instantiated:
+//! fn run_profiled(instance: &Instance, layout: &ProfileLayout) -> Vec<u8> {
+//!     instance.call("main", &[]).unwrap();
+//!     profiling::collect(instance, layout).to_pprof(None, "main.wasm")
+//! }
+//! ```
+//!
+//! [`Profiling`]: struct.Profiling.html
+//! [`ProfileLayout`]: struct.ProfileLayout.html
+//! [`collect`]: fn.collect.html
+//! [`Profile`]: struct.Profile.html
+
+mod debug_line;
+mod lcov;
+mod pprof;
+
+pub use self::debug_line::{DebugLineMap, SourceLocation};
+
+use std::sync::{Arc, Mutex};
+use wasmer_runtime_core::{
+    codegen::{Event, EventSink, FunctionMiddleware, InternalEvent},
+    module::ModuleInfo,
+    structures::TypedIndex,
+    types::{FuncIndex, LocalFuncIndex},
+    wasmparser::Operator,
+    Instance,
+};
+
+/// What starts a block of a function.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlockKind {
+    /// The entry of the function.
+    Entry,
+    /// The header of a `loop`, entered once per iteration.
+    Loop,
+    /// The body of an `if`.
+    Then,
+    /// The body of an `else`.
+    Else,
+    /// The code following the `end` of a `block`, `loop` or `if`.
+    End,
+    /// The code following a `br_if` which did not branch.
+    BrIfFallthrough,
+    /// The code following an unconditional branch, which never runs.
+    Unreachable,
+}
+
+impl BlockKind {
+    /// Returns a short name of the kind, as used in exported profiles.
+    pub fn name(self) -> &'static str {
+        match self {
+            BlockKind::Entry => "entry",
+            BlockKind::Loop => "loop",
+            BlockKind::Then => "then",
+            BlockKind::Else => "else",
+            BlockKind::End => "end",
+            BlockKind::BrIfFallthrough => "br_if",
+            BlockKind::Unreachable => "unreachable",
+        }
+    }
+}
+
+/// A block of straight-line code of a function.
+#[derive(Clone, Debug)]
+pub struct BlockLayout {
+    pub kind: BlockKind,
+    /// The offset in the module of the operator starting the block.
+    pub start: u32,
+    /// The offset in the module of the end of the block, exclusive.
+    pub end: u32,
+    /// The index of the counter of the block, `None` for unreachable code.
+    pub counter: Option<u32>,
+}
+
+/// The blocks of a function, the first one being its entry.
+#[derive(Clone, Debug)]
+pub struct FunctionLayout {
+    pub func_index: FuncIndex,
+    pub blocks: Vec<BlockLayout>,
+}
+
+/// The counters allocated by a `Profiling` middleware while compiling a module.
+///
+/// A layout describes the counters of a single module, so each module must be compiled
+/// with its own layout.
+#[derive(Clone, Debug, Default)]
+pub struct ProfileLayout {
+    pub functions: Vec<FunctionLayout>,
+    counter_count: u32,
+}
+
+impl ProfileLayout {
+    pub fn new() -> ProfileLayout {
+        ProfileLayout::default()
+    }
+
+    /// Returns the number of counters used by the module.
+    pub fn counter_count(&self) -> usize {
+        self.counter_count as usize
+    }
+
+    /// Pairs the blocks of the layout with their `counters`. The functions are named with
+    /// `func_name`.
+    pub fn profile<F>(&self, counters: &[u64], func_name: F) -> Profile
+    where
+        F: Fn(FuncIndex) -> Option<String>,
+    {
+        let count = |counter: Option<u32>| {
+            counter
+                .and_then(|counter| counters.get(counter as usize))
+                .cloned()
+                .unwrap_or(0)
+        };
+        Profile {
+            functions: self
+                .functions
+                .iter()
+                .map(|function| FunctionProfile {
+                    func_index: function.func_index,
+                    name: func_name(function.func_index),
+                    count: function
+                        .blocks
+                        .first()
+                        .map(|block| count(block.counter))
+                        .unwrap_or(0),
+                    blocks: function
+                        .blocks
+                        .iter()
+                        .map(|block| BlockProfile {
+                            kind: block.kind,
+                            start: block.start,
+                            end: block.end,
+                            count: count(block.counter),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    fn allocate_counter(&mut self) -> u32 {
+        self.counter_count += 1;
+        self.counter_count - 1
+    }
+}
+
+/// The execution counts of the functions of a module.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub functions: Vec<FunctionProfile>,
+}
+
+/// The execution counts of a function and of its blocks.
+#[derive(Clone, Debug)]
+pub struct FunctionProfile {
+    pub func_index: FuncIndex,
+    pub name: Option<String>,
+    /// The number of calls of the function.
+    pub count: u64,
+    pub blocks: Vec<BlockProfile>,
+}
+
+impl FunctionProfile {
+    /// Returns the name of the function, or a name made from its index.
+    pub fn label(&self) -> String {
+        match self.name {
+            Some(ref name) => name.clone(),
+            None => format!("func{}", self.func_index.index()),
+        }
+    }
+}
+
+/// The execution count of a block.
+#[derive(Clone, Debug)]
+pub struct BlockProfile {
+    pub kind: BlockKind,
+    /// The offset in the module of the operator starting the block.
+    pub start: u32,
+    /// The offset in the module of the end of the block, exclusive.
+    pub end: u32,
+    /// The number of times the block was entered.
+    pub count: u64,
+}
+
+impl Profile {
+    /// Exports the profile as folded stacks, one line per executed block, as read by
+    /// `flamegraph.pl` and compatible tools.
+    pub fn to_folded(&self) -> String {
+        let mut out = String::new();
+        for function in &self.functions {
+            let label = function.label();
+            for block in function.blocks.iter().filter(|block| block.count > 0) {
+                out.push_str(&format!(
+                    "{};{}@{:#x} {}\n",
+                    label,
+                    block.kind.name(),
+                    block.start,
+                    block.count
+                ));
+            }
+        }
+        out
+    }
+}
+
+/// Returns the profile of the calls made by an Instance since it was created, including
+/// its start function, or since its counters were reset with `Instance::reset_counters`.
+/// The functions are named from the `name` custom section of its module, which is only
+/// read after the code of the module.
+pub fn collect(instance: &Instance, layout: &ProfileLayout) -> Profile {
+    let info = &instance.module.info;
+    layout.profile(instance.counters(), |func_index| {
+        info.func_name(func_index).map(String::from)
+    })
+}
+
+/// Profiling is a compiler middleware counting how many times each function and each
+/// block of straight-line code is executed.
+///
+/// The counters are recorded in the shared [`ProfileLayout`] and allocated for each
+/// instance when it is created. Counting is supported by the singlepass and LLVM backends.
+///
+/// [`ProfileLayout`]: struct.ProfileLayout.html
+pub struct Profiling {
+    layout: Arc<Mutex<ProfileLayout>>,
+    function: Option<FunctionLayout>,
+    depth: usize,
+}
+
+impl Profiling {
+    pub fn new(layout: Arc<Mutex<ProfileLayout>>) -> Profiling {
+        Profiling {
+            layout,
+            function: None,
+            depth: 0,
+        }
+    }
+
+    /// Ends the current block at `source_loc` and starts a block of the given kind.
+    fn begin_block(&mut self, kind: BlockKind, source_loc: u32, sink: &mut EventSink) {
+        let function = self.function.as_mut().unwrap();
+        if let Some(last) = function.blocks.last_mut() {
+            last.end = source_loc;
+        }
+        let counter = if kind == BlockKind::Unreachable {
+            None
+        } else {
+            let counter = self.layout.lock().unwrap().allocate_counter();
+            sink.push(Event::Internal(InternalEvent::IncrementCounter(counter)));
+            Some(counter)
+        };
+        function.blocks.push(BlockLayout {
+            kind,
+            start: source_loc,
+            end: source_loc,
+            counter,
+        });
+    }
+
+    /// Ends the current function at `end`, recording it in the layout.
+    fn end_function(&mut self, end: u32) {
+        if let Some(mut function) = self.function.take() {
+            if let Some(last) = function.blocks.last_mut() {
+                last.end = end;
+            }
+            self.layout.lock().unwrap().functions.push(function);
+        }
+    }
+
+    fn in_unreachable_block(&self) -> bool {
+        self.function
+            .as_ref()
+            .and_then(|function| function.blocks.last())
+            .map(|block| block.kind == BlockKind::Unreachable)
+            .unwrap_or(false)
+    }
+}
+
+impl FunctionMiddleware for Profiling {
+    type Error = String;
+    fn feed_event<'a, 'b: 'a>(
+        &mut self,
+        op: Event<'a, 'b>,
+        module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+        source_loc: u32,
+    ) -> Result<(), Self::Error> {
+        let next_block = match op {
+            Event::Internal(InternalEvent::FunctionBegin(local_index)) => {
+                let func_index = LocalFuncIndex::new(local_index as usize).convert_up(module_info);
+                self.function = Some(FunctionLayout {
+                    func_index,
+                    blocks: vec![],
+                });
+                self.depth = 1;
+                Some(BlockKind::Entry)
+            }
+            Event::Wasm(&ref op) | Event::WasmOwned(ref op) => match *op {
+                Operator::Block { .. } => {
+                    self.depth += 1;
+                    None
+                }
+                Operator::Loop { .. } => {
+                    self.depth += 1;
+                    Some(BlockKind::Loop)
+                }
+                Operator::If { .. } => {
+                    self.depth += 1;
+                    Some(BlockKind::Then)
+                }
+                Operator::Else => Some(BlockKind::Else),
+                Operator::End => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        // The end of the function body is a single byte.
+                        self.end_function(source_loc + 1);
+                        None
+                    } else {
+                        Some(BlockKind::End)
+                    }
+                }
+                Operator::BrIf { .. } => Some(BlockKind::BrIfFallthrough),
+                Operator::Br { .. }
+                | Operator::BrTable { .. }
+                | Operator::Return
+                | Operator::Unreachable => {
+                    if self.in_unreachable_block() {
+                        None
+                    } else {
+                        Some(BlockKind::Unreachable)
+                    }
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        sink.push(op);
+        if let Some(kind) = next_block {
+            self.begin_block(kind, source_loc, sink);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(kind: BlockKind, start: u32, end: u32, counter: Option<u32>) -> BlockLayout {
+        BlockLayout {
+            kind,
+            start,
+            end,
+            counter,
+        }
+    }
+
+    pub(super) fn sample_layout() -> ProfileLayout {
+        ProfileLayout {
+            functions: vec![
+                FunctionLayout {
+                    func_index: FuncIndex::new(1),
+                    blocks: vec![
+                        block(BlockKind::Entry, 0x30, 0x34, Some(0)),
+                        block(BlockKind::Loop, 0x34, 0x40, Some(1)),
+                        block(BlockKind::BrIfFallthrough, 0x40, 0x42, Some(2)),
+                        block(BlockKind::Unreachable, 0x42, 0x44, None),
+                        block(BlockKind::End, 0x44, 0x48, Some(3)),
+                    ],
+                },
+                FunctionLayout {
+                    func_index: FuncIndex::new(2),
+                    blocks: vec![block(BlockKind::Entry, 0x48, 0x50, Some(4))],
+                },
+            ],
+            counter_count: 5,
+        }
+    }
+
+    /// Profiles `sample_layout` with function 1 named "sum".
+    pub(super) fn sample_profile(counters: &[u64]) -> Profile {
+        sample_layout().profile(counters, |func_index| {
+            if func_index.index() == 1 {
+                Some("sum".to_string())
+            } else {
+                None
+            }
+        })
+    }
+
+    #[test]
+    fn test_profile_counts() {
+        let profile = sample_profile(&[2, 20, 18, 2, 0]);
+        let sum = &profile.functions[0];
+        assert_eq!(sum.label(), "sum");
+        assert_eq!(sum.count, 2);
+        let counts: Vec<u64> = sum.blocks.iter().map(|block| block.count).collect();
+        assert_eq!(counts, vec![2, 20, 18, 0, 2]);
+        assert_eq!(profile.functions[1].label(), "func2");
+        assert_eq!(profile.functions[1].count, 0);
+    }
+
+    #[test]
+    fn test_to_folded() {
+        let profile = sample_profile(&[2, 20, 18, 2, 0]);
+        assert_eq!(
+            profile.to_folded(),
+            "sum;entry@0x30 2\nsum;loop@0x34 20\nsum;br_if@0x40 18\nsum;end@0x44 2\n"
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Export of profiles in the pprof format, as read by `go tool pprof`.
+//!
+//! The profile is encoded as an uncompressed `perftools.profiles.Profile` protocol buffer.
+
+use super::{DebugLineMap, Profile};
+use std::collections::HashMap;
+
+// Field numbers of `profile.proto`.
+const PROFILE_SAMPLE_TYPE: u32 = 1;
+const PROFILE_SAMPLE: u32 = 2;
+const PROFILE_LOCATION: u32 = 4;
+const PROFILE_FUNCTION: u32 = 5;
+const PROFILE_STRING_TABLE: u32 = 6;
+const PROFILE_PERIOD_TYPE: u32 = 11;
+const PROFILE_PERIOD: u32 = 12;
+const VALUE_TYPE_TYPE: u32 = 1;
+const VALUE_TYPE_UNIT: u32 = 2;
+const SAMPLE_LOCATION_ID: u32 = 1;
+const SAMPLE_VALUE: u32 = 2;
+const LOCATION_ID: u32 = 1;
+const LOCATION_ADDRESS: u32 = 3;
+const LOCATION_LINE: u32 = 4;
+const LINE_FUNCTION_ID: u32 = 1;
+const LINE_LINE: u32 = 2;
+const FUNCTION_ID: u32 = 1;
+const FUNCTION_NAME: u32 = 2;
+const FUNCTION_SYSTEM_NAME: u32 = 3;
+const FUNCTION_FILENAME: u32 = 4;
+const FUNCTION_START_LINE: u32 = 5;
+
+/// A protocol buffer message being encoded.
+#[derive(Default)]
+struct Message {
+    buf: Vec<u8>,
+}
+
+impl Message {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+
+    /// Writes a varint field, omitting it when zero like protobuf encoders do.
+    fn uint64(&mut self, field: u32, value: u64) {
+        if value != 0 {
+            self.varint(u64::from(field) << 3);
+            self.varint(value);
+        }
+    }
+
+    fn bytes(&mut self, field: u32, bytes: &[u8]) {
+        self.varint(u64::from(field) << 3 | 2);
+        self.varint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn message(&mut self, field: u32, message: Message) {
+        self.bytes(field, &message.buf);
+    }
+
+    fn packed(&mut self, field: u32, values: &[u64]) {
+        let mut packed = Message::default();
+        for &value in values {
+            packed.varint(value);
+        }
+        self.message(field, packed);
+    }
+}
+
+/// The string table of a profile, whose first string must be empty.
+struct Strings {
+    strings: Vec<String>,
+    indices: HashMap<String, u64>,
+}
+
+impl Strings {
+    fn new() -> Strings {
+        let mut strings = Strings {
+            strings: vec![],
+            indices: HashMap::new(),
+        };
+        strings.index("");
+        strings
+    }
+
+    fn index(&mut self, string: &str) -> u64 {
+        if let Some(&index) = self.indices.get(string) {
+            return index;
+        }
+        let index = self.strings.len() as u64;
+        self.strings.push(string.to_string());
+        self.indices.insert(string.to_string(), index);
+        index
+    }
+}
+
+fn value_type(strings: &mut Strings, ty: &str, unit: &str) -> Message {
+    let mut value_type = Message::default();
+    value_type.uint64(VALUE_TYPE_TYPE, strings.index(ty));
+    value_type.uint64(VALUE_TYPE_UNIT, strings.index(unit));
+    value_type
+}
+
+impl Profile {
+    /// Exports the profile in the pprof format, with one sample per executed block.
+    ///
+    /// Blocks are reported at their offsets in the module, and at their source lines when
+    /// a `debug_line` map is given. Functions without a source file are reported in a
+    /// file named `module_name`.
+    pub fn to_pprof(&self, debug_line: Option<&DebugLineMap>, module_name: &str) -> Vec<u8> {
+        let mut strings = Strings::new();
+        let mut profile = Message::default();
+        let sample_type = value_type(&mut strings, "executions", "count");
+        profile.message(PROFILE_SAMPLE_TYPE, sample_type);
+
+        let mut location_id = 0;
+        for (function_index, function) in self.functions.iter().enumerate() {
+            let function_id = function_index as u64 + 1;
+            let entry = function
+                .blocks
+                .first()
+                .and_then(|entry| debug_line?.lookup(entry.start));
+
+            let label = strings.index(&function.label());
+            let mut message = Message::default();
+            message.uint64(FUNCTION_ID, function_id);
+            message.uint64(FUNCTION_NAME, label);
+            message.uint64(FUNCTION_SYSTEM_NAME, label);
+            message.uint64(
+                FUNCTION_FILENAME,
+                strings.index(entry.map(|entry| entry.file).unwrap_or(module_name)),
+            );
+            message.uint64(
+                FUNCTION_START_LINE,
+                entry.map(|entry| u64::from(entry.line)).unwrap_or(0),
+            );
+            profile.message(PROFILE_FUNCTION, message);
+
+            for block in function.blocks.iter().filter(|block| block.count > 0) {
+                location_id += 1;
+                let mut line = Message::default();
+                line.uint64(LINE_FUNCTION_ID, function_id);
+                if let Some(location) = debug_line.and_then(|map| map.lookup(block.start)) {
+                    line.uint64(LINE_LINE, u64::from(location.line));
+                }
+                let mut location = Message::default();
+                location.uint64(LOCATION_ID, location_id);
+                location.uint64(LOCATION_ADDRESS, u64::from(block.start));
+                location.message(LOCATION_LINE, line);
+                profile.message(PROFILE_LOCATION, location);
+
+                let mut sample = Message::default();
+                sample.packed(SAMPLE_LOCATION_ID, &[location_id]);
+                sample.packed(SAMPLE_VALUE, &[block.count]);
+                profile.message(PROFILE_SAMPLE, sample);
+            }
+        }
+
+        let period_type = value_type(&mut strings, "executions", "count");
+        profile.message(PROFILE_PERIOD_TYPE, period_type);
+        profile.uint64(PROFILE_PERIOD, 1);
+        for string in strings.strings.iter() {
+            profile.bytes(PROFILE_STRING_TABLE, string.as_bytes());
+        }
+        profile.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::profiling::tests::sample_profile;
+
+    /// The value of a field of a message.
+    #[derive(Debug, PartialEq)]
+    enum Value<'a> {
+        Varint(u64),
+        Bytes(&'a [u8]),
+    }
+
+    impl<'a> Value<'a> {
+        fn varint(&self) -> Result<u64, String> {
+            match *self {
+                Value::Varint(value) => Ok(value),
+                Value::Bytes(_) => Err("expected a varint field".to_string()),
+            }
+        }
+
+        fn bytes(&self) -> Result<&'a [u8], String> {
+            match *self {
+                Value::Bytes(bytes) => Ok(bytes),
+                Value::Varint(_) => Err("expected a length-delimited field".to_string()),
+            }
+        }
+    }
+
+    /// Returns the fields of a message as numbers and values, failing on truncated
+    /// input and on wire types the profile does not use.
+    fn fields(mut buf: &[u8]) -> Result<Vec<(u32, Value)>, String> {
+        fn varint(buf: &mut &[u8]) -> Result<u64, String> {
+            let mut value = 0;
+            for shift in (0..64).step_by(7) {
+                let (&byte, rest) = buf.split_first().ok_or("truncated varint")?;
+                *buf = rest;
+                value |= u64::from(byte & 0x7f) << shift;
+                if byte & 0x80 == 0 {
+                    return Ok(value);
+                }
+            }
+            Err("varint is too long".to_string())
+        }
+        let mut fields = vec![];
+        while !buf.is_empty() {
+            let key = varint(&mut buf)?;
+            let value = match key & 7 {
+                0 => Value::Varint(varint(&mut buf)?),
+                2 => {
+                    let length = varint(&mut buf)? as usize;
+                    if length > buf.len() {
+                        return Err("truncated length-delimited field".to_string());
+                    }
+                    let (bytes, rest) = buf.split_at(length);
+                    buf = rest;
+                    Value::Bytes(bytes)
+                }
+                wire_type => return Err(format!("unexpected wire type {}", wire_type)),
+            };
+            fields.push(((key >> 3) as u32, value));
+        }
+        Ok(fields)
+    }
+
+    #[test]
+    fn test_to_pprof() -> Result<(), String> {
+        let profile = sample_profile(&[2, 20, 18, 2, 0]);
+        let pprof = profile.to_pprof(None, "sum.wasm");
+        let fields = fields(&pprof)?;
+
+        let strings = fields
+            .iter()
+            .filter(|(field, _)| *field == super::PROFILE_STRING_TABLE)
+            .map(|(_, value)| value.bytes())
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(strings[0], b"");
+        assert!(strings.contains(&&b"sum"[..]));
+        assert!(strings.contains(&&b"func2"[..]));
+        assert!(strings.contains(&&b"sum.wasm"[..]));
+
+        let values = fields
+            .iter()
+            .filter(|(field, _)| *field == super::PROFILE_SAMPLE)
+            .map(|(_, sample)| {
+                let sample = self::fields(sample.bytes()?)?;
+                assert_eq!(sample[1].0, super::SAMPLE_VALUE);
+                // The values are packed, decode them as the payload of field 1.
+                self::fields(&[&[8], sample[1].1.bytes()?].concat())?[0]
+                    .1
+                    .varint()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(values, vec![2, 20, 18, 2]);
+
+        let functions = fields
+            .iter()
+            .filter(|(field, _)| *field == super::PROFILE_FUNCTION)
+            .count();
+        assert_eq!(functions, 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+use wasmer_runtime_core::{
+    codegen::{Event, EventSink, FunctionMiddleware, InternalEvent},
+    module::ModuleInfo,
+    vm::{Ctx, InternalField},
+    wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType},
+    Instance,
+};
+
+static DEPTH_FIELD: InternalField = InternalField::allocate();
+
+/// Holds the operand of a branch while the call depth is updated.
+static OPERAND_FIELD: InternalField = InternalField::allocate();
+
+/// StackDepthLimit is a compiler middleware that counts the calls in progress in an instance
+/// and traps with a `StackDepthExceededError` when a call would exceed the `limit`.
+///
+/// The call depth is incremented when a function is entered and decremented when it returns,
+/// either with `return`, by reaching the end of its body or by branching to its outermost
+/// label. Calls of imported functions are not counted.
+///
+/// Unlike the exhaustion of the native stack, which depends on its size and on the frame
+/// layout of the backend, the trap happens at the same call for the same input with every
+/// compiler backend with StackDepthLimit enabled.
+///
+/// When a call from the host traps, the call depth of the instance is restored to its value
+/// before the call, so that the trap happens at the same call on the next calls.
+pub struct StackDepthLimit {
+    limit: u64,
+    /// The number of blocks open in the current function.
+    depth: u32,
+}
+
+impl StackDepthLimit {
+    pub fn new(limit: u64) -> StackDepthLimit {
+        StackDepthLimit { limit, depth: 0 }
+    }
+
+    /// Increments the call depth and traps with a `StackDepthExceededError` if it exceeds
+    /// the limit.
+    fn enter<'a, 'b: 'a>(&self, sink: &mut EventSink<'a, 'b>) {
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            DEPTH_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I64Const { value: 1 }));
+        sink.push(Event::WasmOwned(Operator::I64Add));
+        sink.push(Event::Internal(InternalEvent::SetInternal(
+            DEPTH_FIELD.index() as _,
+        )));
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            DEPTH_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I64Const {
+            value: self.limit as i64,
+        }));
+        sink.push(Event::WasmOwned(Operator::I64GtU));
+        sink.push(Event::WasmOwned(Operator::If {
+            ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+        }));
+        sink.push(Event::Internal(InternalEvent::Breakpoint(Box::new(|_| {
+            Err(Box::new(StackDepthExceededError))
+        }))));
+        sink.push(Event::WasmOwned(Operator::End));
+    }
+
+    /// Decrements the call depth.
+    fn leave<'a, 'b: 'a>(&self, sink: &mut EventSink<'a, 'b>) {
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            DEPTH_FIELD.index() as _,
+        )));
+        sink.push(Event::WasmOwned(Operator::I64Const { value: 1 }));
+        sink.push(Event::WasmOwned(Operator::I64Sub));
+        sink.push(Event::Internal(InternalEvent::SetInternal(
+            DEPTH_FIELD.index() as _,
+        )));
+    }
+
+    /// Decrements the call depth if the `i32` operand on top of the stack, which is left in
+    /// place, selects a branch out of the function. `returns` pushes an `i32` which is one if
+    /// the operand, available with `push_operand`, does so and zero otherwise.
+    fn leave_if<'a, 'b: 'a, F>(&self, sink: &mut EventSink<'a, 'b>, returns: F)
+    where
+        F: FnOnce(&mut EventSink<'a, 'b>),
+    {
+        sink.push(Event::WasmOwned(Operator::I64ExtendI32U));
+        sink.push(Event::Internal(InternalEvent::SetInternal(
+            OPERAND_FIELD.index() as _,
+        )));
+        sink.push(Event::Internal(InternalEvent::GetInternal(
+            DEPTH_FIELD.index() as _,
+        )));
+        returns(sink);
+        sink.push(Event::WasmOwned(Operator::I64ExtendI32U));
+        sink.push(Event::WasmOwned(Operator::I64Sub));
+        sink.push(Event::Internal(InternalEvent::SetInternal(
+            DEPTH_FIELD.index() as _,
+        )));
+        push_operand(sink);
+    }
+}
+
+/// Pushes the operand saved by `StackDepthLimit::leave_if`.
+fn push_operand<'a, 'b: 'a>(sink: &mut EventSink<'a, 'b>) {
+    sink.push(Event::Internal(InternalEvent::GetInternal(
+        OPERAND_FIELD.index() as _,
+    )));
+    sink.push(Event::WasmOwned(Operator::I32WrapI64));
+}
+
+/// The error of a call exceeding the limit of a `StackDepthLimit` middleware.
+#[derive(Copy, Clone, Debug)]
+pub struct StackDepthExceededError;
+
+impl FunctionMiddleware for StackDepthLimit {
+    type Error = String;
+    fn feed_event<'a, 'b: 'a>(
+        &mut self,
+        op: Event<'a, 'b>,
+        _module_info: &ModuleInfo,
+        sink: &mut EventSink<'a, 'b>,
+        _source_loc: u32,
+    ) -> Result<(), Self::Error> {
+        match op {
+            Event::Internal(InternalEvent::FunctionBegin(_)) => {
+                self.depth = 0;
+                sink.push(op);
+                self.enter(sink);
+                return Ok(());
+            }
+            Event::Wasm(&ref op) | Event::WasmOwned(ref op) => match *op {
+                Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+                    self.depth += 1;
+                }
+                Operator::End => {
+                    if self.depth == 0 {
+                        self.leave(sink);
+                    } else {
+                        self.depth -= 1;
+                    }
+                }
+                Operator::Return => {
+                    self.leave(sink);
+                }
+                Operator::Br { relative_depth } => {
+                    if relative_depth == self.depth {
+                        self.leave(sink);
+                    }
+                }
+                Operator::BrIf { relative_depth } => {
+                    if relative_depth == self.depth {
+                        self.leave_if(sink, |sink| {
+                            push_operand(sink);
+                            sink.push(Event::WasmOwned(Operator::I32Eqz));
+                            sink.push(Event::WasmOwned(Operator::I32Eqz));
+                        });
+                    }
+                }
+                Operator::BrTable { ref table } => {
+                    let (targets, default_target) = table
+                        .read_table()
+                        .map_err(|e| format!("BrTable read_table: {:?}", e))?;
+                    let depth = self.depth;
+                    let returning: Vec<usize> = targets
+                        .iter()
+                        .enumerate()
+                        .filter(|&(_, &target)| target == depth)
+                        .map(|(i, _)| i)
+                        .collect();
+                    if default_target == depth || !returning.is_empty() {
+                        self.leave_if(sink, |sink| {
+                            // Whether the default target is selected.
+                            if default_target == depth {
+                                push_operand(sink);
+                                sink.push(Event::WasmOwned(Operator::I32Const {
+                                    value: targets.len() as i32,
+                                }));
+                                sink.push(Event::WasmOwned(Operator::I32GeU));
+                            } else {
+                                sink.push(Event::WasmOwned(Operator::I32Const { value: 0 }));
+                            }
+                            for i in returning {
+                                push_operand(sink);
+                                sink.push(Event::WasmOwned(Operator::I32Const { value: i as i32 }));
+                                sink.push(Event::WasmOwned(Operator::I32Eq));
+                                sink.push(Event::WasmOwned(Operator::I32Or));
+                            }
+                        });
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        sink.push(op);
+        Ok(())
+    }
+
+    fn internals_restored_on_trap(&self) -> Vec<&'static InternalField> {
+        vec![&DEPTH_FIELD]
+    }
+}
+
+/// Returns the call depth of an Instance.
+pub fn get_call_depth(instance: &Instance) -> u64 {
+    instance.get_internal(&DEPTH_FIELD)
+}
+
+/// Sets the call depth of an Instance.
+pub fn set_call_depth(instance: &mut Instance, value: u64) {
+    instance.set_internal(&DEPTH_FIELD, value);
+}
+
+/// Returns the call depth in a Ctx.
+pub fn get_call_depth_ctx(ctx: &Ctx) -> u64 {
+    ctx.get_internal(&DEPTH_FIELD)
+}
+
+/// Sets the call depth in a Ctx.
+pub fn set_call_depth_ctx(ctx: &mut Ctx, value: u64) {
+    ctx.set_internal(&DEPTH_FIELD, value);
+}
@@ -8,7 +8,7 @@ use crate::{
     memory::wasmer_memory_t,
     module::wasmer_module_t,
     table::wasmer_table_t,
-    value::{wasmer_value, wasmer_value_t, wasmer_value_tag},
+    value::{wasmer_value_t, wasmer_value_tag, write_results},
     wasmer_byte_array, wasmer_result_t,
 };
 use libc::{c_int, c_uint};
@@ -424,6 +424,37 @@ pub unsafe extern "C" fn wasmer_export_to_memory(
     }
 }
 
+/// Gets a table pointer from an export pointer.
+///
+/// The caller owns the table and should call `wasmer_table_destroy` to free it.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[no_mangle]
+#[allow(clippy::cast_ptr_alignment)]
+pub unsafe extern "C" fn wasmer_export_to_table(
+    export: *const wasmer_export_t,
+    table: *mut *mut wasmer_table_t,
+) -> wasmer_result_t {
+    let named_export = &*(export as *const NamedExport);
+    let export = &named_export.export;
+
+    if let Export::Table(exported_table) = export {
+        let exported_table = Box::new(exported_table.clone());
+        *table = Box::into_raw(exported_table) as *mut wasmer_table_t;
+        wasmer_result_t::WASMER_OK
+    } else {
+        update_last_error(CApiError {
+            msg: "cannot cast the `wasmer_export_t` pointer to a `wasmer_table_t` \
+                  pointer because it does not represent a table export."
+                .to_string(),
+        });
+        wasmer_result_t::WASMER_ERROR
+    }
+}
+
 /// Gets name from wasmer_export
 #[no_mangle]
 #[allow(clippy::cast_ptr_alignment)]
@@ -436,7 +467,8 @@ pub unsafe extern "C" fn wasmer_export_name(export: *mut wasmer_export_t) -> was
 }
 
 /// Calls a `func` with the provided parameters.
-/// Results are set using the provided `results` pointer.
+/// Results are set using the provided `results` pointer, which must
+/// hold at least as many values as the function returns.
 ///
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
 ///
@@ -486,27 +518,9 @@ pub unsafe extern "C" fn wasmer_export_func_call(
 
     match result {
         Ok(results_vec) => {
-            if !results_vec.is_empty() {
-                let ret = match results_vec[0] {
-                    Value::I32(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_I32,
-                        value: wasmer_value { I32: x },
-                    },
-                    Value::I64(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_I64,
-                        value: wasmer_value { I64: x },
-                    },
-                    Value::F32(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_F32,
-                        value: wasmer_value { F32: x },
-                    },
-                    Value::F64(x) => wasmer_value_t {
-                        tag: wasmer_value_tag::WASM_F64,
-                        value: wasmer_value { F64: x },
-                    },
-                    Value::V128(_) => unimplemented!("returning V128 type"),
-                };
-                results[0] = ret;
+            if let Err(err) = write_results(results_vec, results) {
+                update_last_error(err);
+                return wasmer_result_t::WASMER_ERROR;
             }
             wasmer_result_t::WASMER_OK
         }
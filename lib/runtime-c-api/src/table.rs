@@ -1,8 +1,21 @@
-//! Create, grow, destroy tables of an instance.
+//! Create, grow, read, write, destroy tables of an instance.
 
-use crate::{error::update_last_error, wasmer_limits_t, wasmer_result_t};
-use wasmer_runtime::Table;
-use wasmer_runtime_core::types::{ElementType, TableDescriptor};
+use crate::{
+    error::{update_last_error, CApiError},
+    export::{wasmer_export_func_t, NamedExport},
+    import::wasmer_import_func_t,
+    instance::wasmer_instance_t,
+    value::{wasmer_value_t, write_results},
+    wasmer_limits_t, wasmer_result_t,
+};
+use libc::c_uint;
+use std::slice;
+use wasmer_runtime::{Instance, Table, Value};
+use wasmer_runtime_core::{
+    export::Export,
+    table::{Anyfunc, Element},
+    types::{ElementType, TableDescriptor},
+};
 
 #[repr(C)]
 #[derive(Clone)]
@@ -72,6 +85,239 @@ pub extern "C" fn wasmer_table_length(table: *mut wasmer_table_t) -> u32 {
     table.size()
 }
 
+/// Gets the reference at `index` of the given Table, a `WASM_FUNCREF` or
+/// a `WASM_EXTERNREF` value, and writes it to `value`.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_table_get(
+    table: *const wasmer_table_t,
+    index: u32,
+    value: *mut wasmer_value_t,
+) -> wasmer_result_t {
+    if table.is_null() || value.is_null() {
+        update_last_error(CApiError {
+            msg: "table or value ptr is null".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+
+    let table = &*(table as *const Table);
+    match table.get(index) {
+        Some(reference) => {
+            *value = reference.into();
+            wasmer_result_t::WASMER_OK
+        }
+        None => {
+            update_last_error(CApiError {
+                msg: format!("table index {} is out of bounds", index),
+            });
+            wasmer_result_t::WASMER_ERROR
+        }
+    }
+}
+
+/// Sets the element at `index` of the given Table to a reference value,
+/// `WASM_FUNCREF` or `WASM_EXTERNREF` depending on the element type of the
+/// table.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_table_set(
+    table: *mut wasmer_table_t,
+    index: u32,
+    value: wasmer_value_t,
+) -> wasmer_result_t {
+    if table.is_null() {
+        update_last_error(CApiError {
+            msg: "table ptr is null".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+
+    let table = &*(table as *mut Table);
+    match table.set_value(index, value.into()) {
+        Ok(()) => wasmer_result_t::WASMER_OK,
+        Err(()) => {
+            update_last_error(CApiError {
+                msg: format!(
+                    "table index {} is out of bounds or the value does not match the element type",
+                    index
+                ),
+            });
+            wasmer_result_t::WASMER_ERROR
+        }
+    }
+}
+
+/// Writes a function to the element at `index` of the given Table.
+unsafe fn set_func(table: *mut wasmer_table_t, index: u32, export: &Export) -> wasmer_result_t {
+    let table = &*(table as *mut Table);
+    let anyfunc = match Anyfunc::from_export(export) {
+        Some(anyfunc) => anyfunc,
+        None => {
+            update_last_error(CApiError {
+                msg: "the export is not a function".to_string(),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+    };
+
+    match table.set(index, Element::Anyfunc(anyfunc)) {
+        Ok(()) => wasmer_result_t::WASMER_OK,
+        Err(()) => {
+            update_last_error(CApiError {
+                msg: format!(
+                    "table index {} is out of bounds or the table does not hold functions",
+                    index
+                ),
+            });
+            wasmer_result_t::WASMER_ERROR
+        }
+    }
+}
+
+/// Sets the element at `index` of the given Table to a host function
+/// created with `wasmer_import_func_new()`.
+///
+/// The host function receives the context of the instance calling it, and
+/// must not be destroyed while the table holds it.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_table_set_import_func(
+    table: *mut wasmer_table_t,
+    index: u32,
+    func: *const wasmer_import_func_t,
+) -> wasmer_result_t {
+    if table.is_null() || func.is_null() {
+        update_last_error(CApiError {
+            msg: "table or func ptr is null".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+
+    set_func(table, index, &*(func as *const Export))
+}
+
+/// Sets the element at `index` of the given Table to a function exported
+/// by an instance, read with `wasmer_export_to_func()`.
+///
+/// The instance must not be destroyed while the table holds the function.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_table_set_export_func(
+    table: *mut wasmer_table_t,
+    index: u32,
+    func: *const wasmer_export_func_t,
+) -> wasmer_result_t {
+    if table.is_null() || func.is_null() {
+        update_last_error(CApiError {
+            msg: "table or func ptr is null".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+
+    set_func(table, index, &(*(func as *const NamedExport)).export)
+}
+
+/// Calls the function at `index` of the given Table through an instance,
+/// whose module must declare the signature of the function.
+///
+/// `results` must be large enough to hold all the values the function
+/// returns.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+#[allow(clippy::cast_ptr_alignment)]
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_table_call(
+    instance: *const wasmer_instance_t,
+    table: *const wasmer_table_t,
+    index: u32,
+    params: *const wasmer_value_t,
+    params_len: c_uint,
+    results: *mut wasmer_value_t,
+    results_len: c_uint,
+) -> wasmer_result_t {
+    if instance.is_null() || table.is_null() {
+        update_last_error(CApiError {
+            msg: "instance or table ptr is null".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+
+    if params_len > 0 && params.is_null() {
+        update_last_error(CApiError {
+            msg: "params ptr is null".to_string(),
+        });
+        return wasmer_result_t::WASMER_ERROR;
+    }
+
+    let instance = &*(instance as *const Instance);
+    let table = &*(table as *const Table);
+    let func = match instance.table_func(table, index) {
+        Some(func) => func,
+        None => {
+            update_last_error(CApiError {
+                msg: format!(
+                    "no function with a signature of the instance at table index {}",
+                    index
+                ),
+            });
+            return wasmer_result_t::WASMER_ERROR;
+        }
+    };
+
+    let params: Vec<Value> = if params_len == 0 {
+        vec![]
+    } else {
+        slice::from_raw_parts(params, params_len as usize)
+            .iter()
+            .cloned()
+            .map(|x| x.into())
+            .collect()
+    };
+    let results: &mut [wasmer_value_t] = if results_len == 0 {
+        &mut []
+    } else {
+        slice::from_raw_parts_mut(results, results_len as usize)
+    };
+
+    match func.call(&params) {
+        Ok(values) => {
+            if let Err(err) = write_results(values, results) {
+                update_last_error(err);
+                return wasmer_result_t::WASMER_ERROR;
+            }
+            wasmer_result_t::WASMER_OK
+        }
+        Err(err) => {
+            update_last_error(err);
+            wasmer_result_t::WASMER_ERROR
+        }
+    }
+}
+
 /// Frees memory for the given Table
 #[allow(clippy::cast_ptr_alignment)]
 #[no_mangle]
@@ -1,7 +1,8 @@
 //! Create and map Rust to WebAssembly values.
 
+use crate::error::CApiError;
 use wasmer_runtime::Value;
-use wasmer_runtime_core::types::Type;
+use wasmer_runtime_core::types::{ExternRef, FuncRef, NativeWasmType, Type};
 
 /// Represents all possibles WebAssembly value types.
 ///
@@ -21,6 +22,12 @@ pub enum wasmer_value_tag {
 
     /// Represents the `f64` WebAssembly type.
     WASM_F64,
+
+    /// Represents the `funcref` WebAssembly type.
+    WASM_FUNCREF,
+
+    /// Represents the `externref` WebAssembly type.
+    WASM_EXTERNREF,
 }
 
 /// Represents a WebAssembly value.
@@ -37,6 +44,8 @@ pub union wasmer_value {
     pub I64: i64,
     pub F32: f32,
     pub F64: f64,
+    pub FUNCREF: u64,
+    pub EXTERNREF: u64,
 }
 
 /// Represents a WebAssembly type and value pair,
@@ -90,6 +99,14 @@ impl From<wasmer_value_t> for Value {
                     tag: wasmer_value_tag::WASM_F64,
                     value: wasmer_value { F64 },
                 } => Value::F64(F64),
+                wasmer_value_t {
+                    tag: wasmer_value_tag::WASM_FUNCREF,
+                    value: wasmer_value { FUNCREF },
+                } => Value::FuncRef(FuncRef::from_raw(FUNCREF)),
+                wasmer_value_t {
+                    tag: wasmer_value_tag::WASM_EXTERNREF,
+                    value: wasmer_value { EXTERNREF },
+                } => Value::ExternRef(ExternRef::from_binary(EXTERNREF)),
                 _ => unreachable!("unknown WASM type"),
             }
         }
@@ -115,11 +132,53 @@ impl From<Value> for wasmer_value_t {
                 tag: wasmer_value_tag::WASM_F64,
                 value: wasmer_value { F64: x },
             },
+            Value::FuncRef(x) => wasmer_value_t {
+                tag: wasmer_value_tag::WASM_FUNCREF,
+                value: wasmer_value {
+                    FUNCREF: x.to_raw(),
+                },
+            },
+            Value::ExternRef(x) => wasmer_value_t {
+                tag: wasmer_value_tag::WASM_EXTERNREF,
+                value: wasmer_value {
+                    EXTERNREF: x.to_binary(),
+                },
+            },
             Value::V128(_) => unimplemented!("V128 not supported in C API"),
         }
     }
 }
 
+/// Writes the values returned by a function call into the `results`
+/// buffer provided by the caller, which must be large enough to hold
+/// all of them.
+pub(crate) fn write_results(
+    values: Vec<Value>,
+    results: &mut [wasmer_value_t],
+) -> Result<(), CApiError> {
+    if values.len() > results.len() {
+        return Err(CApiError {
+            msg: format!(
+                "the function returns {} values but the results buffer can only hold {}",
+                values.len(),
+                results.len()
+            ),
+        });
+    }
+
+    if values.iter().any(|value| value.ty() == Type::V128) {
+        return Err(CApiError {
+            msg: "V128 results are not supported in the C API".to_string(),
+        });
+    }
+
+    for (value, result) in values.into_iter().zip(results.iter_mut()) {
+        *result = value.into();
+    }
+
+    Ok(())
+}
+
 impl From<Type> for wasmer_value_tag {
     fn from(ty: Type) -> Self {
         #[allow(unreachable_patterns)]
@@ -128,6 +187,8 @@ impl From<Type> for wasmer_value_tag {
             Type::I64 => wasmer_value_tag::WASM_I64,
             Type::F32 => wasmer_value_tag::WASM_F32,
             Type::F64 => wasmer_value_tag::WASM_F64,
+            Type::FuncRef => wasmer_value_tag::WASM_FUNCREF,
+            Type::ExternRef => wasmer_value_tag::WASM_EXTERNREF,
             Type::V128 => unreachable!("V128 not supported in C API"),
         }
     }
@@ -141,6 +202,8 @@ impl From<wasmer_value_tag> for Type {
             wasmer_value_tag::WASM_I64 => Type::I64,
             wasmer_value_tag::WASM_F32 => Type::F32,
             wasmer_value_tag::WASM_F64 => Type::F64,
+            wasmer_value_tag::WASM_FUNCREF => Type::FuncRef,
+            wasmer_value_tag::WASM_EXTERNREF => Type::ExternRef,
             _ => unreachable!("unknown WASM type"),
         }
     }
@@ -153,6 +216,8 @@ impl From<&wasmer_runtime::wasm::Type> for wasmer_value_tag {
             Type::I64 => wasmer_value_tag::WASM_I64,
             Type::F32 => wasmer_value_tag::WASM_F32,
             Type::F64 => wasmer_value_tag::WASM_F64,
+            Type::FuncRef => wasmer_value_tag::WASM_FUNCREF,
+            Type::ExternRef => wasmer_value_tag::WASM_EXTERNREF,
             Type::V128 => unimplemented!("V128 not supported in C API"),
         }
     }
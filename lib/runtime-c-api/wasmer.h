@@ -122,6 +122,14 @@ enum wasmer_value_tag {
    * Represents the `f64` WebAssembly type.
    */
   WASM_F64,
+  /**
+   * Represents the `funcref` WebAssembly type.
+   */
+  WASM_FUNCREF,
+  /**
+   * Represents the `externref` WebAssembly type.
+   */
+  WASM_EXTERNREF,
 };
 typedef uint32_t wasmer_value_tag;
 
@@ -192,6 +200,8 @@ typedef union {
   int64_t I64;
   float F32;
   double F64;
+  uint64_t FUNCREF;
+  uint64_t EXTERNREF;
 } wasmer_value;
 
 /**
@@ -510,7 +520,8 @@ int wasmer_export_descriptors_len(wasmer_export_descriptors_t *exports);
 
 /**
  * Calls a `func` with the provided parameters.
- * Results are set using the provided `results` pointer.
+ * Results are set using the provided `results` pointer, which must
+ * hold at least as many values as the function returns.
  *
  * Returns `wasmer_result_t::WASMER_OK` upon success.
  *
@@ -593,6 +604,18 @@ const wasmer_export_func_t *wasmer_export_to_func(const wasmer_export_t *export_
  */
 wasmer_result_t wasmer_export_to_memory(const wasmer_export_t *export_, wasmer_memory_t **memory);
 
+/**
+ * Gets a table pointer from an export pointer.
+ *
+ * The caller owns the table and should call `wasmer_table_destroy` to free it.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_export_to_table(const wasmer_export_t *export_, wasmer_table_t **table);
+
 /**
  * Frees the memory for the given exports.
  *
@@ -1350,11 +1373,42 @@ wasmer_result_t wasmer_serialized_module_from_bytes(wasmer_serialized_module_t *
                                                     const uint8_t *serialized_module_bytes,
                                                     uint32_t serialized_module_bytes_length);
 
+/**
+ * Calls the function at `index` of the given Table through an instance,
+ * whose module must declare the signature of the function.
+ *
+ * `results` must be large enough to hold all the values the function
+ * returns.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_table_call(const wasmer_instance_t *instance,
+                                  const wasmer_table_t *table,
+                                  uint32_t index,
+                                  const wasmer_value_t *params,
+                                  unsigned int params_len,
+                                  wasmer_value_t *results,
+                                  unsigned int results_len);
+
 /**
  * Frees memory for the given Table
  */
 void wasmer_table_destroy(wasmer_table_t *table);
 
+/**
+ * Gets the reference at `index` of the given Table, a `WASM_FUNCREF` or
+ * a `WASM_EXTERNREF` value, and writes it to `value`.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_table_get(const wasmer_table_t *table, uint32_t index, wasmer_value_t *value);
+
 /**
  * Grows a Table by the given number of elements.
  *
@@ -1383,6 +1437,49 @@ uint32_t wasmer_table_length(wasmer_table_t *table);
  */
 wasmer_result_t wasmer_table_new(wasmer_table_t **table, wasmer_limits_t limits);
 
+/**
+ * Sets the element at `index` of the given Table to a reference value,
+ * `WASM_FUNCREF` or `WASM_EXTERNREF` depending on the element type of the
+ * table.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_table_set(wasmer_table_t *table, uint32_t index, wasmer_value_t value);
+
+/**
+ * Sets the element at `index` of the given Table to a function exported
+ * by an instance, read with `wasmer_export_to_func()`.
+ *
+ * The instance must not be destroyed while the table holds the function.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_table_set_export_func(wasmer_table_t *table,
+                                             uint32_t index,
+                                             const wasmer_export_func_t *func);
+
+/**
+ * Sets the element at `index` of the given Table to a host function
+ * created with `wasmer_import_func_new()`.
+ *
+ * The host function receives the context of the instance calling it, and
+ * must not be destroyed while the table holds it.
+ *
+ * Returns `wasmer_result_t::WASMER_OK` upon success.
+ *
+ * Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+ * and `wasmer_last_error_message` to get an error message.
+ */
+wasmer_result_t wasmer_table_set_import_func(wasmer_table_t *table,
+                                             uint32_t index,
+                                             const wasmer_import_func_t *func);
+
 #if (!defined(_WIN32) && defined(ARCH_X86_64))
 /**
  * Adds a callinfo trampoline to the builder.
@@ -86,6 +86,10 @@ enum class wasmer_value_tag : uint32_t {
   WASM_F32,
   /// Represents the `f64` WebAssembly type.
   WASM_F64,
+  /// Represents the `funcref` WebAssembly type.
+  WASM_FUNCREF,
+  /// Represents the `externref` WebAssembly type.
+  WASM_EXTERNREF,
 };
 
 struct wasmer_module_t {
@@ -143,6 +147,8 @@ union wasmer_value {
   int64_t I64;
   float F32;
   double F64;
+  uint64_t FUNCREF;
+  uint64_t EXTERNREF;
 };
 
 /// Represents a WebAssembly type and value pair,
@@ -404,7 +410,8 @@ wasmer_export_descriptor_t *wasmer_export_descriptors_get(wasmer_export_descript
 int wasmer_export_descriptors_len(wasmer_export_descriptors_t *exports);
 
 /// Calls a `func` with the provided parameters.
-/// Results are set using the provided `results` pointer.
+/// Results are set using the provided `results` pointer, which must
+/// hold at least as many values as the function returns.
 ///
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
 ///
@@ -470,6 +477,16 @@ const wasmer_export_func_t *wasmer_export_to_func(const wasmer_export_t *export_
 /// and `wasmer_last_error_message` to get an error message.
 wasmer_result_t wasmer_export_to_memory(const wasmer_export_t *export_, wasmer_memory_t **memory);
 
+/// Gets a table pointer from an export pointer.
+///
+/// The caller owns the table and should call `wasmer_table_destroy` to free it.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_export_to_table(const wasmer_export_t *export_, wasmer_table_t **table);
+
 /// Frees the memory for the given exports.
 ///
 /// Check the `wasmer_instance_exports()` function to get a complete
@@ -1119,9 +1136,36 @@ wasmer_result_t wasmer_serialized_module_from_bytes(wasmer_serialized_module_t *
                                                     const uint8_t *serialized_module_bytes,
                                                     uint32_t serialized_module_bytes_length);
 
+/// Calls the function at `index` of the given Table through an instance,
+/// whose module must declare the signature of the function.
+///
+/// `results` must be large enough to hold all the values the function
+/// returns.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_table_call(const wasmer_instance_t *instance,
+                                  const wasmer_table_t *table,
+                                  uint32_t index,
+                                  const wasmer_value_t *params,
+                                  unsigned int params_len,
+                                  wasmer_value_t *results,
+                                  unsigned int results_len);
+
 /// Frees memory for the given Table
 void wasmer_table_destroy(wasmer_table_t *table);
 
+/// Gets the reference at `index` of the given Table, a `WASM_FUNCREF` or
+/// a `WASM_EXTERNREF` value, and writes it to `value`.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_table_get(const wasmer_table_t *table, uint32_t index, wasmer_value_t *value);
+
 /// Grows a Table by the given number of elements.
 ///
 /// Returns `wasmer_result_t::WASMER_OK` upon success.
@@ -1144,6 +1188,43 @@ uint32_t wasmer_table_length(wasmer_table_t *table);
 /// and `wasmer_last_error_message` to get an error message.
 wasmer_result_t wasmer_table_new(wasmer_table_t **table, wasmer_limits_t limits);
 
+/// Sets the element at `index` of the given Table to a reference value,
+/// `WASM_FUNCREF` or `WASM_EXTERNREF` depending on the element type of the
+/// table.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_table_set(wasmer_table_t *table, uint32_t index, wasmer_value_t value);
+
+/// Sets the element at `index` of the given Table to a function exported
+/// by an instance, read with `wasmer_export_to_func()`.
+///
+/// The instance must not be destroyed while the table holds the function.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_table_set_export_func(wasmer_table_t *table,
+                                             uint32_t index,
+                                             const wasmer_export_func_t *func);
+
+/// Sets the element at `index` of the given Table to a host function
+/// created with `wasmer_import_func_new()`.
+///
+/// The host function receives the context of the instance calling it, and
+/// must not be destroyed while the table holds it.
+///
+/// Returns `wasmer_result_t::WASMER_OK` upon success.
+///
+/// Returns `wasmer_result_t::WASMER_ERROR` upon failure. Use `wasmer_last_error_length`
+/// and `wasmer_last_error_message` to get an error message.
+wasmer_result_t wasmer_table_set_import_func(wasmer_table_t *table,
+                                             uint32_t index,
+                                             const wasmer_import_func_t *func);
+
 #if (!defined(_WIN32) && defined(ARCH_X86_64))
 /// Adds a callinfo trampoline to the builder.
 uintptr_t wasmer_trampoline_buffer_builder_add_callinfo_trampoline(wasmer_trampoline_buffer_builder_t *builder,
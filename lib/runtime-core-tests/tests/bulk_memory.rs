@@ -0,0 +1,86 @@
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, Features},
+    compile_with_config, imports,
+    types::Value,
+};
+use wasmer_runtime_core_tests::get_compiler;
+
+#[test]
+fn bulk_memory_operations() {
+    const MODULE: &str = r#"
+(module
+  (memory 1)
+  (data "\01\02\03\04")
+  (func (export "init") (param i32 i32 i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    memory.init 0)
+  (func (export "copy") (param i32 i32 i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    memory.copy)
+  (func (export "fill") (param i32 i32 i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    memory.fill)
+  (func (export "drop")
+    data.drop 0))
+"#;
+
+    let mut features = wabt::Features::new();
+    features.enable_bulk_memory();
+    let wasm_binary =
+        wabt::wat2wasm_with_features(MODULE, features).expect("WAST not valid or malformed");
+    let module = compile_with_config(
+        &wasm_binary,
+        &get_compiler(),
+        CompilerConfig {
+            features: Features {
+                bulk_memory: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let bytes = |range: std::ops::Range<usize>| -> Vec<u8> {
+        instance.context().memory(0).view::<u8>()[range]
+            .iter()
+            .map(|cell| cell.get())
+            .collect()
+    };
+
+    instance
+        .call("init", &[Value::I32(8), Value::I32(1), Value::I32(3)])
+        .unwrap();
+    assert_eq!(bytes(8..12), vec![2, 3, 4, 0]);
+
+    instance
+        .call("copy", &[Value::I32(9), Value::I32(8), Value::I32(3)])
+        .unwrap();
+    assert_eq!(bytes(8..12), vec![2, 2, 3, 4]);
+
+    instance
+        .call("fill", &[Value::I32(10), Value::I32(0x1ff), Value::I32(2)])
+        .unwrap();
+    assert_eq!(bytes(8..12), vec![2, 2, 0xff, 0xff]);
+
+    assert!(instance
+        .call("fill", &[Value::I32(0xffff), Value::I32(0), Value::I32(2)])
+        .is_err());
+    assert!(instance
+        .call("copy", &[Value::I32(0), Value::I32(0xffff), Value::I32(2)])
+        .is_err());
+
+    instance.call("drop", &[]).unwrap();
+    assert!(instance
+        .call("init", &[Value::I32(0), Value::I32(0), Value::I32(1)])
+        .is_err());
+    instance
+        .call("init", &[Value::I32(0), Value::I32(0), Value::I32(0)])
+        .unwrap();
+}
@@ -1,4 +1,6 @@
-use wasmer_runtime_core::{compile_with, imports};
+use wasmer_runtime_core::{
+    backend::ExceptionCode, compile_with, error::RuntimeError, func, imports, typed_func::Func, vm,
+};
 use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
 
 #[test]
@@ -19,3 +21,74 @@ fn exception_handling_works() {
         assert!(instance.call("throw_trap", &[]).is_err());
     }
 }
+
+#[test]
+fn traps_are_structured() {
+    const MODULE: &str = r#"
+(module
+  (import "env" "host_panic" (func $host_panic))
+  (func (export "throw_trap")
+    unreachable)
+  (func (export "div_by_zero") (result i32)
+    i32.const 1
+    i32.const 0
+    i32.div_s)
+  (func (export "call_panic")
+    call $host_panic))
+"#;
+
+    fn host_panic(_: &mut vm::Ctx) {
+        panic!("host panic");
+    }
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let imports = imports! {
+        "env" => {
+            "host_panic" => func!(host_panic),
+        },
+    };
+    let instance = module.instantiate(&imports).unwrap();
+
+    let throw_trap: Func<(), ()> = instance.func("throw_trap").unwrap();
+    let err = throw_trap.call().unwrap_err();
+    assert_eq!(err.exception_code(), Some(ExceptionCode::Unreachable));
+
+    let div_by_zero: Func<(), i32> = instance.func("div_by_zero").unwrap();
+    let err = div_by_zero.call().unwrap_err();
+    assert_eq!(err.exception_code(), Some(ExceptionCode::IllegalArithmetic));
+
+    let call_panic: Func<(), ()> = instance.func("call_panic").unwrap();
+    match call_panic.call().unwrap_err() {
+        RuntimeError::Panic(payload) => {
+            assert_eq!(payload.downcast_ref::<&str>(), Some(&"host panic"))
+        }
+        err => panic!("expected a host panic, got {}", err),
+    }
+}
+
+#[test]
+fn traps_carry_a_backtrace() {
+    const MODULE: &str = r#"
+(module
+  (func $inner
+    unreachable)
+  (func $outer (export "outer")
+    call $inner))
+"#;
+
+    let wasm_binary = wabt::Wat2Wasm::new()
+        .write_debug_names(true)
+        .convert(MODULE)
+        .expect("WAST not valid or malformed");
+    let module = compile_with(wasm_binary.as_ref(), &get_compiler()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+
+    let outer: Func<(), ()> = instance.func("outer").unwrap();
+    let err = outer.call().unwrap_err();
+    let trap = err.trap().expect("expected a trap");
+    assert_eq!(trap.code, Some(ExceptionCode::Unreachable));
+    let frame = trap.frames.first().expect("expected a backtrace");
+    assert_eq!(frame.func_index.index(), 0);
+    assert_eq!(frame.name.as_ref().map(String::as_str), Some("inner"));
+}
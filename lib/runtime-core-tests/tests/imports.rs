@@ -24,7 +24,7 @@ macro_rules! call_and_assert {
                 expected_value,
                 concat!("Expected right when calling `", stringify!($function), "`.")
             ),
-            (Err(RuntimeError(data)), Err(RuntimeError(expected_data))) => {
+            (Err(RuntimeError::User(data)), Err(RuntimeError::User(expected_data))) => {
                 if let (Some(data), Some(expected_data)) = (
                     data.downcast_ref::<&str>(),
                     expected_data.downcast_ref::<&str>(),
@@ -82,6 +82,8 @@ fn imported_functions_forms(test: &dyn Fn(&Instance)) {
   (import "env" "callback_closure_dynamic_2" (func $callback_closure_dynamic_2 (param i32 i64) (result i64)))
   (import "env" "callback_closure_dynamic_3" (func $callback_closure_dynamic_3 (param i32 i64 f32) (result f32)))
   (import "env" "callback_closure_dynamic_4" (func $callback_closure_dynamic_4 (param i32 i64 f32 f64) (result f64)))
+  (import "env" "callback_closure_dynamic_with_env" (func $callback_closure_dynamic_with_env (type $type)))
+  (import "env" "callback_closure_dynamic_wrong_type" (func $callback_closure_dynamic_wrong_type (type $type)))
   (import "env" "callback_closure_with_env" (func $callback_closure_with_env (type $type)))
   (import "env" "callback_fn_with_vmctx" (func $callback_fn_with_vmctx (type $type)))
   (import "env" "callback_closure_with_vmctx" (func $callback_closure_with_vmctx (type $type)))
@@ -129,6 +131,14 @@ fn imported_functions_forms(test: &dyn Fn(&Instance)) {
     get_local 3
     call $callback_closure_dynamic_4)
 
+  (func (export "function_closure_dynamic_with_env") (type $type)
+    get_local 0
+    call $callback_closure_dynamic_with_env)
+
+  (func (export "function_closure_dynamic_wrong_type") (type $type)
+    get_local 0
+    call $callback_closure_dynamic_wrong_type)
+
   (func (export "function_closure_with_env") (type $type)
     get_local 0
     call $callback_closure_with_env)
@@ -259,6 +269,30 @@ fn imported_functions_forms(test: &dyn Fn(&Instance)) {
                 }
             ),
 
+            // Polymorphic closure with a captured environment.
+            "callback_closure_dynamic_with_env" => {
+                let env = Arc::new(shift);
+
+                DynamicFunc::new(
+                    Arc::new(FuncSig::new(vec![Type::I32], vec![Type::I32])),
+                    move |_, inputs: &[Value]| -> Vec<Value> {
+                        let n: i32 = (&inputs[0]).try_into().unwrap();
+
+                        vec![Value::I32(*env + n)]
+                    }
+                )
+            },
+
+            // Polymorphic closure returning a value of the wrong type.
+            "callback_closure_dynamic_wrong_type" => DynamicFunc::new(
+                Arc::new(FuncSig::new(vec![Type::I32], vec![Type::I32])),
+                |_, inputs: &[Value]| -> Vec<Value> {
+                    let n: i32 = (&inputs[0]).try_into().unwrap();
+
+                    vec![Value::I64(n as i64)]
+                }
+            ),
+
             // Closure with a captured environment (a single variable + an instance of `Memory`).
             "callback_closure_with_env" => Func::new(move |n: i32| -> Result<i32, ()> {
                 let shift_ = shift + memory.view::<i32>()[0].get();
@@ -386,6 +420,18 @@ test!(
     function_closure_dynamic_4(i32, i64, f32, f64) -> f64,
     (1, 2, 3., 4.) == Ok(1. + 2. + 3. + 4. + shift as f64 + SHIFT as f64)
 );
+test!(
+    test_closure_dynamic_with_env,
+    function_closure_dynamic_with_env(i32) -> i32,
+    (1) == Ok(1 + shift)
+);
+test!(
+    test_closure_dynamic_wrong_type,
+    function_closure_dynamic_wrong_type(i32) -> i32,
+    (1) == Err(RuntimeError::User(Box::new(
+        "polymorphic host function returned values of types [I64] instead of [I32]".to_string()
+    )))
+);
 test!(
     test_closure_with_env,
     function_closure_with_env(i32) -> i32,
@@ -405,25 +451,25 @@ test!(
 test!(
     test_fn_trap,
     function_fn_trap(i32) -> i32,
-    (1) == Err(RuntimeError(Box::new(format!("foo {}", 2))))
+    (1) == Err(RuntimeError::User(Box::new(format!("foo {}", 2))))
 );
 test!(
     test_closure_trap,
     function_closure_trap(i32) -> i32,
-    (1) == Err(RuntimeError(Box::new(format!("bar {}", 2))))
+    (1) == Err(RuntimeError::User(Box::new(format!("bar {}", 2))))
 );
 test!(
     test_fn_trap_with_vmctx,
     function_fn_trap_with_vmctx(i32) -> i32,
-    (1) == Err(RuntimeError(Box::new(format!("baz {}", 2 + SHIFT))))
+    (1) == Err(RuntimeError::User(Box::new(format!("baz {}", 2 + SHIFT))))
 );
 test!(
     test_closure_trap_with_vmctx,
     function_closure_trap_with_vmctx(i32) -> i32,
-    (1) == Err(RuntimeError(Box::new(format!("qux {}", 2 + SHIFT))))
+    (1) == Err(RuntimeError::User(Box::new(format!("qux {}", 2 + SHIFT))))
 );
 test!(
     test_closure_trap_with_vmctx_and_env,
     function_closure_trap_with_vmctx_and_env(i32) -> i32,
-    (1) == Err(RuntimeError(Box::new(format!("! {}", 2 + shift + SHIFT))))
+    (1) == Err(RuntimeError::User(Box::new(format!("! {}", 2 + shift + SHIFT))))
 );
@@ -0,0 +1,73 @@
+#![cfg(unix)]
+
+use std::time::Duration;
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, ExceptionCode},
+    compile_with_config, imports,
+    typed_func::Func,
+};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+const MODULE: &str = r#"
+(module
+  (func (export "spin")
+    (loop $l
+      br $l))
+  (func (export "add") (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    i32.add))
+"#;
+
+#[test]
+fn interrupt_after_deadline() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with_config(
+        &wasm_binary,
+        &get_compiler(),
+        CompilerConfig {
+            full_preemption: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+
+    let handle = instance.interrupt_handle().unwrap();
+    handle.interrupt_after(Duration::from_millis(50));
+
+    let spin: Func<(), ()> = instance.func("spin").unwrap();
+    let err = spin.call().unwrap_err();
+    assert_eq!(err.exception_code(), Some(ExceptionCode::Interrupted));
+
+    // Once cleared, the instance runs normally again.
+    handle.clear_interrupt().unwrap();
+    let add: Func<(i32, i32), i32> = instance.func("add").unwrap();
+    assert_eq!(add.call(1, 2).unwrap(), 3);
+}
+
+#[test]
+fn interrupt_from_another_thread() {
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with_config(
+        &wasm_binary,
+        &get_compiler(),
+        CompilerConfig {
+            full_preemption: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+
+    let handle = instance.interrupt_handle().unwrap();
+    let interrupter = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        handle.interrupt().unwrap();
+    });
+
+    let spin: Func<(), ()> = instance.func("spin").unwrap();
+    let err = spin.call().unwrap_err();
+    assert_eq!(err.exception_code(), Some(ExceptionCode::Interrupted));
+    interrupter.join().unwrap();
+}
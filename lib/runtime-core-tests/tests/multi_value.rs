@@ -0,0 +1,197 @@
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, Features},
+    compile_with_config,
+    error::CompileResult,
+    module::Module,
+};
+use wasmer_runtime_core_tests::get_compiler;
+
+fn compile(wat: &str) -> CompileResult<Module> {
+    let mut features = wabt::Features::new();
+    features.enable_multi_value();
+    let wasm_binary =
+        wabt::wat2wasm_with_features(wat, features).expect("WAST not valid or malformed");
+    compile_with_config(
+        &wasm_binary,
+        &get_compiler(),
+        CompilerConfig {
+            features: Features {
+                multi_value: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+}
+
+const MODULE: &str = r#"
+(module
+  (type $pair (func (param i32) (result i32 i64)))
+  (import "env" "split" (func $split (param i64) (result i32 i32 i32 i32 i32)))
+  (table 1 funcref)
+  (elem (i32.const 0) $pair)
+  (func $pair (type $pair)
+    local.get 0
+    local.get 0
+    i64.extend_i32_s
+    i64.const 1
+    i64.add)
+  (func (export "values") (result i32 i64 f32 f64)
+    i32.const 1
+    i64.const 2
+    f32.const 3.5
+    f64.const 4.5)
+  (func (export "block") (param i32 i32) (result i32 i32)
+    local.get 0
+    local.get 1
+    block (param i32 i32) (result i32 i32)
+      i32.add
+      i32.const 10
+    end)
+  (func (export "loop") (param i32) (result i32)
+    i32.const 0
+    local.get 0
+    loop (param i32 i32) (result i32)
+      local.set 0
+      local.get 0
+      i32.add
+      local.get 0
+      i32.const 1
+      i32.sub
+      local.tee 0
+      local.get 0
+      br_if 0
+      drop
+    end)
+  (func (export "if") (param i32 i32) (result i32 i32)
+    local.get 1
+    local.get 1
+    local.get 0
+    if (param i32 i32) (result i32 i32)
+      i32.mul
+      i32.const 1
+    else
+      i32.add
+      i32.const 0
+    end)
+  (func (export "if_without_else") (param i32 i32) (result i32)
+    local.get 1
+    local.get 0
+    if (param i32) (result i32)
+      i32.const 1
+      i32.add
+    end)
+  (func (export "call_indirect") (param i32) (result i32 i64)
+    local.get 0
+    i32.const 0
+    call_indirect (type $pair))
+  (func (export "split") (param i64) (result i32 i32 i32 i32 i32)
+    local.get 0
+    call $split))
+"#;
+
+#[cfg(not(feature = "backend-cranelift"))]
+#[test]
+fn multi_value() {
+    use std::sync::Arc;
+    use wasmer_runtime_core::{
+        imports,
+        typed_func::DynamicFunc,
+        types::{FuncSig, Type, Value},
+    };
+
+    let import_object = imports! {
+        "env" => {
+            "split" => DynamicFunc::new(
+                Arc::new(FuncSig::new(vec![Type::I64], vec![Type::I32; 5])),
+                |_, inputs: &[Value]| -> Vec<Value> {
+                    let n = inputs[0].to_u128() as u64;
+                    (0..5).map(|i| Value::I32((n >> (i * 8)) as u8 as i32)).collect()
+                },
+            ),
+        },
+    };
+    let instance = compile(MODULE)
+        .unwrap()
+        .instantiate(&import_object)
+        .unwrap();
+
+    assert_eq!(
+        instance.call("values", &[]).unwrap(),
+        vec![
+            Value::I32(1),
+            Value::I64(2),
+            Value::F32(3.5),
+            Value::F64(4.5)
+        ]
+    );
+    assert_eq!(
+        instance
+            .call("block", &[Value::I32(3), Value::I32(4)])
+            .unwrap(),
+        vec![Value::I32(7), Value::I32(10)]
+    );
+    assert_eq!(
+        instance.call("loop", &[Value::I32(4)]).unwrap(),
+        vec![Value::I32(10)]
+    );
+    assert_eq!(
+        instance
+            .call("if", &[Value::I32(1), Value::I32(5)])
+            .unwrap(),
+        vec![Value::I32(25), Value::I32(1)]
+    );
+    assert_eq!(
+        instance
+            .call("if", &[Value::I32(0), Value::I32(5)])
+            .unwrap(),
+        vec![Value::I32(10), Value::I32(0)]
+    );
+    assert_eq!(
+        instance
+            .call("if_without_else", &[Value::I32(1), Value::I32(5)])
+            .unwrap(),
+        vec![Value::I32(6)]
+    );
+    assert_eq!(
+        instance
+            .call("if_without_else", &[Value::I32(0), Value::I32(5)])
+            .unwrap(),
+        vec![Value::I32(5)]
+    );
+    assert_eq!(
+        instance.call("call_indirect", &[Value::I32(-3)]).unwrap(),
+        vec![Value::I32(-3), Value::I64(-2)]
+    );
+    assert_eq!(
+        instance
+            .call("split", &[Value::I64(0x05_04_03_02_01)])
+            .unwrap(),
+        vec![
+            Value::I32(1),
+            Value::I32(2),
+            Value::I32(3),
+            Value::I32(4),
+            Value::I32(5)
+        ]
+    );
+}
+
+#[cfg(feature = "backend-cranelift")]
+#[test]
+fn multi_value_unsupported() {
+    assert!(compile(MODULE).is_err());
+    // A block with a function type is rejected too, even without multiple results.
+    assert!(compile(
+        r#"
+(module
+  (func (export "block") (param i32) (result i32)
+    local.get 0
+    block (param i32) (result i32)
+      i32.const 1
+      i32.add
+    end))
+"#
+    )
+    .is_err());
+}
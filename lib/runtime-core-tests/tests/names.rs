@@ -0,0 +1,29 @@
+use wasmer_runtime_core::{compile_with, structures::TypedIndex, types::FuncIndex};
+use wasmer_runtime_core_tests::get_compiler;
+
+#[test]
+fn name_section_is_parsed() {
+    const MODULE: &str = r#"
+(module
+  (import "env" "host" (func $host))
+  (func $add (param $lhs i32) (param $rhs i32) (result i32)
+    (local $tmp i32)
+    local.get $lhs
+    local.get $rhs
+    i32.add))
+"#;
+
+    let wasm_binary = wabt::Wat2Wasm::new()
+        .write_debug_names(true)
+        .convert(MODULE)
+        .expect("WAST not valid or malformed");
+    let module = compile_with(wasm_binary.as_ref(), &get_compiler()).unwrap();
+    let info = module.info();
+
+    assert_eq!(info.func_name(FuncIndex::new(0)), Some("host"));
+    assert_eq!(info.func_name(FuncIndex::new(1)), Some("add"));
+    assert_eq!(info.local_name(FuncIndex::new(1), 0), Some("lhs"));
+    assert_eq!(info.local_name(FuncIndex::new(1), 1), Some("rhs"));
+    assert_eq!(info.local_name(FuncIndex::new(1), 2), Some("tmp"));
+    assert_eq!(info.local_name(FuncIndex::new(1), 3), None);
+}
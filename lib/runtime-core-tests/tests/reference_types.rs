@@ -0,0 +1,106 @@
+use wasmer_runtime_core::{
+    backend::{CompilerConfig, Features},
+    compile_with_config, imports,
+    types::{ExternRef, Value},
+};
+use wasmer_runtime_core_tests::get_compiler;
+
+fn instantiate(wat: &str) -> wasmer_runtime_core::Instance {
+    let mut features = wabt::Features::new();
+    features.enable_reference_types();
+    let wasm_binary =
+        wabt::wat2wasm_with_features(wat, features).expect("WAST not valid or malformed");
+    let module = compile_with_config(
+        &wasm_binary,
+        &get_compiler(),
+        CompilerConfig {
+            features: Features {
+                reference_types: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    module.instantiate(&imports! {}).unwrap()
+}
+
+#[test]
+fn externref_table_operations() {
+    const MODULE: &str = r#"
+(module
+  (table $t 2 anyref)
+  (func (export "get") (param i32) (result anyref)
+    local.get 0
+    table.get $t)
+  (func (export "set") (param i32 anyref)
+    local.get 0
+    local.get 1
+    table.set $t)
+  (func (export "grow") (param i32 anyref) (result i32)
+    local.get 1
+    local.get 0
+    table.grow $t)
+  (func (export "size") (result i32)
+    table.size $t)
+  (func (export "is_null") (param anyref) (result i32)
+    local.get 0
+    ref.is_null))
+"#;
+
+    let instance = instantiate(MODULE);
+    let handle = Value::ExternRef(ExternRef::new(42));
+
+    assert_eq!(
+        instance.call("get", &[Value::I32(0)]).unwrap(),
+        vec![Value::ExternRef(ExternRef::null())]
+    );
+    instance
+        .call("set", &[Value::I32(1), handle.clone()])
+        .unwrap();
+    assert_eq!(
+        instance.call("get", &[Value::I32(1)]).unwrap(),
+        vec![handle.clone()]
+    );
+    assert_eq!(
+        instance.call("is_null", &[handle.clone()]).unwrap(),
+        vec![Value::I32(0)]
+    );
+
+    assert_eq!(
+        instance
+            .call("grow", &[Value::I32(3), handle.clone()])
+            .unwrap(),
+        vec![Value::I32(2)]
+    );
+    assert_eq!(instance.call("size", &[]).unwrap(), vec![Value::I32(5)]);
+    assert_eq!(
+        instance.call("get", &[Value::I32(4)]).unwrap(),
+        vec![handle]
+    );
+
+    assert!(instance.call("get", &[Value::I32(5)]).is_err());
+}
+
+#[test]
+fn funcref_from_ref_func_is_callable() {
+    const MODULE: &str = r#"
+(module
+  (type $ret_i32 (func (result i32)))
+  (table $t 1 funcref)
+  (func $seven (result i32)
+    i32.const 7)
+  (func (export "call_stored") (result i32)
+    i32.const 0
+    ref.func $seven
+    table.set $t
+    i32.const 0
+    call_indirect $t (type $ret_i32)))
+"#;
+
+    let instance = instantiate(MODULE);
+    assert_eq!(
+        instance.call("call_stored", &[]).unwrap(),
+        vec![Value::I32(7)]
+    );
+}
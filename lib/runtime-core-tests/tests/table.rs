@@ -0,0 +1,53 @@
+use wasmer_runtime_core::{compile_with, imports, table::Element, typed_func::Func, types::Value};
+use wasmer_runtime_core_tests::{get_compiler, wat2wasm};
+
+#[test]
+fn call_table_elements() {
+    const MODULE: &str = r#"
+(module
+  (type $unary (func (param i32) (result i32)))
+  (table (export "table") 2 funcref)
+  (elem (i32.const 0) $double)
+  (func $double (type $unary)
+    local.get 0
+    i32.const 2
+    i32.mul)
+  (func (export "dispatch") (param i32 i32) (result i32)
+    local.get 1
+    local.get 0
+    call_indirect (type $unary)))
+"#;
+
+    let wasm_binary = wat2wasm(MODULE.as_bytes()).expect("WAST not valid or malformed");
+    let module = compile_with(&wasm_binary, &get_compiler()).unwrap();
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let table = instance.table("table").unwrap();
+
+    let double = instance.table_func(&table, 0).unwrap();
+    assert_eq!(
+        double.call(&[Value::I32(21)]).unwrap(),
+        vec![Value::I32(42)]
+    );
+    assert!(instance.table_func(&table, 1).is_none());
+
+    let increment = Func::new(|x: i32| x + 1);
+    table.set(1, Element::Anyfunc(increment.into())).unwrap();
+
+    let increment = instance.table_func(&table, 1).unwrap();
+    assert_eq!(
+        increment.call(&[Value::I32(41)]).unwrap(),
+        vec![Value::I32(42)]
+    );
+    assert_eq!(
+        instance
+            .call("dispatch", &[Value::I32(1), Value::I32(9)])
+            .unwrap(),
+        vec![Value::I32(10)]
+    );
+    assert_eq!(
+        instance
+            .call("dispatch", &[Value::I32(0), Value::I32(9)])
+            .unwrap(),
+        vec![Value::I32(18)]
+    );
+}
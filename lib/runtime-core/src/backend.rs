@@ -90,6 +90,22 @@ pub struct Features {
     ///
     /// [threads-prop]: https://github.com/webassembly/threads
     pub threads: bool,
+    /// Whether support for the [reference types proposal][reference-types-prop] is enabled.
+    ///
+    /// [reference-types-prop]: https://github.com/webassembly/reference-types
+    pub reference_types: bool,
+    /// Whether support for the [bulk memory operations proposal][bulk-memory-prop] is enabled.
+    ///
+    /// [bulk-memory-prop]: https://github.com/webassembly/bulk-memory-operations
+    pub bulk_memory: bool,
+    /// Whether support for the [multi-value proposal][multi-value-prop] is enabled.
+    ///
+    /// The singlepass and LLVM backends support it. The Cranelift backend fails to
+    /// compile modules with functions returning several values or blocks with a
+    /// function type.
+    ///
+    /// [multi-value-prop]: https://github.com/webassembly/multi-value
+    pub multi_value: bool,
 }
 
 /// Use this to point to a compiler config struct provided by the backend.
@@ -181,6 +197,12 @@ pub enum ExceptionCode {
     IllegalArithmetic = 4,
     /// Misaligned atomic access trap.
     MisalignedAtomicAccess = 5,
+    /// The call stack was exhausted.
+    StackOverflow = 6,
+    /// Table out of bounds trap.
+    TableOutOfBounds = 7,
+    /// The call was interrupted with an `InterruptHandle`.
+    Interrupted = 8,
 }
 
 impl fmt::Display for ExceptionCode {
@@ -197,6 +219,9 @@ impl fmt::Display for ExceptionCode {
                 ExceptionCode::CallIndirectOOB => "`call_indirect` out-of-bounds",
                 ExceptionCode::IllegalArithmetic => "illegal arithmetic operation",
                 ExceptionCode::MisalignedAtomicAccess => "misaligned atomic access",
+                ExceptionCode::StackOverflow => "call stack exhausted",
+                ExceptionCode::TableOutOfBounds => "table out-of-bounds access",
+                ExceptionCode::Interrupted => "interrupted",
             }
         )
     }
@@ -7,16 +7,16 @@ use crate::{
     module::{ImportName, ModuleInfo, ModuleInner},
     sig_registry::SigRegistry,
     structures::{BoxedMap, Map, SliceMap, TypedIndex},
-    table::Table,
+    table::{intern_func_ref, Table},
     typed_func::{always_trap, Func},
     types::{
-        ImportedFuncIndex, ImportedGlobalIndex, ImportedMemoryIndex, ImportedTableIndex,
-        Initializer, LocalFuncIndex, LocalGlobalIndex, LocalMemoryIndex, LocalOrImport,
-        LocalTableIndex, SigIndex, Value,
+        DataIndex, ElemIndex, FuncIndex, FuncRef, ImportedFuncIndex, ImportedGlobalIndex,
+        ImportedMemoryIndex, ImportedTableIndex, Initializer, LocalFuncIndex, LocalGlobalIndex,
+        LocalMemoryIndex, LocalOrImport, LocalTableIndex, SigIndex, Value,
     },
     vm,
 };
-use std::{fmt::Debug, ptr::NonNull, slice};
+use std::{collections::HashSet, fmt::Debug, ptr::NonNull, slice};
 
 /// Size of the array for internal instance usage
 pub const INTERNALS_SIZE: usize = 256;
@@ -53,6 +53,10 @@ pub struct LocalBacking {
     pub(crate) dynamic_sigindices: BoxedMap<SigIndex, vm::SigId>,
     pub(crate) local_functions: BoxedMap<LocalFuncIndex, *const vm::Func>,
 
+    /// The passive segments dropped by `data.drop` and `elem.drop`.
+    pub(crate) dropped_data: HashSet<DataIndex>,
+    pub(crate) dropped_elements: HashSet<ElemIndex>,
+
     pub(crate) internals: Internals,
 }
 
@@ -74,7 +78,7 @@ impl LocalBacking {
             }
         };
         let mut tables = Self::generate_tables(module);
-        let mut globals = Self::generate_globals(module, imports)?;
+        let mut globals = Self::generate_globals(module, imports, vmctx)?;
 
         // Ensure all initializers are valid before running finalizers
         Self::validate_memories(module, imports)?;
@@ -86,6 +90,7 @@ impl LocalBacking {
 
         let dynamic_sigindices = Self::generate_sigindices(&module.info);
         let local_functions = Self::generate_local_functions(module);
+        let internals = Self::generate_internals(&module.info);
 
         Ok(Self {
             memories,
@@ -99,10 +104,22 @@ impl LocalBacking {
             dynamic_sigindices,
             local_functions,
 
-            internals: Internals([0; INTERNALS_SIZE]),
+            dropped_data: HashSet::new(),
+            dropped_elements: HashSet::new(),
+
+            internals,
         })
     }
 
+    /// Creates the internal fields, set to the initial values given by middlewares.
+    fn generate_internals(info: &ModuleInfo) -> Internals {
+        let mut internals = Internals([0; INTERNALS_SIZE]);
+        for &(index, value) in &info.internal_initializers {
+            internals.0[index] = value;
+        }
+        internals
+    }
+
     fn generate_local_functions(module: &ModuleInner) -> BoxedMap<LocalFuncIndex, *const vm::Func> {
         (0..module.info.func_assoc.len() - module.info.imported_functions.len())
             .map(|index| {
@@ -153,6 +170,11 @@ impl LocalBacking {
                         message: "a const initializer must be an i32".to_string(),
                     }]);
                 }
+                Initializer::RefFunc(_) => {
+                    return Err(vec![LinkError::Generic {
+                        message: "a ref.func initializer cannot be an offset".to_string(),
+                    }]);
+                }
                 Initializer::GetGlobal(import_global_index) => {
                     if import_global_index.index() >= imports.globals.len() {
                         return Err(vec![LinkError::Generic {
@@ -214,6 +236,11 @@ impl LocalBacking {
                         message: "a const initializer must be an i32".to_string(),
                     }]);
                 }
+                Initializer::RefFunc(_) => {
+                    return Err(vec![LinkError::Generic {
+                        message: "a ref.func initializer cannot be an offset".to_string(),
+                    }]);
+                }
                 Initializer::GetGlobal(import_global_index) => {
                     if import_global_index.index() >= imports.globals.len() {
                         return Err(vec![LinkError::Generic {
@@ -287,6 +314,11 @@ impl LocalBacking {
                         message: "a const initializer must be an i32".to_string(),
                     }]);
                 }
+                Initializer::RefFunc(_) => {
+                    return Err(vec![LinkError::Generic {
+                        message: "a ref.func initializer cannot be an offset".to_string(),
+                    }]);
+                }
                 Initializer::GetGlobal(import_global_index) => {
                     if import_global_index.index() >= imports.globals.len() {
                         return Err(vec![LinkError::Generic {
@@ -345,6 +377,11 @@ impl LocalBacking {
                         message: "a const initializer be an i32".to_string(),
                     }]);
                 }
+                Initializer::RefFunc(_) => {
+                    return Err(vec![LinkError::Generic {
+                        message: "a ref.func initializer cannot be an offset".to_string(),
+                    }]);
+                }
                 Initializer::GetGlobal(import_global_index) => {
                     if import_global_index.index() >= imports.globals.len() {
                         return Err(vec![LinkError::Generic {
@@ -361,73 +398,15 @@ impl LocalBacking {
                 }
             } as usize;
 
-            match init.table_index.local_or_import(&module.info) {
-                LocalOrImport::Local(local_table_index) => {
-                    let table = &tables[local_table_index];
-                    table.anyfunc_direct_access_mut(|elements| {
-                        for (i, &func_index) in init.elements.iter().enumerate() {
-                            let sig_index = module.info.func_assoc[func_index];
-                            // let signature = &module.info.signatures[sig_index];
-                            let signature = SigRegistry
-                                .lookup_signature_ref(&module.info.signatures[sig_index]);
-                            let sig_id =
-                                vm::SigId(SigRegistry.lookup_sig_index(signature).index() as u32);
-
-                            let (func, ctx) = match func_index.local_or_import(&module.info) {
-                                LocalOrImport::Local(local_func_index) => (
-                                    module
-                                        .runnable_module
-                                        .get_func(&module.info, local_func_index)
-                                        .unwrap()
-                                        .as_ptr()
-                                        as *const vm::Func,
-                                    vmctx,
-                                ),
-                                LocalOrImport::Import(imported_func_index) => {
-                                    let vm::ImportedFunc { func, func_ctx } =
-                                        imports.vm_functions[imported_func_index];
-                                    (func, unsafe { func_ctx.as_ref() }.vmctx.as_ptr())
-                                }
-                            };
-
-                            elements[init_base + i] = vm::Anyfunc { func, ctx, sig_id };
-                        }
-                    });
-                }
-                LocalOrImport::Import(import_table_index) => {
-                    let table = &imports.tables[import_table_index];
-
-                    table.anyfunc_direct_access_mut(|elements| {
-                        for (i, &func_index) in init.elements.iter().enumerate() {
-                            let sig_index = module.info.func_assoc[func_index];
-                            let signature = SigRegistry
-                                .lookup_signature_ref(&module.info.signatures[sig_index]);
-                            // let signature = &module.info.signatures[sig_index];
-                            let sig_id =
-                                vm::SigId(SigRegistry.lookup_sig_index(signature).index() as u32);
-
-                            let (func, ctx) = match func_index.local_or_import(&module.info) {
-                                LocalOrImport::Local(local_func_index) => (
-                                    module
-                                        .runnable_module
-                                        .get_func(&module.info, local_func_index)
-                                        .unwrap()
-                                        .as_ptr()
-                                        as *const vm::Func,
-                                    vmctx,
-                                ),
-                                LocalOrImport::Import(imported_func_index) => {
-                                    let vm::ImportedFunc { func, func_ctx } =
-                                        imports.vm_functions[imported_func_index];
-                                    (func, unsafe { func_ctx.as_ref() }.vmctx.as_ptr())
-                                }
-                            };
+            let table = match init.table_index.local_or_import(&module.info) {
+                LocalOrImport::Local(local_table_index) => &tables[local_table_index],
+                LocalOrImport::Import(import_table_index) => &imports.tables[import_table_index],
+            };
 
-                            elements[init_base + i] = vm::Anyfunc { func, ctx, sig_id };
-                        }
-                    });
-                }
-            }
+            let elements = init.elements.iter().map(|element| {
+                element.map(|func_index| Self::func_anyfunc(module, imports, vmctx, func_index))
+            });
+            table.init_elements(init_base, elements);
         }
 
         Ok(tables
@@ -437,9 +416,51 @@ impl LocalBacking {
             .into_boxed_map())
     }
 
+    /// The caller-checked anyfunc of the function at `func_index`.
+    pub(crate) fn func_anyfunc(
+        module: &ModuleInner,
+        imports: &ImportBacking,
+        vmctx: *mut vm::Ctx,
+        func_index: FuncIndex,
+    ) -> vm::Anyfunc {
+        let sig_index = module.info.func_assoc[func_index];
+        let signature = SigRegistry.lookup_signature_ref(&module.info.signatures[sig_index]);
+        let sig_id = vm::SigId(SigRegistry.lookup_sig_index(signature).index() as u32);
+
+        let (func, ctx) = match func_index.local_or_import(&module.info) {
+            LocalOrImport::Local(local_func_index) => (
+                module
+                    .runnable_module
+                    .get_func(&module.info, local_func_index)
+                    .unwrap()
+                    .as_ptr() as *const vm::Func,
+                vmctx,
+            ),
+            LocalOrImport::Import(imported_func_index) => {
+                let vm::ImportedFunc { func, func_ctx } = imports.vm_functions[imported_func_index];
+                (func, unsafe { func_ctx.as_ref() }.vmctx.as_ptr())
+            }
+        };
+
+        vm::Anyfunc { func, ctx, sig_id }
+    }
+
+    /// The `funcref` of the function at `func_index`, as produced by `ref.func`.
+    ///
+    /// The referenced anyfunc lives until the instance of the function is dropped.
+    pub(crate) fn func_ref(
+        module: &ModuleInner,
+        imports: &ImportBacking,
+        vmctx: *mut vm::Ctx,
+        func_index: FuncIndex,
+    ) -> FuncRef {
+        intern_func_ref(Self::func_anyfunc(module, imports, vmctx, func_index))
+    }
+
     fn generate_globals(
         module: &ModuleInner,
         imports: &ImportBacking,
+        vmctx: *mut vm::Ctx,
     ) -> LinkResult<BoxedMap<LocalGlobalIndex, Global>> {
         let mut globals = Map::with_capacity(module.info.globals.len());
 
@@ -458,6 +479,9 @@ impl LocalBacking {
 
                     imports.globals[*import_global_index].get()
                 }
+                Initializer::RefFunc(func_index) => {
+                    Value::FuncRef(Self::func_ref(module, imports, vmctx, *func_index))
+                }
             };
 
             let global = if global_init.desc.mutable {
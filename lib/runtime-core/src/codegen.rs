@@ -10,6 +10,7 @@ use crate::{
     module::{ModuleInfo, ModuleInner},
     structures::Map,
     types::{FuncIndex, FuncSig, SigIndex},
+    vm::InternalField,
 };
 use smallvec::SmallVec;
 use std::any::Any;
@@ -51,6 +52,9 @@ pub enum InternalEvent {
     SetInternal(u32),
     /// Indicates getting an internal field.
     GetInternal(u32),
+    /// Indicates incrementing a counter of the instance's counter buffer, which is sized
+    /// from the highest counter index in the module.
+    IncrementCounter(u32),
 }
 
 impl fmt::Debug for InternalEvent {
@@ -61,6 +65,7 @@ impl fmt::Debug for InternalEvent {
             InternalEvent::Breakpoint(_) => write!(f, "Breakpoint"),
             InternalEvent::SetInternal(_) => write!(f, "SetInternal"),
             InternalEvent::GetInternal(_) => write!(f, "GetInternal"),
+            InternalEvent::IncrementCounter(_) => write!(f, "IncrementCounter"),
         }
     }
 }
@@ -257,10 +262,10 @@ pub fn validating_parser_config(features: &Features) -> wasmparser::ValidatingPa
     wasmparser::ValidatingParserConfig {
         operator_config: wasmparser::OperatorValidatorConfig {
             enable_threads: features.threads,
-            enable_reference_types: false,
+            enable_reference_types: features.reference_types,
             enable_simd: features.simd,
-            enable_bulk_memory: false,
-            enable_multi_value: false,
+            enable_bulk_memory: features.bulk_memory,
+            enable_multi_value: features.multi_value,
 
             #[cfg(feature = "deterministic-execution")]
             deterministic_only: true,
@@ -349,6 +354,28 @@ impl<
                     writer
                         .debug_info_manager
                         .register_new_jit_code_entry(&debug_image);
+
+                    // Names from the `name` section, for modules without DWARF.
+                    use crate::{structures::TypedIndex, types::LocalFuncIndex};
+                    let functions = dbg_info
+                        .pointers
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &(ptr, size))| {
+                            let func_index = LocalFuncIndex::new(i).convert_up(&writer);
+                            let name = writer
+                                .func_name(func_index)
+                                .map(String::from)
+                                .unwrap_or_else(|| {
+                                    format!("wasm-function[{}]", func_index.index())
+                                });
+                            (name, ptr as usize, size)
+                        })
+                        .collect::<Vec<_>>();
+                    let symbol_file = crate::jit_debug::build_symbol_file(&functions);
+                    writer
+                        .debug_info_manager
+                        .register_new_jit_code_entry(&symbol_file);
                 }
             }
         }
@@ -384,12 +411,17 @@ impl<'a, 'b> EventSink<'a, 'b> {
 /// A container for a chain of middlewares.
 pub struct MiddlewareChain {
     chain: Vec<Box<dyn GenericFunctionMiddleware>>,
+    /// The number of counters incremented by the events produced so far.
+    counter_count: usize,
 }
 
 impl MiddlewareChain {
     /// Create a new empty `MiddlewareChain`.
     pub fn new() -> MiddlewareChain {
-        MiddlewareChain { chain: vec![] }
+        MiddlewareChain {
+            chain: vec![],
+            counter_count: 0,
+        }
     }
 
     /// Push a new `FunctionMiddleware` to this `MiddlewareChain`.
@@ -415,6 +447,11 @@ impl MiddlewareChain {
                 m.feed_event(ev, module_info, &mut sink, source_loc)?;
             }
         }
+        for ev in &sink.buffer {
+            if let Event::Internal(InternalEvent::IncrementCounter(counter)) = *ev {
+                self.counter_count = self.counter_count.max(counter as usize + 1);
+            }
+        }
         if let Some(fcg) = fcg {
             for ev in sink.buffer {
                 fcg.feed_event(ev, module_info, source_loc)
@@ -424,6 +461,32 @@ impl MiddlewareChain {
 
         Ok(())
     }
+
+    /// Returns the number of counters incremented by `InternalEvent::IncrementCounter`
+    /// events produced by this chain.
+    pub(crate) fn counter_count(&self) -> usize {
+        self.counter_count
+    }
+
+    /// Returns the initial values of the internal fields used by the middlewares of this
+    /// chain, by field index.
+    pub(crate) fn internal_initializers(&self) -> Vec<(usize, u64)> {
+        self.chain
+            .iter()
+            .flat_map(|m| m.initial_internals())
+            .map(|(field, value)| (field.index(), value))
+            .collect()
+    }
+
+    /// Returns the indices of the internal fields which the middlewares of this chain ask
+    /// to be restored when a call traps.
+    pub(crate) fn internals_restored_on_trap(&self) -> Vec<usize> {
+        self.chain
+            .iter()
+            .flat_map(|m| m.internals_restored_on_trap())
+            .map(|field| field.index())
+            .collect()
+    }
 }
 
 /// A trait that represents the signature required to implement middleware for a function.
@@ -438,6 +501,18 @@ pub trait FunctionMiddleware {
         sink: &mut EventSink<'a, 'b>,
         source_loc: u32,
     ) -> Result<(), Self::Error>;
+
+    /// Returns the initial values of the internal fields used by this middleware, which are
+    /// written into every instance of the module before its start function runs.
+    fn initial_internals(&self) -> Vec<(&'static InternalField, u64)> {
+        vec![]
+    }
+
+    /// Returns the internal fields used by this middleware which are restored to their
+    /// value before a call from the host when the call traps.
+    fn internals_restored_on_trap(&self) -> Vec<&'static InternalField> {
+        vec![]
+    }
 }
 
 pub(crate) trait GenericFunctionMiddleware {
@@ -448,6 +523,10 @@ pub(crate) trait GenericFunctionMiddleware {
         sink: &mut EventSink<'a, 'b>,
         source_loc: u32,
     ) -> Result<(), String>;
+
+    fn initial_internals(&self) -> Vec<(&'static InternalField, u64)>;
+
+    fn internals_restored_on_trap(&self) -> Vec<&'static InternalField>;
 }
 
 impl<E: Debug, T: FunctionMiddleware<Error = E>> GenericFunctionMiddleware for T {
@@ -461,6 +540,14 @@ impl<E: Debug, T: FunctionMiddleware<Error = E>> GenericFunctionMiddleware for T
         <Self as FunctionMiddleware>::feed_event(self, op, module_info, sink, source_loc)
             .map_err(|x| format!("{:?}", x))
     }
+
+    fn initial_internals(&self) -> Vec<(&'static InternalField, u64)> {
+        <Self as FunctionMiddleware>::initial_internals(self)
+    }
+
+    fn internals_restored_on_trap(&self) -> Vec<&'static InternalField> {
+        <Self as FunctionMiddleware>::internals_restored_on_trap(self)
+    }
 }
 
 /// The function-scope code generator trait.
@@ -1,7 +1,11 @@
 //! The error module contains the data structures and helper functions used to implement errors that
 //! are produced and returned from the wasmer runtime core.
 use crate::backend::ExceptionCode;
-use crate::types::{FuncSig, GlobalDescriptor, MemoryDescriptor, TableDescriptor, Type};
+use crate::module::{ModuleInfo, ModuleInner};
+use crate::structures::TypedIndex;
+use crate::types::{
+    FuncIndex, FuncSig, GlobalDescriptor, LocalFuncIndex, MemoryDescriptor, TableDescriptor, Type,
+};
 use core::borrow::Borrow;
 use std::any::Any;
 
@@ -179,7 +183,74 @@ impl std::error::Error for LinkError {}
 /// The main way to do this is `Instance.call`.
 ///
 /// Comparing two `RuntimeError`s always evaluates to false.
-pub struct RuntimeError(pub Box<dyn Any + Send>);
+pub enum RuntimeError {
+    /// The WebAssembly code trapped, e.g. by accessing memory out of bounds,
+    /// dividing by zero, overflowing the stack or executing `unreachable`.
+    Trap(Trap),
+    /// A host function panicked.  This holds the panic payload, which is
+    /// usually a `String` or a `&'static str`.
+    Panic(Box<dyn Any + Send>),
+    /// A host function returned an error, or a middleware stopped the
+    /// execution, e.g. with `ExecutionLimitExceededError`.  Use
+    /// [`RuntimeError::downcast_ref`] to retrieve it.
+    User(Box<dyn Any + Send>),
+}
+
+/// The payload a host function panicked with, boxed on its way through the
+/// backend so that it can be told apart from an error the function returned.
+pub(crate) struct HostPanic(pub(crate) Box<dyn Any + Send>);
+
+impl RuntimeError {
+    /// Builds a `RuntimeError` from the payload a backend unwound with.
+    pub(crate) fn from_unwind(data: Box<dyn Any + Send>) -> Self {
+        let data = match data.downcast::<Trap>() {
+            Ok(trap) => return RuntimeError::Trap(*trap),
+            Err(data) => data,
+        };
+        let data = match data.downcast::<ExceptionCode>() {
+            Ok(code) => return RuntimeError::Trap(Trap::new(Some(*code))),
+            Err(data) => data,
+        };
+        match data.downcast::<HostPanic>() {
+            Ok(panic) => RuntimeError::Panic(panic.0),
+            Err(data) => RuntimeError::User(data),
+        }
+    }
+
+    /// Returns the trap if the WebAssembly code trapped.
+    pub fn trap(&self) -> Option<&Trap> {
+        match self {
+            RuntimeError::Trap(trap) => Some(trap),
+            _ => None,
+        }
+    }
+
+    /// Returns the exception code if the WebAssembly code trapped for a
+    /// known reason.
+    pub fn exception_code(&self) -> Option<ExceptionCode> {
+        self.trap().and_then(|trap| trap.code)
+    }
+
+    /// Returns a reference to the error a host function returned or the
+    /// middleware error if it is of type `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        match self {
+            RuntimeError::User(data) => data.downcast_ref::<T>(),
+            _ => None,
+        }
+    }
+
+    /// Returns the error a host function returned or the middleware error if
+    /// it is of type `T`, otherwise gives back `self`.
+    pub fn downcast<T: Any>(self) -> std::result::Result<T, Self> {
+        match self {
+            RuntimeError::User(data) => {
+                data.downcast::<T>().map(|x| *x).map_err(RuntimeError::User)
+            }
+            other => Err(other),
+        }
+    }
+}
 
 impl PartialEq for RuntimeError {
     fn eq(&self, _other: &RuntimeError) -> bool {
@@ -189,15 +260,26 @@ impl PartialEq for RuntimeError {
 
 impl std::fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let data = &*self.0;
-        if let Some(s) = data.downcast_ref::<String>() {
-            write!(f, "\"{}\"", s)
-        } else if let Some(s) = data.downcast_ref::<&str>() {
-            write!(f, "\"{}\"", s)
-        } else if let Some(exc_code) = data.downcast_ref::<ExceptionCode>() {
-            write!(f, "Caught exception of type \"{:?}\".", exc_code)
-        } else {
-            write!(f, "unknown error")
+        match self {
+            RuntimeError::Trap(trap) => write!(f, "{}", trap),
+            RuntimeError::Panic(data) => {
+                if let Some(s) = data.downcast_ref::<String>() {
+                    write!(f, "host function panicked: \"{}\"", s)
+                } else if let Some(s) = data.downcast_ref::<&str>() {
+                    write!(f, "host function panicked: \"{}\"", s)
+                } else {
+                    write!(f, "host function panicked")
+                }
+            }
+            RuntimeError::User(data) => {
+                if let Some(s) = data.downcast_ref::<String>() {
+                    write!(f, "\"{}\"", s)
+                } else if let Some(s) = data.downcast_ref::<&str>() {
+                    write!(f, "\"{}\"", s)
+                } else {
+                    write!(f, "unknown error")
+                }
+            }
         }
     }
 }
@@ -210,6 +292,129 @@ impl std::fmt::Debug for RuntimeError {
 
 impl std::error::Error for RuntimeError {}
 
+/// A trap raised by WebAssembly code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trap {
+    /// The reason of the trap, `None` if the backend could not determine it.
+    pub code: Option<ExceptionCode>,
+    /// The WebAssembly frames that were active when the trap happened,
+    /// innermost first.  This is empty if the backend could not read the
+    /// stack, and backends without a `ModuleStateMap` only report the frame
+    /// that trapped.
+    pub frames: Vec<TrapFrame>,
+}
+
+/// A WebAssembly frame that was active when a trap happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrapFrame {
+    /// The index of the function.
+    pub func_index: FuncIndex,
+    /// The name of the function from the `name` custom section.
+    pub name: Option<String>,
+    /// The offset in the module's binary of the instruction being executed,
+    /// if known.
+    pub offset: Option<u32>,
+}
+
+impl TrapFrame {
+    /// Creates a frame of the function `func_index` of the module described
+    /// by `info`.
+    pub fn new(info: &ModuleInfo, func_index: FuncIndex, offset: Option<u32>) -> Self {
+        TrapFrame {
+            func_index,
+            name: info.func_name(func_index).map(String::from),
+            offset,
+        }
+    }
+}
+
+impl Trap {
+    /// Creates a trap without frames.
+    pub fn new(code: Option<ExceptionCode>) -> Self {
+        Trap {
+            code,
+            frames: vec![],
+        }
+    }
+
+    /// Creates a trap from the native addresses of the WebAssembly frames
+    /// that were active, innermost first, for backends that cannot read the
+    /// WebAssembly stack.
+    ///
+    /// The functions are found with `RunnableModule::get_local_function_offsets`
+    /// and addresses outside of the module's code are skipped.  `offset` is
+    /// the offset in the module's binary of the instruction that trapped.
+    pub fn from_native_frames(
+        code: Option<ExceptionCode>,
+        module: &ModuleInner,
+        addresses: &[usize],
+        offset: Option<u32>,
+    ) -> Self {
+        let runnable_module = &module.runnable_module;
+        let (base, size) = match runnable_module.get_code() {
+            Some(code) => (code.as_ptr() as usize, code.len()),
+            None => return Trap::new(code),
+        };
+        let func_offsets = runnable_module
+            .get_local_function_offsets()
+            .unwrap_or_default();
+
+        let frames = addresses
+            .iter()
+            .filter(|&&address| address >= base && address < base + size)
+            .filter_map(|&address| {
+                // the function starting closest before the address contains it
+                func_offsets
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &start)| start <= address - base)
+                    .max_by_key(|&(_, &start)| start)
+                    .map(|(local_index, _)| LocalFuncIndex::new(local_index))
+            })
+            .enumerate()
+            .map(|(i, local_index)| {
+                let func_index = local_index.convert_up(&module.info);
+                TrapFrame::new(&module.info, func_index, if i == 0 { offset } else { None })
+            })
+            .collect();
+
+        Trap { code, frames }
+    }
+
+    /// The index of the function that trapped, if known.
+    pub fn func_index(&self) -> Option<FuncIndex> {
+        self.frames.first().map(|frame| frame.func_index)
+    }
+
+    /// The offset in the module's binary of the instruction that trapped, if
+    /// known.
+    pub fn code_offset(&self) -> Option<u32> {
+        self.frames.first().and_then(|frame| frame.offset)
+    }
+}
+
+impl std::fmt::Display for Trap {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "Caught exception of type \"{:?}\".", code)?,
+            None => write!(f, "Caught exception of unknown type.")?,
+        }
+        if !self.frames.is_empty() {
+            write!(f, "\nBacktrace:")?;
+        }
+        for (i, frame) in self.frames.iter().enumerate() {
+            write!(f, "\n    {}: function {}", i, frame.func_index.index())?;
+            if let Some(ref name) = frame.name {
+                write!(f, " <{}>", name)?;
+            }
+            if let Some(offset) = frame.offset {
+                write!(f, " @ 0x{:x}", offset)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// This error type is produced by resolving a wasm function
 /// given its name.
 ///
@@ -316,6 +521,8 @@ pub enum CreationError {
     UnableToCreateTable,
     /// Invalid descriptor error with message.
     InvalidDescriptor(String),
+    /// Unable to create an interrupt handle, with message.
+    UnableToCreateInterruptHandle(String),
 }
 
 impl PartialEq for CreationError {
@@ -334,6 +541,9 @@ impl std::fmt::Display for CreationError {
                 "Unable to create because the supplied descriptor is invalid: \"{}\"",
                 msg
             ),
+            CreationError::UnableToCreateInterruptHandle(msg) => {
+                write!(f, "Unable to create interrupt handle: {}", msg)
+            }
         }
     }
 }
@@ -568,3 +778,52 @@ impl From<wasmparser::BinaryReaderError> for ParseError {
         ParseError::BinaryReadError
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn runtime_error_from_unwind() {
+        let err = RuntimeError::from_unwind(Box::new(ExceptionCode::Unreachable));
+        assert_eq!(err.exception_code(), Some(ExceptionCode::Unreachable));
+        assert_eq!(err.trap().unwrap().func_index(), None);
+
+        let trap = Trap {
+            code: Some(ExceptionCode::MemoryOutOfBounds),
+            frames: vec![
+                TrapFrame {
+                    func_index: FuncIndex::new(3),
+                    name: Some("inner".to_string()),
+                    offset: Some(0x2a),
+                },
+                TrapFrame {
+                    func_index: FuncIndex::new(1),
+                    name: None,
+                    offset: Some(0x1f),
+                },
+            ],
+        };
+        let err = RuntimeError::from_unwind(Box::new(trap.clone()));
+        assert_eq!(err.trap(), Some(&trap));
+        assert_eq!(trap.func_index(), Some(FuncIndex::new(3)));
+        assert_eq!(trap.code_offset(), Some(0x2a));
+        assert_eq!(
+            trap.to_string(),
+            "Caught exception of type \"MemoryOutOfBounds\".\n\
+             Backtrace:\n    \
+             0: function 3 <inner> @ 0x2a\n    \
+             1: function 1 @ 0x1f"
+        );
+
+        let err = RuntimeError::from_unwind(Box::new(HostPanic(Box::new("oops"))));
+        match err {
+            RuntimeError::Panic(payload) => assert_eq!(payload.downcast_ref(), Some(&"oops")),
+            _ => panic!("expected a panic"),
+        }
+
+        let err = RuntimeError::from_unwind(Box::new(42u32));
+        assert_eq!(err.downcast_ref::<u32>(), Some(&42));
+        assert_eq!(err.downcast::<u32>().ok(), Some(42));
+    }
+}
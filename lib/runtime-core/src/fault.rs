@@ -27,9 +27,13 @@ pub mod raw {
     }
 }
 
+use crate::backend::ExceptionCode;
 use crate::codegen::{BreakpointInfo, BreakpointMap};
+use crate::error::{CreationError, MemoryProtectionError, Trap, TrapFrame};
 use crate::state::x64::{build_instance_image, read_stack, X64Register, GPR};
 use crate::state::{CodeVersion, ExecutionStateImage};
+use crate::structures::TypedIndex;
+use crate::types::{FuncIndex, LocalFuncIndex};
 use crate::vm;
 use libc::{mmap, mprotect, siginfo_t, MAP_ANON, MAP_PRIVATE, PROT_NONE, PROT_READ, PROT_WRITE};
 use nix::sys::signal::{
@@ -38,10 +42,13 @@ use nix::sys::signal::{
 };
 use std::any::Any;
 use std::cell::{Cell, RefCell, UnsafeCell};
+use std::collections::HashMap;
 use std::ffi::c_void;
 use std::process;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Once;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Once, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[cfg(target_arch = "x86_64")]
 pub(crate) unsafe fn run_on_alternative_stack(stack_end: *mut u64, stack_begin: *mut u64) -> u64 {
@@ -135,6 +142,21 @@ pub unsafe fn with_ctx<R, F: FnOnce() -> R>(ctx: *mut vm::Ctx, cb: F) -> R {
     ret
 }
 
+/// Returns the name of a function of the module running on this thread (see
+/// `with_ctx`) from its `name` custom section.
+pub fn current_func_name(func_index: FuncIndex) -> Option<String> {
+    unsafe {
+        let ctx = *CURRENT_CTX.with(|x| x.get());
+        if ctx.is_null() {
+            return None;
+        }
+        (*(*ctx).module)
+            .info
+            .func_name(func_index)
+            .map(String::from)
+    }
+}
+
 /// Pushes a new `CodeVersion` to the current code versions.
 pub fn push_code_version(version: CodeVersion) {
     CURRENT_CODE_VERSIONS.with(|x| x.borrow_mut().push(version));
@@ -145,6 +167,37 @@ pub fn pop_code_version() -> Option<CodeVersion> {
     CURRENT_CODE_VERSIONS.with(|x| x.borrow_mut().pop())
 }
 
+/// Runs `f` with the current code versions, or with the baseline code of the
+/// module running on this thread (see `with_ctx`) if none were pushed.
+unsafe fn with_code_versions<R, F: FnOnce(&[CodeVersion]) -> R>(f: F) -> R {
+    CURRENT_CODE_VERSIONS.with(|versions| {
+        let versions = versions.borrow();
+        if !versions.is_empty() {
+            return f(&versions);
+        }
+
+        let ctx = *CURRENT_CTX.with(|x| x.get());
+        if ctx.is_null() {
+            return f(&[]);
+        }
+        let runnable_module = &(*(*ctx).module).runnable_module;
+        match (
+            runnable_module.get_module_state_map(),
+            runnable_module.get_code(),
+        ) {
+            (Some(msm), Some(code)) => f(&[CodeVersion {
+                baseline: true,
+                msm,
+                base: code.as_ptr() as usize,
+                // not needed to handle faults
+                backend: "",
+                runnable_module: runnable_module.clone(),
+            }]),
+            _ => f(&[]),
+        }
+    })
+}
+
 /// Gets the wasm interrupt signal mem.
 pub unsafe fn get_wasm_interrupt_signal_mem() -> *mut u8 {
     INTERRUPT_SIGNAL_MEM.0
@@ -178,6 +231,254 @@ pub unsafe fn clear_wasm_interrupt() {
     }
 }
 
+/// The number of interrupt pages reserved for `InterruptHandle`s.
+const INTERRUPT_PAGE_COUNT: usize = 1 << 16;
+
+/// The address of the region reserved for the interrupt pages, or zero if it is not reserved
+/// yet. Signal handlers read it without locking.
+static INTERRUPT_PAGES_BASE: AtomicUsize = AtomicUsize::new(0);
+
+struct InterruptPages {
+    /// The number of pages handed out at least once.
+    used: usize,
+    /// The pages released by dropped handles.
+    free: Vec<usize>,
+}
+
+lazy_static! {
+    /// The interrupt pages, or the reason they could not be reserved.
+    static ref INTERRUPT_PAGES: Result<Mutex<InterruptPages>, String> = {
+        let ptr = unsafe {
+            mmap(
+                ::std::ptr::null_mut(),
+                INTERRUPT_SIGNAL_MEM_SIZE * INTERRUPT_PAGE_COUNT,
+                PROT_NONE,
+                MAP_PRIVATE | MAP_ANON,
+                -1,
+                0,
+            )
+        };
+        if ptr as isize == -1 {
+            return Err(format!(
+                "cannot reserve interrupt pages: {}",
+                errno::errno()
+            ));
+        }
+        INTERRUPT_PAGES_BASE.store(ptr as usize, Ordering::SeqCst);
+        Ok(Mutex::new(InterruptPages {
+            used: 0,
+            free: vec![],
+        }))
+    };
+
+    /// The deadlines of the handles, by interrupt page, served by a single timer thread.
+    static ref DEADLINES: Deadlines = Deadlines {
+        state: Mutex::new(DeadlinesState {
+            deadlines: HashMap::new(),
+            timer_started: false,
+        }),
+        changed: Condvar::new(),
+    };
+}
+
+/// Returns `true` if a fault at `faulting_addr` was caused by an `InterruptHandle`, in which
+/// case the interrupt is cleared so that the instance can run again.
+///
+/// Signal handlers of the backends call this to report such faults as
+/// `ExceptionCode::Interrupted` traps.
+pub unsafe fn take_interrupt(faulting_addr: *const c_void) -> bool {
+    let base = INTERRUPT_PAGES_BASE.load(Ordering::SeqCst);
+    let addr = faulting_addr as usize;
+    if base == 0 || addr < base || addr >= base + INTERRUPT_SIGNAL_MEM_SIZE * INTERRUPT_PAGE_COUNT {
+        return false;
+    }
+    let page = addr - (addr - base) % INTERRUPT_SIGNAL_MEM_SIZE;
+    mprotect(page as _, INTERRUPT_SIGNAL_MEM_SIZE, PROT_READ | PROT_WRITE);
+    true
+}
+
+/// A handle to interrupt the calls of an `Instance` from any thread, obtained with
+/// `Instance::interrupt_handle`.
+///
+/// An interrupted call traps with `ExceptionCode::Interrupted` at its next preemption
+/// checkpoint, at the header of a function or a loop. The module must be compiled with
+/// `full_preemption` enabled in the `CompilerConfig`, otherwise no checkpoint is emitted.
+///
+/// An interrupt requested while no call is running interrupts the next call, unless it is
+/// withdrawn with [`clear_interrupt`].
+///
+/// [`clear_interrupt`]: #method.clear_interrupt
+#[derive(Clone)]
+pub struct InterruptHandle {
+    inner: Arc<InterruptHandleInner>,
+}
+
+struct InterruptHandleInner {
+    page: usize,
+}
+
+struct Deadlines {
+    state: Mutex<DeadlinesState>,
+    changed: Condvar,
+}
+
+struct DeadlinesState {
+    deadlines: HashMap<usize, (Instant, Weak<InterruptHandleInner>)>,
+    timer_started: bool,
+}
+
+impl InterruptHandle {
+    pub(crate) fn new() -> Result<InterruptHandle, CreationError> {
+        {
+            let mut state = DEADLINES.state.lock().unwrap();
+            if !state.timer_started {
+                thread::Builder::new()
+                    .name("wasmer-interrupt-timer".to_string())
+                    .spawn(run_deadline_timer)
+                    .map_err(|e| {
+                        CreationError::UnableToCreateInterruptHandle(format!(
+                            "cannot start the timer thread: {}",
+                            e
+                        ))
+                    })?;
+                state.timer_started = true;
+            }
+        }
+        let page = {
+            let mut pages = INTERRUPT_PAGES
+                .as_ref()
+                .map_err(|e| CreationError::UnableToCreateInterruptHandle(e.clone()))?
+                .lock()
+                .unwrap();
+            let index = match pages.free.pop() {
+                Some(index) => index,
+                None if pages.used < INTERRUPT_PAGE_COUNT => {
+                    pages.used += 1;
+                    pages.used - 1
+                }
+                None => {
+                    return Err(CreationError::UnableToCreateInterruptHandle(format!(
+                        "at most {} interrupt handles can exist at once",
+                        INTERRUPT_PAGE_COUNT
+                    )))
+                }
+            };
+            INTERRUPT_PAGES_BASE.load(Ordering::SeqCst) + index * INTERRUPT_SIGNAL_MEM_SIZE
+        };
+        // From here on the page is released by `Drop` if making it readable fails.
+        let handle = InterruptHandle {
+            inner: Arc::new(InterruptHandleInner { page }),
+        };
+        handle
+            .clear_interrupt()
+            .map_err(|e| CreationError::UnableToCreateInterruptHandle(e.to_string()))?;
+        Ok(handle)
+    }
+
+    /// The page the preemption checkpoints of the instance read.
+    pub(crate) fn signal_mem(&self) -> *mut u8 {
+        self.inner.page as *mut u8
+    }
+
+    /// Interrupts the running call, or the next one if no call is running.
+    pub fn interrupt(&self) -> Result<(), MemoryProtectionError> {
+        self.inner.protect(PROT_NONE)
+    }
+
+    /// Withdraws an interrupt which did not interrupt a call yet.
+    pub fn clear_interrupt(&self) -> Result<(), MemoryProtectionError> {
+        self.inner.protect(PROT_READ | PROT_WRITE)
+    }
+
+    /// Interrupts the running call once `deadline` is reached, replacing the previous deadline.
+    ///
+    /// The deadlines of all handles are served by a single timer thread.
+    pub fn interrupt_at(&self, deadline: Instant) {
+        DEADLINES
+            .state
+            .lock()
+            .unwrap()
+            .deadlines
+            .insert(self.inner.page, (deadline, Arc::downgrade(&self.inner)));
+        DEADLINES.changed.notify_all();
+    }
+
+    /// Interrupts the running call once `timeout` has elapsed, replacing the previous deadline.
+    pub fn interrupt_after(&self, timeout: Duration) {
+        self.interrupt_at(Instant::now() + timeout);
+    }
+
+    /// Cancels the deadline set with `interrupt_at` or `interrupt_after`, if it is not reached
+    /// yet.
+    pub fn cancel_deadline(&self) {
+        DEADLINES
+            .state
+            .lock()
+            .unwrap()
+            .deadlines
+            .remove(&self.inner.page);
+    }
+}
+
+impl InterruptHandleInner {
+    fn protect(&self, protection: i32) -> Result<(), MemoryProtectionError> {
+        if unsafe { mprotect(self.page as _, INTERRUPT_SIGNAL_MEM_SIZE, protection) } < 0 {
+            return Err(MemoryProtectionError::ProtectionFailed(
+                self.page,
+                INTERRUPT_SIGNAL_MEM_SIZE,
+                errno::errno().to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn run_deadline_timer() {
+    let mut state = DEADLINES.state.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        let expired: Vec<usize> = state
+            .deadlines
+            .iter()
+            .filter(|(_, (at, _))| *at <= now)
+            .map(|(page, _)| *page)
+            .collect();
+        let handles: Vec<Arc<InterruptHandleInner>> = expired
+            .iter()
+            .filter_map(|page| state.deadlines.remove(page))
+            .filter_map(|(_, handle)| handle.upgrade())
+            .collect();
+        if !handles.is_empty() {
+            // The handles may be dropped here, which takes the lock.
+            drop(state);
+            for inner in handles {
+                // A failure leaves the call running, as if the deadline was not reached.
+                let _ = inner.protect(PROT_NONE);
+            }
+            state = DEADLINES.state.lock().unwrap();
+            continue;
+        }
+        state = match state.deadlines.values().map(|(at, _)| *at).min() {
+            None => DEADLINES.changed.wait(state).unwrap(),
+            Some(at) => DEADLINES.changed.wait_timeout(state, at - now).unwrap().0,
+        };
+    }
+}
+
+impl Drop for InterruptHandleInner {
+    fn drop(&mut self) {
+        DEADLINES.state.lock().unwrap().deadlines.remove(&self.page);
+        unsafe {
+            mprotect(self.page as _, INTERRUPT_SIGNAL_MEM_SIZE, PROT_NONE);
+        }
+        let index =
+            (self.page - INTERRUPT_PAGES_BASE.load(Ordering::SeqCst)) / INTERRUPT_SIGNAL_MEM_SIZE;
+        if let Ok(pages) = INTERRUPT_PAGES.as_ref() {
+            pages.lock().unwrap().free.push(index);
+        }
+    }
+}
+
 /// Catches an unsafe unwind with the given functions and breakpoints.
 pub unsafe fn catch_unsafe_unwind<R, F: FnOnce() -> R>(
     f: F,
@@ -284,8 +585,7 @@ extern "C" fn signal_trap_handler(
     unsafe {
         let fault = get_fault_info(siginfo as _, ucontext);
         let early_return = allocate_and_run(TRAP_STACK_SIZE, || {
-            CURRENT_CODE_VERSIONS.with(|versions| {
-                let versions = versions.borrow();
+            with_code_versions(|versions| {
                 for v in versions.iter() {
                     let magic_size =
                         if let Some(x) = v.runnable_module.get_inline_breakpoint_size(ARCH) {
@@ -336,6 +636,7 @@ extern "C" fn signal_trap_handler(
 
         should_unwind = allocate_and_run(TRAP_STACK_SIZE, || {
             let mut is_suspend_signal = false;
+            let mut is_interrupt = false;
 
             WAS_SIGINT_TRIGGERED.with(|x| x.set(false));
 
@@ -368,6 +669,8 @@ extern "C" fn signal_trap_handler(
                         if INTERRUPT_SIGNAL_DELIVERED.swap(false, Ordering::SeqCst) {
                             WAS_SIGINT_TRIGGERED.with(|x| x.set(true));
                         }
+                    } else if take_interrupt(fault.faulting_addr) {
+                        is_interrupt = true;
                     }
                 }
                 _ => {}
@@ -379,28 +682,20 @@ extern "C" fn signal_trap_handler(
             // So here we check whether this exception is caused by a suspend signal, return the
             // state image if so, or throw the exception out otherwise.
 
-            let ctx: &mut vm::Ctx = &mut **CURRENT_CTX.with(|x| x.get());
+            let ctx: *mut vm::Ctx = *CURRENT_CTX.with(|x| x.get());
             let es_image = fault
                 .read_stack(None)
                 .expect("fault.read_stack() failed. Broken invariants?");
 
             if is_suspend_signal {
                 // If this is a suspend signal, we parse the runtime state and return the resulting image.
-                let image = build_instance_image(ctx, es_image);
+                let image = build_instance_image(&mut *ctx, es_image);
                 unwind_result = Box::new(image);
             } else {
                 // Otherwise, this is a real exception and we just throw it to the caller.
-                if !es_image.frames.is_empty() {
-                    eprintln!(
-                        "\n{}",
-                        "Wasmer encountered an error while running your WebAssembly program."
-                    );
-                    es_image.print_backtrace_if_needed();
-                }
 
                 // Look up the exception tables and try to find an exception code.
-                let exc_code = CURRENT_CODE_VERSIONS.with(|versions| {
-                    let versions = versions.borrow();
+                let exc_code = with_code_versions(|versions| {
                     for v in versions.iter() {
                         if let Some(table) = v.runnable_module.get_exception_table() {
                             let ip = fault.ip.get();
@@ -414,9 +709,32 @@ extern "C" fn signal_trap_handler(
                     }
                     None
                 });
-                if let Some(code) = exc_code {
-                    unwind_result = Box::new(code);
-                }
+                // The frames are reported with the indices of the module that is running.
+                let frames = if ctx.is_null() {
+                    vec![]
+                } else {
+                    let info = &(*(*ctx).module).info;
+                    es_image
+                        .frames
+                        .iter()
+                        .map(|frame| {
+                            let local_index = LocalFuncIndex::new(frame.local_function_id);
+                            TrapFrame::new(
+                                info,
+                                local_index.convert_up(info),
+                                frame.wasm_source_loc,
+                            )
+                        })
+                        .collect()
+                };
+                unwind_result = Box::new(Trap {
+                    code: if is_interrupt {
+                        Some(ExceptionCode::Interrupted)
+                    } else {
+                        exc_code
+                    },
+                    frames,
+                });
             }
 
             true
@@ -487,8 +805,7 @@ impl FaultInfo {
     pub unsafe fn read_stack(&self, max_depth: Option<usize>) -> Option<ExecutionStateImage> {
         let rsp = self.known_registers[X64Register::GPR(GPR::RSP).to_index().0]?;
 
-        Some(CURRENT_CODE_VERSIONS.with(|versions| {
-            let versions = versions.borrow();
+        let mut image = with_code_versions(|versions| {
             read_stack(
                 || versions.iter(),
                 rsp as usize as *const u64,
@@ -496,7 +813,13 @@ impl FaultInfo {
                 Some(self.ip.get() as u64),
                 max_depth,
             )
-        }))
+        });
+
+        let ctx: *mut vm::Ctx = *CURRENT_CTX.with(|x| x.get());
+        if !ctx.is_null() {
+            image.attach_names(&(*(*ctx).module).info);
+        }
+        Some(image)
     }
 }
 
@@ -1024,3 +1347,24 @@ pub unsafe fn get_fault_info(siginfo: *const c_void, ucontext: *mut c_void) -> F
         known_registers,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_interrupt_page_protection() {
+        let handle = InterruptHandle::new().unwrap();
+        let page = handle.signal_mem() as *const u8;
+        unsafe {
+            assert!(!take_interrupt(ptr::null()));
+            assert_eq!(ptr::read_volatile(page), 0);
+
+            handle.interrupt().unwrap();
+            assert!(take_interrupt(page.add(1) as *const c_void));
+            // Taking the interrupt makes the page readable again.
+            assert_eq!(ptr::read_volatile(page), 0);
+        }
+    }
+}
@@ -3,7 +3,7 @@
 use crate::{
     export::Export,
     import::IsExport,
-    types::{GlobalDescriptor, Type, Value},
+    types::{ExternRef, FuncRef, GlobalDescriptor, NativeWasmType, Type, Value},
     vm,
 };
 use std::{
@@ -57,6 +57,8 @@ impl Global {
                 Value::F32(x) => x.to_bits() as u128,
                 Value::F64(x) => x.to_bits() as u128,
                 Value::V128(x) => x,
+                Value::FuncRef(x) => x.to_raw() as u128,
+                Value::ExternRef(x) => x.to_binary() as u128,
             },
         };
 
@@ -87,6 +89,10 @@ impl Global {
                         Value::F32(x) => x.to_bits() as u128,
                         Value::F64(x) => x.to_bits() as u128,
                         Value::V128(x) => x,
+                        Value::FuncRef(x) => x.to_raw() as u128,
+                        Value::ExternRef(x) => x.to_binary() as u128,
+                        Value::FuncRef(x) => x.to_raw() as u128,
+                        Value::ExternRef(x) => x.to_binary() as u128,
                     },
                 };
                 let mut storage = self.storage.lock().unwrap();
@@ -110,6 +116,8 @@ impl Global {
             Type::F32 => Value::F32(f32::from_bits(data as u32)),
             Type::F64 => Value::F64(f64::from_bits(data as u64)),
             Type::V128 => Value::V128(data),
+            Type::FuncRef => Value::FuncRef(unsafe { FuncRef::from_raw(data as u64) }),
+            Type::ExternRef => Value::ExternRef(ExternRef::from_binary(data as u64)),
         }
     }
 
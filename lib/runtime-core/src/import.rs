@@ -5,6 +5,7 @@ use crate::export::Export;
 use std::collections::VecDeque;
 use std::collections::{hash_map::Entry, HashMap};
 use std::{
+    any::{Any, TypeId},
     borrow::{Borrow, BorrowMut},
     ffi::c_void,
     sync::{Arc, Mutex},
@@ -33,6 +34,8 @@ impl IsExport for Export {
     }
 }
 
+type TypedStateCreator = dyn Fn() -> (TypeId, Box<dyn Any + Send>) + Send + Sync + 'static;
+
 /// All of the import data used when instantiating.
 ///
 /// It's suggested that you use the [`imports!`] macro
@@ -58,6 +61,7 @@ pub struct ImportObject {
     map: Arc<Mutex<HashMap<String, Box<dyn LikeNamespace + Send>>>>,
     pub(crate) state_creator:
         Option<Arc<dyn Fn() -> (*mut c_void, fn(*mut c_void)) + Send + Sync + 'static>>,
+    typed_state_creators: Vec<Arc<TypedStateCreator>>,
     /// Allow missing functions to be generated and instantiation to continue when required
     /// functions are not provided.
     pub allow_missing_functions: bool,
@@ -69,6 +73,7 @@ impl ImportObject {
         Self {
             map: Arc::new(Mutex::new(HashMap::new())),
             state_creator: None,
+            typed_state_creators: Vec::new(),
             allow_missing_functions: false,
         }
     }
@@ -81,6 +86,7 @@ impl ImportObject {
         Self {
             map: Arc::new(Mutex::new(HashMap::new())),
             state_creator: Some(Arc::new(state_creator)),
+            typed_state_creators: Vec::new(),
             allow_missing_functions: false,
         }
     }
@@ -89,6 +95,46 @@ impl ImportObject {
         self.state_creator.as_ref().map(|state_gen| state_gen())
     }
 
+    /// Attach a state of type `T`, created by `state_creator`, to each
+    /// instance created with this `ImportObject`.
+    ///
+    /// The state is created before the `start` function runs and can be
+    /// accessed with [`Ctx::state`].  This works alongside the data of
+    /// `new_with_data`, so it can be used with the import objects of ABI
+    /// implementations such as WASI.
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::import::ImportObject;
+    /// struct Config {
+    ///     verbose: bool,
+    /// }
+    ///
+    /// let mut import_object = ImportObject::new();
+    /// import_object.add_state(|| Config { verbose: true });
+    /// ```
+    ///
+    /// [`Ctx::state`]: ../vm/struct.Ctx.html#method.state
+    pub fn add_state<T, F>(&mut self, state_creator: F)
+    where
+        T: Any + Send,
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        self.typed_state_creators.push(Arc::new(move || {
+            (
+                TypeId::of::<T>(),
+                Box::new(state_creator()) as Box<dyn Any + Send>,
+            )
+        }));
+    }
+
+    pub(crate) fn call_typed_state_creators(&self) -> Vec<(TypeId, Box<dyn Any + Send>)> {
+        self.typed_state_creators
+            .iter()
+            .map(|state_gen| state_gen())
+            .collect()
+    }
+
     /// Register anything that implements `LikeNamespace` as a namespace.
     ///
     /// # Usage:
@@ -163,6 +209,7 @@ impl ImportObject {
         Self {
             map: Arc::clone(&self.map),
             state_creator: self.state_creator.clone(),
+            typed_state_creators: self.typed_state_creators.clone(),
             allow_missing_functions: false,
         }
     }
@@ -1,9 +1,11 @@
 //! The instance module contains the implementation data structures and helper functions used to
 //! manipulate and access wasm instances.
+#[cfg(unix)]
+use crate::fault::InterruptHandle;
 use crate::{
     backend::RunnableModule,
     backing::{ImportBacking, LocalBacking},
-    error::{CallError, CallResult, ResolveError, ResolveResult, Result, RuntimeError},
+    error::{CallError, CallResult, ResolveError, ResolveResult, Result, RuntimeError, Trap},
     export::{Context, Export, ExportIter, FuncPointer},
     global::Global,
     import::{ImportObject, LikeNamespace},
@@ -12,13 +14,17 @@ use crate::{
     module::{ExportIndex, Module, ModuleInfo, ModuleInner},
     sig_registry::SigRegistry,
     structures::TypedIndex,
-    table::Table,
+    table::{self, Table},
     typed_func::{Func, Wasm, WasmTypeList},
-    types::{FuncIndex, FuncSig, GlobalIndex, LocalOrImport, MemoryIndex, TableIndex, Type, Value},
+    types::{
+        ExternRef, FuncIndex, FuncRef, FuncSig, GlobalIndex, LocalOrImport, MemoryIndex,
+        NativeWasmType, SigIndex, TableIndex, Type, Value,
+    },
     vm::{self, InternalField},
 };
 use smallvec::{smallvec, SmallVec};
 use std::{
+    any::Any,
     mem,
     pin::Pin,
     ptr::{self, NonNull},
@@ -37,6 +43,9 @@ unsafe impl Send for InstanceInner {}
 
 impl Drop for InstanceInner {
     fn drop(&mut self) {
+        // Forget the `funcref`s to the functions of this instance.
+        let vmctx = self.vmctx;
+        table::prune_func_refs(|anyfunc| anyfunc.ctx == vmctx);
         // Drop the vmctx.
         unsafe { Box::from_raw(self.vmctx) };
     }
@@ -55,6 +64,9 @@ pub struct Instance {
     inner: Pin<Box<InstanceInner>>,
     #[allow(dead_code)]
     import_object: ImportObject,
+    #[cfg(unix)]
+    interrupt_handle: Option<InterruptHandle>,
+    counters: Box<[u64]>,
 }
 
 impl Instance {
@@ -86,14 +98,22 @@ impl Instance {
                 None => vm::Ctx::new(backing, import_backing, &module),
             };
             vmctx.as_mut_ptr().write(real_ctx);
+            let ctx = &mut *vmctx.as_mut_ptr();
+            ctx.states.extend(imports.call_typed_state_creators());
         };
         Box::leak(vmctx);
 
-        let instance = Instance {
+        let counters = vec![0; module.info.counter_count].into_boxed_slice();
+        let mut instance = Instance {
             module,
             inner,
             import_object: imports.clone_ref(),
+            #[cfg(unix)]
+            interrupt_handle: None,
+            counters,
         };
+        let counters = instance.counters.as_mut_ptr();
+        instance.context_mut().internal.counters = counters;
 
         if let Some(start_index) = instance.module.info.start_func {
             // We know that the start function takes no arguments and returns no values.
@@ -307,7 +327,7 @@ impl Instance {
                 signature,
                 module: &self.module,
                 instance_inner: &self.inner,
-                func_index: *func_index,
+                callee: Callee::Index(*func_index),
             })
         } else {
             Err(ResolveError::ExportWrongType {
@@ -317,6 +337,82 @@ impl Instance {
         }
     }
 
+    /// Get an exported table by name.
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::Instance;
+    /// # use wasmer_runtime_core::error::ResolveResult;
+    /// # fn get_table(instance: &Instance) -> ResolveResult<()> {
+    /// let table = instance.table("table")?;
+    /// let size = table.size();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn table(&self, name: &str) -> ResolveResult<Table> {
+        let export_index =
+            self.module
+                .info
+                .exports
+                .get(name)
+                .ok_or_else(|| ResolveError::ExportNotFound {
+                    name: name.to_string(),
+                })?;
+
+        if let ExportIndex::Table(table_index) = export_index {
+            Ok(self.inner.get_table_from_index(&self.module, *table_index))
+        } else {
+            Err(ResolveError::ExportWrongType {
+                name: name.to_string(),
+            }
+            .into())
+        }
+    }
+
+    /// Get the function in the element at `index` of `table` as a [`DynFunc`],
+    /// called through this instance.
+    ///
+    /// The element can hold a function of any instance or a host function,
+    /// but its signature must be declared by the module of this instance.
+    /// Returns `None` if the index is out of bounds, the element is null or
+    /// its signature is not declared.
+    ///
+    /// [`DynFunc`]: struct.DynFunc.html
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::Instance;
+    /// # use wasmer_runtime_core::error::CallResult;
+    /// # use wasmer_runtime_core::table::Table;
+    /// # fn call_first(instance: &Instance, table: &Table) -> CallResult<()> {
+    /// if let Some(func) = instance.table_func(table, 0) {
+    ///     func.call(&[])?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn table_func(&self, table: &Table, index: u32) -> Option<DynFunc> {
+        let anyfunc = match table.get(index)? {
+            Value::FuncRef(func_ref) if !func_ref.is_null() => table::resolve_func_ref(func_ref)?,
+            _ => return None,
+        };
+
+        let signature = SigRegistry.lookup_signature(SigIndex::new(anyfunc.sig_id.0 as usize));
+        let (sig_index, _) = self
+            .module
+            .info
+            .signatures
+            .iter()
+            .find(|(_, sig)| **sig == *signature)?;
+
+        Some(DynFunc {
+            signature,
+            module: &self.module,
+            instance_inner: &self.inner,
+            callee: Callee::Anyfunc { anyfunc, sig_index },
+        })
+    }
+
     /// Call an exported WebAssembly function given the export name.
     /// Pass arguments by wrapping each one in the [`Value`] enum.
     /// The returned values are also each wrapped in a [`Value`].
@@ -325,7 +421,8 @@ impl Instance {
     ///
     /// # Note:
     /// This returns `CallResult<Vec<Value>>` in order to support
-    /// the future multi-value returns WebAssembly feature.
+    /// functions with several results, from the multi-value
+    /// WebAssembly feature.
     ///
     /// # Usage:
     /// ```
@@ -389,6 +486,81 @@ impl Instance {
         unsafe { &mut *self.inner.vmctx }
     }
 
+    /// Returns an [`InterruptHandle`] to interrupt the calls of this instance from any
+    /// thread. The module must be compiled with `full_preemption` enabled.
+    ///
+    /// # Usage:
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use wasmer_runtime_core::{backend::ExceptionCode, error::Error, Instance};
+    /// fn run_with_timeout(instance: &mut Instance) -> Result<bool, Error> {
+    ///     let handle = instance.interrupt_handle()?;
+    ///     handle.interrupt_after(Duration::from_secs(1));
+    ///     let result = instance.call("main", &[]);
+    ///     handle.cancel_deadline();
+    ///     match result {
+    ///         Ok(_) => Ok(true),
+    ///         Err(wasmer_runtime_core::error::CallError::Runtime(ref e))
+    ///             if e.exception_code() == Some(ExceptionCode::Interrupted) =>
+    ///         {
+    ///             Ok(false)
+    ///         }
+    ///         Err(e) => Err(e.into()),
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// Fails if the interrupt page of the handle cannot be set up, or if the timer thread
+    /// serving the deadlines of all handles cannot be started.
+    ///
+    /// [`InterruptHandle`]: fault/struct.InterruptHandle.html
+    #[cfg(unix)]
+    pub fn interrupt_handle(
+        &mut self,
+    ) -> std::result::Result<InterruptHandle, crate::error::CreationError> {
+        if let Some(handle) = &self.interrupt_handle {
+            return Ok(handle.clone());
+        }
+        let handle = InterruptHandle::new()?;
+        self.context_mut().internal.interrupt_signal_mem = handle.signal_mem();
+        self.interrupt_handle = Some(handle.clone());
+        Ok(handle)
+    }
+
+    /// Sets the counters incremented by [`InternalEvent::IncrementCounter`] events back
+    /// to zero.
+    ///
+    /// [`InternalEvent::IncrementCounter`]: codegen/enum.InternalEvent.html#variant.IncrementCounter
+    pub fn reset_counters(&mut self) {
+        for counter in self.counters.iter_mut() {
+            *counter = 0;
+        }
+    }
+
+    /// Returns the counters incremented by [`InternalEvent::IncrementCounter`] events,
+    /// which are allocated when the instance is created, before its start function runs.
+    ///
+    /// [`InternalEvent::IncrementCounter`]: codegen/enum.InternalEvent.html#variant.IncrementCounter
+    pub fn counters(&self) -> &[u64] {
+        &self.counters
+    }
+
+    /// Returns the state of type `T` attached to this instance, if there is one.
+    ///
+    /// See [`Ctx::state`].
+    ///
+    /// [`Ctx::state`]: vm/struct.Ctx.html#method.state
+    pub fn state<T: Any + Send>(&self) -> Option<&T> {
+        self.context().state()
+    }
+
+    /// Returns the state of type `T` attached to this instance mutably, if
+    /// there is one.
+    pub fn state_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.context_mut().state_mut()
+    }
+
     /// Returns an iterator over all of the items
     /// exported from this instance.
     pub fn exports(&self) -> ExportIter {
@@ -654,6 +826,12 @@ pub(crate) fn call_func_with_index_inner(
             Value::F64(f) => {
                 raw_args.push(f.to_bits() as u64);
             }
+            Value::FuncRef(r) => {
+                raw_args.push(r.to_raw());
+            }
+            Value::ExternRef(r) => {
+                raw_args.push(r.to_binary());
+            }
             Value::V128(v) => {
                 let bytes = v.to_le_bytes();
                 let mut lo = [0u8; 8];
@@ -675,22 +853,24 @@ pub(crate) fn call_func_with_index_inner(
     let run_wasm = |result_space: *mut u64| unsafe {
         let mut error_out = None;
 
-        let success = invoke(
-            trampoline,
-            ctx_ptr,
-            func_ptr,
-            raw_args.as_ptr(),
-            result_space,
-            &mut error_out,
-            invoke_env,
-        );
+        let success = vm::Ctx::restore_internals_on_trap(ctx_ptr, || {
+            invoke(
+                trampoline,
+                ctx_ptr,
+                func_ptr,
+                raw_args.as_ptr(),
+                result_space,
+                &mut error_out,
+                invoke_env,
+            )
+        });
 
         if success {
             Ok(())
         } else {
             Err(error_out
-                .map(RuntimeError)
-                .unwrap_or_else(|| RuntimeError(Box::new("invoke(): Unknown error".to_string()))))
+                .map(RuntimeError::from_unwind)
+                .unwrap_or_else(|| RuntimeError::Trap(Trap::new(None))))
         }
     };
 
@@ -700,6 +880,8 @@ pub(crate) fn call_func_with_index_inner(
         Type::F32 => Value::F32(f32::from_bits(raw as u32)),
         Type::F64 => Value::F64(f64::from_bits(raw)),
         Type::V128 => unreachable!("V128 does not map to any single value"),
+        Type::FuncRef => Value::FuncRef(unsafe { FuncRef::from_raw(raw) }),
+        Type::ExternRef => Value::ExternRef(ExternRef::from_binary(raw)),
     };
 
     match signature.returns() {
@@ -734,12 +916,17 @@ pub(crate) fn call_func_with_index_inner(
 
             run_wasm(results.as_mut_ptr())?;
 
-            rets.extend(
-                results
-                    .iter()
-                    .zip(result_tys.iter())
-                    .map(|(&raw, &ty)| raw_to_value(raw, ty)),
-            );
+            let mut results = results.iter();
+            for &ty in result_tys {
+                let raw = *results.next().unwrap();
+                rets.push(match ty {
+                    Type::V128 => {
+                        let high = *results.next().unwrap();
+                        Value::V128(raw as u128 | (high as u128) << 64)
+                    }
+                    _ => raw_to_value(raw, ty),
+                });
+            }
 
             Ok(())
         }
@@ -751,7 +938,19 @@ pub struct DynFunc<'a> {
     pub(crate) signature: Arc<FuncSig>,
     module: &'a ModuleInner,
     pub(crate) instance_inner: &'a InstanceInner,
-    func_index: FuncIndex,
+    callee: Callee,
+}
+
+/// The function called by a `DynFunc`.
+enum Callee {
+    /// A local or imported function of the instance.
+    Index(FuncIndex),
+    /// The function of a table element, whose signature is at `sig_index` in
+    /// the module of the instance.
+    Anyfunc {
+        anyfunc: vm::Anyfunc,
+        sig_index: SigIndex,
+    },
 }
 
 impl<'a> DynFunc<'a> {
@@ -764,7 +963,8 @@ impl<'a> DynFunc<'a> {
     ///
     /// # Note:
     /// This returns `CallResult<Vec<Value>>` in order to support
-    /// the future multi-value returns WebAssembly feature.
+    /// functions with several results, from the multi-value
+    /// WebAssembly feature.
     ///
     /// # Usage:
     /// ```
@@ -780,15 +980,39 @@ impl<'a> DynFunc<'a> {
     pub fn call(&self, params: &[Value]) -> CallResult<Vec<Value>> {
         let mut results = Vec::new();
 
-        call_func_with_index(
-            &self.module.info,
-            &**self.module.runnable_module,
-            &self.instance_inner.import_backing,
-            self.instance_inner.vmctx,
-            self.func_index,
-            params,
-            &mut results,
-        )?;
+        match self.callee {
+            Callee::Index(func_index) => call_func_with_index(
+                &self.module.info,
+                &**self.module.runnable_module,
+                &self.instance_inner.import_backing,
+                self.instance_inner.vmctx,
+                func_index,
+                params,
+                &mut results,
+            )?,
+            Callee::Anyfunc { anyfunc, sig_index } => {
+                // A null context means the function takes the context of its caller.
+                let ctx_ptr = if anyfunc.ctx.is_null() {
+                    self.instance_inner.vmctx
+                } else {
+                    anyfunc.ctx
+                };
+                let wasm = self
+                    .module
+                    .runnable_module
+                    .get_trampoline(&self.module.info, sig_index)
+                    .expect("wasm trampoline");
+
+                call_func_with_index_inner(
+                    ctx_ptr,
+                    NonNull::new(anyfunc.func as *mut _).unwrap(),
+                    &self.signature,
+                    wasm,
+                    params,
+                    &mut results,
+                )?
+            }
+        }
 
         Ok(results)
     }
@@ -800,16 +1024,19 @@ impl<'a> DynFunc<'a> {
 
     /// Gets a const pointer to the function represent by this `DynFunc`.
     pub fn raw(&self) -> *const vm::Func {
-        match self.func_index.local_or_import(&self.module.info) {
-            LocalOrImport::Local(local_func_index) => self
-                .module
-                .runnable_module
-                .get_func(&self.module.info, local_func_index)
-                .unwrap()
-                .as_ptr(),
-            LocalOrImport::Import(import_func_index) => {
-                self.instance_inner.import_backing.vm_functions[import_func_index].func
-            }
+        self.anyfunc().func
+    }
+
+    /// Gets the caller-checked anyfunc of the function represented by this `DynFunc`.
+    pub(crate) fn anyfunc(&self) -> vm::Anyfunc {
+        match self.callee {
+            Callee::Index(func_index) => LocalBacking::func_anyfunc(
+                self.module,
+                &self.instance_inner.import_backing,
+                self.instance_inner.vmctx,
+                func_index,
+            ),
+            Callee::Anyfunc { anyfunc, .. } => anyfunc,
         }
     }
 }
@@ -236,3 +236,120 @@ impl JitCodeDebugInfoManager {
         handle
     }
 }
+
+/// Builds an ELF file with a symbol for each of the given functions, so that
+/// the debugger can name JIT code even when the module has no DWARF.
+///
+/// `functions` are the name, address and size of each function.
+pub(crate) fn build_symbol_file(functions: &[(String, usize, usize)]) -> Vec<u8> {
+    const EHDR_SIZE: usize = 64;
+    const SHDR_SIZE: usize = 64;
+    const SYM_SIZE: usize = 24;
+
+    #[cfg(target_arch = "aarch64")]
+    const MACHINE: u16 = 183; // EM_AARCH64
+    #[cfg(not(target_arch = "aarch64"))]
+    const MACHINE: u16 = 62; // EM_X86_64
+
+    let shstrtab: &[u8] = b"\0.text\0.symtab\0.strtab\0.shstrtab\0";
+
+    let mut strtab = vec![0u8];
+    let mut symtab = vec![0u8; SYM_SIZE];
+    for (name, addr, size) in functions {
+        let name_offset = strtab.len() as u32;
+        strtab.extend_from_slice(name.as_bytes());
+        strtab.push(0);
+
+        symtab.extend_from_slice(&name_offset.to_le_bytes());
+        symtab.push(0x12); // STB_GLOBAL, STT_FUNC
+        symtab.push(0);
+        symtab.extend_from_slice(&1u16.to_le_bytes()); // .text
+        symtab.extend_from_slice(&(*addr as u64).to_le_bytes());
+        symtab.extend_from_slice(&(*size as u64).to_le_bytes());
+    }
+
+    let text_start = functions.iter().map(|f| f.1).min().unwrap_or(0);
+    let text_end = functions.iter().map(|f| f.1 + f.2).max().unwrap_or(0);
+
+    let symtab_offset = EHDR_SIZE;
+    let strtab_offset = symtab_offset + symtab.len();
+    let shstrtab_offset = strtab_offset + strtab.len();
+    // section headers are 8-byte aligned
+    let shdrs_offset = (shstrtab_offset + shstrtab.len() + 7) & !7;
+
+    let mut out = Vec::with_capacity(shdrs_offset + 5 * SHDR_SIZE);
+
+    // ELF header: 64-bit, little endian, executable.
+    out.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    out.extend_from_slice(&[0; 8]);
+    out.extend_from_slice(&2u16.to_le_bytes()); // e_type
+    out.extend_from_slice(&MACHINE.to_le_bytes()); // e_machine
+    out.extend_from_slice(&1u32.to_le_bytes()); // e_version
+    out.extend_from_slice(&0u64.to_le_bytes()); // e_entry
+    out.extend_from_slice(&0u64.to_le_bytes()); // e_phoff
+    out.extend_from_slice(&(shdrs_offset as u64).to_le_bytes()); // e_shoff
+    out.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+    out.extend_from_slice(&(EHDR_SIZE as u16).to_le_bytes()); // e_ehsize
+    out.extend_from_slice(&0u16.to_le_bytes()); // e_phentsize
+    out.extend_from_slice(&0u16.to_le_bytes()); // e_phnum
+    out.extend_from_slice(&(SHDR_SIZE as u16).to_le_bytes()); // e_shentsize
+    out.extend_from_slice(&5u16.to_le_bytes()); // e_shnum
+    out.extend_from_slice(&4u16.to_le_bytes()); // e_shstrndx
+
+    out.extend_from_slice(&symtab);
+    out.extend_from_slice(&strtab);
+    out.extend_from_slice(shstrtab);
+    out.resize(shdrs_offset, 0);
+
+    let mut section_header = |name: u32,
+                              ty: u32,
+                              flags: u64,
+                              addr: u64,
+                              offset: usize,
+                              size: usize,
+                              link: u32,
+                              info: u32,
+                              align: u64,
+                              entsize: u64| {
+        out.extend_from_slice(&name.to_le_bytes());
+        out.extend_from_slice(&ty.to_le_bytes());
+        out.extend_from_slice(&flags.to_le_bytes());
+        out.extend_from_slice(&addr.to_le_bytes());
+        out.extend_from_slice(&(offset as u64).to_le_bytes());
+        out.extend_from_slice(&(size as u64).to_le_bytes());
+        out.extend_from_slice(&link.to_le_bytes());
+        out.extend_from_slice(&info.to_le_bytes());
+        out.extend_from_slice(&align.to_le_bytes());
+        out.extend_from_slice(&entsize.to_le_bytes());
+    };
+    section_header(0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+    // .text only describes where the code is; its contents are not included.
+    section_header(
+        1,
+        8,   // SHT_NOBITS
+        0x6, // SHF_ALLOC | SHF_EXECINSTR
+        text_start as u64,
+        0,
+        text_end - text_start,
+        0,
+        0,
+        16,
+        0,
+    );
+    section_header(
+        7,
+        2, // SHT_SYMTAB
+        0,
+        0,
+        symtab_offset,
+        symtab.len(),
+        3, // .strtab
+        1, // all symbols but the first are global
+        8,
+        SYM_SIZE as u64,
+    );
+    section_header(15, 3, 0, 0, strtab_offset, strtab.len(), 0, 0, 1, 0);
+    section_header(23, 3, 0, 0, shstrtab_offset, shstrtab.len(), 0, 0, 1, 0);
+
+    out
+}
@@ -49,6 +49,7 @@ pub mod instance;
 pub mod loader;
 pub mod memory;
 pub mod module;
+pub mod multi_value;
 pub mod parse;
 mod sig_registry;
 pub mod structures;
@@ -155,9 +156,9 @@ pub fn validate_and_report_errors_with_features(
     let config = wasmparser::ValidatingParserConfig {
         operator_config: wasmparser::OperatorValidatorConfig {
             enable_simd: features.simd,
-            enable_bulk_memory: false,
-            enable_multi_value: false,
-            enable_reference_types: false,
+            enable_bulk_memory: features.bulk_memory,
+            enable_multi_value: features.multi_value,
+            enable_reference_types: features.reference_types,
             enable_threads: features.threads,
 
             #[cfg(feature = "deterministic-execution")]
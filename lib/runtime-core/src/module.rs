@@ -7,10 +7,10 @@ use crate::{
     import::ImportObject,
     structures::{Map, TypedIndex},
     types::{
-        FuncIndex, FuncSig, GlobalDescriptor, GlobalIndex, GlobalInit, ImportedFuncIndex,
-        ImportedGlobalIndex, ImportedMemoryIndex, ImportedTableIndex, Initializer,
-        LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryDescriptor, MemoryIndex,
-        SigIndex, TableDescriptor, TableIndex,
+        DataIndex, ElemIndex, FuncIndex, FuncSig, GlobalDescriptor, GlobalIndex, GlobalInit,
+        ImportedFuncIndex, ImportedGlobalIndex, ImportedMemoryIndex, ImportedTableIndex,
+        Initializer, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryDescriptor,
+        MemoryIndex, SigIndex, TableDescriptor, TableIndex,
     },
     Instance,
 };
@@ -58,6 +58,11 @@ pub struct ModuleInfo {
     pub data_initializers: Vec<DataInitializer>,
     /// Vector of table initializers.
     pub elem_initializers: Vec<TableInitializer>,
+    /// Map of passive data segment index to the bytes of the segment.
+    pub passive_data: HashMap<DataIndex, Vec<u8>>,
+    /// Map of passive element segment index to the elements of the segment,
+    /// `None` being a null reference.
+    pub passive_elements: HashMap<ElemIndex, Vec<Option<FuncIndex>>>,
 
     /// Index of optional start function.
     pub start_func: Option<FuncIndex>,
@@ -80,6 +85,22 @@ pub struct ModuleInfo {
     /// Custom sections.
     pub custom_sections: HashMap<String, Vec<u8>>,
 
+    /// Module name from the `name` custom section.
+    pub module_name: Option<String>,
+    /// Function names from the `name` custom section.
+    pub func_names: HashMap<FuncIndex, String>,
+    /// Local names from the `name` custom section, by function and local index.
+    pub local_names: HashMap<FuncIndex, HashMap<u32, String>>,
+
+    /// Initial values of internal fields by index, set by middlewares.
+    pub internal_initializers: Vec<(usize, u64)>,
+    /// Indices of internal fields restored to their value before a call from the host
+    /// when the call traps, set by middlewares.
+    pub internals_restored_on_trap: Vec<usize>,
+    /// Number of counters incremented by `InternalEvent::IncrementCounter` events in the
+    /// code of the module.
+    pub counter_count: usize,
+
     /// Flag controlling whether or not debug information for use in a debugger
     /// will be generated.
     pub generate_debug_info: bool,
@@ -107,6 +128,20 @@ impl ModuleInfo {
         }
         Ok(())
     }
+
+    /// Returns the name of a function from the `name` custom section.
+    pub fn func_name(&self, func_index: FuncIndex) -> Option<&str> {
+        self.func_names.get(&func_index).map(String::as_str)
+    }
+
+    /// Returns the name of a local of a function from the `name` custom
+    /// section.
+    pub fn local_name(&self, func_index: FuncIndex, local_index: u32) -> Option<&str> {
+        self.local_names
+            .get(&func_index)?
+            .get(&local_index)
+            .map(String::as_str)
+    }
 }
 
 /// A compiled WebAssembly module.
@@ -223,8 +258,8 @@ pub struct TableInitializer {
     pub table_index: TableIndex,
     /// Either a constant offset or a `get_global`
     pub base: Initializer,
-    /// The values to write into the table elements.
-    pub elements: Vec<FuncIndex>,
+    /// The values to write into the table elements, `None` being a null reference.
+    pub elements: Vec<Option<FuncIndex>>,
 }
 
 /// String table builder.
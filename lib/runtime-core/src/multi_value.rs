@@ -0,0 +1,222 @@
+//! The multi value module describes how functions with several results return them.
+//!
+//! Such a function returns its results the way a C function returns a `#[repr(C)]`
+//! struct with one field per result, which is how host functions return tuples. With the
+//! System V ABI, a struct of at most 16 bytes is returned in registers, one per eightbyte:
+//! `%rax` then `%rdx` for the eightbytes holding an integer, `%xmm0` then `%xmm1` for the
+//! eightbytes holding only floats. A larger struct is written to the memory pointed to by
+//! a hidden first parameter, placed before the `vm::Ctx`, and the pointer is returned in
+//! `%rax`.
+//!
+//! Backends follow this convention for every function with several results, so that
+//! WebAssembly and host functions can call each other whichever backend compiled them.
+
+use crate::types::{Type, Value};
+
+/// The class of an eightbyte of results returned in registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eightbyte {
+    /// The eightbyte holds an integer and is returned in the next integer register.
+    Integer,
+    /// The eightbyte holds only floats and is returned in the next SSE register.
+    Sse,
+}
+
+/// The layout of the `#[repr(C)]` struct holding the results of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultsLayout {
+    types: Vec<Type>,
+    offsets: Vec<usize>,
+    size: usize,
+}
+
+impl ResultsLayout {
+    /// Computes the layout of the results of types `types`.
+    pub fn new(types: &[Type]) -> Self {
+        let mut offsets = Vec::with_capacity(types.len());
+        let mut size = 0;
+        let mut align = 1;
+        for ty in types {
+            let field_size = Self::value_size(*ty);
+            size = (size + field_size - 1) / field_size * field_size;
+            offsets.push(size);
+            size += field_size;
+            align = align.max(field_size);
+        }
+
+        Self {
+            types: types.to_vec(),
+            offsets,
+            size: (size + align - 1) / align * align,
+        }
+    }
+
+    /// Returns the size in bytes of a value of type `ty`, which is also its alignment.
+    pub fn value_size(ty: Type) -> usize {
+        match ty {
+            Type::I32 | Type::F32 => 4,
+            Type::I64 | Type::F64 | Type::FuncRef | Type::ExternRef => 8,
+            Type::V128 => 16,
+        }
+    }
+
+    /// Returns the types of the results.
+    pub fn types(&self) -> &[Type] {
+        &self.types
+    }
+
+    /// Returns the offset of each result in the struct.
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    /// Returns the size of the struct, in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether the results are returned through memory rather than in registers.
+    pub fn in_memory(&self) -> bool {
+        self.size > 16
+    }
+
+    /// Returns the class of each eightbyte of the struct, when it is returned in registers.
+    pub fn eightbytes(&self) -> Vec<Eightbyte> {
+        (0..(self.size + 7) / 8)
+            .map(|eightbyte| {
+                let only_floats = self
+                    .types
+                    .iter()
+                    .zip(&self.offsets)
+                    .filter(|&(ty, &offset)| {
+                        offset / 8 <= eightbyte
+                            && eightbyte < (offset + Self::value_size(*ty) + 7) / 8
+                    })
+                    .all(|(ty, _)| *ty == Type::F32 || *ty == Type::F64);
+                if only_floats {
+                    Eightbyte::Sse
+                } else {
+                    Eightbyte::Integer
+                }
+            })
+            .collect()
+    }
+
+    /// Writes `values` into the struct bytes `bytes`.
+    pub fn write_values(&self, values: &[Value], bytes: &mut [u8]) {
+        for ((value, ty), &offset) in values.iter().zip(&self.types).zip(&self.offsets) {
+            let size = Self::value_size(*ty);
+            bytes[offset..offset + size].copy_from_slice(&value.to_u128().to_le_bytes()[..size]);
+        }
+    }
+
+    /// Reads the results from the struct bytes `bytes` into `slots`, one 64-bit slot per
+    /// result, or two for a `V128`, as trampolines return them.
+    pub fn read_slots(&self, bytes: &[u8], slots: &mut [u64]) {
+        let mut slots = slots.iter_mut();
+        for (ty, &offset) in self.types.iter().zip(&self.offsets) {
+            let mut value = [0u8; 16];
+            value[..Self::value_size(*ty)]
+                .copy_from_slice(&bytes[offset..offset + Self::value_size(*ty)]);
+            let value = u128::from_le_bytes(value);
+
+            *slots.next().unwrap() = value as u64;
+            if *ty == Type::V128 {
+                *slots.next().unwrap() = (value >> 64) as u64;
+            }
+        }
+    }
+
+    /// Returns the integer and SSE registers holding the struct bytes `bytes`.
+    pub fn to_registers(&self, bytes: &[u8]) -> ([u64; 2], [u64; 2]) {
+        let mut ints = [0; 2];
+        let mut sses = [0; 2];
+        let (mut next_int, mut next_sse) = (0, 0);
+        for (chunk, class) in bytes.chunks(8).zip(self.eightbytes()) {
+            let mut value = [0u8; 8];
+            value[..chunk.len()].copy_from_slice(chunk);
+            let value = u64::from_le_bytes(value);
+            match class {
+                Eightbyte::Integer => {
+                    ints[next_int] = value;
+                    next_int += 1;
+                }
+                Eightbyte::Sse => {
+                    sses[next_sse] = value;
+                    next_sse += 1;
+                }
+            }
+        }
+        (ints, sses)
+    }
+
+    /// Returns the struct bytes held by the integer registers `ints` and the SSE registers
+    /// `sses`.
+    pub fn from_registers(&self, ints: [u64; 2], sses: [u64; 2]) -> Vec<u8> {
+        let mut ints = ints.iter();
+        let mut sses = sses.iter();
+        let mut bytes: Vec<u8> = self
+            .eightbytes()
+            .into_iter()
+            .flat_map(|class| {
+                let value = match class {
+                    Eightbyte::Integer => ints.next(),
+                    Eightbyte::Sse => sses.next(),
+                };
+                value.unwrap().to_le_bytes().to_vec()
+            })
+            .collect();
+        bytes.truncate(self.size);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let layout = ResultsLayout::new(&[Type::I32, Type::F32, Type::F64]);
+        assert_eq!(layout.offsets(), &[0, 4, 8]);
+        assert_eq!(layout.size(), 16);
+        assert!(!layout.in_memory());
+        assert_eq!(layout.eightbytes(), vec![Eightbyte::Integer, Eightbyte::Sse]);
+
+        let layout = ResultsLayout::new(&[Type::F32, Type::F32, Type::I64]);
+        assert_eq!(layout.eightbytes(), vec![Eightbyte::Sse, Eightbyte::Integer]);
+
+        let layout = ResultsLayout::new(&[Type::I32, Type::I64, Type::I32]);
+        assert_eq!(layout.offsets(), &[0, 8, 16]);
+        assert_eq!(layout.size(), 24);
+        assert!(layout.in_memory());
+
+        let layout = ResultsLayout::new(&[Type::I32, Type::V128]);
+        assert_eq!(layout.offsets(), &[0, 16]);
+        assert_eq!(layout.size(), 32);
+    }
+
+    #[test]
+    fn test_registers_round_trip() {
+        let values = [Value::F32(1.5), Value::I32(-2), Value::F64(0.25)];
+        let layout = ResultsLayout::new(&values.iter().map(Value::ty).collect::<Vec<_>>());
+        let mut bytes = vec![0; layout.size()];
+        layout.write_values(&values, &mut bytes);
+
+        let (ints, sses) = layout.to_registers(&bytes);
+        assert_eq!(ints, [(-2i32 as u32 as u64) << 32 | 1.5f32.to_bits() as u64, 0]);
+        assert_eq!(sses, [0.25f64.to_bits(), 0]);
+        assert_eq!(layout.from_registers(ints, sses), bytes);
+
+        let mut slots = [0; 3];
+        layout.read_slots(&bytes, &mut slots);
+        assert_eq!(
+            slots,
+            [
+                1.5f32.to_bits() as u64,
+                -2i32 as u32 as u64,
+                0.25f64.to_bits()
+            ]
+        );
+    }
+}
@@ -11,9 +11,9 @@ use crate::{
     },
     structures::{Map, TypedIndex},
     types::{
-        ElementType, FuncIndex, FuncSig, GlobalDescriptor, GlobalIndex, GlobalInit,
-        ImportedGlobalIndex, Initializer, MemoryDescriptor, MemoryIndex, SigIndex, TableDescriptor,
-        TableIndex, Type, Value,
+        DataIndex, ElemIndex, ElementType, FuncIndex, FuncSig, GlobalDescriptor, GlobalIndex,
+        GlobalInit, ImportedGlobalIndex, Initializer, MemoryDescriptor, MemoryIndex, SigIndex,
+        TableDescriptor, TableIndex, Type, Value,
     },
     units::Pages,
 };
@@ -22,7 +22,7 @@ use std::fmt::Debug;
 use std::sync::{Arc, RwLock};
 use wasmparser::{
     BinaryReaderError, ElemSectionEntryTable, ElementItem, ExternalKind, FuncType,
-    ImportSectionEntryType, Operator, Type as WpType, WasmDecoder,
+    ImportSectionEntryType, NameEntry, Operator, Type as WpType, WasmDecoder,
 };
 
 /// Kind of load error.
@@ -83,6 +83,8 @@ pub fn read_module<
 
         data_initializers: Vec::new(),
         elem_initializers: Vec::new(),
+        passive_data: HashMap::new(),
+        passive_elements: HashMap::new(),
 
         start_func: None,
 
@@ -97,6 +99,14 @@ pub fn read_module<
 
         custom_sections: HashMap::new(),
 
+        module_name: None,
+        func_names: HashMap::new(),
+        local_names: HashMap::new(),
+
+        internal_initializers: Vec::new(),
+        internals_restored_on_trap: Vec::new(),
+        counter_count: 0,
+
         generate_debug_info: compiler_config.should_generate_debug_info(),
         #[cfg(feature = "generate-debug-information")]
         debug_info_manager: crate::jit_debug::JitCodeDebugInfoManager::new(),
@@ -110,7 +120,14 @@ pub fn read_module<
     let mut namespace_builder = Some(StringTableBuilder::new());
     let mut name_builder = Some(StringTableBuilder::new());
     let mut func_count: usize = 0;
+    let mut data_count: usize = 0;
+    let mut elem_count: usize = 0;
     let mut mcg_info_fed = false;
+    // The `name` section comes after the code section. The names are stored in
+    // the module info once the whole module has been read.
+    let mut module_name = None;
+    let mut func_names = HashMap::new();
+    let mut local_names = HashMap::new();
 
     loop {
         use wasmparser::ParserState;
@@ -140,9 +157,8 @@ pub fn read_module<
                             .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?;
                     }
                     ImportSectionEntryType::Table(table_ty) => {
-                        assert_eq!(table_ty.element_type, WpType::AnyFunc);
                         let table_desc = TableDescriptor {
-                            element: ElementType::Anyfunc,
+                            element: wp_type_to_element_type(table_ty.element_type)?,
                             minimum: table_ty.limits.initial,
                             maximum: table_ty.limits.maximum,
                         };
@@ -183,7 +199,7 @@ pub fn read_module<
             }
             ParserState::TableSectionEntry(table_ty) => {
                 let table_desc = TableDescriptor {
-                    element: ElementType::Anyfunc,
+                    element: wp_type_to_element_type(table_ty.element_type)?,
                     minimum: table_ty.limits.initial,
                     maximum: table_ty.limits.maximum,
                 };
@@ -329,12 +345,10 @@ pub fn read_module<
                     .map_err(|x| LoadError::Codegen(format!("{:?}", x)))?;
                 func_count = func_count.wrapping_add(1);
             }
-            ParserState::BeginElementSectionEntry {
-                table: ElemSectionEntryTable::Active(table_index_raw),
-                ty: WpType::AnyFunc,
-            } => {
-                let table_index = TableIndex::new(table_index_raw as usize);
-                let mut elements: Option<Vec<FuncIndex>> = None;
+            ParserState::BeginElementSectionEntry { table, ty: _ } => {
+                let elem_index = ElemIndex::new(elem_count);
+                elem_count += 1;
+                let mut elements: Option<Vec<Option<FuncIndex>>> = None;
                 let mut base: Option<Initializer> = None;
 
                 loop {
@@ -349,10 +363,12 @@ pub fn read_module<
                                 _elements
                                     .iter()
                                     .map(|elem_idx| match elem_idx {
-                                        ElementItem::Null => Err(LoadError::Parse(format!("Error at table {}: null entries in tables are not yet supported", table_index_raw))),
-                                        ElementItem::Func(idx) => Ok(FuncIndex::new(*idx as usize)),
+                                        ElementItem::Null => None,
+                                        ElementItem::Func(idx) => {
+                                            Some(FuncIndex::new(*idx as usize))
+                                        }
                                     })
-                                    .collect::<Result<Vec<FuncIndex>, LoadError>>()?,
+                                    .collect(),
                             );
                         }
                         ParserState::BeginInitExpressionBody
@@ -362,24 +378,29 @@ pub fn read_module<
                     }
                 }
 
-                let table_init = TableInitializer {
-                    table_index,
-                    base: base.unwrap(),
-                    elements: elements.unwrap(),
-                };
+                match table {
+                    ElemSectionEntryTable::Active(table_index_raw) => {
+                        let table_init = TableInitializer {
+                            table_index: TableIndex::new(table_index_raw as usize),
+                            base: base.unwrap(),
+                            elements: elements.unwrap(),
+                        };
 
-                info.write().unwrap().elem_initializers.push(table_init);
-            }
-            ParserState::BeginElementSectionEntry {
-                table: ElemSectionEntryTable::Active(table_index),
-                ty,
-            } => {
-                return Err(LoadError::Parse(format!(
-                    "Error at table {}: type \"{:?}\" is not supported in tables yet",
-                    table_index, ty
-                )));
+                        info.write().unwrap().elem_initializers.push(table_init);
+                    }
+                    ElemSectionEntryTable::Passive => {
+                        info.write()
+                            .unwrap()
+                            .passive_elements
+                            .insert(elem_index, elements.unwrap());
+                    }
+                    // Declared segments only declare functions for `ref.func`
+                    // and are dropped as soon as the module is instantiated.
+                    ElemSectionEntryTable::Declared => {}
+                }
             }
             ParserState::BeginActiveDataSectionEntry(memory_index) => {
+                data_count += 1;
                 let memory_index = MemoryIndex::new(memory_index as usize);
                 let mut base: Option<Initializer> = None;
                 let mut data: Vec<u8> = vec![];
@@ -410,11 +431,40 @@ pub fn read_module<
                 };
                 info.write().unwrap().data_initializers.push(data_init);
             }
+            ParserState::BeginPassiveDataSectionEntry => {
+                let data_index = DataIndex::new(data_count);
+                data_count += 1;
+                let mut data: Vec<u8> = vec![];
+
+                loop {
+                    let state = parser.read();
+                    match *state {
+                        ParserState::Error(ref err) => return Err(err.into()),
+                        ParserState::DataSectionEntryBodyChunk(chunk) => {
+                            data.extend_from_slice(chunk);
+                        }
+                        ParserState::BeginDataSectionEntryBody(_)
+                        | ParserState::EndDataSectionEntryBody => {}
+                        ParserState::EndDataSectionEntry => break,
+                        _ => unreachable!(),
+                    }
+                }
+
+                info.write().unwrap().passive_data.insert(data_index, data);
+            }
             ParserState::BeginGlobalSectionEntry(ty) => {
                 let init = loop {
                     let state = parser.read();
                     match *state {
                         ParserState::Error(ref err) => return Err(err.into()),
+                        ParserState::InitExpressionOperator(Operator::RefNull) => {
+                            // `ref.null` takes the type of the global it initializes.
+                            let ty = wp_type_to_type(ty.content_type)?;
+                            let null = Value::null_ref(ty).ok_or_else(|| {
+                                LoadError::Parse(format!("ref.null is not a value of type {}", ty))
+                            })?;
+                            break Initializer::Const(null);
+                        }
                         ParserState::InitExpressionOperator(ref op) => {
                             break eval_init_expr(op)?;
                         }
@@ -431,7 +481,38 @@ pub fn read_module<
 
                 info.write().unwrap().globals.push(global_init);
             }
+            ParserState::NameSectionEntry(ref entry) => match *entry {
+                NameEntry::Module(name) => module_name = Some(name.to_string()),
+                NameEntry::Function(ref names) => {
+                    for naming in names.iter() {
+                        func_names.insert(
+                            FuncIndex::new(naming.index as usize),
+                            naming.name.to_string(),
+                        );
+                    }
+                }
+                NameEntry::Local(ref funcs) => {
+                    for func in funcs.iter() {
+                        let locals: HashMap<u32, String> = func
+                            .locals
+                            .iter()
+                            .map(|naming| (naming.index, naming.name.to_string()))
+                            .collect();
+                        local_names.insert(FuncIndex::new(func.index as usize), locals);
+                    }
+                }
+            },
             ParserState::EndWasm => {
+                {
+                    let mut info_write = info.write().unwrap();
+                    info_write.module_name = module_name.take();
+                    info_write.func_names = std::mem::replace(&mut func_names, HashMap::new());
+                    info_write.local_names = std::mem::replace(&mut local_names, HashMap::new());
+                    info_write.internal_initializers = middlewares.internal_initializers();
+                    info_write.internals_restored_on_trap =
+                        middlewares.internals_restored_on_trap();
+                    info_write.counter_count = middlewares.counter_count();
+                }
                 // TODO Consolidate with BeginFunction body if possible
                 if !mcg_info_fed {
                     info.write().unwrap().namespace_table =
@@ -460,6 +541,8 @@ pub fn wp_type_to_type(ty: WpType) -> Result<Type, LoadError> {
         WpType::F32 => Ok(Type::F32),
         WpType::F64 => Ok(Type::F64),
         WpType::V128 => Ok(Type::V128),
+        WpType::AnyFunc => Ok(Type::FuncRef),
+        WpType::AnyRef => Ok(Type::ExternRef),
         _ => {
             return Err(LoadError::Parse(
                 "broken invariant, invalid type".to_string(),
@@ -476,6 +559,20 @@ pub fn type_to_wp_type(ty: Type) -> WpType {
         Type::F32 => WpType::F32,
         Type::F64 => WpType::F64,
         Type::V128 => WpType::V128,
+        Type::FuncRef => WpType::AnyFunc,
+        Type::ExternRef => WpType::AnyRef,
+    }
+}
+
+/// Convert the given `WpType` of a table to an `ElementType`.
+pub fn wp_type_to_element_type(ty: WpType) -> Result<ElementType, LoadError> {
+    match ty {
+        WpType::AnyFunc => Ok(ElementType::Anyfunc),
+        WpType::AnyRef => Ok(ElementType::ExternRef),
+        _ => Err(LoadError::Parse(format!(
+            "type \"{:?}\" is not supported in tables",
+            ty
+        ))),
     }
 }
 
@@ -514,6 +611,9 @@ fn eval_init_expr(op: &Operator) -> Result<Initializer, LoadError> {
         Operator::V128Const { value } => {
             Initializer::Const(Value::V128(u128::from_le_bytes(*value.bytes())))
         }
+        Operator::RefFunc { function_index } => {
+            Initializer::RefFunc(FuncIndex::new(function_index as usize))
+        }
         _ => {
             return Err(LoadError::Parse(
                 "init expr evaluation failed: unsupported opcode".to_string(),
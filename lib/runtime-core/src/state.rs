@@ -3,6 +3,9 @@
 //! generated code from one tier to another, or serializing state of a running instace.
 
 use crate::backend::RunnableModule;
+use crate::module::ModuleInfo;
+use crate::structures::TypedIndex;
+use crate::types::LocalFuncIndex;
 use std::collections::BTreeMap;
 use std::ops::Bound::{Included, Unbounded};
 use std::sync::Arc;
@@ -35,6 +38,8 @@ pub struct MachineState {
     pub wasm_stack_private_depth: usize,
     /// Wasm instruction offset.
     pub wasm_inst_offset: usize,
+    /// Offset of the wasm instruction in the module's binary, if known.
+    pub wasm_source_loc: Option<u32>,
 }
 
 /// A diff of two `MachineState`s.
@@ -61,6 +66,8 @@ pub struct MachineStateDiff {
     pub wasm_stack_private_depth: usize, // absolute value; not a diff.
     /// Wasm instruction offset.
     pub wasm_inst_offset: usize, // absolute value; not a diff.
+    /// Offset of the wasm instruction in the module's binary, if known.
+    pub wasm_source_loc: Option<u32>, // absolute value; not a diff.
 }
 
 /// A kind of machine value.
@@ -149,10 +156,19 @@ pub struct WasmFunctionStateDump {
     pub local_function_id: usize,
     /// Wasm instruction offset.
     pub wasm_inst_offset: usize,
+    /// Offset of the wasm instruction in the module's binary, if known.
+    #[serde(default)]
+    pub wasm_source_loc: Option<u32>,
+    /// Name of the function from the `name` custom section, if known.
+    #[serde(default)]
+    pub name: Option<String>,
     /// Stack.
     pub stack: Vec<Option<u64>>,
     /// Locals.
     pub locals: Vec<Option<u64>>,
+    /// Names of the locals from the `name` custom section, if known.
+    #[serde(default)]
+    pub local_names: Vec<Option<String>>,
 }
 
 /// An image of the execution state.
@@ -336,6 +352,7 @@ impl MachineState {
             wasm_stack_private_depth: self.wasm_stack_private_depth,
 
             wasm_inst_offset: self.wasm_inst_offset,
+            wasm_source_loc: self.wasm_source_loc,
         }
     }
 }
@@ -379,11 +396,24 @@ impl MachineStateDiff {
         }
         state.wasm_stack_private_depth = self.wasm_stack_private_depth;
         state.wasm_inst_offset = self.wasm_inst_offset;
+        state.wasm_source_loc = self.wasm_source_loc;
         state
     }
 }
 
 impl ExecutionStateImage {
+    /// Fills in the function and local names of the frames from the `name`
+    /// custom section of the module described by `info`.
+    pub fn attach_names(&mut self, info: &ModuleInfo) {
+        for frame in &mut self.frames {
+            let func_index = LocalFuncIndex::new(frame.local_function_id).convert_up(info);
+            frame.name = info.func_name(func_index).map(String::from);
+            frame.local_names = (0..frame.locals.len())
+                .map(|i| info.local_name(func_index, i as u32).map(String::from))
+                .collect();
+        }
+    }
+
     /// Prints a backtrace if the `WASMER_BACKTRACE` environment variable is 1.
     pub fn print_backtrace_if_needed(&self) {
         use std::env;
@@ -416,6 +446,25 @@ impl ExecutionStateImage {
             ret
         }
 
+        fn format_locals(x: &[Option<u64>], names: &[Option<String>]) -> String {
+            if x.is_empty() {
+                "(empty)".into()
+            } else {
+                join_strings(
+                    x.iter().enumerate().map(|(i, x)| {
+                        let value = x
+                            .map(|x| format!("{}", x))
+                            .unwrap_or_else(|| "?".to_string());
+                        match names.get(i) {
+                            Some(Some(name)) => format!("[{}] ${} = {}", i, name, value),
+                            _ => format!("[{}] = {}", i, value),
+                        }
+                    }),
+                    ", ",
+                )
+            }
+        }
+
         fn format_optional_u64_sequence(x: &[Option<u64>]) -> String {
             if x.is_empty() {
                 "(empty)".into()
@@ -444,12 +493,15 @@ impl ExecutionStateImage {
             ret += "\n";
             for (i, f) in self.frames.iter().enumerate() {
                 ret += &format!("* Frame {} @ Local function {}", i, f.local_function_id);
+                if let Some(ref name) = f.name {
+                    ret += &format!(" <{}>", name);
+                }
                 ret += "\n";
                 ret += &format!("  {} {}\n", "Offset:", format!("{}", f.wasm_inst_offset),);
                 ret += &format!(
                     "  {} {}\n",
                     "Locals:",
-                    format_optional_u64_sequence(&f.locals)
+                    format_locals(&f.locals, &f.local_names)
                 );
                 ret += &format!(
                     "  {} {}\n\n",
@@ -646,6 +698,7 @@ pub mod x64 {
             wasm_stack: vec![],
             wasm_stack_private_depth: 0,
             wasm_inst_offset: ::std::usize::MAX,
+            wasm_source_loc: None,
         }
     }
 
@@ -1247,8 +1300,11 @@ pub mod x64 {
             let wfs = WasmFunctionStateDump {
                 local_function_id: fsm.local_function_id,
                 wasm_inst_offset: state.wasm_inst_offset,
+                wasm_source_loc: state.wasm_source_loc,
+                name: None,
                 stack: wasm_stack,
                 locals: wasm_locals,
+                local_names: vec![],
             };
             results.push(wfs);
         }
@@ -1,20 +1,29 @@
 use crate::{
     error::CreationError,
+    export::{Context, Export},
     instance::DynFunc,
     sig_registry::SigRegistry,
     structures::TypedIndex,
-    types::{FuncSig, TableDescriptor},
+    typed_func::{register_table_func_env, DynamicFunc, Func, Host, WasmTypeList},
+    types::{FuncRef, FuncSig, TableDescriptor},
     vm,
 };
 
-use std::{ptr, sync::Arc};
+use std::{
+    collections::HashMap,
+    ptr::{self, NonNull},
+    sync::{Arc, Mutex},
+};
 
 enum AnyfuncInner<'a> {
     Host {
         ptr: *const vm::Func,
+        ctx: *mut vm::Ctx,
+        func_env: Option<NonNull<vm::FuncEnv>>,
         signature: Arc<FuncSig>,
     },
     Managed(DynFunc<'a>),
+    Dynamic(DynamicFunc<'static>),
 }
 
 /// Anyfunc data type.
@@ -24,6 +33,8 @@ pub struct Anyfunc<'a> {
 
 impl<'a> Anyfunc<'a> {
     /// Create a new `Anyfunc`.
+    ///
+    /// The function is called with the `vm::Ctx` of its caller.
     pub unsafe fn new<Sig>(func: *const vm::Func, signature: Sig) -> Self
     where
         Sig: Into<Arc<FuncSig>>,
@@ -31,10 +42,41 @@ impl<'a> Anyfunc<'a> {
         Self {
             inner: AnyfuncInner::Host {
                 ptr: func as _,
+                ctx: ptr::null_mut(),
+                func_env: None,
                 signature: signature.into(),
             },
         }
     }
+
+    /// Create an `Anyfunc` from an exported function, of an instance or of
+    /// the host, or `None` if `export` is not a function.
+    ///
+    /// The function must outlive the tables it is written to.
+    pub unsafe fn from_export(export: &Export) -> Option<Self> {
+        match export {
+            Export::Function {
+                func,
+                ctx,
+                signature,
+            } => {
+                let (ctx, func_env) = match *ctx {
+                    Context::External(ctx) => (ctx, None),
+                    Context::ExternalWithEnv(ctx, func_env) => (ctx, func_env),
+                    Context::Internal => (ptr::null_mut(), None),
+                };
+                Some(Self {
+                    inner: AnyfuncInner::Host {
+                        ptr: func.inner(),
+                        ctx,
+                        func_env,
+                        signature: Arc::clone(signature),
+                    },
+                })
+            }
+            _ => None,
+        }
+    }
 }
 
 impl<'a> From<DynFunc<'a>> for Anyfunc<'a> {
@@ -45,9 +87,101 @@ impl<'a> From<DynFunc<'a>> for Anyfunc<'a> {
     }
 }
 
+/// A host function, which the tables it is written to keep alive.
+impl<'a> From<DynamicFunc<'static>> for Anyfunc<'a> {
+    fn from(function: DynamicFunc<'static>) -> Self {
+        Anyfunc {
+            inner: AnyfuncInner::Dynamic(function),
+        }
+    }
+}
+
+/// A host function, which the tables it is written to keep alive.
+impl<'a, Args, Rets> From<Func<'static, Args, Rets, Host>> for Anyfunc<'a>
+where
+    Args: WasmTypeList,
+    Rets: WasmTypeList,
+{
+    fn from(function: Func<'static, Args, Rets, Host>) -> Self {
+        DynamicFunc::from(function).into()
+    }
+}
+
+/// A boxed copy of an anyfunc handed out as a `funcref`, so that its address is stable.
+struct BoxedAnyfunc(Box<vm::Anyfunc>);
+
+// The anyfunc is only copied out, never called through, by the registry.
+unsafe impl Send for BoxedAnyfunc {}
+
+/// The anyfuncs handed out as `funcref`s by all tables and instances.
+#[derive(Default)]
+struct FuncRefs {
+    /// The anyfuncs by function, context and signature.
+    by_key: HashMap<(usize, usize, u32), BoxedAnyfunc>,
+    /// The keys of the anyfuncs by address, to check the `FuncRef`s given back.
+    by_addr: HashMap<usize, (usize, usize, u32)>,
+}
+
+lazy_static! {
+    static ref FUNC_REFS: Mutex<FuncRefs> = Mutex::new(FuncRefs::default());
+}
+
+/// The `funcref` of `anyfunc`, which must not be null.
+///
+/// The same reference is returned for equal anyfuncs, until it is pruned with
+/// `prune_func_refs`.
+pub(crate) fn intern_func_ref(anyfunc: vm::Anyfunc) -> FuncRef {
+    let key = (
+        anyfunc.func as usize,
+        anyfunc.ctx as usize,
+        anyfunc.sig_id.0,
+    );
+    let mut refs = FUNC_REFS.lock().unwrap();
+    let addr = {
+        let boxed = refs
+            .by_key
+            .entry(key)
+            .or_insert_with(|| BoxedAnyfunc(Box::new(anyfunc)));
+        &*boxed.0 as *const vm::Anyfunc
+    };
+    refs.by_addr.insert(addr as usize, key);
+    FuncRef::from_anyfunc(addr)
+}
+
+/// The anyfunc `value` refers to, or `None` if it is not a reference handed out
+/// by `intern_func_ref` which is still alive.
+pub(crate) fn resolve_func_ref(value: FuncRef) -> Option<vm::Anyfunc> {
+    if value.is_null() {
+        return Some(vm::Anyfunc::null());
+    }
+    let refs = FUNC_REFS.lock().unwrap();
+    let key = refs.by_addr.get(&value.addr())?;
+    Some(*refs.by_key[key].0)
+}
+
+/// Forget the `funcref`s to the anyfuncs for which `is_dead` returns `true`, as
+/// their function or context is about to be freed.
+pub(crate) fn prune_func_refs<F>(is_dead: F)
+where
+    F: Fn(&vm::Anyfunc) -> bool,
+{
+    let mut refs = FUNC_REFS.lock().unwrap();
+    let FuncRefs { by_key, by_addr } = &mut *refs;
+    by_key.retain(|_, boxed| {
+        if is_dead(&boxed.0) {
+            by_addr.remove(&(&*boxed.0 as *const vm::Anyfunc as usize));
+            false
+        } else {
+            true
+        }
+    });
+}
+
 pub struct AnyfuncTable {
     pub(crate) backing: Vec<vm::Anyfunc>,
     max: Option<u32>,
+    /// The host functions written to this table, by function pointer.
+    host_funcs: HashMap<usize, DynamicFunc<'static>>,
 }
 
 impl AnyfuncTable {
@@ -60,6 +194,7 @@ impl AnyfuncTable {
         let mut storage = Box::new(AnyfuncTable {
             backing: vec![vm::Anyfunc::null(); initial_table_backing_len],
             max: desc.maximum,
+            host_funcs: HashMap::new(),
         });
 
         let storage_ptr: *mut AnyfuncTable = &mut *storage;
@@ -79,7 +214,8 @@ impl AnyfuncTable {
         &mut self.backing
     }
 
-    pub fn grow(&mut self, delta: u32, local: &mut vm::LocalTable) -> Option<u32> {
+    pub fn grow(&mut self, delta: u32, init: FuncRef, local: &mut vm::LocalTable) -> Option<u32> {
+        let init = resolve_func_ref(init)?;
         let starting_len = self.backing.len() as u32;
 
         let new_len = starting_len.checked_add(delta)?;
@@ -90,7 +226,7 @@ impl AnyfuncTable {
             }
         }
 
-        self.backing.resize(new_len as usize, vm::Anyfunc::null());
+        self.backing.resize(new_len as usize, init);
 
         local.base = self.backing.as_mut_ptr() as *mut u8;
         local.count = self.backing.len();
@@ -101,13 +237,21 @@ impl AnyfuncTable {
     pub fn set(&mut self, index: u32, element: Anyfunc) -> Result<(), ()> {
         if let Some(slot) = self.backing.get_mut(index as usize) {
             let anyfunc = match element.inner {
-                AnyfuncInner::Host { ptr, signature } => {
+                AnyfuncInner::Host {
+                    ptr,
+                    ctx,
+                    func_env,
+                    signature,
+                } => {
+                    if let (Some(func), Some(func_env)) = (NonNull::new(ptr as *mut _), func_env) {
+                        register_table_func_env(func, func_env);
+                    }
                     let sig_index = SigRegistry.lookup_sig_index(signature);
                     let sig_id = vm::SigId(sig_index.index() as u32);
 
                     vm::Anyfunc {
                         func: ptr,
-                        ctx: ptr::null_mut(),
+                        ctx,
                         sig_id,
                     }
                 }
@@ -116,11 +260,23 @@ impl AnyfuncTable {
                     let sig_id = vm::SigId(sig_index.index() as u32);
 
                     vm::Anyfunc {
-                        func: func.raw(),
-                        ctx: func.instance_inner.vmctx,
                         sig_id,
+                        ..func.anyfunc()
                     }
                 }
+                AnyfuncInner::Dynamic(func) => {
+                    if let Some(func_env) = func.func_env {
+                        register_table_func_env(func.func, func_env);
+                    }
+                    let sig_index = SigRegistry.lookup_sig_index(Arc::clone(&func.signature));
+                    let anyfunc = vm::Anyfunc {
+                        func: func.func.as_ptr(),
+                        ctx: func.vmctx,
+                        sig_id: vm::SigId(sig_index.index() as u32),
+                    };
+                    self.host_funcs.insert(func.func.as_ptr() as usize, func);
+                    anyfunc
+                }
             };
 
             *slot = anyfunc;
@@ -130,4 +286,45 @@ impl AnyfuncTable {
             Err(())
         }
     }
+
+    pub fn get_ref(&self, index: u32) -> Option<FuncRef> {
+        let anyfunc = *self.backing.get(index as usize)?;
+        if anyfunc.func.is_null() {
+            return Some(FuncRef::null());
+        }
+        Some(intern_func_ref(anyfunc))
+    }
+
+    /// Set the element at `index`, failing if it is out of bounds or `value` is
+    /// not alive.
+    pub fn set_ref(&mut self, index: u32, value: FuncRef) -> Result<(), ()> {
+        let anyfunc = resolve_func_ref(value).ok_or(())?;
+        let slot = self.backing.get_mut(index as usize).ok_or(())?;
+        *slot = anyfunc;
+        Ok(())
+    }
+
+    /// Set the `len` elements starting at `start`, failing if the range is out of
+    /// bounds or `value` is not alive.
+    pub fn fill(&mut self, start: u32, value: FuncRef, len: u32) -> Result<(), ()> {
+        let anyfunc = resolve_func_ref(value).ok_or(())?;
+        let end = start.checked_add(len).ok_or(())?;
+        let slots = self
+            .backing
+            .get_mut(start as usize..end as usize)
+            .ok_or(())?;
+        for slot in slots {
+            *slot = anyfunc;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AnyfuncTable {
+    fn drop(&mut self) {
+        let host_funcs = &self.host_funcs;
+        if !host_funcs.is_empty() {
+            prune_func_refs(|anyfunc| host_funcs.contains_key(&(anyfunc.func as usize)));
+        }
+    }
 }
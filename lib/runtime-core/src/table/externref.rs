@@ -0,0 +1,75 @@
+use crate::{
+    error::CreationError,
+    types::{ExternRef, TableDescriptor},
+    vm,
+};
+
+pub struct ExternRefTable {
+    pub(crate) backing: Vec<ExternRef>,
+    max: Option<u32>,
+}
+
+impl ExternRefTable {
+    pub fn new(
+        desc: TableDescriptor,
+        local: &mut vm::LocalTable,
+    ) -> Result<Box<Self>, CreationError> {
+        let mut storage = Box::new(ExternRefTable {
+            backing: vec![ExternRef::null(); desc.minimum as usize],
+            max: desc.maximum,
+        });
+
+        let storage_ptr: *mut ExternRefTable = &mut *storage;
+
+        local.base = storage.backing.as_mut_ptr() as *mut u8;
+        local.count = storage.backing.len();
+        local.table = storage_ptr as *mut ();
+
+        Ok(storage)
+    }
+
+    pub fn current_size(&self) -> u32 {
+        self.backing.len() as u32
+    }
+
+    pub fn grow(&mut self, delta: u32, init: ExternRef, local: &mut vm::LocalTable) -> Option<u32> {
+        let starting_len = self.backing.len() as u32;
+
+        let new_len = starting_len.checked_add(delta)?;
+
+        if let Some(max) = self.max {
+            if new_len > max {
+                return None;
+            }
+        }
+
+        self.backing.resize(new_len as usize, init);
+
+        local.base = self.backing.as_mut_ptr() as *mut u8;
+        local.count = self.backing.len();
+
+        Some(starting_len)
+    }
+
+    pub fn get(&self, index: u32) -> Option<ExternRef> {
+        self.backing.get(index as usize).cloned()
+    }
+
+    pub fn set(&mut self, index: u32, value: ExternRef) -> Result<(), ()> {
+        let slot = self.backing.get_mut(index as usize).ok_or(())?;
+        *slot = value;
+        Ok(())
+    }
+
+    pub fn fill(&mut self, start: u32, value: ExternRef, len: u32) -> Result<(), ()> {
+        let end = start.checked_add(len).ok_or(())?;
+        let slots = self
+            .backing
+            .get_mut(start as usize..end as usize)
+            .ok_or(())?;
+        for slot in slots {
+            *slot = value;
+        }
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@ use crate::{
     error::CreationError,
     export::Export,
     import::IsExport,
-    types::{ElementType, TableDescriptor},
+    types::{ElementType, ExternRef, TableDescriptor, Value},
     vm,
 };
 use std::{
@@ -13,15 +13,19 @@ use std::{
 };
 
 mod anyfunc;
+mod externref;
 
 pub use self::anyfunc::Anyfunc;
-pub(crate) use self::anyfunc::AnyfuncTable;
+pub(crate) use self::anyfunc::{intern_func_ref, prune_func_refs, resolve_func_ref, AnyfuncTable};
+pub(crate) use self::externref::ExternRefTable;
 use crate::error::GrowError;
 
 /// Kind of table element.
 pub enum Element<'a> {
     /// Anyfunc.
     Anyfunc(Anyfunc<'a>),
+    /// Host reference.
+    ExternRef(ExternRef),
 }
 
 /// Kind of table storage.
@@ -29,6 +33,8 @@ pub enum Element<'a> {
 pub enum TableStorage {
     /// This is intended to be a caller-checked Anyfunc.
     Anyfunc(Box<AnyfuncTable>),
+    /// Opaque host references.
+    ExternRef(Box<ExternRefTable>),
 }
 
 /// Container with a descriptor and a reference to a table storage.
@@ -76,6 +82,9 @@ impl Table {
 
         let storage = match desc.element {
             ElementType::Anyfunc => TableStorage::Anyfunc(AnyfuncTable::new(desc, &mut local)?),
+            ElementType::ExternRef => {
+                TableStorage::ExternRef(ExternRefTable::new(desc, &mut local)?)
+            }
         };
 
         Ok(Self {
@@ -89,26 +98,106 @@ impl Table {
         self.desc
     }
 
+    /// Get the element at index as a reference value.
+    ///
+    /// Returns `None` if the index is out of bounds.
+    pub fn get(&self, index: u32) -> Option<Value> {
+        let storage = self.storage.lock().unwrap();
+        match &*storage {
+            (TableStorage::Anyfunc(ref anyfunc_table), _) => {
+                anyfunc_table.get_ref(index).map(Value::FuncRef)
+            }
+            (TableStorage::ExternRef(ref externref_table), _) => {
+                externref_table.get(index).map(Value::ExternRef)
+            }
+        }
+    }
+
     /// Set the element at index.
     pub fn set(&self, index: u32, element: Element) -> Result<(), ()> {
         let mut storage = self.storage.lock().unwrap();
-        match &mut *storage {
-            (TableStorage::Anyfunc(ref mut anyfunc_table), _) => {
-                match element {
-                    Element::Anyfunc(anyfunc) => anyfunc_table.set(index, anyfunc),
-                    // _ => panic!("wrong element type for anyfunc table"),
-                }
+        match (&mut *storage, element) {
+            ((TableStorage::Anyfunc(ref mut anyfunc_table), _), Element::Anyfunc(anyfunc)) => {
+                anyfunc_table.set(index, anyfunc)
+            }
+            ((TableStorage::ExternRef(ref mut externref_table), _), Element::ExternRef(value)) => {
+                externref_table.set(index, value)
+            }
+            _ => Err(()),
+        }
+    }
+
+    /// Set the element at index to a reference value of the element type of this table.
+    pub fn set_value(&self, index: u32, value: Value) -> Result<(), ()> {
+        let mut storage = self.storage.lock().unwrap();
+        match (&mut *storage, value) {
+            ((TableStorage::Anyfunc(ref mut anyfunc_table), _), Value::FuncRef(value)) => {
+                anyfunc_table.set_ref(index, value)
+            }
+            ((TableStorage::ExternRef(ref mut externref_table), _), Value::ExternRef(value)) => {
+                externref_table.set(index, value)
             }
+            _ => Err(()),
         }
     }
 
-    pub(crate) fn anyfunc_direct_access_mut<F, R>(&self, f: F) -> R
+    /// Set the `len` elements starting at `start` to a reference value of the
+    /// element type of this table.
+    pub fn fill(&self, start: u32, value: Value, len: u32) -> Result<(), ()> {
+        let mut storage = self.storage.lock().unwrap();
+        match (&mut *storage, value) {
+            ((TableStorage::Anyfunc(ref mut anyfunc_table), _), Value::FuncRef(value)) => {
+                anyfunc_table.fill(start, value, len)
+            }
+            ((TableStorage::ExternRef(ref mut externref_table), _), Value::ExternRef(value)) => {
+                externref_table.fill(start, value, len)
+            }
+            _ => Err(()),
+        }
+    }
+
+    /// Copy the `len` elements starting at `src` in `src_table` to the elements
+    /// starting at `dst` in this table, as if through an intermediate buffer.
+    ///
+    /// Nothing is copied if either range is out of bounds.
+    pub fn copy(&self, dst: u32, src_table: &Table, src: u32, len: u32) -> Result<(), ()> {
+        let in_bounds = |start: u32, table: &Table| {
+            start
+                .checked_add(len)
+                .map_or(false, |end| end <= table.size())
+        };
+        if !in_bounds(dst, self) || !in_bounds(src, src_table) {
+            return Err(());
+        }
+
+        // `src_table` may be this table, so the elements are read before any is written.
+        let values: Vec<Value> = (src..src + len)
+            .map(|index| src_table.get(index).unwrap())
+            .collect();
+        for (index, value) in (dst..).zip(values) {
+            self.set_value(index, value)?;
+        }
+        Ok(())
+    }
+
+    /// Write the elements of an element segment, `None` being a null reference.
+    pub(crate) fn init_elements<I>(&self, start: usize, elements: I)
     where
-        F: FnOnce(&mut [vm::Anyfunc]) -> R,
+        I: Iterator<Item = Option<vm::Anyfunc>>,
     {
         let mut storage = self.storage.lock().unwrap();
         match &mut *storage {
-            (TableStorage::Anyfunc(ref mut anyfunc_table), _) => f(anyfunc_table.internal_buffer()),
+            (TableStorage::Anyfunc(ref mut anyfunc_table), _) => {
+                for (slot, element) in anyfunc_table.backing[start..].iter_mut().zip(elements) {
+                    *slot = element.unwrap_or_else(vm::Anyfunc::null);
+                }
+            }
+            (TableStorage::ExternRef(ref mut externref_table), _) => {
+                // Element segments of `externref` tables can only hold null references.
+                for (slot, _) in externref_table.backing[start..].iter_mut().zip(elements) {
+                    *slot = ExternRef::null();
+                }
+            }
         }
     }
 
@@ -117,21 +206,35 @@ impl Table {
         let storage = self.storage.lock().unwrap();
         match &*storage {
             (TableStorage::Anyfunc(ref anyfunc_table), _) => anyfunc_table.current_size(),
+            (TableStorage::ExternRef(ref externref_table), _) => externref_table.current_size(),
         }
     }
 
-    /// Grow this table by `delta`.
+    /// Grow this table by `delta`, filling the new elements with null references.
     pub fn grow(&self, delta: u32) -> Result<u32, GrowError> {
+        let null = Value::null_ref(self.desc.element.value_type()).unwrap();
+        self.grow_with(delta, null)
+    }
+
+    /// Grow this table by `delta`, filling the new elements with `init`.
+    pub fn grow_with(&self, delta: u32, init: Value) -> Result<u32, GrowError> {
         if delta == 0 {
             return Ok(self.size());
         }
 
         let mut storage = self.storage.lock().unwrap();
-        match &mut *storage {
-            (TableStorage::Anyfunc(ref mut anyfunc_table), ref mut local) => anyfunc_table
-                .grow(delta, local)
-                .ok_or(GrowError::TableGrowError),
+        match (&mut *storage, init) {
+            (
+                (TableStorage::Anyfunc(ref mut anyfunc_table), ref mut local),
+                Value::FuncRef(init),
+            ) => anyfunc_table.grow(delta, init, local),
+            (
+                (TableStorage::ExternRef(ref mut externref_table), ref mut local),
+                Value::ExternRef(init),
+            ) => externref_table.grow(delta, init, local),
+            _ => None,
         }
+        .ok_or(GrowError::TableGrowError)
     }
 
     /// Get a mutable pointer to underlying table storage.
@@ -168,7 +271,14 @@ impl fmt::Debug for Table {
 #[cfg(test)]
 mod table_tests {
 
-    use super::{ElementType, Table, TableDescriptor};
+    use super::{resolve_func_ref, Anyfunc, Element, ElementType, Table, TableDescriptor};
+    use crate::{
+        export::Export,
+        sig_registry::SigRegistry,
+        structures::TypedIndex,
+        typed_func::Func,
+        types::{ExternRef, FuncRef, FuncSig, SigIndex, Type, Value},
+    };
 
     #[test]
     fn test_initial_table_size() {
@@ -180,4 +290,97 @@ mod table_tests {
         .unwrap();
         assert_eq!(table.size(), 10);
     }
+
+    #[test]
+    fn test_extern_ref_table() {
+        let table = Table::new(TableDescriptor {
+            element: ElementType::ExternRef,
+            minimum: 2,
+            maximum: Some(4),
+        })
+        .unwrap();
+        let handle = Value::ExternRef(ExternRef::new(42));
+
+        assert_eq!(table.get(0), Some(Value::ExternRef(ExternRef::null())));
+        table.set_value(1, handle.clone()).unwrap();
+        assert_eq!(table.get(1), Some(handle.clone()));
+        assert_eq!(table.get(2), None);
+
+        assert_eq!(table.grow_with(2, handle.clone()).unwrap(), 2);
+        assert_eq!(table.get(3), Some(handle.clone()));
+        assert!(table.grow_with(1, handle).is_err());
+
+        assert!(table.set_value(0, Value::I32(0)).is_err());
+    }
+
+    #[test]
+    fn test_overlapping_copy() {
+        let table = Table::new(TableDescriptor {
+            element: ElementType::ExternRef,
+            minimum: 4,
+            maximum: None,
+        })
+        .unwrap();
+        for index in 0..3 {
+            let value = Value::ExternRef(ExternRef::new(index as u64 + 1));
+            table.set_value(index, value).unwrap();
+        }
+
+        table.copy(1, &table, 0, 3).unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|index| match table.get(index) {
+                Some(Value::ExternRef(value)) => value.handle(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(handles, vec![1, 1, 2, 3]);
+
+        assert!(table.copy(2, &table, 0, 3).is_err());
+        assert!(table.copy(4, &table, 0, 0).is_ok());
+        assert!(table.copy(5, &table, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_host_func_element() {
+        let table = Table::new(TableDescriptor {
+            element: ElementType::Anyfunc,
+            minimum: 2,
+            maximum: None,
+        })
+        .unwrap();
+        let func: Func<i32, i32, _> = Func::new(|x: i32| x + 1);
+        let func_ptr = func.get_vm_func().as_ptr() as *const _;
+
+        table.set(1, Element::Anyfunc(func.into())).unwrap();
+        let anyfunc = match table.get(1) {
+            Some(Value::FuncRef(value)) => resolve_func_ref(value).unwrap(),
+            _ => unreachable!(),
+        };
+        assert_eq!(anyfunc.func, func_ptr);
+        // The function takes the context of its caller.
+        assert!(anyfunc.ctx.is_null());
+        assert_eq!(
+            *SigRegistry.lookup_signature(SigIndex::new(anyfunc.sig_id.0 as usize)),
+            FuncSig::new(vec![Type::I32], vec![Type::I32])
+        );
+
+        let export = Export::Table(table.clone());
+        assert!(unsafe { Anyfunc::from_export(&export) }.is_none());
+        assert!(table
+            .set(2, Element::Anyfunc(Func::new(|| {}).into()))
+            .is_err());
+    }
+
+    #[test]
+    fn test_unknown_func_ref() {
+        let table = Table::new(TableDescriptor {
+            element: ElementType::Anyfunc,
+            minimum: 1,
+            maximum: None,
+        })
+        .unwrap();
+        let forged = unsafe { FuncRef::from_raw(0xdead_beef) };
+        assert!(table.set_value(0, Value::FuncRef(forged)).is_err());
+        assert!(table.set_value(0, Value::FuncRef(FuncRef::null())).is_ok());
+    }
 }
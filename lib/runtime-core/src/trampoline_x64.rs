@@ -256,6 +256,77 @@ impl TrampolineBufferBuilder {
             stack_offset += 8;
         }
 
+        self.emit_collect_params(num_params, stack_offset);
+        self.emit_call_target(context, target as usize);
+        self.code.extend_from_slice(&[
+            0x48, 0x81, 0xc4, // add ?, %rsp
+        ]);
+        self.code.extend_from_slice(value_to_bytes(&stack_offset));
+        self.code.extend_from_slice(&[
+            0xc3, //retq
+        ]);
+        idx
+    }
+
+    /// Adds a callinfo trampoline that can return several values.
+    ///
+    /// This works like `add_callinfo_trampoline`, except that `target` receives a third argument
+    /// pointing to a return area of 6 `u64`s. When `target` returns, the trampoline loads `%rax`
+    /// from the first slot, `%rdx` from the second one, `%xmm0` from the third and fourth ones
+    /// and `%xmm1` from the last two ones, which covers all the ways the System V ABI returns
+    /// values in registers.
+    pub fn add_callinfo_trampoline_with_returns(
+        &mut self,
+        target: unsafe extern "C" fn(*const CallContext, *const u64, *mut u64),
+        context: *const CallContext,
+        num_params: u32,
+    ) -> usize {
+        let idx = self.offsets.len();
+        self.offsets.push(self.code.len());
+
+        let params_size: u32 = num_params.checked_mul(8).unwrap();
+        let rets_offset = (params_size + 15) & !15;
+        // The return area is 48 bytes, and `%rsp` must be 16-byte aligned at the call.
+        let stack_offset = rets_offset + 48 + 8;
+
+        self.emit_collect_params(num_params, stack_offset);
+        self.code.extend_from_slice(&[
+            0x48, 0x8d, 0x94, 0x24, // lea ?(%rsp), %rdx
+        ]);
+        self.code.extend_from_slice(value_to_bytes(&rets_offset));
+        self.emit_call_target(context, target as usize);
+        self.code.extend_from_slice(&[
+            0x48, 0x8b, 0x84, 0x24, // mov ?(%rsp), %rax
+        ]);
+        self.code.extend_from_slice(value_to_bytes(&rets_offset));
+        self.code.extend_from_slice(&[
+            0x48, 0x8b, 0x94, 0x24, // mov ?(%rsp), %rdx
+        ]);
+        self.code
+            .extend_from_slice(value_to_bytes(&(rets_offset + 8)));
+        self.code.extend_from_slice(&[
+            0xf3, 0x0f, 0x6f, 0x84, 0x24, // movdqu ?(%rsp), %xmm0
+        ]);
+        self.code
+            .extend_from_slice(value_to_bytes(&(rets_offset + 16)));
+        self.code.extend_from_slice(&[
+            0xf3, 0x0f, 0x6f, 0x8c, 0x24, // movdqu ?(%rsp), %xmm1
+        ]);
+        self.code
+            .extend_from_slice(value_to_bytes(&(rets_offset + 32)));
+        self.code.extend_from_slice(&[
+            0x48, 0x81, 0xc4, // add ?, %rsp
+        ]);
+        self.code.extend_from_slice(value_to_bytes(&stack_offset));
+        self.code.extend_from_slice(&[
+            0xc3, //retq
+        ]);
+        idx
+    }
+
+    /// Reserves `stack_offset` bytes on the stack and copies the first `num_params` parameters
+    /// at the bottom of it.
+    fn emit_collect_params(&mut self, num_params: u32, stack_offset: u32) {
         self.code.extend_from_slice(&[0x48, 0x81, 0xec]); // sub ?, %rsp
         self.code.extend_from_slice(value_to_bytes(&stack_offset));
         for i in 0..num_params {
@@ -287,6 +358,11 @@ impl TrampolineBufferBuilder {
                 }
             }
         }
+    }
+
+    /// Calls `target` with `context` as the first argument and the collected parameters as the
+    /// second one.
+    fn emit_call_target(&mut self, context: *const CallContext, target: usize) {
         self.code.extend_from_slice(&[
             0x48, 0xbf, // movabsq ?, %rdi
         ]);
@@ -302,14 +378,6 @@ impl TrampolineBufferBuilder {
         self.code.extend_from_slice(&[
             0xff, 0xd0, // callq *%rax
         ]);
-        self.code.extend_from_slice(&[
-            0x48, 0x81, 0xc4, // add ?, %rsp
-        ]);
-        self.code.extend_from_slice(value_to_bytes(&stack_offset));
-        self.code.extend_from_slice(&[
-            0xc3, //retq
-        ]);
-        idx
     }
 
     /// Inserts this trampoline to the global trampoline buffer.
@@ -407,6 +475,40 @@ mod tests {
         assert_eq!(ret, 136);
     }
 
+    #[test]
+    fn test_callinfo_trampoline_with_returns() {
+        unsafe extern "C" fn do_split(ctx: *const CallContext, args: *const u64, rets: *mut u64) {
+            let args: &[u64] = slice::from_raw_parts(args, 7);
+            let sum = args.iter().fold(ctx as u64, |a, b| a + b);
+            let rets: &mut [u64] = slice::from_raw_parts_mut(rets, 6);
+            rets[0] = sum;
+            rets[1] = sum * 2;
+            rets[2] = (sum as f64).to_bits();
+            rets[4] = (-(sum as f64)).to_bits();
+        }
+        #[repr(C)]
+        struct Ints(u64, u64);
+        #[repr(C)]
+        struct Floats(f64, f64);
+
+        let mut builder = TrampolineBufferBuilder::new();
+        let idx = builder.add_callinfo_trampoline_with_returns(do_split, 100 as *const _, 7);
+        let buf = builder.build();
+        let t = buf.get_trampoline(idx);
+        let ints = unsafe {
+            mem::transmute::<_, extern "C" fn(u64, u64, u64, u64, u64, u64, u64) -> Ints>(t)(
+                1, 2, 3, 4, 5, 6, 7,
+            )
+        };
+        assert_eq!((ints.0, ints.1), (128, 256));
+        let floats = unsafe {
+            mem::transmute::<_, extern "C" fn(u64, u64, u64, u64, u64, u64, u64) -> Floats>(t)(
+                1, 2, 3, 4, 5, 6, 7,
+            )
+        };
+        assert_eq!((floats.0, floats.1), (128.0, -128.0));
+    }
+
     #[test]
     fn test_many_global_trampolines() {
         unsafe extern "C" fn inner(n: *const CallContext, args: *const u64) -> u64 {
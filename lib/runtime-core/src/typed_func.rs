@@ -1,20 +1,22 @@
 //! The typed func module implements a way of representing a wasm function
 //! with the correct types from rust. Function calls using a typed func have a low overhead.
 use crate::{
-    error::RuntimeError,
+    error::{HostPanic, RuntimeError, Trap},
     export::{Context, Export, FuncPointer},
     import::IsExport,
-    types::{FuncSig, NativeWasmType, Type, WasmExternType},
+    types::{FuncRef, FuncSig, NativeWasmType, Type, WasmExternType},
     vm,
 };
+use lazy_static::lazy_static;
 use std::{
     any::Any,
+    collections::HashMap,
     convert::Infallible,
     ffi::c_void,
     marker::PhantomData,
     mem, panic,
     ptr::{self, NonNull},
-    sync::Arc,
+    sync::{Arc, Mutex},
 };
 
 /// This is just an empty trait to constrict that types that
@@ -190,25 +192,46 @@ where
     }
 }
 
+lazy_static! {
+    /// The environments of the host functions written to tables, by function
+    /// pointer, so that they can be called without being imported.
+    static ref TABLE_FUNC_ENVS: Mutex<HashMap<usize, usize>> = Mutex::new(HashMap::new());
+}
+
+/// Registers the environment of a host function written to a table.
+pub(crate) fn register_table_func_env(func: NonNull<vm::Func>, func_env: NonNull<vm::FuncEnv>) {
+    TABLE_FUNC_ENVS
+        .lock()
+        .unwrap()
+        .insert(func.as_ptr() as usize, func_env.as_ptr() as usize);
+}
+
+/// The environment of a host function written to a table, if it was registered.
+fn table_func_env(func: *const vm::Func) -> Option<NonNull<vm::FuncEnv>> {
+    let envs = TABLE_FUNC_ENVS.lock().unwrap();
+    envs.get(&(func as usize))
+        .and_then(|&func_env| NonNull::new(func_env as *mut vm::FuncEnv))
+}
+
 /// Represents a type-erased function provided by either the host or the WebAssembly program.
 pub struct DynamicFunc<'a> {
     _inner: Box<dyn Kind>,
 
     /// The function pointer.
-    func: NonNull<vm::Func>,
+    pub(crate) func: NonNull<vm::Func>,
 
     /// The function environment.
-    func_env: Option<NonNull<vm::FuncEnv>>,
+    pub(crate) func_env: Option<NonNull<vm::FuncEnv>>,
 
     /// The famous `vm::Ctx`.
-    vmctx: *mut vm::Ctx,
+    pub(crate) vmctx: *mut vm::Ctx,
 
     /// The runtime signature of this function.
     ///
     /// When converted from a `Func`, this is determined by the static `Args` and `Rets` type parameters.
     /// otherwise the signature is dynamically assigned during `DynamicFunc` creation, usually when creating
     /// a polymorphic host function.
-    signature: Arc<FuncSig>,
+    pub(crate) signature: Arc<FuncSig>,
 
     _phantom: PhantomData<&'a ()>,
 }
@@ -298,72 +321,127 @@ where
 
 impl<'a> DynamicFunc<'a> {
     /// Creates a dynamic function that is polymorphic over its argument and return types.
+    ///
+    /// `func` can capture an environment, and must return values of the return types of
+    /// `signature`, otherwise the call fails with a `RuntimeError::User`. For the caller, the function follows the C calling convention of a host
+    /// `Func` with the same signature: several results are returned as described in the
+    /// [`multi_value`](../multi_value/index.html) module, and 128-bit values are passed and
+    /// returned as two 64-bit halves.
     #[allow(unused_variables)]
     #[cfg(all(unix, target_arch = "x86_64"))]
     pub fn new<F>(signature: Arc<FuncSig>, func: F) -> Self
     where
         F: Fn(&mut vm::Ctx, &[crate::types::Value]) -> Vec<crate::types::Value> + 'static,
     {
+        use crate::multi_value::ResultsLayout;
         use crate::trampoline_x64::{CallContext, TrampolineBufferBuilder};
         use crate::types::Value;
-        use std::convert::TryFrom;
 
         struct PolymorphicContext {
             arg_types: Vec<Type>,
+            ret_types: Vec<Type>,
             func: Box<dyn Fn(&mut vm::Ctx, &[Value]) -> Vec<Value>>,
         }
+
+        /// Whether several results are returned through memory, in which case the caller
+        /// passes a pointer to the struct to fill as the first parameter.
+        fn returns_in_memory(ret_types: &[Type]) -> bool {
+            ret_types.len() > 1 && ResultsLayout::new(ret_types).in_memory()
+        }
+
         unsafe extern "C" fn enter_host_polymorphic(
             ctx: *const CallContext,
             args: *const u64,
-        ) -> u64 {
+            rets_out: *mut u64,
+        ) {
             let ctx = &*(ctx as *const PolymorphicContext);
+            let rets_out = std::slice::from_raw_parts_mut(rets_out, 6);
+
+            let (sret, args) = if returns_in_memory(&ctx.ret_types) {
+                (*args as *mut u8, args.offset(1))
+            } else {
+                (ptr::null_mut(), args)
+            };
             let vmctx = &mut *(*args.offset(0) as *mut vm::Ctx);
+            let mut i = 1; // skip vmctx
             let args: Vec<Value> = ctx
                 .arg_types
                 .iter()
-                .enumerate()
-                .map(|(i, t)| {
-                    let i = i + 1; // skip vmctx
+                .map(|t| {
+                    let x = *args.offset(i as _);
+                    i += 1;
                     match *t {
-                        Type::I32 => Value::I32(*args.offset(i as _) as i32),
-                        Type::I64 => Value::I64(*args.offset(i as _) as i64),
-                        Type::F32 => Value::F32(f32::from_bits(*args.offset(i as _) as u32)),
-                        Type::F64 => Value::F64(f64::from_bits(*args.offset(i as _) as u64)),
+                        Type::I32 => Value::I32(x as i32),
+                        Type::I64 => Value::I64(x as i64),
+                        Type::F32 => Value::F32(f32::from_bits(x as u32)),
+                        Type::F64 => Value::F64(f64::from_bits(x)),
+                        Type::FuncRef => Value::FuncRef(FuncRef::from_raw(x)),
+                        Type::ExternRef => Value::ExternRef(NativeWasmType::from_binary(x)),
                         Type::V128 => {
-                            todo!("enter_host_polymorphic: 128-bit types are not supported")
+                            let high = *args.offset(i as _);
+                            i += 1;
+                            Value::V128(x as u128 | (high as u128) << 64)
                         }
                     }
                 })
                 .collect();
+
             let rets = (ctx.func)(vmctx, &args);
-            if rets.len() == 0 {
-                0
-            } else if rets.len() == 1 {
-                u64::try_from(rets[0].to_u128()).expect(
-                    "128-bit return value from polymorphic host functions is not yet supported",
-                )
-            } else {
-                panic!(
-                    "multiple return values from polymorphic host functions is not yet supported"
+            let ret_types: Vec<Type> = rets.iter().map(Value::ty).collect();
+            if ret_types != ctx.ret_types {
+                let err = format!(
+                    "polymorphic host function returned values of types {:?} instead of {:?}",
+                    ret_types, ctx.ret_types
                 );
+                (&*vmctx.module)
+                    .runnable_module
+                    .do_early_trap(Box::new(err));
             }
-        }
 
-        // Disable "fat" closures for possible future changes.
-        if mem::size_of::<F>() != 0 {
-            unimplemented!("DynamicFunc with captured environment is not yet supported");
+            if rets.len() == 1 {
+                // A single result is returned both in the integer and the SSE registers.
+                let bits = rets[0].to_u128();
+                rets_out[0] = bits as u64;
+                rets_out[1] = (bits >> 64) as u64;
+                rets_out[2] = bits as u64;
+                rets_out[3] = (bits >> 64) as u64;
+            } else if rets.len() > 1 {
+                let layout = ResultsLayout::new(&ret_types);
+                let mut bytes = vec![0u8; layout.size()];
+                layout.write_values(&rets, &mut bytes);
+
+                if !sret.is_null() {
+                    ptr::copy_nonoverlapping(bytes.as_ptr(), sret, layout.size());
+                    rets_out[0] = sret as u64;
+                } else {
+                    let (ints, sses) = layout.to_registers(&bytes);
+                    rets_out[0] = ints[0];
+                    rets_out[1] = ints[1];
+                    rets_out[2] = sses[0];
+                    rets_out[4] = sses[1];
+                }
+            }
         }
 
+        let num_params = 1 // vmctx
+            + signature
+                .params()
+                .iter()
+                .map(|ty| if *ty == Type::V128 { 2 } else { 1 })
+                .sum::<u32>()
+            + if returns_in_memory(signature.returns()) { 1 } else { 0 };
+
         let mut builder = TrampolineBufferBuilder::new();
         let ctx: Box<PolymorphicContext> = Box::new(PolymorphicContext {
             arg_types: signature.params().to_vec(),
+            ret_types: signature.returns().to_vec(),
             func: Box::new(func),
         });
         let ctx = Box::into_raw(ctx);
-        builder.add_callinfo_trampoline(
+        builder.add_callinfo_trampoline_with_returns(
             enter_host_polymorphic,
             ctx as *const _,
-            (signature.params().len() + 1) as u32, // +vmctx
+            num_params,
         );
         let ptr = builder
             .insert_global()
@@ -517,20 +595,23 @@ macro_rules! impl_traits {
                 let mut rets = Rets::empty_ret_array();
                 let mut error_out = None;
 
-                if (wasm.invoke)(
-                    wasm.trampoline,
-                    ctx,
-                    f,
-                    args.as_ptr(),
-                    rets.as_mut().as_mut_ptr(),
-                    &mut error_out,
-                    wasm.invoke_env
-                ) {
+                let success = vm::Ctx::restore_internals_on_trap(ctx, || {
+                    (wasm.invoke)(
+                        wasm.trampoline,
+                        ctx,
+                        f,
+                        args.as_ptr(),
+                        rets.as_mut().as_mut_ptr(),
+                        &mut error_out,
+                        wasm.invoke_env
+                    )
+                });
+                if success {
                     Ok(Rets::from_ret_array(rets))
                 } else {
-                    Err(error_out.map(RuntimeError).unwrap_or_else(|| {
-                        RuntimeError(Box::new("invoke(): Unknown error".to_string()))
-                    }))
+                    Err(error_out
+                        .map(RuntimeError::from_unwind)
+                        .unwrap_or_else(|| RuntimeError::Trap(Trap::new(None))))
                 }
             }
         }
@@ -555,7 +636,7 @@ macro_rules! impl_traits {
                 // able to unwind through this function.
                 #[cfg_attr(nightly, unwind(allowed))]
                 extern fn wrap<$( $x, )* Rets, Trap, FN>(
-                    vmctx: &vm::Ctx $( , $x: <$x as WasmExternType>::Native )*
+                    vmctx: *mut vm::Ctx $( , $x: <$x as WasmExternType>::Native )*
                 ) -> Rets::CStruct
                 where
                     $( $x: WasmExternType, )*
@@ -567,10 +648,11 @@ macro_rules! impl_traits {
                     let self_pointer = wrap::<$( $x, )* Rets, Trap, FN> as *const vm::Func;
 
                     // Get the collection of imported functions.
-                    let vm_imported_functions = unsafe { &(*vmctx.import_backing).vm_functions };
+                    let vm_imported_functions = unsafe { &(*(*vmctx).import_backing).vm_functions };
 
-                    // Retrieve the `vm::FuncCtx`.
-                    let mut func_ctx: NonNull<vm::FuncCtx> = vm_imported_functions
+                    // Retrieve the `vm::FuncCtx`, unless the function is
+                    // not imported but called from a table.
+                    let func_ctx = vm_imported_functions
                         .iter()
                         .find_map(|(_, imported_func)| {
                             if imported_func.func == self_pointer {
@@ -578,16 +660,28 @@ macro_rules! impl_traits {
                             } else {
                                 None
                             }
-                        })
-                        .expect("Import backing is not well-formed, cannot find `func_ctx`.");
-                    let func_ctx = unsafe { func_ctx.as_mut() };
+                        });
 
-                    // Extract `vm::Ctx` from `vm::FuncCtx`. The
-                    // pointer is always non-null.
-                    let vmctx = unsafe { func_ctx.vmctx.as_mut() };
+                    let (vmctx, func_env) = match func_ctx {
+                        Some(mut func_ctx) => {
+                            let func_ctx = unsafe { func_ctx.as_mut() };
 
-                    // Extract `vm::FuncEnv` from `vm::FuncCtx`.
-                    let func_env = func_ctx.func_env;
+                            // Extract `vm::Ctx` and `vm::FuncEnv` from
+                            // `vm::FuncCtx`. The `vm::Ctx` pointer is
+                            // always non-null.
+                            (unsafe { func_ctx.vmctx.as_mut() }, func_ctx.func_env)
+                        }
+
+                        // Called from a table, the function receives the
+                        // `vm::Ctx` of its caller, and its `vm::FuncEnv`
+                        // was registered when it was written to the table.
+                        None => (
+                            unsafe { &mut *vmctx },
+                            Some(table_func_env(self_pointer).expect(
+                                "Import backing is not well-formed, cannot find `func_ctx`.",
+                            )),
+                        ),
+                    };
 
                     let func: &FN = match func_env {
                         // The imported function is a regular
@@ -620,7 +714,7 @@ macro_rules! impl_traits {
                             let b: Box<_> = err.into();
                             b as Box<dyn Any + Send>
                         },
-                        Err(err) => err,
+                        Err(err) => Box::new(HostPanic(err)) as Box<dyn Any + Send>,
                     };
 
                     // At this point, there is an error that needs to
@@ -671,7 +765,7 @@ macro_rules! impl_traits {
                 // able to unwind through this function.
                 #[cfg_attr(nightly, unwind(allowed))]
                 extern fn wrap<$( $x, )* Rets, Trap, FN>(
-                    vmctx: &vm::Ctx $( , $x: <$x as WasmExternType>::Native )*
+                    vmctx: *mut vm::Ctx $( , $x: <$x as WasmExternType>::Native )*
                 ) -> Rets::CStruct
                 where
                     $( $x: WasmExternType, )*
@@ -683,10 +777,11 @@ macro_rules! impl_traits {
                     let self_pointer = wrap::<$( $x, )* Rets, Trap, FN> as *const vm::Func;
 
                     // Get the collection of imported functions.
-                    let vm_imported_functions = unsafe { &(*vmctx.import_backing).vm_functions };
+                    let vm_imported_functions = unsafe { &(*(*vmctx).import_backing).vm_functions };
 
-                    // Retrieve the `vm::FuncCtx`.
-                    let mut func_ctx: NonNull<vm::FuncCtx> = vm_imported_functions
+                    // Retrieve the `vm::FuncCtx`, unless the function is
+                    // not imported but called from a table.
+                    let func_ctx = vm_imported_functions
                         .iter()
                         .find_map(|(_, imported_func)| {
                             if imported_func.func == self_pointer {
@@ -694,16 +789,28 @@ macro_rules! impl_traits {
                             } else {
                                 None
                             }
-                        })
-                        .expect("Import backing is not well-formed, cannot find `func_ctx`.");
-                    let func_ctx = unsafe { func_ctx.as_mut() };
+                        });
 
-                    // Extract `vm::Ctx` from `vm::FuncCtx`. The
-                    // pointer is always non-null.
-                    let vmctx = unsafe { func_ctx.vmctx.as_mut() };
+                    let (vmctx, func_env) = match func_ctx {
+                        Some(mut func_ctx) => {
+                            let func_ctx = unsafe { func_ctx.as_mut() };
 
-                    // Extract `vm::FuncEnv` from `vm::FuncCtx`.
-                    let func_env = func_ctx.func_env;
+                            // Extract `vm::Ctx` and `vm::FuncEnv` from
+                            // `vm::FuncCtx`. The `vm::Ctx` pointer is
+                            // always non-null.
+                            (unsafe { func_ctx.vmctx.as_mut() }, func_ctx.func_env)
+                        }
+
+                        // Called from a table, the function receives the
+                        // `vm::Ctx` of its caller, and its `vm::FuncEnv`
+                        // was registered when it was written to the table.
+                        None => (
+                            unsafe { &mut *vmctx },
+                            Some(table_func_env(self_pointer).expect(
+                                "Import backing is not well-formed, cannot find `func_ctx`.",
+                            )),
+                        ),
+                    };
 
                     let func: &FN = match func_env {
                         // The imported function is a regular
@@ -733,7 +840,7 @@ macro_rules! impl_traits {
                             let b: Box<_> = err.into();
                             b as Box<dyn Any + Send>
                         },
-                        Err(err) => err,
+                        Err(err) => Box::new(HostPanic(err)) as Box<dyn Any + Send>,
                     };
 
                     // At this point, there is an error that needs to
@@ -964,6 +1071,71 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_dynamic_func_with_env_and_returns() {
+        use crate::types::{FuncSig, Type, Value};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[repr(C)]
+        struct IntFloat(i32, f64);
+        #[repr(C)]
+        struct ThreeInts(i64, i64, i64);
+        #[repr(C)]
+        struct Halves(u64, u64);
+
+        let mut fake_vmctx = vec![0u64; mem::size_of::<vm::Ctx>() / 8 + 1];
+        let vmctx = fake_vmctx.as_mut_ptr() as *mut vm::Ctx;
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let counted = calls.clone();
+        let int_float = DynamicFunc::new(
+            Arc::new(FuncSig::new(
+                vec![Type::I32, Type::I64],
+                vec![Type::I32, Type::F64],
+            )),
+            move |_, args| {
+                counted.fetch_add(1, Ordering::SeqCst);
+                match (&args[0], &args[1]) {
+                    (Value::I32(x), Value::I64(y)) => {
+                        vec![Value::I32(x + 1), Value::F64(*y as f64 / 2.0)]
+                    }
+                    _ => unreachable!(),
+                }
+            },
+        );
+        let f: extern "C" fn(*mut vm::Ctx, i32, i64) -> IntFloat =
+            unsafe { mem::transmute(int_float.func) };
+        let ret = f(vmctx, -2, 5);
+        assert_eq!((ret.0, ret.1), (-1, 2.5));
+
+        let counted = calls.clone();
+        let three_ints = DynamicFunc::new(
+            Arc::new(FuncSig::new(vec![], vec![Type::I64; 3])),
+            move |_, _| {
+                counted.fetch_add(1, Ordering::SeqCst);
+                vec![Value::I64(1), Value::I64(-2), Value::I64(3)]
+            },
+        );
+        let f: extern "C" fn(*mut vm::Ctx) -> ThreeInts =
+            unsafe { mem::transmute(three_ints.func) };
+        let ret = f(vmctx);
+        assert_eq!((ret.0, ret.1, ret.2), (1, -2, 3));
+
+        let v128 = DynamicFunc::new(
+            Arc::new(FuncSig::new(vec![Type::V128], vec![Type::V128])),
+            |_, args| match args[0] {
+                Value::V128(x) => vec![Value::V128(x.rotate_left(64))],
+                _ => unreachable!(),
+            },
+        );
+        let f: extern "C" fn(*mut vm::Ctx, u64, u64) -> Halves =
+            unsafe { mem::transmute(v128.func) };
+        let ret = f(vmctx, 1, 2);
+        assert_eq!((ret.0, ret.1), (2, 1));
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
     #[test]
     fn test_many_new_dynamics() {
         use crate::types::{FuncSig, Type};
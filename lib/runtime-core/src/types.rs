@@ -1,7 +1,7 @@
 //! The runtime types modules represent type used within the wasm runtime and helper functions to
 //! convert to other represenations.
 
-use crate::{memory::MemoryType, module::ModuleInfo, structures::TypedIndex, units::Pages};
+use crate::{memory::MemoryType, module::ModuleInfo, structures::TypedIndex, units::Pages, vm};
 use std::{borrow::Cow, convert::TryFrom};
 
 /// Represents a WebAssembly type.
@@ -17,6 +17,10 @@ pub enum Type {
     F64,
     /// The `v128` type.
     V128,
+    /// The `funcref` type.
+    FuncRef,
+    /// The `externref` type, also known as `anyref`.
+    ExternRef,
 }
 
 impl std::fmt::Display for Type {
@@ -41,6 +45,11 @@ pub enum Value {
     F64(f64),
     /// The `v128` type.
     V128(u128),
+    /// The `funcref` type. Only the null reference can be serialized.
+    #[serde(with = "null_func_ref")]
+    FuncRef(FuncRef),
+    /// The `externref` type.
+    ExternRef(ExternRef),
 }
 
 impl Value {
@@ -52,6 +61,8 @@ impl Value {
             Value::F32(_) => Type::F32,
             Value::F64(_) => Type::F64,
             Value::V128(_) => Type::V128,
+            Value::FuncRef(_) => Type::FuncRef,
+            Value::ExternRef(_) => Type::ExternRef,
         }
     }
 
@@ -63,6 +74,17 @@ impl Value {
             Value::F32(x) => f32::to_bits(x) as u128,
             Value::F64(x) => f64::to_bits(x) as u128,
             Value::V128(x) => x,
+            Value::FuncRef(x) => x.to_raw() as u128,
+            Value::ExternRef(x) => x.to_binary() as u128,
+        }
+    }
+
+    /// The null reference of the given reference `Type`, if `ty` is one.
+    pub fn null_ref(ty: Type) -> Option<Self> {
+        match ty {
+            Type::FuncRef => Some(Value::FuncRef(FuncRef::null())),
+            Type::ExternRef => Some(Value::ExternRef(ExternRef::null())),
+            _ => None,
         }
     }
 }
@@ -93,6 +115,104 @@ value_conversions!(i64, I64);
 value_conversions!(f32, F32);
 value_conversions!(f64, F64);
 value_conversions!(u128, V128);
+value_conversions!(FuncRef, FuncRef);
+value_conversions!(ExternRef, ExternRef);
+
+/// A reference to a function, the value of a `funcref`.
+///
+/// A non-null `FuncRef` points to a caller-checked [`vm::Anyfunc`] owned by the
+/// runtime. It is checked to still be alive before it is written to a table, and
+/// is no longer valid once the instance or table it refers to is dropped.
+///
+/// [`vm::Anyfunc`]: ../vm/struct.Anyfunc.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FuncRef(u64);
+
+impl FuncRef {
+    /// The null `funcref`.
+    pub const fn null() -> Self {
+        FuncRef(0)
+    }
+
+    /// Whether this is the null `funcref`.
+    pub fn is_null(self) -> bool {
+        self.0 == 0
+    }
+
+    pub(crate) fn from_anyfunc(anyfunc: *const vm::Anyfunc) -> Self {
+        FuncRef(anyfunc as u64)
+    }
+
+    /// Create a `FuncRef` from its raw representation, as returned by `to_raw`.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be the representation of a `FuncRef` obtained from the runtime.
+    /// References which are not alive are rejected when they are written to a
+    /// table, but may still be passed to WebAssembly code.
+    pub unsafe fn from_raw(raw: u64) -> Self {
+        FuncRef(raw)
+    }
+
+    /// The raw representation of this reference, as passed to WebAssembly code.
+    pub fn to_raw(self) -> u64 {
+        self.0
+    }
+
+    /// The address of the `vm::Anyfunc` this reference points to, which must be
+    /// checked with `table::resolve_func_ref` before it is dereferenced.
+    pub(crate) fn addr(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Serializes the null `FuncRef` as a unit. The other references point into the
+/// runtime and cannot be serialized.
+mod null_func_ref {
+    use super::FuncRef;
+    use serde::{de::Deserialize, ser::Error, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &FuncRef, serializer: S) -> Result<S::Ok, S::Error> {
+        if !value.is_null() {
+            return Err(S::Error::custom("only the null funcref can be serialized"));
+        }
+        serializer.serialize_unit()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<FuncRef, D::Error> {
+        <()>::deserialize(deserializer)?;
+        Ok(FuncRef::null())
+    }
+}
+
+/// An opaque reference to a host value, the value of an `externref`.
+///
+/// The runtime never looks at the handle: it is up to the host to map it
+/// back to its own data. The handle `0` is the null `externref`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExternRef(u64);
+
+impl ExternRef {
+    /// Create an `ExternRef` from a host handle.
+    pub const fn new(handle: u64) -> Self {
+        ExternRef(handle)
+    }
+
+    /// The null `externref`.
+    pub const fn null() -> Self {
+        ExternRef(0)
+    }
+
+    /// Whether this is the null `externref`.
+    pub fn is_null(self) -> bool {
+        self.0 == 0
+    }
+
+    /// The host handle of this reference.
+    pub fn handle(self) -> u64 {
+        self.0
+    }
+}
 
 /// Represents a native wasm type.
 pub unsafe trait NativeWasmType: Copy + Into<Value>
@@ -157,6 +277,18 @@ unsafe impl NativeWasmType for f64 {
     }
 }
 
+unsafe impl NativeWasmType for ExternRef {
+    const TYPE: Type = Type::ExternRef;
+
+    fn from_binary(bits: u64) -> Self {
+        ExternRef(bits)
+    }
+
+    fn to_binary(self) -> u64 {
+        self.0
+    }
+}
+
 /// A trait to represent a wasm extern type.
 pub unsafe trait WasmExternType: Copy
 where
@@ -199,6 +331,24 @@ wasm_extern_type!(u64 => i64);
 wasm_extern_type!(f32 => f32);
 wasm_extern_type!(f64 => f64);
 
+macro_rules! wasm_extern_ref_type {
+    ($type:ty) => {
+        unsafe impl WasmExternType for $type {
+            type Native = $type;
+
+            fn from_native(native: Self::Native) -> Self {
+                native
+            }
+
+            fn to_native(self) -> Self::Native {
+                self
+            }
+        }
+    };
+}
+
+wasm_extern_ref_type!(ExternRef);
+
 // pub trait IntegerAtomic
 // where
 //     Self: Sized
@@ -250,6 +400,18 @@ convert_value_impl!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
 pub enum ElementType {
     /// Any wasm function.
     Anyfunc,
+    /// Any host reference.
+    ExternRef,
+}
+
+impl ElementType {
+    /// The `Type` of the values stored in a table of this element type.
+    pub fn value_type(self) -> Type {
+        match self {
+            ElementType::Anyfunc => Type::FuncRef,
+            ElementType::ExternRef => Type::ExternRef,
+        }
+    }
 }
 
 /// Describes the properties of a table including the element types, minimum and optional maximum,
@@ -284,6 +446,8 @@ pub enum Initializer {
     Const(Value),
     /// Corresponds to a `get_global` instruction.
     GetGlobal(ImportedGlobalIndex),
+    /// Corresponds to a `ref.func` instruction.
+    RefFunc(FuncIndex),
 }
 
 /// Describes the mutability and type of a Global
@@ -517,6 +681,36 @@ impl TypedIndex for SigIndex {
     }
 }
 
+/// Index for data segment.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct DataIndex(u32);
+impl TypedIndex for DataIndex {
+    #[doc(hidden)]
+    fn new(index: usize) -> Self {
+        DataIndex(index as _)
+    }
+
+    #[doc(hidden)]
+    fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Index for element segment.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ElemIndex(u32);
+impl TypedIndex for ElemIndex {
+    #[doc(hidden)]
+    fn new(index: usize) -> Self {
+        ElemIndex(index as _)
+    }
+
+    #[doc(hidden)]
+    fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
 /// Kind of local or import type.
 pub enum LocalOrImport<T>
 where
@@ -12,6 +12,7 @@ use crate::{
     vmcalls,
 };
 use std::{
+    any::{Any, TypeId},
     cell::UnsafeCell,
     ffi::c_void,
     mem,
@@ -62,12 +63,18 @@ pub struct Ctx {
     /// Alternatively, per-function data can be used if the function in the
     /// [`ImportObject`] is a closure.  This cannot duplicate data though,
     /// so if data may be shared if the [`ImportObject`] is reused.
+    ///
+    /// State that should live next to the data of other ABI implementations
+    /// can be attached by type instead, see [`Ctx::state`].
     pub data: *mut c_void,
 
     /// If there's a function set in this field, it gets called
     /// when the context is destructed, e.g. when an `Instance`
     /// is dropped.
     pub data_finalizer: Option<fn(data: *mut c_void)>,
+
+    /// The states attached to this instance, keyed by their type.
+    pub(crate) states: HashMap<TypeId, Box<dyn Any + Send>>,
 }
 
 /// When an instance context is destructed, we're calling its `data_finalizer`
@@ -134,6 +141,9 @@ pub struct InternalCtx {
 
     /// Interrupt signal mem.
     pub interrupt_signal_mem: *mut u8,
+
+    /// Mutable pointer to the counters incremented by `InternalEvent::IncrementCounter`.
+    pub counters: *mut u64,
 }
 
 static INTERNAL_FIELDS: AtomicUsize = AtomicUsize::new(0);
@@ -306,6 +316,8 @@ impl Ctx {
                 internals: &mut local_backing.internals.0,
 
                 interrupt_signal_mem: get_interrupt_signal_mem(),
+
+                counters: ptr::null_mut(),
             },
             local_functions: local_backing.local_functions.as_ptr(),
 
@@ -315,6 +327,7 @@ impl Ctx {
 
             data: ptr::null_mut(),
             data_finalizer: None,
+            states: HashMap::new(),
         }
     }
 
@@ -359,6 +372,8 @@ impl Ctx {
                 internals: &mut local_backing.internals.0,
 
                 interrupt_signal_mem: get_interrupt_signal_mem(),
+
+                counters: ptr::null_mut(),
             },
             local_functions: local_backing.local_functions.as_ptr(),
 
@@ -368,9 +383,67 @@ impl Ctx {
 
             data,
             data_finalizer: Some(data_finalizer),
+            states: HashMap::new(),
         }
     }
 
+    /// Returns the state of type `T` attached to this instance, if there is one.
+    ///
+    /// Each type of state is attached at most once, so ABI implementations
+    /// and embedders can each keep their own state on the same instance.
+    /// States are attached before the `start` function runs with
+    /// [`ImportObject::add_state`] or at any time with [`Ctx::insert_state`],
+    /// and are dropped with the instance.
+    ///
+    /// # Usage:
+    ///
+    /// ```
+    /// # use wasmer_runtime_core::vm::Ctx;
+    /// struct Counter(u32);
+    ///
+    /// fn count(ctx: &mut Ctx) -> u32 {
+    ///     match ctx.state_mut::<Counter>() {
+    ///         Some(counter) => {
+    ///             counter.0 += 1;
+    ///             counter.0
+    ///         }
+    ///         None => 0,
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [`ImportObject::add_state`]: ../import/struct.ImportObject.html#method.add_state
+    pub fn state<T: Any + Send>(&self) -> Option<&T> {
+        self.states
+            .get(&TypeId::of::<T>())
+            .and_then(|state| state.downcast_ref())
+    }
+
+    /// Returns the state of type `T` attached to this instance mutably, if
+    /// there is one.
+    pub fn state_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.states
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|state| state.downcast_mut())
+    }
+
+    /// Attaches `state` to this instance, returning the state of the same
+    /// type it replaces.
+    pub fn insert_state<T: Any + Send>(&mut self, state: T) -> Option<T> {
+        self.states
+            .insert(TypeId::of::<T>(), Box::new(state))
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    /// Detaches the state of type `T` from this instance and returns it.
+    pub fn remove_state<T: Any + Send>(&mut self) -> Option<T> {
+        self.states
+            .remove(&TypeId::of::<T>())
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+
     /// This exposes the specified memory of the WebAssembly instance
     /// as a immutable slice.
     ///
@@ -441,6 +514,32 @@ impl Ctx {
         }
     }
 
+    /// Runs `call`, a call from the host of a function of the context `ctx` returning
+    /// whether it succeeded, and restores the internal fields which middlewares ask to be
+    /// restored when the call traps.
+    pub(crate) unsafe fn restore_internals_on_trap<F>(ctx: *mut Ctx, call: F) -> bool
+    where
+        F: FnOnce() -> bool,
+    {
+        if ctx.is_null() || (*ctx).module.is_null() || (*ctx).internal.internals.is_null() {
+            return call();
+        }
+        let internals = (*ctx).internal.internals;
+        let saved: Vec<(usize, u64)> = (*(*ctx).module)
+            .info
+            .internals_restored_on_trap
+            .iter()
+            .map(|&index| (index, (*internals)[index]))
+            .collect();
+        let success = call();
+        if !success {
+            for (index, value) in saved {
+                (*internals)[index] = value;
+            }
+        }
+        success
+    }
+
     /// Calls a host or Wasm function at the given table index
     pub fn call_with_table_index(
         &mut self,
@@ -536,9 +635,13 @@ impl Ctx {
         13 * (mem::size_of::<usize>() as u8)
     }
 
-    pub const fn offset_local_functions() -> u8 {
+    pub const fn offset_counters() -> u8 {
         14 * (mem::size_of::<usize>() as u8)
     }
+
+    pub const fn offset_local_functions() -> u8 {
+        15 * (mem::size_of::<usize>() as u8)
+    }
 }
 
 /// Represents a function pointer. It is mostly used in the
@@ -629,7 +732,8 @@ pub struct LocalTable {
     pub base: *mut u8,
     /// Number of elements in the table (NOT necessarily the size of the table in bytes!).
     pub count: usize,
-    /// The table that this represents. At the moment, this can only be `*mut AnyfuncTable`.
+    /// The table that this represents, either a `*mut AnyfuncTable` or a `*mut ExternRefTable`
+    /// depending on the element type of the table.
     pub table: *mut (),
 }
 
@@ -900,6 +1004,11 @@ mod vm_offset_tests {
             offset_of!(InternalCtx, interrupt_signal_mem),
         );
 
+        assert_eq!(
+            Ctx::offset_counters() as usize,
+            offset_of!(InternalCtx, counters),
+        );
+
         assert_eq!(
             Ctx::offset_local_functions() as usize,
             offset_of!(Ctx, local_functions),
@@ -1027,6 +1136,9 @@ mod vm_ctx_tests {
             dynamic_sigindices: Map::new().into_boxed_map(),
             local_functions: Map::new().into_boxed_map(),
 
+            dropped_data: std::collections::HashSet::new(),
+            dropped_elements: std::collections::HashSet::new(),
+
             internals: crate::backing::Internals([0; crate::backing::INTERNALS_SIZE]),
         };
 
@@ -1061,6 +1173,65 @@ mod vm_ctx_tests {
         drop(ctx);
     }
 
+    #[test]
+    fn test_typed_states() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        static DROPPED: AtomicBool = AtomicBool::new(false);
+        struct Droppable;
+        impl Drop for Droppable {
+            fn drop(&mut self) {
+                DROPPED.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let mut local_backing = LocalBacking {
+            memories: Map::new().into_boxed_map(),
+            tables: Map::new().into_boxed_map(),
+            globals: Map::new().into_boxed_map(),
+
+            vm_memories: Map::new().into_boxed_map(),
+            vm_tables: Map::new().into_boxed_map(),
+            vm_globals: Map::new().into_boxed_map(),
+
+            dynamic_sigindices: Map::new().into_boxed_map(),
+            local_functions: Map::new().into_boxed_map(),
+
+            dropped_data: std::collections::HashSet::new(),
+            dropped_elements: std::collections::HashSet::new(),
+
+            internals: crate::backing::Internals([0; crate::backing::INTERNALS_SIZE]),
+        };
+
+        let mut import_backing = ImportBacking {
+            memories: Map::new().into_boxed_map(),
+            tables: Map::new().into_boxed_map(),
+            globals: Map::new().into_boxed_map(),
+
+            vm_functions: Map::new().into_boxed_map(),
+            vm_memories: Map::new().into_boxed_map(),
+            vm_tables: Map::new().into_boxed_map(),
+            vm_globals: Map::new().into_boxed_map(),
+        };
+
+        let module = generate_module();
+        let mut ctx = unsafe { Ctx::new(&mut local_backing, &mut import_backing, &module) };
+
+        assert_eq!(ctx.state::<u32>(), None);
+        assert_eq!(ctx.insert_state(1u32), None);
+        assert_eq!(ctx.insert_state("abi".to_string()), None);
+        *ctx.state_mut::<u32>().unwrap() += 1;
+        assert_eq!(ctx.state::<u32>(), Some(&2));
+        assert_eq!(ctx.state::<String>().map(String::as_str), Some("abi"));
+        assert_eq!(ctx.insert_state(5u32), Some(2));
+        assert_eq!(ctx.remove_state::<u32>(), Some(5));
+        assert_eq!(ctx.state::<u32>(), None);
+
+        ctx.insert_state(Droppable);
+        drop(ctx);
+        assert!(DROPPED.load(Ordering::SeqCst));
+    }
+
     fn cast_test_data(data: *mut c_void) -> &'static mut TestData {
         let test_data: &mut TestData = unsafe { &mut *(data as *mut TestData) };
         test_data
@@ -1117,6 +1288,8 @@ mod vm_ctx_tests {
 
                 data_initializers: Vec::new(),
                 elem_initializers: Vec::new(),
+                passive_data: HashMap::new(),
+                passive_elements: HashMap::new(),
 
                 start_func: None,
 
@@ -1131,6 +1304,14 @@ mod vm_ctx_tests {
 
                 custom_sections: HashMap::new(),
 
+                module_name: None,
+                func_names: HashMap::new(),
+                local_names: HashMap::new(),
+
+                internal_initializers: Vec::new(),
+                internals_restored_on_trap: Vec::new(),
+                counter_count: 0,
+
                 generate_debug_info: false,
                 #[cfg(feature = "generate-debug-information")]
                 debug_info_manager: crate::jit_debug::JitCodeDebugInfoManager::new(),
@@ -3,12 +3,20 @@
 #![allow(clippy::cast_ptr_alignment)]
 
 use crate::{
+    backend::ExceptionCode,
+    backing::LocalBacking,
     memory::{DynamicMemory, StaticMemory},
     structures::TypedIndex,
-    types::{ImportedMemoryIndex, LocalMemoryIndex, LocalTableIndex},
+    table::{resolve_func_ref, Table},
+    types::{
+        DataIndex, ElemIndex, ElementType, ExternRef, FuncIndex, FuncRef, ImportedMemoryIndex,
+        ImportedTableIndex, LocalMemoryIndex, LocalOrImport, LocalTableIndex, NativeWasmType,
+        TableIndex, Value,
+    },
     units::Pages,
     vm,
 };
+use std::ptr;
 
 // +*****************************+
 // |       LOCAL MEMORIES        |
@@ -208,19 +216,516 @@ pub unsafe extern "C" fn imported_dynamic_memory_size(
 // |        LOCAL TABLES         |
 // +*****************************+
 
+/// Increase the size of the local table with index `table_index` by `delta`
+/// elements, set to the reference `init`.
+///
+/// This function returns the number of elements before growing if successful,
+/// or `-1` if the grow failed.
+///
+/// # Safety
+///
+/// The index given by `table_index` is not bounds-checked.
 pub unsafe extern "C" fn local_table_grow(
     ctx: &mut vm::Ctx,
     table_index: LocalTableIndex,
     delta: u32,
+    init: u64,
 ) -> i32 {
-    let _ = table_index;
-    let _ = delta;
-    let _ = ctx;
-    unimplemented!("vmcalls::local_table_grow")
+    let table = &(*ctx.local_backing).tables[table_index];
+    table_grow(ctx, table, delta, init)
 }
 
+/// Get the size of the local table with index `table_index`, in elements.
+///
+/// # Safety
+///
+/// The index given by `table_index` is not bounds-checked.
 pub unsafe extern "C" fn local_table_size(ctx: &vm::Ctx, table_index: LocalTableIndex) -> u32 {
-    let _ = table_index;
-    let _ = ctx;
-    unimplemented!("vmcalls::local_table_size")
+    (*ctx.local_backing).tables[table_index].size()
+}
+
+/// Get the element at `index` of the local table with index `table_index`.
+///
+/// Traps if `index` is out of bounds.
+///
+/// # Safety
+///
+/// The index given by `table_index` is not bounds-checked.
+pub unsafe extern "C" fn local_table_get(
+    ctx: &mut vm::Ctx,
+    table_index: LocalTableIndex,
+    index: u32,
+) -> u64 {
+    let table = &(*ctx.local_backing).tables[table_index];
+    table_get(ctx, table, index)
+}
+
+/// Set the element at `index` of the local table with index `table_index`.
+///
+/// Traps if `index` is out of bounds.
+///
+/// # Safety
+///
+/// The index given by `table_index` is not bounds-checked, and `value` must be
+/// a reference of the element type of the table.
+pub unsafe extern "C" fn local_table_set(
+    ctx: &mut vm::Ctx,
+    table_index: LocalTableIndex,
+    index: u32,
+    value: u64,
+) {
+    let table = &(*ctx.local_backing).tables[table_index];
+    table_set(ctx, table, index, value)
+}
+
+/// Set the `len` elements starting at `start` of the local table with index
+/// `table_index`.
+///
+/// Traps if the range is out of bounds.
+///
+/// # Safety
+///
+/// The index given by `table_index` is not bounds-checked, and `value` must be
+/// a reference of the element type of the table.
+pub unsafe extern "C" fn local_table_fill(
+    ctx: &mut vm::Ctx,
+    table_index: LocalTableIndex,
+    start: u32,
+    value: u64,
+    len: u32,
+) {
+    let table = &(*ctx.local_backing).tables[table_index];
+    table_fill(ctx, table, start, value, len)
+}
+
+// +*****************************+
+// |       IMPORTED TABLES       |
+// +*****************************+
+
+/// Increase the size of the imported table with index `table_index` by `delta`
+/// elements, set to the reference `init`.
+///
+/// This function returns the number of elements before growing if successful,
+/// or `-1` if the grow failed.
+///
+/// # Safety
+///
+/// The index given by `table_index` is not bounds-checked.
+pub unsafe extern "C" fn imported_table_grow(
+    ctx: &mut vm::Ctx,
+    table_index: ImportedTableIndex,
+    delta: u32,
+    init: u64,
+) -> i32 {
+    let table = &(*ctx.import_backing).tables[table_index];
+    table_grow(ctx, table, delta, init)
+}
+
+/// Get the size of the imported table with index `table_index`, in elements.
+///
+/// # Safety
+///
+/// The index given by `table_index` is not bounds-checked.
+pub unsafe extern "C" fn imported_table_size(
+    ctx: &vm::Ctx,
+    table_index: ImportedTableIndex,
+) -> u32 {
+    (*ctx.import_backing).tables[table_index].size()
+}
+
+/// Get the element at `index` of the imported table with index `table_index`.
+///
+/// Traps if `index` is out of bounds.
+///
+/// # Safety
+///
+/// The index given by `table_index` is not bounds-checked.
+pub unsafe extern "C" fn imported_table_get(
+    ctx: &mut vm::Ctx,
+    table_index: ImportedTableIndex,
+    index: u32,
+) -> u64 {
+    let table = &(*ctx.import_backing).tables[table_index];
+    table_get(ctx, table, index)
+}
+
+/// Set the element at `index` of the imported table with index `table_index`.
+///
+/// Traps if `index` is out of bounds.
+///
+/// # Safety
+///
+/// The index given by `table_index` is not bounds-checked, and `value` must be
+/// a reference of the element type of the table.
+pub unsafe extern "C" fn imported_table_set(
+    ctx: &mut vm::Ctx,
+    table_index: ImportedTableIndex,
+    index: u32,
+    value: u64,
+) {
+    let table = &(*ctx.import_backing).tables[table_index];
+    table_set(ctx, table, index, value)
+}
+
+/// Set the `len` elements starting at `start` of the imported table with index
+/// `table_index`.
+///
+/// Traps if the range is out of bounds.
+///
+/// # Safety
+///
+/// The index given by `table_index` is not bounds-checked, and `value` must be
+/// a reference of the element type of the table.
+pub unsafe extern "C" fn imported_table_fill(
+    ctx: &mut vm::Ctx,
+    table_index: ImportedTableIndex,
+    start: u32,
+    value: u64,
+    len: u32,
+) {
+    let table = &(*ctx.import_backing).tables[table_index];
+    table_fill(ctx, table, start, value, len)
+}
+
+// +*****************************+
+// |         REFERENCES          |
+// +*****************************+
+
+/// Get a `funcref` to the function with index `func_index`.
+///
+/// # Safety
+///
+/// The index given by `func_index` is not bounds-checked.
+pub unsafe extern "C" fn func_ref(ctx: &mut vm::Ctx, func_index: FuncIndex) -> u64 {
+    let vmctx: *mut vm::Ctx = ctx;
+    LocalBacking::func_ref(&*ctx.module, &*ctx.import_backing, vmctx, func_index).to_raw()
+}
+
+// +*****************************+
+// |         BULK MEMORY         |
+// +*****************************+
+
+/// Copy `len` bytes from `src` to `dst` in the local memory with index
+/// `memory_index`. The ranges may overlap.
+///
+/// Traps if either range is out of bounds.
+///
+/// # Safety
+///
+/// The index given by `memory_index` is not bounds-checked.
+pub unsafe extern "C" fn local_memory_copy(
+    ctx: &vm::Ctx,
+    memory_index: LocalMemoryIndex,
+    dst: u32,
+    src: u32,
+    len: u32,
+) {
+    let local_memory = *ctx.internal.memories.add(memory_index.index());
+    memory_copy(ctx, local_memory, dst, src, len)
+}
+
+/// Set `len` bytes starting at `dst` in the local memory with index
+/// `memory_index` to the low byte of `value`.
+///
+/// Traps if the range is out of bounds.
+///
+/// # Safety
+///
+/// The index given by `memory_index` is not bounds-checked.
+pub unsafe extern "C" fn local_memory_fill(
+    ctx: &vm::Ctx,
+    memory_index: LocalMemoryIndex,
+    dst: u32,
+    value: u32,
+    len: u32,
+) {
+    let local_memory = *ctx.internal.memories.add(memory_index.index());
+    memory_fill(ctx, local_memory, dst, value, len)
+}
+
+/// Copy `len` bytes starting at `src` in the data segment with index
+/// `segment_index` to `dst` in the local memory with index `memory_index`.
+///
+/// Traps if either range is out of bounds. Dropped and active segments are
+/// empty.
+///
+/// # Safety
+///
+/// The index given by `memory_index` is not bounds-checked.
+pub unsafe extern "C" fn local_memory_init(
+    ctx: &vm::Ctx,
+    memory_index: LocalMemoryIndex,
+    segment_index: DataIndex,
+    dst: u32,
+    src: u32,
+    len: u32,
+) {
+    let local_memory = *ctx.internal.memories.add(memory_index.index());
+    memory_init(ctx, local_memory, segment_index, dst, src, len)
+}
+
+/// Copy `len` bytes from `src` to `dst` in the imported memory with index
+/// `memory_index`. The ranges may overlap.
+///
+/// Traps if either range is out of bounds.
+///
+/// # Safety
+///
+/// The index given by `memory_index` is not bounds-checked.
+pub unsafe extern "C" fn imported_memory_copy(
+    ctx: &vm::Ctx,
+    memory_index: ImportedMemoryIndex,
+    dst: u32,
+    src: u32,
+    len: u32,
+) {
+    let local_memory = *ctx.internal.imported_memories.add(memory_index.index());
+    memory_copy(ctx, local_memory, dst, src, len)
+}
+
+/// Set `len` bytes starting at `dst` in the imported memory with index
+/// `memory_index` to the low byte of `value`.
+///
+/// Traps if the range is out of bounds.
+///
+/// # Safety
+///
+/// The index given by `memory_index` is not bounds-checked.
+pub unsafe extern "C" fn imported_memory_fill(
+    ctx: &vm::Ctx,
+    memory_index: ImportedMemoryIndex,
+    dst: u32,
+    value: u32,
+    len: u32,
+) {
+    let local_memory = *ctx.internal.imported_memories.add(memory_index.index());
+    memory_fill(ctx, local_memory, dst, value, len)
+}
+
+/// Copy `len` bytes starting at `src` in the data segment with index
+/// `segment_index` to `dst` in the imported memory with index `memory_index`.
+///
+/// Traps if either range is out of bounds. Dropped and active segments are
+/// empty.
+///
+/// # Safety
+///
+/// The index given by `memory_index` is not bounds-checked.
+pub unsafe extern "C" fn imported_memory_init(
+    ctx: &vm::Ctx,
+    memory_index: ImportedMemoryIndex,
+    segment_index: DataIndex,
+    dst: u32,
+    src: u32,
+    len: u32,
+) {
+    let local_memory = *ctx.internal.imported_memories.add(memory_index.index());
+    memory_init(ctx, local_memory, segment_index, dst, src, len)
+}
+
+/// Drop the data segment with index `segment_index`, making it empty.
+///
+/// # Safety
+///
+/// The index given by `segment_index` is not bounds-checked.
+pub unsafe extern "C" fn data_drop(ctx: &mut vm::Ctx, segment_index: DataIndex) {
+    (*ctx.local_backing).dropped_data.insert(segment_index);
+}
+
+/// Copy `len` elements from `src` in the table with index `src_table_index` to
+/// `dst` in the table with index `dst_table_index`. The ranges may overlap.
+///
+/// Both tables may be local or imported, so they are given by their module-wide
+/// index.
+///
+/// Traps if either range is out of bounds.
+///
+/// # Safety
+///
+/// The indices given by `dst_table_index` and `src_table_index` are not
+/// bounds-checked.
+pub unsafe extern "C" fn table_copy(
+    ctx: &vm::Ctx,
+    dst_table_index: TableIndex,
+    src_table_index: TableIndex,
+    dst: u32,
+    src: u32,
+    len: u32,
+) {
+    let dst_table = table_at(ctx, dst_table_index);
+    let src_table = table_at(ctx, src_table_index);
+    if dst_table.copy(dst, src_table, src, len).is_err() {
+        table_out_of_bounds(ctx)
+    }
+}
+
+/// Copy `len` elements starting at `src` in the element segment with index
+/// `segment_index` to `dst` in the table with index `table_index`.
+///
+/// Traps if either range is out of bounds. Dropped and active segments are
+/// empty.
+///
+/// # Safety
+///
+/// The index given by `table_index` is not bounds-checked.
+pub unsafe extern "C" fn table_init(
+    ctx: &mut vm::Ctx,
+    table_index: TableIndex,
+    segment_index: ElemIndex,
+    dst: u32,
+    src: u32,
+    len: u32,
+) {
+    let vmctx: *mut vm::Ctx = ctx;
+    let module = &*ctx.module;
+    let local_backing = &*ctx.local_backing;
+    let elements: &[Option<FuncIndex>] = match module.info.passive_elements.get(&segment_index) {
+        Some(elements) if !local_backing.dropped_elements.contains(&segment_index) => elements,
+        _ => &[],
+    };
+
+    let table = table_at(ctx, table_index);
+    if !in_bounds(src, len, elements.len()) || !in_bounds(dst, len, table.size() as usize) {
+        table_out_of_bounds(ctx)
+    }
+
+    let import_backing = &*ctx.import_backing;
+    let elements = elements[src as usize..(src + len) as usize]
+        .iter()
+        .map(|element| {
+            element.map(|func_index| {
+                LocalBacking::func_anyfunc(module, import_backing, vmctx, func_index)
+            })
+        });
+    table.init_elements(dst as usize, elements);
+}
+
+/// Drop the element segment with index `segment_index`, making it empty.
+///
+/// # Safety
+///
+/// The index given by `segment_index` is not bounds-checked.
+pub unsafe extern "C" fn elem_drop(ctx: &mut vm::Ctx, segment_index: ElemIndex) {
+    (*ctx.local_backing).dropped_elements.insert(segment_index);
+}
+
+fn in_bounds(start: u32, len: u32, size: usize) -> bool {
+    start as usize + len as usize <= size
+}
+
+unsafe fn memory_copy(ctx: &vm::Ctx, memory: *const vm::LocalMemory, dst: u32, src: u32, len: u32) {
+    let (base, bound) = ((*memory).base, (*memory).bound);
+    if !in_bounds(dst, len, bound) || !in_bounds(src, len, bound) {
+        memory_out_of_bounds(ctx)
+    }
+    ptr::copy(base.add(src as usize), base.add(dst as usize), len as usize);
+}
+
+unsafe fn memory_fill(
+    ctx: &vm::Ctx,
+    memory: *const vm::LocalMemory,
+    dst: u32,
+    value: u32,
+    len: u32,
+) {
+    let (base, bound) = ((*memory).base, (*memory).bound);
+    if !in_bounds(dst, len, bound) {
+        memory_out_of_bounds(ctx)
+    }
+    ptr::write_bytes(base.add(dst as usize), value as u8, len as usize);
+}
+
+unsafe fn memory_init(
+    ctx: &vm::Ctx,
+    memory: *const vm::LocalMemory,
+    segment_index: DataIndex,
+    dst: u32,
+    src: u32,
+    len: u32,
+) {
+    let data: &[u8] = match (*ctx.module).info.passive_data.get(&segment_index) {
+        Some(data) if !(*ctx.local_backing).dropped_data.contains(&segment_index) => data,
+        _ => &[],
+    };
+
+    let (base, bound) = ((*memory).base, (*memory).bound);
+    if !in_bounds(dst, len, bound) || !in_bounds(src, len, data.len()) {
+        memory_out_of_bounds(ctx)
+    }
+    ptr::copy_nonoverlapping(
+        data.as_ptr().add(src as usize),
+        base.add(dst as usize),
+        len as usize,
+    );
+}
+
+unsafe fn memory_out_of_bounds(ctx: &vm::Ctx) -> ! {
+    (&*ctx.module)
+        .runnable_module
+        .do_early_trap(Box::new(ExceptionCode::MemoryOutOfBounds))
+}
+
+unsafe fn table_at(ctx: &vm::Ctx, table_index: TableIndex) -> &Table {
+    match table_index.local_or_import(&(*ctx.module).info) {
+        LocalOrImport::Local(local_table_index) => &(*ctx.local_backing).tables[local_table_index],
+        LocalOrImport::Import(import_table_index) => {
+            &(*ctx.import_backing).tables[import_table_index]
+        }
+    }
+}
+
+/// The reference value `value` of the element type of `table`.
+///
+/// Traps if it is a `funcref` to a function of a dropped instance or table.
+unsafe fn table_ref(ctx: &vm::Ctx, table: &Table, value: u64) -> Value {
+    match table.descriptor().element {
+        ElementType::Anyfunc => {
+            let value = FuncRef::from_raw(value);
+            if resolve_func_ref(value).is_none() {
+                (&*ctx.module)
+                    .runnable_module
+                    .do_early_trap(Box::new("funcref to a dropped function".to_string()))
+            }
+            Value::FuncRef(value)
+        }
+        ElementType::ExternRef => Value::ExternRef(ExternRef::from_binary(value)),
+    }
+}
+
+unsafe fn table_grow(ctx: &vm::Ctx, table: &Table, delta: u32, init: u64) -> i32 {
+    match table.grow_with(delta, table_ref(ctx, table, init)) {
+        Ok(old_size) => old_size as i32,
+        Err(_) => -1,
+    }
+}
+
+unsafe fn table_get(ctx: &vm::Ctx, table: &Table, index: u32) -> u64 {
+    match table.get(index) {
+        Some(value) => value.to_u128() as u64,
+        None => table_out_of_bounds(ctx),
+    }
+}
+
+unsafe fn table_set(ctx: &vm::Ctx, table: &Table, index: u32, value: u64) {
+    if table
+        .set_value(index, table_ref(ctx, table, value))
+        .is_err()
+    {
+        table_out_of_bounds(ctx)
+    }
+}
+
+unsafe fn table_fill(ctx: &vm::Ctx, table: &Table, start: u32, value: u64, len: u32) {
+    if table
+        .fill(start, table_ref(ctx, table, value), len)
+        .is_err()
+    {
+        table_out_of_bounds(ctx)
+    }
+}
+
+unsafe fn table_out_of_bounds(ctx: &vm::Ctx) -> ! {
+    (&*ctx.module)
+        .runnable_module
+        .do_early_trap(Box::new(ExceptionCode::TableOutOfBounds))
 }
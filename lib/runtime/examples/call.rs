@@ -1,4 +1,4 @@
-use wasmer_runtime::{compile, error, error::RuntimeError, imports, Ctx, Func, Value};
+use wasmer_runtime::{compile, error, imports, Ctx, Func, Value};
 
 use wabt::wat2wasm;
 
@@ -71,7 +71,7 @@ fn main() -> Result<(), error::Error> {
     println!("result: {:?}", result);
 
     if let Err(e) = result {
-        if let Ok(exit_code) = e.0.downcast::<ExitCode>() {
+        if let Ok(exit_code) = e.downcast::<ExitCode>() {
             println!("exit code: {:?}", exit_code);
         }
     }
@@ -40,7 +40,7 @@ fn error_propagation() {
 
     let result = foo.call();
 
-    if let Err(RuntimeError(e)) = result {
+    if let Err(e @ RuntimeError::User(_)) = result {
         let exit_code = e.downcast::<ExitCode>().unwrap();
         assert_eq!(exit_code.code, 42);
     } else {
@@ -31,6 +31,7 @@ use wasmer_runtime_core::{
     loader::CodeMemory,
     memory::MemoryType,
     module::{ModuleInfo, ModuleInner},
+    multi_value::{Eightbyte, ResultsLayout},
     state::{
         x64::new_machine_state, x64::X64Register, FunctionStateMap, MachineState, MachineValue,
         ModuleStateMap, OffsetInfo, SuspendOffset, WasmAbstractValue,
@@ -42,6 +43,7 @@ use wasmer_runtime_core::{
         TableIndex, Type,
     },
     vm::{self, LocalGlobal, LocalTable, INTERNALS_SIZE},
+    vmcalls,
     wasmparser::{MemoryImmediate, Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType},
 };
 
@@ -63,11 +65,16 @@ static BACKEND_ID: &str = "singlepass";
 #[cfg(target_arch = "x86_64")]
 lazy_static! {
     /// Performs a System V call to `target` with [stack_top..stack_base] as the argument list, from right to left.
-    static ref CONSTRUCT_STACK_AND_CALL_WASM: unsafe extern "C" fn (stack_top: *const u64, stack_base: *const u64, ctx: *mut vm::Ctx, target: *const vm::Func) -> u64 = {
+    ///
+    /// `RAX`, `RDX`, `XMM0` and `XMM1` after the call are written to `registers`, for functions
+    /// with several results.
+    static ref CONSTRUCT_STACK_AND_CALL_WASM: unsafe extern "C" fn (stack_top: *const u64, stack_base: *const u64, ctx: *mut vm::Ctx, target: *const vm::Func, registers: *mut [u64; 4]) -> u64 = {
         let mut assembler = Assembler::new().unwrap();
         let offset = assembler.offset();
         dynasm!(
             assembler
+            ; push rbx
+            ; mov rbx, r8
             ; push r15
             ; push r14
             ; push r13
@@ -133,6 +140,11 @@ lazy_static! {
             ; and rsp, rax
             ; call r12
 
+            ; mov [rbx], rax
+            ; mov [rbx + 8], rdx
+            ; movsd [rbx + 16], xmm0
+            ; movsd [rbx + 24], xmm1
+
             ; mov rsp, rbp
             ; pop rbp
             ; pop r11
@@ -140,6 +152,7 @@ lazy_static! {
             ; pop r13
             ; pop r14
             ; pop r15
+            ; pop rbx
             ; ret
         );
         let buf = assembler.finalize().unwrap();
@@ -195,6 +208,9 @@ pub struct X64ModuleCodeGenerator {
     function_labels: Option<HashMap<usize, (DynamicLabel, Option<AssemblyOffset>)>>,
     assembler: Option<Assembler>,
     func_import_count: usize,
+    /// The trampolines of imported functions for callers passing a pointer to the results
+    /// before vmctx. Only emitted if multi-value is enabled.
+    import_results_in_memory_labels: Arc<Vec<DynamicLabel>>,
 
     config: Option<Arc<CodegenConfig>>,
 }
@@ -206,6 +222,7 @@ pub struct X64FunctionCode {
     function_signatures: Arc<Map<FuncIndex, SigIndex>>,
     fsm: FunctionStateMap,
     offset: usize,
+    import_results_in_memory_labels: Arc<Vec<DynamicLabel>>,
 
     assembler: Option<Assembler>,
     function_labels: Option<HashMap<usize, (DynamicLabel, Option<AssemblyOffset>)>>,
@@ -217,6 +234,12 @@ pub struct X64FunctionCode {
     >,
     returns: SmallVec<[WpType; 1]>,
     locals: Vec<Location>,
+    /// The machine stack slots through which several values are passed to block labels,
+    /// and the results of calls are read. `None` if multi-value is disabled.
+    multi_value_area: Option<Location>,
+    /// The slot holding the pointer to write the results to, if the function returns them
+    /// in memory.
+    results_pointer: Option<Location>,
     num_params: usize,
     num_locals: usize,
     value_stack: Vec<Location>,
@@ -278,6 +301,7 @@ pub struct ControlFrame {
     pub label: DynamicLabel,
     pub loop_like: bool,
     pub if_else: IfElseState,
+    pub params: SmallVec<[WpType; 1]>,
     pub returns: SmallVec<[WpType; 1]>,
     pub value_stack_depth: usize,
     pub state: MachineState,
@@ -380,149 +404,196 @@ impl RunnableModule for X64ExecutionContext {
             args: *const u64,
             rets: *mut u64,
             error_out: *mut Option<Box<dyn Any + Send>>,
-            num_params_plus_one: Option<NonNull<c_void>>,
+            sig: Option<NonNull<c_void>>,
         ) -> bool {
             let rm: &Box<dyn RunnableModule> = &(&*(*ctx).module).runnable_module;
 
-            let args =
-                slice::from_raw_parts(args, num_params_plus_one.unwrap().as_ptr() as usize - 1);
-
-            let ret = match fault::catch_unsafe_unwind(
-                || {
-                    // Puts the arguments onto the stack and calls Wasm entry.
-                    #[cfg(target_arch = "x86_64")]
-                    {
-                        let args_reverse: SmallVec<[u64; 8]> = args.iter().cloned().rev().collect();
-                        CONSTRUCT_STACK_AND_CALL_WASM(
-                            args_reverse.as_ptr(),
-                            args_reverse.as_ptr().offset(args_reverse.len() as isize),
-                            ctx,
-                            func.as_ptr(),
-                        )
-                    }
+            let sig = &*(sig.unwrap().as_ptr() as *const FuncSig);
+            let args = slice::from_raw_parts(args, sig.params().len());
 
-                    // FIXME: Currently we are doing a hack here to convert between native aarch64 and
-                    // "emulated" x86 ABIs. Ideally, this should be done using handwritten assembly.
-                    #[cfg(target_arch = "aarch64")]
-                    {
-                        struct CallCtx<'a> {
-                            args: &'a [u64],
-                            ctx: *mut vm::Ctx,
-                            callable: NonNull<vm::Func>,
+            let results = if sig.returns().len() > 1 {
+                Some(ResultsLayout::new(sig.returns()))
+            } else {
+                None
+            };
+            let results_in_memory = results.as_ref().map_or(false, ResultsLayout::in_memory);
+            let mut results_buffer: Vec<u64> =
+                vec![0; results.as_ref().map_or(0, |layout| (layout.size() + 7) / 8)];
+            let mut registers = [0u64; 4];
+
+            // Makes `ctx` visible to the fault handler so that traps can be attributed to
+            // wasm functions.
+            let ret = match fault::with_ctx(ctx, || {
+                fault::catch_unsafe_unwind(
+                    || {
+                        // Puts the arguments onto the stack and calls Wasm entry.
+                        #[cfg(target_arch = "x86_64")]
+                        {
+                            // The pointer to the results, if any, is passed before vmctx.
+                            let (first_arg, args_reverse): (_, SmallVec<[u64; 8]>) =
+                                if results_in_memory {
+                                    (
+                                        results_buffer.as_mut_ptr() as *mut vm::Ctx,
+                                        iter::once(ctx as u64)
+                                            .chain(args.iter().cloned())
+                                            .rev()
+                                            .collect(),
+                                    )
+                                } else {
+                                    (ctx, args.iter().cloned().rev().collect())
+                                };
+                            CONSTRUCT_STACK_AND_CALL_WASM(
+                                args_reverse.as_ptr(),
+                                args_reverse.as_ptr().offset(args_reverse.len() as isize),
+                                first_arg,
+                                func.as_ptr(),
+                                &mut registers,
+                            )
                         }
-                        extern "C" fn call_fn(f: *mut u8) -> u64 {
-                            unsafe {
-                                let f = &*(f as *const CallCtx);
-                                let callable: extern "C" fn(
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                    u64,
-                                )
-                                    -> u64 = std::mem::transmute(f.callable);
-                                let mut args = f.args.iter();
-                                callable(
-                                    f.ctx as u64,
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                    args.next().cloned().unwrap_or(0),
-                                )
+
+                        // FIXME: Currently we are doing a hack here to convert between native aarch64 and
+                        // "emulated" x86 ABIs. Ideally, this should be done using handwritten assembly.
+                        #[cfg(target_arch = "aarch64")]
+                        {
+                            struct CallCtx<'a> {
+                                args: &'a [u64],
+                                ctx: *mut vm::Ctx,
+                                callable: NonNull<vm::Func>,
                             }
+                            extern "C" fn call_fn(f: *mut u8) -> u64 {
+                                unsafe {
+                                    let f = &*(f as *const CallCtx);
+                                    let callable: extern "C" fn(
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                        u64,
+                                    )
+                                        -> u64 = std::mem::transmute(f.callable);
+                                    let mut args = f.args.iter();
+                                    callable(
+                                        f.ctx as u64,
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                        args.next().cloned().unwrap_or(0),
+                                    )
+                                }
+                            }
+                            let mut cctx = CallCtx {
+                                args: &args,
+                                ctx: ctx,
+                                callable: func,
+                            };
+                            use libc::{
+                                mmap, munmap, MAP_ANON, MAP_NORESERVE, MAP_PRIVATE, PROT_READ,
+                                PROT_WRITE,
+                            };
+                            const STACK_SIZE: usize = 1048576 * 1024; // 1GB of virtual address space for stack.
+                            let stack_ptr = mmap(
+                                ::std::ptr::null_mut(),
+                                STACK_SIZE,
+                                PROT_READ | PROT_WRITE,
+                                MAP_PRIVATE | MAP_ANON | MAP_NORESERVE,
+                                -1,
+                                0,
+                            );
+                            if stack_ptr as isize == -1 {
+                                panic!("unable to allocate stack");
+                            }
+                            // TODO: Mark specific regions in the stack as PROT_NONE.
+                            let ret = SWITCH_STACK(
+                                (stack_ptr as *mut u8).offset(STACK_SIZE as isize) as *mut u64,
+                                call_fn,
+                                &mut cctx as *mut CallCtx as *mut u8,
+                            );
+                            munmap(stack_ptr, STACK_SIZE);
+                            ret
                         }
-                        let mut cctx = CallCtx {
-                            args: &args,
-                            ctx: ctx,
-                            callable: func,
-                        };
-                        use libc::{
-                            mmap, munmap, MAP_ANON, MAP_NORESERVE, MAP_PRIVATE, PROT_READ,
-                            PROT_WRITE,
-                        };
-                        const STACK_SIZE: usize = 1048576 * 1024; // 1GB of virtual address space for stack.
-                        let stack_ptr = mmap(
-                            ::std::ptr::null_mut(),
-                            STACK_SIZE,
-                            PROT_READ | PROT_WRITE,
-                            MAP_PRIVATE | MAP_ANON | MAP_NORESERVE,
-                            -1,
-                            0,
-                        );
-                        if stack_ptr as isize == -1 {
-                            panic!("unable to allocate stack");
-                        }
-                        // TODO: Mark specific regions in the stack as PROT_NONE.
-                        let ret = SWITCH_STACK(
-                            (stack_ptr as *mut u8).offset(STACK_SIZE as isize) as *mut u64,
-                            call_fn,
-                            &mut cctx as *mut CallCtx as *mut u8,
-                        );
-                        munmap(stack_ptr, STACK_SIZE);
-                        ret
-                    }
-                },
-                rm.get_breakpoints(),
-            ) {
+                    },
+                    rm.get_breakpoints(),
+                )
+            }) {
                 Ok(x) => {
                     if !rets.is_null() {
-                        *rets = x;
+                        match results {
+                            Some(layout) => {
+                                let bytes = if layout.in_memory() {
+                                    slice::from_raw_parts(
+                                        results_buffer.as_ptr() as *const u8,
+                                        layout.size(),
+                                    )
+                                    .to_vec()
+                                } else {
+                                    layout.from_registers(
+                                        [registers[0], registers[1]],
+                                        [registers[2], registers[3]],
+                                    )
+                                };
+                                layout.read_slots(
+                                    &bytes,
+                                    slice::from_raw_parts_mut(rets, sig.returns().len()),
+                                );
+                            }
+                            None => *rets = x,
+                        }
                     }
                     true
                 }
@@ -547,7 +618,7 @@ impl RunnableModule for X64ExecutionContext {
             Wasm::from_raw_parts(
                 dummy_trampoline,
                 invoke,
-                NonNull::new((self.signatures.get(sig_index).unwrap().params().len() + 1) as _), // +1 to keep it non-zero
+                NonNull::new(self.signatures.get(sig_index).unwrap() as *const FuncSig as _),
             )
         })
     }
@@ -643,6 +714,7 @@ struct CodegenConfig {
     enforce_stack_check: bool,
     track_state: bool,
     full_preemption: bool,
+    multi_value: bool,
 }
 
 impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
@@ -658,6 +730,7 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
             function_labels: Some(HashMap::new()),
             assembler: Some(a),
             func_import_count: 0,
+            import_results_in_memory_labels: Arc::new(vec![]),
             config: None,
         }
     }
@@ -719,12 +792,15 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
             function_signatures: self.function_signatures.as_ref().unwrap().clone(),
             fsm: FunctionStateMap::new(new_machine_state(), self.functions.len(), 32, vec![]), // only a placeholder; this is initialized later in `begin_body`
             offset: begin_offset.0,
+            import_results_in_memory_labels: self.import_results_in_memory_labels.clone(),
 
             assembler: Some(assembler),
             function_labels: Some(function_labels),
             breakpoints: Some(breakpoints),
             returns: smallvec![],
             locals: vec![],
+            multi_value_area: None,
+            results_pointer: None,
             num_params: 0,
             num_locals: 0,
             value_stack: vec![],
@@ -889,27 +965,41 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
         let imported_func_ctx_addr = imported_func + vm::ImportedFunc::offset_func_ctx() as usize;
         let imported_func_ctx_vmctx_addr = vm::FuncCtx::offset_vmctx() as usize;
 
-        a.emit_mov(
-            Size::S64,
-            Location::Memory(GPR::RDI, imported_funcs_addr as i32),
-            Location::GPR(GPR::RAX),
-        );
-        a.emit_mov(
-            Size::S64,
-            Location::Memory(GPR::RAX, imported_func_ctx_addr as i32),
-            Location::GPR(GPR::RDI),
-        );
-        a.emit_mov(
-            Size::S64,
-            Location::Memory(GPR::RDI, imported_func_ctx_vmctx_addr as i32),
-            Location::GPR(GPR::RDI),
-        );
-        a.emit_mov(
-            Size::S64,
-            Location::Memory(GPR::RAX, imported_func_addr as i32),
-            Location::GPR(GPR::RAX),
-        );
-        a.emit_host_redirection(GPR::RAX);
+        // Callers of functions returning their results in memory pass the pointer to the
+        // results first, and vmctx second.
+        let vmctx_regs: &[GPR] = if self.config.as_ref().unwrap().multi_value {
+            &[GPR::RDI, GPR::RSI]
+        } else {
+            &[GPR::RDI]
+        };
+        for (i, &vmctx) in vmctx_regs.iter().enumerate() {
+            if i > 0 {
+                let label = a.get_label();
+                a.emit_label(label);
+                Arc::make_mut(&mut self.import_results_in_memory_labels).push(label);
+            }
+            a.emit_mov(
+                Size::S64,
+                Location::Memory(vmctx, imported_funcs_addr as i32),
+                Location::GPR(GPR::RAX),
+            );
+            a.emit_mov(
+                Size::S64,
+                Location::Memory(GPR::RAX, imported_func_ctx_addr as i32),
+                Location::GPR(vmctx),
+            );
+            a.emit_mov(
+                Size::S64,
+                Location::Memory(vmctx, imported_func_ctx_vmctx_addr as i32),
+                Location::GPR(vmctx),
+            );
+            a.emit_mov(
+                Size::S64,
+                Location::Memory(GPR::RAX, imported_func_addr as i32),
+                Location::GPR(GPR::RAX),
+            );
+            a.emit_host_redirection(GPR::RAX);
+        }
 
         self.func_import_count += 1;
 
@@ -922,6 +1012,7 @@ impl ModuleCodeGenerator<X64FunctionCode, X64ExecutionContext, CodegenError>
             enforce_stack_check: config.enforce_stack_check,
             track_state: config.track_state,
             full_preemption: config.full_preemption,
+            multi_value: config.features.multi_value,
         }));
         Ok(())
     }
@@ -1665,6 +1756,291 @@ impl X64FunctionCode {
         Ok(())
     }
 
+    /// Returns the types of the params and of the results of a block of type `ty`.
+    fn block_type(
+        signatures: &Map<SigIndex, FuncSig>,
+        ty: WpTypeOrFuncType,
+    ) -> Result<(SmallVec<[WpType; 1]>, SmallVec<[WpType; 1]>), CodegenError> {
+        Ok(match ty {
+            WpTypeOrFuncType::Type(WpType::EmptyBlockType) => (smallvec![], smallvec![]),
+            WpTypeOrFuncType::Type(inner_ty) => (smallvec![], smallvec![inner_ty]),
+            WpTypeOrFuncType::FuncType(sig_index) => {
+                let sig = signatures
+                    .get(SigIndex::new(sig_index as usize))
+                    .ok_or_else(|| CodegenError {
+                        message: format!("block_type: unknown signature {}", sig_index),
+                    })?;
+                (
+                    sig.params().iter().cloned().map(type_to_wp_type).collect(),
+                    sig.returns().iter().cloned().map(type_to_wp_type).collect(),
+                )
+            }
+        })
+    }
+
+    /// Whether a function with results of types `return_types` returns them in memory.
+    fn results_in_memory(return_types: &[WpType]) -> bool {
+        return_types.len() > 1
+            && ResultsLayout::new(
+                &return_types
+                    .iter()
+                    .cloned()
+                    .map(wp_type_to_type)
+                    .collect::<Vec<_>>(),
+            )
+            .in_memory()
+    }
+
+    /// Returns the location of the `i`-th slot of the multi-value area `area`.
+    fn multi_value_slot(area: Option<Location>, i: usize) -> Result<Location, CodegenError> {
+        match area {
+            Some(Location::Memory(base, offset)) => {
+                Ok(Location::Memory(base, offset + (i * 8) as i32))
+            }
+            _ => Err(CodegenError {
+                message: format!("multi_value_slot: multi-value is not enabled"),
+            }),
+        }
+    }
+
+    /// Moves `values`, passed to a block label, to where the label expects them: `RAX` for a
+    /// single value, the multi-value area otherwise.
+    fn emit_store_block_values(
+        a: &mut Assembler,
+        m: &mut Machine,
+        area: Option<Location>,
+        values: &[Location],
+    ) -> Result<(), CodegenError> {
+        if values.len() == 1 {
+            Self::emit_relaxed_binop(
+                a,
+                m,
+                Assembler::emit_mov,
+                Size::S64,
+                values[0],
+                Location::GPR(GPR::RAX),
+            );
+        } else {
+            for (i, value) in values.iter().enumerate() {
+                let slot = Self::multi_value_slot(area, i)?;
+                Self::emit_relaxed_binop(a, m, Assembler::emit_mov, Size::S64, *value, slot);
+            }
+        }
+        Ok(())
+    }
+
+    /// Pushes the values of types `tys` passed to a block label onto the value stack.
+    fn emit_load_block_values(
+        a: &mut Assembler,
+        m: &mut Machine,
+        area: Option<Location>,
+        value_stack: &mut Vec<Location>,
+        tys: &[WpType],
+    ) -> Result<(), CodegenError> {
+        for (i, ty) in tys.iter().enumerate() {
+            let src = if tys.len() == 1 {
+                Location::GPR(GPR::RAX)
+            } else {
+                Self::multi_value_slot(area, i)?
+            };
+            let loc = m.acquire_locations(
+                a,
+                &[(*ty, MachineValue::WasmStack(value_stack.len()))],
+                false,
+            )[0];
+            Self::emit_relaxed_binop(a, m, Assembler::emit_mov, Size::S64, src, loc);
+            value_stack.push(loc);
+        }
+        Ok(())
+    }
+
+    /// Moves the values a branch to `frame` passes, from the top of `value_stack`: the params
+    /// of a loop, or the results of another block.
+    fn emit_branch_values(
+        a: &mut Assembler,
+        m: &mut Machine,
+        area: Option<Location>,
+        value_stack: &[Location],
+        frame: &ControlFrame,
+    ) -> Result<(), CodegenError> {
+        let count = if frame.loop_like {
+            frame.params.len()
+        } else {
+            frame.returns.len()
+        };
+        if count > 0 {
+            Self::emit_store_block_values(a, m, area, &value_stack[value_stack.len() - count..])?;
+        }
+        Ok(())
+    }
+
+    /// Ends the `then` branch of the `if` of `frame`, and starts its `else` branch.
+    fn emit_else(
+        a: &mut Assembler,
+        m: &mut Machine,
+        area: Option<Location>,
+        value_stack: &mut Vec<Location>,
+        frame: &mut ControlFrame,
+        was_unreachable: bool,
+    ) -> Result<(), CodegenError> {
+        if !was_unreachable && frame.returns.len() > 0 {
+            Self::emit_store_block_values(
+                a,
+                m,
+                area,
+                &value_stack[value_stack.len() - frame.returns.len()..],
+            )?;
+        }
+
+        // The params of the `if` stay below the copies the `then` branch used, for the `else`
+        // branch.
+        let else_depth = frame.value_stack_depth + frame.params.len();
+        m.release_locations(a, &value_stack[else_depth..]);
+        value_stack.truncate(else_depth);
+        m.release_locations_keep_state(a, &value_stack[frame.value_stack_depth..]);
+
+        match frame.if_else {
+            IfElseState::If(label) => {
+                a.emit_jmp(Condition::None, frame.label);
+                a.emit_label(label);
+                frame.if_else = IfElseState::Else;
+            }
+            _ => {
+                return Err(CodegenError {
+                    message: format!("Else: frame.if_else unreachable code"),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    /// Pushes the results of a call to a function with several results of types
+    /// `return_types`, returned as described in the `multi_value` module, onto the value stack.
+    fn emit_read_call_results(
+        a: &mut Assembler,
+        m: &mut Machine,
+        area: Option<Location>,
+        value_stack: &mut Vec<Location>,
+        return_types: &[WpType],
+    ) -> Result<(), CodegenError> {
+        let layout = ResultsLayout::new(
+            &return_types
+                .iter()
+                .cloned()
+                .map(wp_type_to_type)
+                .collect::<Vec<_>>(),
+        );
+
+        // Results returned in memory were written to the multi-value area by the callee.
+        if !layout.in_memory() {
+            let mut gprs = [GPR::RAX, GPR::RDX].iter();
+            let mut xmms = [XMM::XMM0, XMM::XMM1].iter();
+            for (i, class) in layout.eightbytes().into_iter().enumerate() {
+                let reg = match class {
+                    Eightbyte::Integer => Location::GPR(*gprs.next().unwrap()),
+                    Eightbyte::Sse => Location::XMM(*xmms.next().unwrap()),
+                };
+                a.emit_mov(Size::S64, reg, Self::multi_value_slot(area, i)?);
+            }
+        }
+
+        let (base, area_offset) = match Self::multi_value_slot(area, 0)? {
+            Location::Memory(base, offset) => (base, offset),
+            _ => unreachable!(),
+        };
+        let tmp = m.acquire_temp_gpr().unwrap();
+        for (ty, offset) in return_types.iter().zip(layout.offsets()) {
+            let size = match ResultsLayout::value_size(wp_type_to_type(*ty)) {
+                4 => Size::S32,
+                _ => Size::S64,
+            };
+            let loc = m.acquire_locations(
+                a,
+                &[(*ty, MachineValue::WasmStack(value_stack.len()))],
+                false,
+            )[0];
+            a.emit_mov(
+                size,
+                Location::Memory(base, area_offset + *offset as i32),
+                Location::GPR(tmp),
+            );
+            a.emit_mov(Size::S64, Location::GPR(tmp), loc);
+            value_stack.push(loc);
+        }
+        m.release_temp_gpr(tmp);
+        Ok(())
+    }
+
+    /// Returns the results of types `return_types` of the function, which are in the
+    /// multi-value area, as described in the `multi_value` module.
+    fn emit_return_results(
+        a: &mut Assembler,
+        area: Option<Location>,
+        results_pointer: Option<Location>,
+        return_types: &[WpType],
+    ) -> Result<(), CodegenError> {
+        let layout = ResultsLayout::new(
+            &return_types
+                .iter()
+                .cloned()
+                .map(wp_type_to_type)
+                .collect::<Vec<_>>(),
+        );
+        let sizes: Vec<Size> = return_types
+            .iter()
+            .map(|ty| match ResultsLayout::value_size(wp_type_to_type(*ty)) {
+                4 => Size::S32,
+                _ => Size::S64,
+            })
+            .collect();
+
+        if let Some(results_pointer) = results_pointer {
+            // The pointer to the results is returned in `RAX`.
+            a.emit_mov(Size::S64, results_pointer, Location::GPR(GPR::RAX));
+            for (i, (size, offset)) in sizes.iter().zip(layout.offsets()).enumerate() {
+                a.emit_mov(
+                    *size,
+                    Self::multi_value_slot(area, i)?,
+                    Location::GPR(GPR::RCX),
+                );
+                a.emit_mov(
+                    *size,
+                    Location::GPR(GPR::RCX),
+                    Location::Memory(GPR::RAX, *offset as i32),
+                );
+            }
+        } else {
+            let (base, area_offset) = match Self::multi_value_slot(area, 0)? {
+                Location::Memory(base, offset) => (base, offset),
+                _ => unreachable!(),
+            };
+            // Packs the results in place: each result moves to a lower offset, which only
+            // overlaps the slots of the results already moved.
+            for (i, (size, offset)) in sizes.iter().zip(layout.offsets()).enumerate() {
+                a.emit_mov(
+                    *size,
+                    Self::multi_value_slot(area, i)?,
+                    Location::GPR(GPR::RCX),
+                );
+                a.emit_mov(
+                    *size,
+                    Location::GPR(GPR::RCX),
+                    Location::Memory(base, area_offset + *offset as i32),
+                );
+            }
+            let mut gprs = [GPR::RAX, GPR::RDX].iter();
+            let mut xmms = [XMM::XMM0, XMM::XMM1].iter();
+            for (i, class) in layout.eightbytes().into_iter().enumerate() {
+                let reg = match class {
+                    Eightbyte::Integer => Location::GPR(*gprs.next().unwrap()),
+                    Eightbyte::Sse => Location::XMM(*xmms.next().unwrap()),
+                };
+                a.emit_mov(Size::S64, Self::multi_value_slot(area, i)?, reg);
+            }
+        }
+        Ok(())
+    }
+
     /// Emits a System V call sequence.
     ///
     /// This function must not use RAX before `cb` is called.
@@ -1906,16 +2282,89 @@ impl X64FunctionCode {
         Ok(())
     }
 
-    /// Emits a System V call sequence, specialized for labels as the call target.
-    fn emit_call_sysv_label<I: Iterator<Item = Location>>(
+    /// Emits a System V call to the runtime function at `target`, passing the vmctx and `params`.
+    fn emit_call_vmcall<I: Iterator<Item = Location>>(
         a: &mut Assembler,
         m: &mut Machine,
-        label: DynamicLabel,
+        target: usize,
         params: I,
-        state_context: Option<(&mut FunctionStateMap, &mut [ControlFrame])>,
     ) -> Result<(), CodegenError> {
-        Self::emit_call_sysv(a, m, |a| a.emit_call_label(label), params, state_context)?;
-        Ok(())
+        a.emit_mov(
+            Size::S64,
+            Location::Imm64(target as u64),
+            Location::GPR(GPR::RAX),
+        );
+        Self::emit_call_sysv(
+            a,
+            m,
+            |a| {
+                let label = a.get_label();
+                let after = a.get_label();
+                a.emit_jmp(Condition::None, after);
+                a.emit_label(label);
+                a.emit_host_redirection(GPR::RAX);
+                a.emit_label(after);
+                a.emit_call_label(label);
+            },
+            params,
+            None,
+        )
+    }
+
+    /// Emits a call to a table vmcall, `local_target` or `imported_target` depending on
+    /// where the table at `table_index` lives, passing the vmctx, the local or imported
+    /// table index and `params`.
+    fn emit_call_table_vmcall(
+        a: &mut Assembler,
+        m: &mut Machine,
+        module_info: &ModuleInfo,
+        table_index: u32,
+        local_target: usize,
+        imported_target: usize,
+        params: &[Location],
+    ) -> Result<(), CodegenError> {
+        let (target, index) = match TableIndex::new(table_index as usize)
+            .local_or_import(module_info)
+        {
+            LocalOrImport::Local(local_table_index) => (local_target, local_table_index.index()),
+            LocalOrImport::Import(import_table_index) => {
+                (imported_target, import_table_index.index())
+            }
+        };
+        Self::emit_call_vmcall(
+            a,
+            m,
+            target,
+            iter::once(Location::Imm32(index as u32)).chain(params.iter().cloned()),
+        )
+    }
+
+    /// Emits a call to a memory vmcall, `local_target` or `imported_target` depending on
+    /// where the memory at `memory_index` lives, passing the vmctx, the local or imported
+    /// memory index and `params`.
+    fn emit_call_memory_vmcall(
+        a: &mut Assembler,
+        m: &mut Machine,
+        module_info: &ModuleInfo,
+        memory_index: u32,
+        local_target: usize,
+        imported_target: usize,
+        params: &[Location],
+    ) -> Result<(), CodegenError> {
+        let (target, index) = match MemoryIndex::new(memory_index as usize)
+            .local_or_import(module_info)
+        {
+            LocalOrImport::Local(local_memory_index) => (local_target, local_memory_index.index()),
+            LocalOrImport::Import(import_memory_index) => {
+                (imported_target, import_memory_index.index())
+            }
+        };
+        Self::emit_call_vmcall(
+            a,
+            m,
+            target,
+            iter::once(Location::Imm32(index as u32)).chain(params.iter().cloned()),
+        )
     }
 
     /// Emits a memory operation.
@@ -2431,12 +2880,28 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
         a.emit_push(Size::S64, Location::GPR(GPR::RBP));
         a.emit_mov(Size::S64, Location::GPR(GPR::RSP), Location::GPR(GPR::RBP));
 
+        let returns_in_memory = self.returns.len() > 1
+            && ResultsLayout::new(
+                &self
+                    .returns
+                    .iter()
+                    .cloned()
+                    .map(wp_type_to_type)
+                    .collect::<Vec<_>>(),
+            )
+            .in_memory();
+
         // Stack check.
         if self.config.enforce_stack_check {
             a.emit_cmp(
                 Size::S64,
                 Location::Memory(
-                    GPR::RDI, // first parameter is vmctx
+                    // vmctx is the first parameter, after the pointer to the results if any.
+                    if returns_in_memory {
+                        GPR::RSI
+                    } else {
+                        GPR::RDI
+                    },
                     vm::Ctx::offset_stack_lower_bound() as i32,
                 ),
                 Location::GPR(GPR::RSP),
@@ -2444,14 +2909,31 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             Self::mark_range_with_exception_code(
                 a,
                 self.exception_table.as_mut().unwrap(),
-                ExceptionCode::MemoryOutOfBounds,
+                ExceptionCode::StackOverflow,
                 |a| a.emit_conditional_trap(Condition::Below),
             );
         }
 
-        self.locals = self
-            .machine
-            .init_locals(a, self.num_locals, self.num_params);
+        self.locals =
+            self.machine
+                .init_locals(a, self.num_locals, self.num_params, returns_in_memory);
+
+        if self.config.multi_value {
+            let num_slots = self
+                .signatures
+                .values()
+                .map(|sig| sig.params().len().max(sig.returns().len()))
+                .max()
+                .unwrap_or(0);
+            // One more slot holds the pointer to the results.
+            let area = self.machine.acquire_stack_area(a, num_slots + 1);
+            if returns_in_memory {
+                let results_pointer = Self::multi_value_slot(Some(area), num_slots)?;
+                a.emit_mov(Size::S64, Location::GPR(GPR::RDI), results_pointer);
+                self.results_pointer = Some(results_pointer);
+            }
+            self.multi_value_area = Some(area);
+        }
 
         self.machine.state.register_values
             [X64Register::GPR(Machine::get_vmctx_reg()).to_index().0] = MachineValue::Vmctx;
@@ -2477,6 +2959,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             label: a.get_label(),
             loop_like: false,
             if_else: IfElseState::None,
+            params: smallvec![],
             returns: self.returns.clone(),
             value_stack_depth: 0,
             state: self.machine.state.clone(),
@@ -2530,7 +3013,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
         &mut self,
         ev: Event,
         module_info: &ModuleInfo,
-        _source_loc: u32,
+        source_loc: u32,
     ) -> Result<(), CodegenError> {
         let a = self.assembler.as_mut().unwrap();
 
@@ -2543,6 +3026,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
         }
 
         self.machine.state.wasm_inst_offset = self.machine.state.wasm_inst_offset.wrapping_add(1);
+        self.machine.state.wasm_source_loc = Some(source_loc);
 
         //println!("{:?} {}", op, self.value_stack.len());
         let was_unreachable;
@@ -2671,6 +3155,34 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                             Location::Memory(tmp, (idx * 8) as i32),
                         );
                         self.machine.release_temp_gpr(tmp);
+                    }
+                    InternalEvent::IncrementCounter(idx) => {
+                        let idx = idx as usize;
+                        if idx >= (std::i32::MAX as usize) / 8 {
+                            return Err(CodegenError {
+                                message: format!("IncrementCounter: incorrect index value"),
+                            });
+                        }
+
+                        let tmp = self.machine.acquire_temp_gpr().unwrap();
+
+                        // Load `counters` pointer.
+                        a.emit_mov(
+                            Size::S64,
+                            Location::Memory(
+                                Machine::get_vmctx_reg(),
+                                vm::Ctx::offset_counters() as i32,
+                            ),
+                            Location::GPR(tmp),
+                        );
+
+                        // Increment the counter in place.
+                        a.emit_add(
+                            Size::S64,
+                            Location::Imm32(1),
+                            Location::Memory(tmp, (idx * 8) as i32),
+                        );
+                        self.machine.release_temp_gpr(tmp);
                     } //_ => unimplemented!(),
                 }
                 return Ok(());
@@ -3433,6 +3945,20 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 &mut self.value_stack,
                 Condition::NotEqual,
             )?,
+            Operator::RefNull => {
+                self.value_stack.push(Location::Imm64(0));
+                self.machine
+                    .state
+                    .wasm_stack
+                    .push(WasmAbstractValue::Const(0));
+            }
+            Operator::RefIsNull => Self::emit_cmpop_i64_dynamic_b(
+                a,
+                &mut self.machine,
+                &mut self.value_stack,
+                Condition::Equal,
+                Location::Imm64(0),
+            )?,
             Operator::I64Eqz => Self::emit_cmpop_i64_dynamic_b(
                 a,
                 &mut self.machine,
@@ -6240,17 +6766,43 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
 
                 self.machine.release_locations_only_osr_state(params.len());
 
-                Self::emit_call_sysv_label(
+                let results_area = if Self::results_in_memory(&return_types) {
+                    Some(Self::multi_value_slot(self.multi_value_area, 0)?)
+                } else {
+                    None
+                };
+                let label = match self.import_results_in_memory_labels.get(function_index) {
+                    Some(import_label) if results_area.is_some() => *import_label,
+                    _ => label,
+                };
+
+                Self::emit_call_sysv(
                     a,
                     &mut self.machine,
-                    label,
-                    params.iter().map(|x| *x),
+                    |a| {
+                        if let Some(results_area) = results_area {
+                            a.emit_lea(Size::S64, results_area, Location::GPR(GPR::RDI));
+                        }
+                        a.emit_call_label(label)
+                    },
+                    // The pointer to the results, if any, is passed before vmctx.
+                    iter::repeat(Location::GPR(Machine::get_vmctx_reg()))
+                        .take(results_area.iter().count())
+                        .chain(params.iter().cloned()),
                     Some((&mut self.fsm, &mut self.control_stack)),
                 )?;
 
                 self.machine.release_locations_only_stack(a, &params);
 
-                if return_types.len() > 0 {
+                if return_types.len() > 1 {
+                    Self::emit_read_call_results(
+                        a,
+                        &mut self.machine,
+                        self.multi_value_area,
+                        &mut self.value_stack,
+                        &return_types,
+                    )?;
+                } else if return_types.len() > 0 {
                     let ret = self.machine.acquire_locations(
                         a,
                         &[(
@@ -6264,11 +6816,16 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 }
             }
             Operator::CallIndirect { index, table_index } => {
-                if table_index != 0 {
-                    return Err(CodegenError {
-                        message: format!("CallIndirect: table_index is not 0"),
-                    });
-                }
+                let (tables_offset, table_slot) =
+                    match TableIndex::new(table_index as usize).local_or_import(module_info) {
+                        LocalOrImport::Local(local_table_index) => {
+                            (vm::Ctx::offset_tables(), local_table_index.index())
+                        }
+                        LocalOrImport::Import(import_table_index) => (
+                            vm::Ctx::offset_imported_tables(),
+                            import_table_index.index(),
+                        ),
+                    };
                 let sig = self.signatures.get(SigIndex::new(index as usize)).unwrap();
                 let param_types: SmallVec<[WpType; 8]> =
                     sig.params().iter().cloned().map(type_to_wp_type).collect();
@@ -6290,18 +6847,12 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
 
                 a.emit_mov(
                     Size::S64,
-                    Location::Memory(
-                        Machine::get_vmctx_reg(),
-                        match TableIndex::new(0).local_or_import(module_info) {
-                            LocalOrImport::Local(_) => vm::Ctx::offset_tables(),
-                            LocalOrImport::Import(_) => vm::Ctx::offset_imported_tables(),
-                        } as i32,
-                    ),
+                    Location::Memory(Machine::get_vmctx_reg(), tables_offset as i32),
                     Location::GPR(table_base),
                 );
                 a.emit_mov(
                     Size::S64,
-                    Location::Memory(table_base, 0),
+                    Location::Memory(table_base, (table_slot * mem::size_of::<usize>()) as i32),
                     Location::GPR(table_base),
                 );
                 a.emit_mov(
@@ -6367,10 +6918,40 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
 
                 self.machine.release_locations_only_osr_state(params.len());
 
+                let results_area = if Self::results_in_memory(&return_types) {
+                    Some(Self::multi_value_slot(self.multi_value_area, 0)?)
+                } else {
+                    None
+                };
+
                 Self::emit_call_sysv(
                     a,
                     &mut self.machine,
                     |a| {
+                        if let Some(results_area) = results_area {
+                            a.emit_lea(Size::S64, results_area, Location::GPR(GPR::RDI));
+                        }
+
+                        // The function is called with the context of the element, or the
+                        // context of the caller, already passed, if that one is null.
+                        let label_caller_ctx = a.get_label();
+                        a.emit_mov(
+                            Size::S64,
+                            Location::Memory(
+                                GPR::RAX,
+                                (vm::Anyfunc::offset_vmctx() as usize) as i32,
+                            ),
+                            Location::GPR(GPR::R10),
+                        );
+                        a.emit_test_gpr_64(GPR::R10);
+                        a.emit_jmp(Condition::Equal, label_caller_ctx);
+                        a.emit_mov(
+                            Size::S64,
+                            Location::GPR(GPR::R10),
+                            Machine::get_param_location(results_area.iter().count()),
+                        );
+                        a.emit_label(label_caller_ctx);
+
                         if a.arch_requires_indirect_call_trampoline() {
                             a.arch_emit_indirect_call_with_trampoline(Location::Memory(
                                 GPR::RAX,
@@ -6383,13 +6964,24 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                             ));
                         }
                     },
-                    params.iter().map(|x| *x),
+                    // The pointer to the results, if any, is passed before vmctx.
+                    iter::repeat(Location::GPR(Machine::get_vmctx_reg()))
+                        .take(results_area.iter().count())
+                        .chain(params.iter().cloned()),
                     Some((&mut self.fsm, &mut self.control_stack)),
                 )?;
 
                 self.machine.release_locations_only_stack(a, &params);
 
-                if return_types.len() > 0 {
+                if return_types.len() > 1 {
+                    Self::emit_read_call_results(
+                        a,
+                        &mut self.machine,
+                        self.multi_value_area,
+                        &mut self.value_stack,
+                        &return_types,
+                    )?;
+                } else if return_types.len() > 0 {
                     let ret = self.machine.acquire_locations(
                         a,
                         &[(
@@ -6403,6 +6995,7 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 }
             }
             Operator::If { ty } => {
+                let (params, returns) = Self::block_type(&self.signatures, ty)?;
                 let label_end = a.get_label();
                 let label_else = a.get_label();
 
@@ -6413,16 +7006,9 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     label: label_end,
                     loop_like: false,
                     if_else: IfElseState::If(label_else),
-                    returns: match ty {
-                        WpTypeOrFuncType::Type(WpType::EmptyBlockType) => smallvec![],
-                        WpTypeOrFuncType::Type(inner_ty) => smallvec![inner_ty],
-                        _ => {
-                            return Err(CodegenError {
-                                message: format!("If: multi-value returns not yet implemented"),
-                            })
-                        }
-                    },
-                    value_stack_depth: self.value_stack.len(),
+                    value_stack_depth: self.value_stack.len() - params.len(),
+                    params: params.clone(),
+                    returns,
                     state: self.machine.state.clone(),
                     state_diff_id: Self::get_state_diff(
                         &self.machine,
@@ -6440,40 +7026,39 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     cond,
                 );
                 a.emit_jmp(Condition::Equal, label_else);
-            }
-            Operator::Else => {
-                let mut frame = self.control_stack.last_mut().unwrap();
 
-                if !was_unreachable && frame.returns.len() > 0 {
-                    let loc = *self.value_stack.last().unwrap();
+                // The `then` branch works on copies of the params, which the `else` branch
+                // needs too.
+                let first_param = self.value_stack.len() - params.len();
+                for (i, ty) in params.iter().enumerate() {
+                    let loc = self.machine.acquire_locations(
+                        a,
+                        &[(*ty, MachineValue::WasmStack(self.value_stack.len()))],
+                        false,
+                    )[0];
                     Self::emit_relaxed_binop(
                         a,
                         &mut self.machine,
                         Assembler::emit_mov,
                         Size::S64,
+                        self.value_stack[first_param + i],
                         loc,
-                        Location::GPR(GPR::RAX),
                     );
+                    self.value_stack.push(loc);
                 }
-
-                let released: &[Location] = &self.value_stack[frame.value_stack_depth..];
-                self.machine.release_locations(a, released);
-                self.value_stack.truncate(frame.value_stack_depth);
-
-                match frame.if_else {
-                    IfElseState::If(label) => {
-                        a.emit_jmp(Condition::None, frame.label);
-                        a.emit_label(label);
-                        frame.if_else = IfElseState::Else;
-                    }
-                    _ => {
-                        return Err(CodegenError {
-                            message: format!("Else: frame.if_else unreachable code"),
-                        })
-                    }
-                }
             }
-            Operator::Select => {
+            Operator::Else => {
+                let frame = self.control_stack.last_mut().unwrap();
+                Self::emit_else(
+                    a,
+                    &mut self.machine,
+                    self.multi_value_area,
+                    &mut self.value_stack,
+                    frame,
+                    was_unreachable,
+                )?;
+            }
+            Operator::Select | Operator::TypedSelect { .. } => {
                 let cond =
                     get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
                 let v_b =
@@ -6524,20 +7109,14 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 a.emit_label(end_label);
             }
             Operator::Block { ty } => {
+                let (params, returns) = Self::block_type(&self.signatures, ty)?;
                 let frame = ControlFrame {
                     label: a.get_label(),
                     loop_like: false,
                     if_else: IfElseState::None,
-                    returns: match ty {
-                        WpTypeOrFuncType::Type(WpType::EmptyBlockType) => smallvec![],
-                        WpTypeOrFuncType::Type(inner_ty) => smallvec![inner_ty],
-                        _ => {
-                            return Err(CodegenError {
-                                message: format!("Block: multi-value returns not yet implemented"),
-                            })
-                        }
-                    },
-                    value_stack_depth: self.value_stack.len(),
+                    value_stack_depth: self.value_stack.len() - params.len(),
+                    params,
+                    returns,
                     state: self.machine.state.clone(),
                     state_diff_id: Self::get_state_diff(
                         &self.machine,
@@ -6548,7 +7127,31 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 self.control_stack.push(frame);
             }
             Operator::Loop { ty } => {
+                let (params, returns) = Self::block_type(&self.signatures, ty)?;
                 let label = a.get_label();
+
+                // The params enter the loop the way branches to it pass them.
+                if params.len() > 0 {
+                    let values = self
+                        .value_stack
+                        .split_off(self.value_stack.len() - params.len());
+                    Self::emit_store_block_values(
+                        a,
+                        &mut self.machine,
+                        self.multi_value_area,
+                        &values,
+                    )?;
+                    self.machine.release_locations(a, &values);
+                }
+                a.emit_label(label);
+                Self::emit_load_block_values(
+                    a,
+                    &mut self.machine,
+                    self.multi_value_area,
+                    &mut self.value_stack,
+                    &params,
+                )?;
+
                 let state_diff_id =
                     Self::get_state_diff(&self.machine, &mut self.fsm, &mut self.control_stack);
                 let activate_offset = a.get_offset().0;
@@ -6557,20 +7160,12 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     label: label,
                     loop_like: true,
                     if_else: IfElseState::None,
-                    returns: match ty {
-                        WpTypeOrFuncType::Type(WpType::EmptyBlockType) => smallvec![],
-                        WpTypeOrFuncType::Type(inner_ty) => smallvec![inner_ty],
-                        _ => {
-                            return Err(CodegenError {
-                                message: format!("Loop: multi-value returns not yet implemented"),
-                            })
-                        }
-                    },
-                    value_stack_depth: self.value_stack.len(),
+                    value_stack_depth: self.value_stack.len() - params.len(),
+                    params,
+                    returns,
                     state: self.machine.state.clone(),
                     state_diff_id,
                 });
-                a.emit_label(label);
 
                 // Check interrupt signal without branching
                 if self.config.full_preemption {
@@ -6689,6 +7284,282 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 self.value_stack.push(ret);
                 a.emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
             }
+            Operator::RefFunc { function_index } => {
+                self.machine.release_locations_only_osr_state(0);
+
+                Self::emit_call_vmcall(
+                    a,
+                    &mut self.machine,
+                    vmcalls::func_ref as usize,
+                    iter::once(Location::Imm32(function_index)),
+                )?;
+
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(
+                        WpType::AnyFunc,
+                        MachineValue::WasmStack(self.value_stack.len()),
+                    )],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                a.emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
+            }
+            Operator::TableGet { table } => {
+                let index = self.value_stack.pop().unwrap();
+
+                self.machine.release_locations_only_regs(&[index]);
+                self.machine.release_locations_only_osr_state(1);
+
+                Self::emit_call_table_vmcall(
+                    a,
+                    &mut self.machine,
+                    module_info,
+                    table,
+                    vmcalls::local_table_get as usize,
+                    vmcalls::imported_table_get as usize,
+                    &[index],
+                )?;
+
+                self.machine.release_locations_only_stack(a, &[index]);
+
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                a.emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
+            }
+            Operator::TableSet { table } => {
+                let value = self.value_stack.pop().unwrap();
+                let index = self.value_stack.pop().unwrap();
+
+                self.machine.release_locations_only_regs(&[index, value]);
+                self.machine.release_locations_only_osr_state(2);
+
+                Self::emit_call_table_vmcall(
+                    a,
+                    &mut self.machine,
+                    module_info,
+                    table,
+                    vmcalls::local_table_set as usize,
+                    vmcalls::imported_table_set as usize,
+                    &[index, value],
+                )?;
+
+                self.machine
+                    .release_locations_only_stack(a, &[index, value]);
+            }
+            Operator::TableGrow { table } => {
+                let delta = self.value_stack.pop().unwrap();
+                let init = self.value_stack.pop().unwrap();
+
+                self.machine.release_locations_only_regs(&[init, delta]);
+                self.machine.release_locations_only_osr_state(2);
+
+                Self::emit_call_table_vmcall(
+                    a,
+                    &mut self.machine,
+                    module_info,
+                    table,
+                    vmcalls::local_table_grow as usize,
+                    vmcalls::imported_table_grow as usize,
+                    &[delta, init],
+                )?;
+
+                self.machine.release_locations_only_stack(a, &[init, delta]);
+
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                a.emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
+            }
+            Operator::TableSize { table } => {
+                self.machine.release_locations_only_osr_state(0);
+
+                Self::emit_call_table_vmcall(
+                    a,
+                    &mut self.machine,
+                    module_info,
+                    table,
+                    vmcalls::local_table_size as usize,
+                    vmcalls::imported_table_size as usize,
+                    &[],
+                )?;
+
+                let ret = self.machine.acquire_locations(
+                    a,
+                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+                a.emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
+            }
+            Operator::TableFill { table } => {
+                let len = self.value_stack.pop().unwrap();
+                let value = self.value_stack.pop().unwrap();
+                let start = self.value_stack.pop().unwrap();
+
+                self.machine
+                    .release_locations_only_regs(&[start, value, len]);
+                self.machine.release_locations_only_osr_state(3);
+
+                Self::emit_call_table_vmcall(
+                    a,
+                    &mut self.machine,
+                    module_info,
+                    table,
+                    vmcalls::local_table_fill as usize,
+                    vmcalls::imported_table_fill as usize,
+                    &[start, value, len],
+                )?;
+
+                self.machine
+                    .release_locations_only_stack(a, &[start, value, len]);
+            }
+            Operator::MemoryCopy => {
+                let len = self.value_stack.pop().unwrap();
+                let src = self.value_stack.pop().unwrap();
+                let dst = self.value_stack.pop().unwrap();
+
+                self.machine.release_locations_only_regs(&[dst, src, len]);
+                self.machine.release_locations_only_osr_state(3);
+
+                Self::emit_call_memory_vmcall(
+                    a,
+                    &mut self.machine,
+                    module_info,
+                    0,
+                    vmcalls::local_memory_copy as usize,
+                    vmcalls::imported_memory_copy as usize,
+                    &[dst, src, len],
+                )?;
+
+                self.machine
+                    .release_locations_only_stack(a, &[dst, src, len]);
+            }
+            Operator::MemoryFill => {
+                let len = self.value_stack.pop().unwrap();
+                let val = self.value_stack.pop().unwrap();
+                let dst = self.value_stack.pop().unwrap();
+
+                self.machine.release_locations_only_regs(&[dst, val, len]);
+                self.machine.release_locations_only_osr_state(3);
+
+                Self::emit_call_memory_vmcall(
+                    a,
+                    &mut self.machine,
+                    module_info,
+                    0,
+                    vmcalls::local_memory_fill as usize,
+                    vmcalls::imported_memory_fill as usize,
+                    &[dst, val, len],
+                )?;
+
+                self.machine
+                    .release_locations_only_stack(a, &[dst, val, len]);
+            }
+            Operator::MemoryInit { segment } => {
+                let len = self.value_stack.pop().unwrap();
+                let src = self.value_stack.pop().unwrap();
+                let dst = self.value_stack.pop().unwrap();
+
+                self.machine.release_locations_only_regs(&[dst, src, len]);
+                self.machine.release_locations_only_osr_state(3);
+
+                Self::emit_call_memory_vmcall(
+                    a,
+                    &mut self.machine,
+                    module_info,
+                    0,
+                    vmcalls::local_memory_init as usize,
+                    vmcalls::imported_memory_init as usize,
+                    &[Location::Imm32(segment), dst, src, len],
+                )?;
+
+                self.machine
+                    .release_locations_only_stack(a, &[dst, src, len]);
+            }
+            Operator::DataDrop { segment } => {
+                self.machine.release_locations_only_osr_state(0);
+
+                Self::emit_call_vmcall(
+                    a,
+                    &mut self.machine,
+                    vmcalls::data_drop as usize,
+                    iter::once(Location::Imm32(segment)),
+                )?;
+            }
+            Operator::TableCopy {
+                dst_table,
+                src_table,
+            } => {
+                let len = self.value_stack.pop().unwrap();
+                let src = self.value_stack.pop().unwrap();
+                let dst = self.value_stack.pop().unwrap();
+
+                self.machine.release_locations_only_regs(&[dst, src, len]);
+                self.machine.release_locations_only_osr_state(3);
+
+                // Either table may be local or imported, so `table_copy` takes module-wide indices.
+                Self::emit_call_vmcall(
+                    a,
+                    &mut self.machine,
+                    vmcalls::table_copy as usize,
+                    [
+                        Location::Imm32(dst_table),
+                        Location::Imm32(src_table),
+                        dst,
+                        src,
+                        len,
+                    ]
+                    .iter()
+                    .cloned(),
+                )?;
+
+                self.machine
+                    .release_locations_only_stack(a, &[dst, src, len]);
+            }
+            Operator::TableInit { segment, table } => {
+                let len = self.value_stack.pop().unwrap();
+                let src = self.value_stack.pop().unwrap();
+                let dst = self.value_stack.pop().unwrap();
+
+                self.machine.release_locations_only_regs(&[dst, src, len]);
+                self.machine.release_locations_only_osr_state(3);
+
+                Self::emit_call_vmcall(
+                    a,
+                    &mut self.machine,
+                    vmcalls::table_init as usize,
+                    [
+                        Location::Imm32(table),
+                        Location::Imm32(segment),
+                        dst,
+                        src,
+                        len,
+                    ]
+                    .iter()
+                    .cloned(),
+                )?;
+
+                self.machine
+                    .release_locations_only_stack(a, &[dst, src, len]);
+            }
+            Operator::ElemDrop { segment } => {
+                self.machine.release_locations_only_osr_state(0);
+
+                Self::emit_call_vmcall(
+                    a,
+                    &mut self.machine,
+                    vmcalls::elem_drop as usize,
+                    iter::once(Location::Imm32(segment)),
+                )?;
+            }
             Operator::I32Load { ref memarg } => {
                 let target =
                     get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
@@ -7448,22 +8319,13 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             }
             Operator::Return => {
                 let frame = &self.control_stack[0];
-                if frame.returns.len() > 0 {
-                    if frame.returns.len() != 1 {
-                        return Err(CodegenError {
-                            message: format!("Return: incorrect frame.returns"),
-                        });
-                    }
-                    let loc = *self.value_stack.last().unwrap();
-                    Self::emit_relaxed_binop(
-                        a,
-                        &mut self.machine,
-                        Assembler::emit_mov,
-                        Size::S64,
-                        loc,
-                        Location::GPR(GPR::RAX),
-                    );
-                }
+                Self::emit_branch_values(
+                    a,
+                    &mut self.machine,
+                    self.multi_value_area,
+                    &self.value_stack,
+                    frame,
+                )?;
                 let released = &self.value_stack[frame.value_stack_depth..];
                 self.machine.release_locations_keep_state(a, released);
                 a.emit_jmp(Condition::None, frame.label);
@@ -7472,15 +8334,13 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
             Operator::Br { relative_depth } => {
                 let frame =
                     &self.control_stack[self.control_stack.len() - 1 - (relative_depth as usize)];
-                if !frame.loop_like && frame.returns.len() > 0 {
-                    if frame.returns.len() != 1 {
-                        return Err(CodegenError {
-                            message: format!("Br: incorrect frame.returns"),
-                        });
-                    }
-                    let loc = *self.value_stack.last().unwrap();
-                    a.emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                }
+                Self::emit_branch_values(
+                    a,
+                    &mut self.machine,
+                    self.multi_value_area,
+                    &self.value_stack,
+                    frame,
+                )?;
                 let released = &self.value_stack[frame.value_stack_depth..];
                 self.machine.release_locations_keep_state(a, released);
                 a.emit_jmp(Condition::None, frame.label);
@@ -7502,15 +8362,13 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
 
                 let frame =
                     &self.control_stack[self.control_stack.len() - 1 - (relative_depth as usize)];
-                if !frame.loop_like && frame.returns.len() > 0 {
-                    if frame.returns.len() != 1 {
-                        return Err(CodegenError {
-                            message: format!("BrIf: incorrect frame.returns"),
-                        });
-                    }
-                    let loc = *self.value_stack.last().unwrap();
-                    a.emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                }
+                Self::emit_branch_values(
+                    a,
+                    &mut self.machine,
+                    self.multi_value_area,
+                    &self.value_stack,
+                    frame,
+                )?;
                 let released = &self.value_stack[frame.value_stack_depth..];
                 self.machine.release_locations_keep_state(a, released);
                 a.emit_jmp(Condition::None, frame.label);
@@ -7550,18 +8408,13 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                     table.push(label);
                     let frame =
                         &self.control_stack[self.control_stack.len() - 1 - (*target as usize)];
-                    if !frame.loop_like && frame.returns.len() > 0 {
-                        if frame.returns.len() != 1 {
-                            return Err(CodegenError {
-                                message: format!(
-                                    "BrTable: incorrect frame.returns for {:?}",
-                                    target
-                                ),
-                            });
-                        }
-                        let loc = *self.value_stack.last().unwrap();
-                        a.emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                    }
+                    Self::emit_branch_values(
+                        a,
+                        &mut self.machine,
+                        self.multi_value_area,
+                        &self.value_stack,
+                        frame,
+                    )?;
                     let released = &self.value_stack[frame.value_stack_depth..];
                     self.machine.release_locations_keep_state(a, released);
                     a.emit_jmp(Condition::None, frame.label);
@@ -7571,15 +8424,13 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 {
                     let frame = &self.control_stack
                         [self.control_stack.len() - 1 - (default_target as usize)];
-                    if !frame.loop_like && frame.returns.len() > 0 {
-                        if frame.returns.len() != 1 {
-                            return Err(CodegenError {
-                                message: format!("BrTable: incorrect frame.returns"),
-                            });
-                        }
-                        let loc = *self.value_stack.last().unwrap();
-                        a.emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                    }
+                    Self::emit_branch_values(
+                        a,
+                        &mut self.machine,
+                        self.multi_value_area,
+                        &self.value_stack,
+                        frame,
+                    )?;
                     let released = &self.value_stack[frame.value_stack_depth..];
                     self.machine.release_locations_keep_state(a, released);
                     a.emit_jmp(Condition::None, frame.label);
@@ -7595,22 +8446,43 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                 get_location_released(a, &mut self.machine, self.value_stack.pop().unwrap());
             }
             Operator::End => {
-                let frame = self.control_stack.pop().unwrap();
+                let mut frame = self.control_stack.pop().unwrap();
+                let mut was_unreachable = was_unreachable;
+
+                // Without an `else` branch, an `if` passes its params through as its results.
+                if let IfElseState::If(_) = frame.if_else {
+                    if frame.params.len() > 0 {
+                        Self::emit_else(
+                            a,
+                            &mut self.machine,
+                            self.multi_value_area,
+                            &mut self.value_stack,
+                            &mut frame,
+                            was_unreachable,
+                        )?;
+                        was_unreachable = false;
+                    }
+                }
 
                 if !was_unreachable && frame.returns.len() > 0 {
-                    let loc = *self.value_stack.last().unwrap();
-                    Self::emit_relaxed_binop(
+                    Self::emit_store_block_values(
                         a,
                         &mut self.machine,
-                        Assembler::emit_mov,
-                        Size::S64,
-                        loc,
-                        Location::GPR(GPR::RAX),
-                    );
+                        self.multi_value_area,
+                        &self.value_stack[self.value_stack.len() - frame.returns.len()..],
+                    )?;
                 }
 
                 if self.control_stack.len() == 0 {
                     a.emit_label(frame.label);
+                    if frame.returns.len() > 1 {
+                        Self::emit_return_results(
+                            a,
+                            self.multi_value_area,
+                            self.results_pointer,
+                            &frame.returns,
+                        )?;
+                    }
                     self.machine.finalize_locals(a, &self.locals);
                     a.emit_mov(Size::S64, Location::GPR(GPR::RBP), Location::GPR(GPR::RSP));
                     a.emit_pop(Size::S64, Location::GPR(GPR::RBP));
@@ -7628,23 +8500,13 @@ impl FunctionCodeGenerator<CodegenError> for X64FunctionCode {
                         a.emit_label(label);
                     }
 
-                    if frame.returns.len() > 0 {
-                        if frame.returns.len() != 1 {
-                            return Err(CodegenError {
-                                message: format!("End: incorrect frame.returns"),
-                            });
-                        }
-                        let loc = self.machine.acquire_locations(
-                            a,
-                            &[(
-                                frame.returns[0],
-                                MachineValue::WasmStack(self.value_stack.len()),
-                            )],
-                            false,
-                        )[0];
-                        a.emit_mov(Size::S64, Location::GPR(GPR::RAX), loc);
-                        self.value_stack.push(loc);
-                    }
+                    Self::emit_load_block_values(
+                        a,
+                        &mut self.machine,
+                        self.multi_value_area,
+                        &mut self.value_stack,
+                        &frame.returns,
+                    )?;
                 }
             }
             Operator::AtomicFence { flags: _ } => {
@@ -9833,6 +10695,21 @@ fn type_to_wp_type(ty: Type) -> WpType {
         Type::F32 => WpType::F32,
         Type::F64 => WpType::F64,
         Type::V128 => WpType::V128,
+        Type::FuncRef => WpType::AnyFunc,
+        Type::ExternRef => WpType::AnyRef,
+    }
+}
+
+fn wp_type_to_type(ty: WpType) -> Type {
+    match ty {
+        WpType::I32 => Type::I32,
+        WpType::I64 => Type::I64,
+        WpType::F32 => Type::F32,
+        WpType::F64 => Type::F64,
+        WpType::V128 => Type::V128,
+        WpType::AnyFunc => Type::FuncRef,
+        WpType::AnyRef => Type::ExternRef,
+        _ => unreachable!(),
     }
 }
 
@@ -152,7 +152,9 @@ impl Machine {
         for (ty, mv) in tys {
             let loc = match *ty {
                 WpType::F32 | WpType::F64 => self.pick_xmm().map(Location::XMM),
-                WpType::I32 | WpType::I64 => self.pick_gpr().map(Location::GPR),
+                WpType::I32 | WpType::I64 | WpType::AnyFunc | WpType::AnyRef => {
+                    self.pick_gpr().map(Location::GPR)
+                }
                 _ => unreachable!(),
             };
 
@@ -328,7 +330,11 @@ impl Machine {
         a: &mut E,
         n: usize,
         n_params: usize,
+        returns_in_memory: bool,
     ) -> Vec<Location> {
+        // The pointer to the results, if any, comes before vmctx.
+        let vmctx_param = if returns_in_memory { 1 } else { 0 };
+
         // Use callee-saved registers for locals.
         fn get_local_location(idx: usize) -> Location {
             match idx {
@@ -345,7 +351,7 @@ impl Machine {
 
         // Determine locations for parameters.
         for i in 0..n_params {
-            let loc = Self::get_param_location(i + 1);
+            let loc = Self::get_param_location(vmctx_param + i + 1);
             locations.push(match loc {
                 Location::GPR(_) => {
                     let old_idx = allocated;
@@ -422,7 +428,7 @@ impl Machine {
 
         // Load in-register parameters into the allocated locations.
         for i in 0..n_params {
-            let loc = Self::get_param_location(i + 1);
+            let loc = Self::get_param_location(vmctx_param + i + 1);
             match loc {
                 Location::GPR(_) => {
                     a.emit_mov(Size::S64, loc, locations[i]);
@@ -444,7 +450,7 @@ impl Machine {
         // Load vmctx.
         a.emit_mov(
             Size::S64,
-            Self::get_param_location(0),
+            Self::get_param_location(vmctx_param),
             Location::GPR(GPR::R15),
         );
 
@@ -456,6 +462,23 @@ impl Machine {
         locations
     }
 
+    /// Reserves `n` machine stack slots for the whole function, and returns the location of
+    /// the lowest one.
+    ///
+    /// This must be called before any stack value is acquired.
+    pub fn acquire_stack_area<E: Emitter>(&mut self, a: &mut E, n: usize) -> Location {
+        a.emit_sub(
+            Size::S64,
+            Location::Imm32((n * 8) as u32),
+            Location::GPR(GPR::RSP),
+        );
+        self.stack_offset.0 += n * 8;
+        for _ in 0..n {
+            self.state.stack_values.push(MachineValue::Undefined);
+        }
+        Location::Memory(GPR::RBP, -(self.stack_offset.0 as i32))
+    }
+
     pub fn finalize_locals<E: Emitter>(&mut self, a: &mut E, locations: &[Location]) {
         // Unwind stack to the "save area".
         a.emit_lea(
@@ -0,0 +1,170 @@
+;;
+;; Tests of functions with several results, following the cases of the
+;; multi-value proposal version of func.wast.
+;;
+
+(module
+  (func $dummy)
+
+  (func (export "value-i32-i64") (result i32 i64)
+    (call $dummy) (i32.const 1) (i64.const 2)
+  )
+  (func (export "value-f32-f64") (result f32 f64)
+    (call $dummy) (f32.const 3.5) (f64.const 4.5)
+  )
+  (func (export "value-i32-i32-i32") (result i32 i32 i32)
+    (call $dummy) (i32.const 1) (i32.const 2) (i32.const 3)
+  )
+  (func (export "value-mixed") (result i32 f32 i64 i32 f64 i32)
+    (call $dummy)
+    (i32.const 1) (f32.const 2) (i64.const 3) (i32.const 4) (f64.const 5) (i32.const 6)
+  )
+  (func (export "value-block-i32-i64") (result i32 i64)
+    (block (result i32 i64) (call $dummy) (i32.const 1) (i64.const 2))
+  )
+
+  (func (export "return-i32-i64") (result i32 i64)
+    (return (i32.const 1) (i64.const 2))
+  )
+  (func (export "return-mixed") (result i32 f32 i64 i32 f64 i32)
+    (return
+      (i32.const 1) (f32.const 2) (i64.const 3) (i32.const 4) (f64.const 5) (i32.const 6)
+    )
+  )
+
+  (func (export "break-i32-i64") (result i32 i64)
+    (br 0 (i32.const 1) (i64.const 2))
+  )
+  (func (export "break-br_if-i32-i64") (param i32) (result i32 i64)
+    (i32.const 1) (i64.const 2)
+    (br_if 0 (local.get 0))
+    (drop) (drop)
+    (i32.const 3) (i64.const 4)
+  )
+  (func (export "break-br_table-i32-i64") (param i32) (result i32 i64)
+    (block $a (result i32 i64)
+      (i32.const 10) (i64.const 20)
+      (br_table $a 1 (local.get 0))
+    )
+    (i64.const 1)
+    (i64.add)
+  )
+
+  (func $swap (param i32 i32) (result i32 i32) (local.get 1) (local.get 0))
+  (func $mixed (result i32 f32 i64 i32 f64 i32)
+    (i32.const 1) (f32.const 2) (i64.const 3) (i32.const 4) (f64.const 5) (i32.const 6)
+  )
+
+  (func (export "call-swap") (param i32 i32) (result i32 i32)
+    (call $swap (local.get 0) (local.get 1))
+  )
+  (func (export "call-swap-twice") (param i32 i32) (result i32 i32)
+    (call $swap (call $swap (local.get 0) (local.get 1)))
+  )
+  (func (export "call-mixed") (result i32 f32 i64 i32 f64 i32)
+    (call $mixed)
+  )
+
+  (type $sig-swap (func (param i32 i32) (result i32 i32)))
+  (type $sig-mixed (func (result i32 f32 i64 i32 f64 i32)))
+  (table funcref (elem $swap $mixed))
+
+  (func (export "call_indirect-swap") (param i32 i32) (result i32 i32)
+    (call_indirect (type $sig-swap) (local.get 0) (local.get 1) (i32.const 0))
+  )
+  (func (export "call_indirect-mixed") (result i32 f32 i64 i32 f64 i32)
+    (call_indirect (type $sig-mixed) (i32.const 1))
+  )
+
+  (func (export "large-sig")
+    (param i32 i64 f32 f32 i32 f64 f32 i32 i32 i32 f32 f64 f64 f64 i32 i32 f32)
+    (result f64 f32 i32 i32 i32 i64 f32 i32 i32 f32 f64 f64 i32 f32 i32 f64)
+    (local.get 5)
+    (local.get 2)
+    (local.get 0)
+    (local.get 8)
+    (local.get 7)
+    (local.get 1)
+    (local.get 3)
+    (local.get 9)
+    (local.get 4)
+    (local.get 6)
+    (local.get 13)
+    (local.get 11)
+    (local.get 15)
+    (local.get 16)
+    (local.get 14)
+    (local.get 12)
+  )
+)
+
+(assert_return (invoke "value-i32-i64") (i32.const 1) (i64.const 2))
+(assert_return (invoke "value-f32-f64") (f32.const 3.5) (f64.const 4.5))
+(assert_return (invoke "value-i32-i32-i32") (i32.const 1) (i32.const 2) (i32.const 3))
+(assert_return (invoke "value-mixed")
+  (i32.const 1) (f32.const 2) (i64.const 3) (i32.const 4) (f64.const 5) (i32.const 6)
+)
+(assert_return (invoke "value-block-i32-i64") (i32.const 1) (i64.const 2))
+
+(assert_return (invoke "return-i32-i64") (i32.const 1) (i64.const 2))
+(assert_return (invoke "return-mixed")
+  (i32.const 1) (f32.const 2) (i64.const 3) (i32.const 4) (f64.const 5) (i32.const 6)
+)
+
+(assert_return (invoke "break-i32-i64") (i32.const 1) (i64.const 2))
+(assert_return (invoke "break-br_if-i32-i64" (i32.const 0)) (i32.const 3) (i64.const 4))
+(assert_return (invoke "break-br_if-i32-i64" (i32.const 1)) (i32.const 1) (i64.const 2))
+(assert_return (invoke "break-br_table-i32-i64" (i32.const 0)) (i32.const 10) (i64.const 21))
+(assert_return (invoke "break-br_table-i32-i64" (i32.const 1)) (i32.const 10) (i64.const 20))
+(assert_return (invoke "break-br_table-i32-i64" (i32.const 7)) (i32.const 10) (i64.const 20))
+
+(assert_return (invoke "call-swap" (i32.const 1) (i32.const 2)) (i32.const 2) (i32.const 1))
+(assert_return (invoke "call-swap-twice" (i32.const 1) (i32.const 2)) (i32.const 1) (i32.const 2))
+(assert_return (invoke "call-mixed")
+  (i32.const 1) (f32.const 2) (i64.const 3) (i32.const 4) (f64.const 5) (i32.const 6)
+)
+(assert_return (invoke "call_indirect-swap" (i32.const 1) (i32.const 2))
+  (i32.const 2) (i32.const 1)
+)
+(assert_return (invoke "call_indirect-mixed")
+  (i32.const 1) (f32.const 2) (i64.const 3) (i32.const 4) (f64.const 5) (i32.const 6)
+)
+
+(assert_return
+  (invoke "large-sig"
+    (i32.const 0) (i64.const 1) (f32.const 2) (f32.const 3)
+    (i32.const 4) (f64.const 5) (f32.const 6) (i32.const 7)
+    (i32.const 8) (i32.const 9) (f32.const 10) (f64.const 11)
+    (f64.const 12) (f64.const 13) (i32.const 14) (i32.const 15)
+    (f32.const 16)
+  )
+  (f64.const 5) (f32.const 2) (i32.const 0) (i32.const 8)
+  (i32.const 7) (i64.const 1) (f32.const 3) (i32.const 9)
+  (i32.const 4) (f32.const 6) (f64.const 13) (f64.const 11)
+  (i32.const 15) (f32.const 16) (i32.const 14) (f64.const 12)
+)
+
+(assert_invalid
+  (module (func $type-empty-i32-i64 (result i32 i64)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $type-value-num-vs-nums (result i32 i32) (i32.const 0)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $type-value-nums-vs-num (result i32) (i32.const 0) (i32.const 0)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $type-return-partial-vs-nums (result i32 i64)
+    (return (i32.const 1))
+  ))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $type-break-partial-vs-nums (result i32 i64)
+    (br 0 (i64.const 1))
+  ))
+  "type mismatch"
+)
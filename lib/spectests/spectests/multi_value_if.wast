@@ -0,0 +1,178 @@
+;;
+;; Tests of ifs with several results and with parameters, following the cases
+;; of the multi-value proposal version of if.wast.
+;;
+
+(module
+  (func $dummy)
+
+  (func (export "multi") (param i32) (result i32 i64 i32)
+    (if (result i32 i64 i32) (local.get 0)
+      (then
+        (call $dummy) (i32.const 8) (call $dummy)
+        (i64.const 1) (call $dummy) (i32.const 9)
+      )
+      (else
+        (call $dummy) (i32.const -8) (call $dummy)
+        (i64.const -1) (call $dummy) (i32.const -9)
+      )
+    )
+  )
+
+  (func (export "as-binary-operands") (param i32) (result i32)
+    (if (result i32 i32) (local.get 0)
+      (then (call $dummy) (i32.const 3) (call $dummy) (i32.const 4))
+      (else (call $dummy) (i32.const 3) (call $dummy) (i32.const -4))
+    )
+    (i32.mul)
+  )
+  (func (export "as-compare-operands") (param i32) (result i32)
+    (if (result f32 f32) (local.get 0)
+      (then (call $dummy) (f32.const 3) (call $dummy) (f32.const 3))
+      (else (call $dummy) (f32.const -2) (call $dummy) (f32.const -3))
+    )
+    (f32.gt)
+  )
+  (func (export "as-mixed-operands") (param i32) (result i32)
+    (if (result i32 i32) (local.get 0)
+      (then (call $dummy) (i32.const 3) (call $dummy) (i32.const 4))
+      (else (call $dummy) (i32.const -3) (call $dummy) (i32.const -4))
+    )
+    (i32.const 5)
+    (i32.add)
+    (i32.mul)
+  )
+
+  (func (export "break-multi-value") (param i32) (result i32 i32 i64)
+    (if (result i32 i32 i64) (local.get 0)
+      (then
+        (br 0 (i32.const 18) (i32.const -18) (i64.const 18))
+        (i32.const 19) (i32.const -19) (i64.const 19)
+      )
+      (else
+        (br 0 (i32.const -18) (i32.const 18) (i64.const -18))
+        (i32.const -19) (i32.const 19) (i64.const -19)
+      )
+    )
+  )
+
+  (func (export "param") (param i32) (result i32)
+    (i32.const 1)
+    (if (param i32) (result i32) (local.get 0)
+      (then (i32.const 2) (i32.add))
+      (else (i32.const -2) (i32.add))
+    )
+  )
+  (func (export "params") (param i32) (result i32)
+    (i32.const 1)
+    (i32.const 2)
+    (if (param i32 i32) (result i32) (local.get 0)
+      (then (i32.add))
+      (else (i32.sub))
+    )
+  )
+  (func (export "params-id") (param i32) (result i32)
+    (i32.const 1)
+    (i32.const 2)
+    (if (param i32 i32) (result i32 i32) (local.get 0) (then))
+    (i32.add)
+  )
+  (func (export "param-break") (param i32) (result i32)
+    (i32.const 1)
+    (if (param i32) (result i32) (local.get 0)
+      (then (i32.const 2) (i32.add) (br 0))
+      (else (i32.const -2) (i32.add) (br 0))
+    )
+  )
+  (func (export "params-break") (param i32) (result i32)
+    (i32.const 1)
+    (i32.const 2)
+    (if (param i32 i32) (result i32) (local.get 0)
+      (then (i32.add) (br 0))
+      (else (i32.sub) (br 0))
+    )
+  )
+  (func (export "params-id-break") (param i32) (result i32)
+    (i32.const 1)
+    (i32.const 2)
+    (if (param i32 i32) (result i32 i32) (local.get 0) (then (br 0)))
+    (i32.add)
+  )
+
+  (type $block-sig-4 (func (param i32 f64 i32) (result i32 f64 i32)))
+
+  (func (export "type-use") (param i32) (result i32)
+    (i32.const 1) (f64.const 2) (i32.const 3)
+    (if (type $block-sig-4) (local.get 0)
+      (then)
+      (else (drop) (drop) (drop) (i32.const -1) (f64.const -2) (i32.const -3))
+    )
+    (drop) (drop)
+  )
+)
+
+(assert_return (invoke "multi" (i32.const 0)) (i32.const -8) (i64.const -1) (i32.const -9))
+(assert_return (invoke "multi" (i32.const 1)) (i32.const 8) (i64.const 1) (i32.const 9))
+
+(assert_return (invoke "as-binary-operands" (i32.const 0)) (i32.const -12))
+(assert_return (invoke "as-binary-operands" (i32.const 1)) (i32.const 12))
+(assert_return (invoke "as-compare-operands" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "as-compare-operands" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "as-mixed-operands" (i32.const 0)) (i32.const -3))
+(assert_return (invoke "as-mixed-operands" (i32.const 1)) (i32.const 27))
+
+(assert_return (invoke "break-multi-value" (i32.const 0))
+  (i32.const -18) (i32.const 18) (i64.const -18)
+)
+(assert_return (invoke "break-multi-value" (i32.const 1))
+  (i32.const 18) (i32.const -18) (i64.const 18)
+)
+
+(assert_return (invoke "param" (i32.const 0)) (i32.const -1))
+(assert_return (invoke "param" (i32.const 1)) (i32.const 3))
+(assert_return (invoke "params" (i32.const 0)) (i32.const -1))
+(assert_return (invoke "params" (i32.const 1)) (i32.const 3))
+(assert_return (invoke "params-id" (i32.const 0)) (i32.const 3))
+(assert_return (invoke "params-id" (i32.const 1)) (i32.const 3))
+(assert_return (invoke "param-break" (i32.const 0)) (i32.const -1))
+(assert_return (invoke "param-break" (i32.const 1)) (i32.const 3))
+(assert_return (invoke "params-break" (i32.const 0)) (i32.const -1))
+(assert_return (invoke "params-break" (i32.const 1)) (i32.const 3))
+(assert_return (invoke "params-id-break" (i32.const 0)) (i32.const 3))
+(assert_return (invoke "params-id-break" (i32.const 1)) (i32.const 3))
+
+(assert_return (invoke "type-use" (i32.const 0)) (i32.const -1))
+(assert_return (invoke "type-use" (i32.const 1)) (i32.const 1))
+
+(assert_invalid
+  (module (func $type-param-void-vs-num
+    (if (param i32) (i32.const 1) (then (drop)))
+  ))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $type-then-value-nums-vs-void
+    (if (i32.const 1) (then (i32.const 1) (i32.const 2)))
+  ))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $type-else-value-empty-vs-nums (result i32 i32)
+    (if (result i32 i32) (i32.const 0) (then (i32.const 0) (i32.const 1)) (else))
+  ))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $type-no-else-vs-nums (result i32 i32)
+    (if (result i32 i32) (i32.const 1) (then (i32.const 0) (i32.const 1)))
+  ))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $type-params-no-else-vs-results (result i32)
+    (i32.const 0)
+    (i32.const 1)
+    (if (param i32 i32) (result i32) (i32.const 1) (then (i32.add)))
+  ))
+  "type mismatch"
+)
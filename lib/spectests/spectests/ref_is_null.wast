@@ -0,0 +1,53 @@
+;;
+;; Tests of ref.is_null, following the cases of the reference types proposal
+;; testsuite.
+;;
+
+(module
+  (table $t1 2 funcref)
+  (table $t2 2 anyref)
+  (elem (i32.const 1) $dummy)
+  (func $dummy)
+
+  (func $f1 (export "funcref") (param $x i32) (result i32)
+    (ref.is_null (table.get $t1 (local.get $x)))
+  )
+  (func $f2 (export "anyref") (param $x i32) (result i32)
+    (ref.is_null (table.get $t2 (local.get $x)))
+  )
+  (func (export "null") (result i32) (ref.is_null (ref.null)))
+  (func (export "func") (result i32) (ref.is_null (ref.func $dummy)))
+
+  (func (export "init") (param $x i32)
+    (table.set $t1 (local.get $x) (ref.func $dummy))
+  )
+  (func (export "deinit") (param $x i32)
+    (table.set $t1 (local.get $x) (ref.null))
+    (table.set $t2 (local.get $x) (ref.null))
+  )
+)
+
+(assert_return (invoke "null") (i32.const 1))
+(assert_return (invoke "func") (i32.const 0))
+
+(assert_return (invoke "funcref" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "funcref" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "anyref" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "anyref" (i32.const 1)) (i32.const 1))
+
+(invoke "init" (i32.const 0))
+(assert_return (invoke "funcref" (i32.const 0)) (i32.const 0))
+
+(invoke "deinit" (i32.const 1))
+(assert_return (invoke "funcref" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "funcref" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "anyref" (i32.const 1)) (i32.const 1))
+
+(assert_invalid
+  (module (func $arg (param i32) (result i32) (ref.is_null (local.get 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $arg-empty (result i32) (ref.is_null)))
+  "type mismatch"
+)
@@ -0,0 +1,31 @@
+;;
+;; Tests of ref.null, following the cases of the reference types proposal
+;; testsuite. References are observed through ref.is_null, as the script
+;; values only cover the numeric types.
+;;
+
+(module
+  (global $a anyref (ref.null))
+  (global $f funcref (ref.null))
+  (global $ma (mut anyref) (ref.null))
+  (global $mf (mut funcref) (ref.null))
+
+  (func (export "anyref") (result i32) (ref.is_null (ref.null)))
+  (func (export "funcref") (result i32) (ref.is_null (ref.null)))
+  (func (export "global-anyref") (result i32) (ref.is_null (global.get $a)))
+  (func (export "global-funcref") (result i32) (ref.is_null (global.get $f)))
+  (func (export "global-mut-anyref") (result i32) (ref.is_null (global.get $ma)))
+  (func (export "global-mut-funcref") (result i32) (ref.is_null (global.get $mf)))
+)
+
+(assert_return (invoke "anyref") (i32.const 1))
+(assert_return (invoke "funcref") (i32.const 1))
+(assert_return (invoke "global-anyref") (i32.const 1))
+(assert_return (invoke "global-funcref") (i32.const 1))
+(assert_return (invoke "global-mut-anyref") (i32.const 1))
+(assert_return (invoke "global-mut-funcref") (i32.const 1))
+
+(assert_invalid
+  (module (func $ref-null-as-i32 (result i32) (ref.null)))
+  "type mismatch"
+)
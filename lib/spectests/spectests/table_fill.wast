@@ -0,0 +1,141 @@
+;;
+;; Tests of table.fill, following the cases of the reference types proposal
+;; testsuite. References are observed through ref.is_null and by calling
+;; them with call_indirect, as the script values only cover the numeric types.
+;;
+
+(module
+  (table $t 10 funcref)
+  (type $ft (func (result i32)))
+  (func $f1 (result i32) (i32.const 1))
+  (func $f2 (result i32) (i32.const 2))
+
+  (func (export "fill-null") (param $i i32) (param $n i32)
+    (table.fill $t (local.get $i) (ref.null) (local.get $n))
+  )
+  (func (export "fill-f1") (param $i i32) (param $n i32)
+    (table.fill $t (local.get $i) (ref.func $f1) (local.get $n))
+  )
+  (func (export "fill-f2") (param $i i32) (param $n i32)
+    (table.fill $t (local.get $i) (ref.func $f2) (local.get $n))
+  )
+
+  (func (export "is_null") (param $i i32) (result i32)
+    (ref.is_null (table.get $t (local.get $i)))
+  )
+  (func (export "call") (param $i i32) (result i32)
+    (call_indirect (type $ft) (local.get $i))
+  )
+)
+
+(assert_return (invoke "is_null" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "is_null" (i32.const 2)) (i32.const 1))
+(assert_return (invoke "is_null" (i32.const 3)) (i32.const 1))
+(assert_return (invoke "is_null" (i32.const 4)) (i32.const 1))
+(assert_return (invoke "is_null" (i32.const 5)) (i32.const 1))
+
+(assert_return (invoke "fill-f1" (i32.const 2) (i32.const 3)))
+(assert_return (invoke "is_null" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "call" (i32.const 2)) (i32.const 1))
+(assert_return (invoke "call" (i32.const 3)) (i32.const 1))
+(assert_return (invoke "call" (i32.const 4)) (i32.const 1))
+(assert_return (invoke "is_null" (i32.const 5)) (i32.const 1))
+
+(assert_return (invoke "fill-f2" (i32.const 4) (i32.const 2)))
+(assert_return (invoke "call" (i32.const 3)) (i32.const 1))
+(assert_return (invoke "call" (i32.const 4)) (i32.const 2))
+(assert_return (invoke "call" (i32.const 5)) (i32.const 2))
+(assert_return (invoke "is_null" (i32.const 6)) (i32.const 1))
+
+(assert_return (invoke "fill-f1" (i32.const 4) (i32.const 0)))
+(assert_return (invoke "call" (i32.const 3)) (i32.const 1))
+(assert_return (invoke "call" (i32.const 4)) (i32.const 2))
+(assert_return (invoke "call" (i32.const 5)) (i32.const 2))
+
+(assert_return (invoke "fill-f2" (i32.const 8) (i32.const 2)))
+(assert_return (invoke "is_null" (i32.const 7)) (i32.const 1))
+(assert_return (invoke "call" (i32.const 8)) (i32.const 2))
+(assert_return (invoke "call" (i32.const 9)) (i32.const 2))
+
+(assert_return (invoke "fill-null" (i32.const 9) (i32.const 1)))
+(assert_return (invoke "call" (i32.const 8)) (i32.const 2))
+(assert_return (invoke "is_null" (i32.const 9)) (i32.const 1))
+
+(assert_return (invoke "fill-f1" (i32.const 10) (i32.const 0)))
+(assert_return (invoke "is_null" (i32.const 9)) (i32.const 1))
+
+;; An out of bounds fill traps without writing any element.
+(assert_trap (invoke "fill-f1" (i32.const 8) (i32.const 3)) "out of bounds")
+(assert_return (invoke "call" (i32.const 8)) (i32.const 2))
+(assert_return (invoke "is_null" (i32.const 9)) (i32.const 1))
+
+(assert_trap (invoke "fill-null" (i32.const 11) (i32.const 0)) "out of bounds")
+(assert_trap (invoke "fill-f2" (i32.const -1) (i32.const 1)) "out of bounds")
+
+
+;; Type errors
+
+(assert_invalid
+  (module
+    (table $t 10 anyref)
+    (func $type-index-value-length-empty-vs-i32-i32
+      (table.fill $t)
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 10 anyref)
+    (func $type-index-empty-vs-i32
+      (table.fill $t (ref.null) (i32.const 1))
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 10 anyref)
+    (func $type-value-empty-vs
+      (table.fill $t (i32.const 1) (i32.const 1))
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 10 anyref)
+    (func $type-length-empty-vs-i32
+      (table.fill $t (i32.const 1) (ref.null))
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 0 anyref)
+    (func $type-index-f32-vs-i32
+      (table.fill $t (f32.const 1) (ref.null) (i32.const 1))
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 0 funcref)
+    (func $type-value-anyref-vs-funcref (param $r anyref)
+      (table.fill $t (i32.const 1) (local.get $r) (i32.const 1))
+    )
+  )
+  "type mismatch"
+)
+
+(assert_invalid
+  (module
+    (table $t 1 anyref)
+    (func $type-result-empty-vs-num (result i32)
+      (table.fill $t (i32.const 0) (ref.null) (i32.const 1))
+    )
+  )
+  "type mismatch"
+)
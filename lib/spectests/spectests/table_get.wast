@@ -0,0 +1,89 @@
+;;
+;; Tests of table.get, following the cases of the reference types proposal
+;; testsuite. References are observed through ref.is_null and by calling
+;; them with call_indirect, as the script values only cover the numeric types.
+;;
+
+(module
+  (table $t3 3 funcref)
+  (table $t2 2 anyref)
+  (elem (i32.const 1) $dummy)
+  (func $dummy (result i32) (i32.const 7))
+  (type $ft (func (result i32)))
+
+  (func (export "is_null-anyref") (param $i i32) (result i32)
+    (ref.is_null (table.get $t2 (local.get $i)))
+  )
+  (func (export "is_null-funcref") (param $i i32) (result i32)
+    (ref.is_null (table.get $t3 (local.get $i)))
+  )
+  (func (export "call-funcref") (param $i i32) (result i32)
+    (table.set $t3 (i32.const 2) (table.get $t3 (local.get $i)))
+    (call_indirect (type $ft) (i32.const 2))
+  )
+)
+
+(assert_return (invoke "is_null-anyref" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "is_null-anyref" (i32.const 1)) (i32.const 1))
+
+(assert_return (invoke "is_null-funcref" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "is_null-funcref" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "is_null-funcref" (i32.const 2)) (i32.const 1))
+(assert_return (invoke "call-funcref" (i32.const 1)) (i32.const 7))
+(assert_return (invoke "is_null-funcref" (i32.const 2)) (i32.const 0))
+
+(assert_trap (invoke "is_null-anyref" (i32.const 2)) "out of bounds")
+(assert_trap (invoke "is_null-funcref" (i32.const 3)) "out of bounds")
+(assert_trap (invoke "is_null-anyref" (i32.const -1)) "out of bounds")
+(assert_trap (invoke "is_null-funcref" (i32.const -1)) "out of bounds")
+
+;; Type errors
+
+(assert_invalid
+  (module
+    (table $t 10 anyref)
+    (func $type-index-empty-vs-i32 (result anyref)
+      (table.get $t)
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 10 anyref)
+    (func $type-index-f32-vs-i32 (result anyref)
+      (table.get $t (f32.const 1))
+    )
+  )
+  "type mismatch"
+)
+
+(assert_invalid
+  (module
+    (table $t 10 anyref)
+    (func $type-result-anyref-vs-empty
+      (table.get $t (i32.const 0))
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 10 anyref)
+    (func $type-result-anyref-vs-funcref (result funcref)
+      (table.get $t (i32.const 1))
+    )
+  )
+  "type mismatch"
+)
+
+(assert_invalid
+  (module
+    (table $t1 1 funcref)
+    (table $t2 1 anyref)
+    (func $type-result-anyref-vs-funcref-multi (result funcref)
+      (table.get $t2 (i32.const 0))
+    )
+  )
+  "type mismatch"
+)
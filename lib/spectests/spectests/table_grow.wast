@@ -0,0 +1,178 @@
+;;
+;; Tests of table.grow, following the cases of the reference types proposal
+;; testsuite. References are observed through ref.is_null and by calling
+;; them with call_indirect, as the script values only cover the numeric types.
+;;
+
+(module
+  (table $t 0 anyref)
+
+  (func (export "is_null") (param $i i32) (result i32)
+    (ref.is_null (table.get $t (local.get $i)))
+  )
+  (func (export "set-null") (param $i i32)
+    (table.set $t (local.get $i) (ref.null))
+  )
+
+  (func (export "grow") (param $sz i32) (result i32)
+    (table.grow $t (ref.null) (local.get $sz))
+  )
+  (func (export "size") (result i32) (table.size $t))
+)
+
+(assert_return (invoke "size") (i32.const 0))
+(assert_trap (invoke "set-null" (i32.const 0)) "out of bounds")
+(assert_trap (invoke "is_null" (i32.const 0)) "out of bounds")
+
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "size") (i32.const 1))
+(assert_return (invoke "is_null" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "set-null" (i32.const 0)))
+(assert_return (invoke "is_null" (i32.const 0)) (i32.const 1))
+(assert_trap (invoke "set-null" (i32.const 1)) "out of bounds")
+(assert_trap (invoke "is_null" (i32.const 1)) "out of bounds")
+
+(assert_return (invoke "grow" (i32.const 4)) (i32.const 1))
+(assert_return (invoke "size") (i32.const 5))
+(assert_return (invoke "is_null" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "is_null" (i32.const 4)) (i32.const 1))
+(assert_return (invoke "set-null" (i32.const 4)))
+(assert_trap (invoke "set-null" (i32.const 5)) "out of bounds")
+(assert_trap (invoke "is_null" (i32.const 5)) "out of bounds")
+
+
+;; Reject growing to size outside i32 value range
+(module
+  (table $t 0x10 anyref)
+  (func (export "grow") (result i32)
+    (table.grow $t (ref.null) (i32.const 0xffff_fff0))
+  )
+)
+
+(assert_return (invoke "grow") (i32.const -1))
+
+
+(module
+  (table $t 0 anyref)
+  (func (export "grow") (param i32) (result i32)
+    (table.grow $t (ref.null) (local.get 0))
+  )
+)
+
+(assert_return (invoke "grow" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "grow" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "grow" (i32.const 2)) (i32.const 1))
+(assert_return (invoke "grow" (i32.const 800)) (i32.const 3))
+
+
+(module
+  (table $t 0 10 anyref)
+  (func (export "grow") (param i32) (result i32)
+    (table.grow $t (ref.null) (local.get 0))
+  )
+)
+
+(assert_return (invoke "grow" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "grow" (i32.const 2)) (i32.const 2))
+(assert_return (invoke "grow" (i32.const 6)) (i32.const 4))
+(assert_return (invoke "grow" (i32.const 0)) (i32.const 10))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const -1))
+(assert_return (invoke "grow" (i32.const 0x10000)) (i32.const -1))
+
+
+;; Growing a funcref table initializes the new elements
+
+(module
+  (table $t 1 funcref)
+  (type $ft (func (result i32)))
+  (func $f (result i32) (i32.const 3))
+
+  (func (export "grow-f") (param $sz i32) (result i32)
+    (table.grow $t (ref.func $f) (local.get $sz))
+  )
+  (func (export "is_null") (param $i i32) (result i32)
+    (ref.is_null (table.get $t (local.get $i)))
+  )
+  (func (export "call") (param $i i32) (result i32)
+    (call_indirect (type $ft) (local.get $i))
+  )
+)
+
+(assert_return (invoke "grow-f" (i32.const 2)) (i32.const 1))
+(assert_return (invoke "is_null" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "is_null" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "call" (i32.const 1)) (i32.const 3))
+(assert_return (invoke "call" (i32.const 2)) (i32.const 3))
+(assert_trap (invoke "call" (i32.const 0)) "uninitialized element")
+(assert_trap (invoke "call" (i32.const 3)) "undefined element")
+
+
+;; Type errors
+
+(assert_invalid
+  (module
+    (table $t 0 anyref)
+    (func $type-init-size-empty-vs-i32-anyref (result i32)
+      (table.grow $t)
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 0 anyref)
+    (func $type-size-empty-vs-i32 (result i32)
+      (table.grow $t (ref.null))
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 0 anyref)
+    (func $type-init-empty-vs-anyref (result i32)
+      (table.grow $t (i32.const 1))
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 0 anyref)
+    (func $type-size-f32-vs-i32 (result i32)
+      (table.grow $t (ref.null) (f32.const 1))
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 0 funcref)
+    (func $type-init-anyref-vs-funcref (param $r anyref) (result i32)
+      (table.grow $t (local.get $r) (i32.const 1))
+    )
+  )
+  "type mismatch"
+)
+
+(assert_invalid
+  (module
+    (table $t 1 anyref)
+    (func $type-result-i32-vs-empty
+      (table.grow $t (ref.null) (i32.const 0))
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 1 anyref)
+    (func $type-result-i32-vs-f32 (result f32)
+      (table.grow $t (ref.null) (i32.const 0))
+    )
+  )
+  "type mismatch"
+)
@@ -0,0 +1,114 @@
+;;
+;; Tests of table.set, following the cases of the reference types proposal
+;; testsuite. References are observed through ref.is_null and by calling
+;; them with call_indirect, as the script values only cover the numeric types.
+;;
+
+(module
+  (table $t3 2 funcref)
+  (table $t2 1 anyref)
+  (elem (i32.const 1) $dummy)
+  (func $dummy (result i32) (i32.const 7))
+  (type $ft (func (result i32)))
+
+  (func (export "is_null-anyref") (param $i i32) (result i32)
+    (ref.is_null (table.get $t2 (local.get $i)))
+  )
+  (func (export "is_null-funcref") (param $i i32) (result i32)
+    (ref.is_null (table.get $t3 (local.get $i)))
+  )
+  (func (export "call-funcref") (param $i i32) (result i32)
+    (call_indirect (type $ft) (local.get $i))
+  )
+
+  (func (export "set-anyref-null") (param $i i32)
+    (table.set $t2 (local.get $i) (ref.null))
+  )
+  (func (export "set-funcref") (param $i i32) (param $j i32)
+    (table.set $t3 (local.get $i) (table.get $t3 (local.get $j)))
+  )
+  (func (export "set-funcref-null") (param $i i32)
+    (table.set $t3 (local.get $i) (ref.null))
+  )
+  (func (export "set-funcref-dummy") (param $i i32)
+    (table.set $t3 (local.get $i) (ref.func $dummy))
+  )
+)
+
+(assert_return (invoke "is_null-anyref" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "set-anyref-null" (i32.const 0)))
+(assert_return (invoke "is_null-anyref" (i32.const 0)) (i32.const 1))
+
+(assert_return (invoke "is_null-funcref" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "set-funcref" (i32.const 0) (i32.const 1)))
+(assert_return (invoke "is_null-funcref" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "call-funcref" (i32.const 0)) (i32.const 7))
+(assert_return (invoke "set-funcref-null" (i32.const 0)))
+(assert_return (invoke "is_null-funcref" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "set-funcref-dummy" (i32.const 0)))
+(assert_return (invoke "is_null-funcref" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "call-funcref" (i32.const 0)) (i32.const 7))
+
+(assert_trap (invoke "set-anyref-null" (i32.const 2)) "out of bounds")
+(assert_trap (invoke "set-funcref-null" (i32.const 3)) "out of bounds")
+(assert_trap (invoke "set-anyref-null" (i32.const -1)) "out of bounds")
+(assert_trap (invoke "set-funcref-null" (i32.const -1)) "out of bounds")
+(assert_trap (invoke "set-funcref" (i32.const 0) (i32.const 2)) "out of bounds")
+
+;; Type errors
+
+(assert_invalid
+  (module
+    (table $t 10 anyref)
+    (func $type-index-value-empty-vs-i32-anyref
+      (table.set $t)
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 10 anyref)
+    (func $type-index-empty-vs-i32
+      (table.set $t (ref.null))
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 10 anyref)
+    (func $type-value-empty-vs-anyref
+      (table.set $t (i32.const 1))
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 10 anyref)
+    (func $type-index-f32-vs-i32
+      (table.set $t (f32.const 1) (ref.null))
+    )
+  )
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table $t 10 funcref)
+    (func $type-value-anyref-vs-funcref (param $r anyref)
+      (table.set $t (i32.const 1) (local.get $r))
+    )
+  )
+  "type mismatch"
+)
+
+(assert_invalid
+  (module
+    (table $t 10 anyref)
+    (func $type-result-empty-vs-num (result i32)
+      (table.set $t (i32.const 0) (ref.null))
+    )
+  )
+  "type mismatch"
+)
@@ -1,10 +1,7 @@
 #[cfg(test)]
 mod tests {
     use wabt::wat2wasm;
-    use wasmer_runtime::{
-        error::{CallError, RuntimeError},
-        ExceptionCode, ImportObject,
-    };
+    use wasmer_runtime::{error::CallError, ImportObject};
 
     // The semantics of stack overflow are documented at:
     // https://webassembly.org/docs/semantics/#stack-overflow
@@ -29,9 +26,8 @@ mod tests {
 
         match result {
             Err(err) => match err {
-                CallError::Runtime(RuntimeError(e)) => {
-                    e.downcast::<ExceptionCode>()
-                        .expect("expecting exception code");
+                CallError::Runtime(e) => {
+                    e.exception_code().expect("expecting exception code");
                 }
                 _ => unimplemented!(),
             },
@@ -250,12 +250,43 @@ mod tests {
         compile_with_config,
         error::CompileError,
         func, imports,
-        types::{ElementType, MemoryDescriptor, TableDescriptor},
+        types::{ElementType, MemoryDescriptor, NativeWasmType, TableDescriptor},
         units::Pages,
         CompilerConfig, Ctx, Export, Features, Global, ImportObject, Instance, LikeNamespace,
         Memory, Table,
     };
 
+    /// The files testing the bulk memory operations proposal. The proposal is only enabled
+    /// for them because it changes how the segments tested by other files are initialized.
+    const BULK_MEMORY_TESTS: &[&str] = &[
+        "bulk.wast",
+        "memory_copy.wast",
+        "memory_fill.wast",
+        "memory_init.wast",
+        "table_copy.wast",
+        "table_init.wast",
+    ];
+
+    /// The files testing the reference types proposal, which is only enabled for them.
+    const REFERENCE_TYPES_TESTS: &[&str] = &[
+        "ref_func.wast",
+        "ref_is_null.wast",
+        "ref_null.wast",
+        "table_fill.wast",
+        "table_get.wast",
+        "table_grow.wast",
+        "table_set.wast",
+        "table_size.wast",
+    ];
+
+    /// The files testing the multi-value proposal, which is only enabled for them.
+    const MULTI_VALUE_TESTS: &[&str] = &[
+        "multi_value_block.wast",
+        "multi_value_func.wast",
+        "multi_value_if.wast",
+        "multi_value_loop.wast",
+    ];
+
     fn parse_and_run(
         path: &PathBuf,
         file_excludes: &HashSet<String>,
@@ -276,9 +307,22 @@ mod tests {
             return Ok(test_report);
         }
 
+        let bulk_memory = BULK_MEMORY_TESTS.contains(&filename);
+        let reference_types = REFERENCE_TYPES_TESTS.contains(&filename);
+        let multi_value = MULTI_VALUE_TESTS.contains(&filename);
+
         let mut features = wabt::Features::new();
         features.enable_simd();
         features.enable_threads();
+        if bulk_memory {
+            features.enable_bulk_memory();
+        }
+        if reference_types {
+            features.enable_reference_types();
+        }
+        if multi_value {
+            features.enable_multi_value();
+        }
         features.enable_sign_extension();
         features.enable_sat_float_to_int();
         let mut parser: ScriptParser =
@@ -325,6 +369,9 @@ mod tests {
                             features: Features {
                                 simd: true,
                                 threads: true,
+                                reference_types,
+                                bulk_memory,
+                                multi_value,
                             },
                             ..Default::default()
                         };
@@ -701,7 +748,7 @@ mod tests {
                             );
                         } else {
                             let call_result = maybe_call_result.unwrap();
-                            use wasmer_runtime::error::{CallError, RuntimeError};
+                            use wasmer_runtime::error::CallError;
                             match call_result {
                                 Err(e) => match e {
                                     CallError::Resolve(_) => {
@@ -717,9 +764,8 @@ mod tests {
                                             line,
                                         );
                                     }
-                                    CallError::Runtime(RuntimeError(e)) => {
-                                        use wasmer_runtime::ExceptionCode;
-                                        if let Some(_) = e.downcast_ref::<ExceptionCode>() {
+                                    CallError::Runtime(e) => {
+                                        if e.exception_code().is_some() {
                                             test_report.count_passed();
                                         } else {
                                             test_report.add_failure(
@@ -763,6 +809,9 @@ mod tests {
                             features: Features {
                                 simd: true,
                                 threads: true,
+                                reference_types,
+                                bulk_memory,
+                                multi_value,
                             },
                             ..Default::default()
                         };
@@ -815,6 +864,9 @@ mod tests {
                             features: Features {
                                 simd: true,
                                 threads: true,
+                                reference_types,
+                                bulk_memory,
+                                multi_value,
                             },
                             ..Default::default()
                         };
@@ -866,6 +918,9 @@ mod tests {
                         features: Features {
                             simd: true,
                             threads: true,
+                            reference_types,
+                            bulk_memory,
+                            multi_value,
                         },
                         ..Default::default()
                     };
@@ -961,6 +1016,9 @@ mod tests {
                             features: Features {
                                 simd: true,
                                 threads: true,
+                                reference_types,
+                                bulk_memory,
+                                multi_value,
                             },
                             ..Default::default()
                         };
@@ -1144,6 +1202,8 @@ mod tests {
             wasmer_runtime::types::Value::F32(x) => format!("{:#x}", x.to_bits()),
             wasmer_runtime::types::Value::F64(x) => format!("{:#x}", x.to_bits()),
             wasmer_runtime::types::Value::V128(x) => format!("{:#x}", x),
+            wasmer_runtime::types::Value::FuncRef(x) => format!("{:#x}", x.to_raw()),
+            wasmer_runtime::types::Value::ExternRef(x) => format!("{:#x}", x.to_binary()),
         }
     }
 
@@ -1154,6 +1214,8 @@ mod tests {
         F32(u32),
         F64(u64),
         V128(u128),
+        FuncRef(u64),
+        ExternRef(u64),
     }
 
     fn convert_wasmer_value(other: wasmer_runtime::types::Value) -> SpectestValue {
@@ -1163,6 +1225,8 @@ mod tests {
             wasmer_runtime::types::Value::F32(v) => SpectestValue::F32(v.to_bits()),
             wasmer_runtime::types::Value::F64(v) => SpectestValue::F64(v.to_bits()),
             wasmer_runtime::types::Value::V128(v) => SpectestValue::V128(v),
+            wasmer_runtime::types::Value::FuncRef(v) => SpectestValue::FuncRef(v.to_raw()),
+            wasmer_runtime::types::Value::ExternRef(v) => SpectestValue::ExternRef(v.to_binary()),
         }
     }
 
@@ -1193,6 +1257,8 @@ mod tests {
             SpectestValue::F32(v) => format!("{:#x}", v),
             SpectestValue::F64(v) => format!("{:#x}", v),
             SpectestValue::V128(v) => format!("{:#x}", v),
+            SpectestValue::FuncRef(v) => format!("{:#x}", v),
+            SpectestValue::ExternRef(v) => format!("{:#x}", v),
         }
     }
 
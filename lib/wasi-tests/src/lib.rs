@@ -61,3 +61,91 @@ fn serializing_works() {
 pub(crate) fn get_wasi_state(ctx: &Ctx) -> &mut WasiState {
     unsafe { state::get_wasi_state(&mut *(ctx as *const Ctx as *mut Ctx)) }
 }
+
+#[test]
+fn sockets_work_through_syscalls() {
+    use std::io::{Read, Write};
+    use std::sync::Arc;
+    use wasmer_wasi::types::{
+        __WASI_EAGAIN, __WASI_EFAULT, __WASI_ESUCCESS, __WASI_SOCK_RECV_WAITALL,
+    };
+
+    const MODULE: &str = r#"
+(module
+  (import "wasi_unstable" "sock_send"
+    (func $sock_send (param i32 i32 i32 i32 i32) (result i32)))
+  (import "wasi_unstable" "sock_recv"
+    (func $sock_recv (param i32 i32 i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  ;; an iovec with the 5 bytes at 64
+  (data (i32.const 0) "\40\00\00\00\05\00\00\00")
+  (data (i32.const 64) "hello")
+  (func (export "send") (param $fd i32) (result i32)
+    (call $sock_send (local.get $fd) (i32.const 0) (i32.const 1) (i32.const 0) (i32.const 16)))
+  ;; receives into 3 bytes at 200 and `$len` bytes at 210
+  (func (export "recv") (param $fd i32) (param $flags i32) (param $len i32) (result i32)
+    (i32.store (i32.const 100) (i32.const 200))
+    (i32.store (i32.const 104) (i32.const 3))
+    (i32.store (i32.const 108) (i32.const 210))
+    (i32.store (i32.const 112) (local.get $len))
+    (call $sock_recv (local.get $fd) (i32.const 100) (i32.const 2) (local.get $flags)
+      (i32.const 20) (i32.const 24))))
+"#;
+
+    let addr = "127.0.0.1:8080".parse().unwrap();
+    let network = LoopbackNetwork::new();
+    network.listen(addr).unwrap();
+    let wasi_state = WasiState::new("sockets")
+        .network(Arc::new(network.clone()))
+        .allow_address(addr)
+        .socket(addr)
+        .build()
+        .unwrap();
+
+    let wasm_binary = wabt::wat2wasm(MODULE).unwrap();
+    let module = compile(&wasm_binary).unwrap();
+    let import_object = generate_import_object_from_state(wasi_state, WasiVersion::Snapshot0);
+    let instance = module.instantiate(&import_object).unwrap();
+    let memory = |start: usize, end: usize| -> Vec<u8> {
+        instance.context().memory(0).view::<u8>()[start..end]
+            .iter()
+            .map(|cell| cell.get())
+            .collect()
+    };
+    let datalen = |start: usize| {
+        let bytes = memory(start, start + 4);
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    };
+
+    // the socket follows the stdio file descriptors
+    let sock = 3;
+    let send: Func<i32, i32> = instance.func("send").unwrap();
+    let recv: Func<(i32, i32, i32), i32> = instance.func("recv").unwrap();
+
+    assert_eq!(send.call(sock).unwrap(), __WASI_ESUCCESS as i32);
+    assert_eq!(datalen(16), 5);
+    let mut server = network.accept(addr).unwrap().unwrap();
+    let mut buf = [0; 5];
+    server.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hello");
+
+    // no data yet, even when waiting for all of it
+    let wait_all = __WASI_SOCK_RECV_WAITALL as i32;
+    assert_eq!(recv.call(sock, wait_all, 16).unwrap(), __WASI_EAGAIN as i32);
+
+    server.write_all(b"ping pong").unwrap();
+    assert_eq!(
+        recv.call(sock, wait_all, 16).unwrap(),
+        __WASI_ESUCCESS as i32
+    );
+    assert_eq!(datalen(20), 9);
+    assert_eq!(memory(200, 203), b"pin");
+    assert_eq!(memory(210, 216), b"g pong");
+
+    // a vector past the end of the memory is rejected before receiving
+    server.write_all(b"more").unwrap();
+    assert_eq!(recv.call(sock, 0, -16).unwrap(), __WASI_EFAULT as i32);
+    assert_eq!(recv.call(sock, 0, 16).unwrap(), __WASI_ESUCCESS as i32);
+    assert_eq!(datalen(20), 4);
+    assert_eq!(memory(200, 203), b"mor");
+}
@@ -72,8 +72,11 @@ pub fn generate_import_object(
         #[allow(deprecated)]
         let state = Box::new(WasiState {
             fs: WasiFs::new(&preopened_files, &mapped_dirs).expect("Could not create WASI FS"),
+            net: Default::default(),
             args: args.clone(),
             envs: envs.clone(),
+            clock: None,
+            random: None,
         });
 
         (
@@ -95,6 +98,8 @@ pub fn generate_import_object_from_state(
     //             require substantial changes to the internals of the WasiFS
     // copy WasiState by serializing and deserializing
     let wasi_state_bytes = wasi_state.freeze().unwrap();
+    // the network backend is shared between instances instead of being copied
+    let network = wasi_state.net.backend.clone();
     let state_gen = move || {
         fn state_destructor(data: *mut c_void) {
             unsafe {
@@ -102,7 +107,8 @@ pub fn generate_import_object_from_state(
             }
         }
 
-        let wasi_state = Box::new(WasiState::unfreeze(&wasi_state_bytes).unwrap());
+        let mut wasi_state = Box::new(WasiState::unfreeze(&wasi_state_bytes).unwrap());
+        wasi_state.net.backend = network.clone();
 
         (
             Box::into_raw(wasi_state) as *mut c_void,
@@ -157,8 +163,11 @@ fn generate_import_object_snapshot0(
         #[allow(deprecated)]
         let state = Box::new(WasiState {
             fs: WasiFs::new(&preopened_files, &mapped_dirs).expect("Could not create WASI FS"),
+            net: Default::default(),
             args: args.clone(),
             envs: envs.clone(),
+            clock: None,
+            random: None,
         });
 
         (
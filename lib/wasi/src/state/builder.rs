@@ -1,8 +1,16 @@
 //! Builder system for configuring a [`WasiState`] and creating it.
 
-use crate::state::{WasiFile, WasiFs, WasiFsError, WasiState};
+use crate::state::tar::read_tar_files;
+use crate::state::{
+    CapturedOutput, HostFileSystem, ReplayInput, WasiClock, WasiFile, WasiFileSystem, WasiFs,
+    WasiFsError, WasiFsQuota, WasiNet, WasiNetwork, WasiRandom, WasiState,
+};
 use crate::syscalls::types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO};
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// Creates an empty [`WasiStateBuilder`].
 ///
@@ -39,6 +47,13 @@ pub struct WasiStateBuilder {
     stdout_override: Option<Box<dyn WasiFile>>,
     stderr_override: Option<Box<dyn WasiFile>>,
     stdin_override: Option<Box<dyn WasiFile>>,
+    filesystem: Option<Arc<dyn WasiFileSystem>>,
+    network: Option<Arc<dyn WasiNetwork>>,
+    allowed_addrs: Vec<SocketAddr>,
+    sockets: Vec<SocketAddr>,
+    quota: WasiFsQuota,
+    clock: Option<Arc<dyn WasiClock>>,
+    random: Option<Arc<dyn WasiRandom>>,
 }
 
 impl std::fmt::Debug for WasiStateBuilder {
@@ -51,6 +66,13 @@ impl std::fmt::Debug for WasiStateBuilder {
             .field("stdout_override exists", &self.stdout_override.is_some())
             .field("stderr_override exists", &self.stderr_override.is_some())
             .field("stdin_override exists", &self.stdin_override.is_some())
+            .field("filesystem", &self.filesystem)
+            .field("network", &self.network)
+            .field("allowed_addrs", &self.allowed_addrs)
+            .field("sockets", &self.sockets)
+            .field("quota", &self.quota)
+            .field("clock", &self.clock)
+            .field("random", &self.random)
             .finish()
     }
 }
@@ -66,6 +88,7 @@ pub enum WasiStateCreationError {
     WasiFsCreationError(String),
     WasiFsSetupError(String),
     WasiFsError(WasiFsError),
+    SocketAddressNotAllowed(SocketAddr),
 }
 
 fn validate_mapped_dir_alias(alias: &str) -> Result<(), WasiStateCreationError> {
@@ -268,6 +291,67 @@ impl WasiStateBuilder {
         Ok(self)
     }
 
+    /// Preopen an in-memory directory as `alias`, seeded with `files`.
+    ///
+    /// `files` maps paths relative to the directory to the contents of the
+    /// files; missing parent directories are created.  Nothing the WASI
+    /// program does to this directory touches the host filesystem.  Use
+    /// [`WasiFs::in_memory_files`] to get the files back out.
+    ///
+    /// Usage:
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use std::path::PathBuf;
+    /// # use wasmer_wasi::state::{WasiState, WasiStateCreationError};
+    /// # fn main() -> Result<(), WasiStateCreationError> {
+    /// let mut files = HashMap::new();
+    /// files.insert(PathBuf::from("src/main.c"), b"int main() {}".to_vec());
+    ///
+    /// let state = WasiState::new("program_name")
+    ///    .preopen_memory("project", files)?
+    ///    .build()?;
+    /// let files = state.fs.in_memory_files("project").unwrap();
+    /// assert_eq!(files[&PathBuf::from("src/main.c")], b"int main() {}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn preopen_memory(
+        &mut self,
+        alias: &str,
+        files: HashMap<PathBuf, Vec<u8>>,
+    ) -> Result<&mut Self, WasiStateCreationError> {
+        let mut pdb = PreopenDirBuilder::new();
+        pdb.in_memory(files)
+            .alias(alias)
+            .read(true)
+            .write(true)
+            .create(true);
+        let preopen = pdb.build()?;
+
+        self.preopens.push(preopen);
+
+        Ok(self)
+    }
+
+    /// Preopen an in-memory directory as `alias`, seeded with the regular
+    /// files of the tar archive `archive`.
+    ///
+    /// See [`WasiStateBuilder::preopen_memory`].
+    pub fn preopen_memory_tar<R>(
+        &mut self,
+        alias: &str,
+        archive: R,
+    ) -> Result<&mut Self, WasiStateCreationError>
+    where
+        R: Read,
+    {
+        let files =
+            read_tar_files(archive).map_err(WasiStateCreationError::PreopenedDirectoryError)?;
+
+        self.preopen_memory(alias, files)
+    }
+
     /// Overwrite the default WASI `stdout`, if you want to hold on to the
     /// original `stdout` use [`WasiFs::swap_file`] after building.
     pub fn stdout(&mut self, new_file: Box<dyn WasiFile>) -> &mut Self {
@@ -292,6 +376,149 @@ impl WasiStateBuilder {
         self
     }
 
+    /// Collect everything the WASI program writes to `stdout` in `output`
+    pub fn capture_stdout(&mut self, output: &CapturedOutput) -> &mut Self {
+        self.stdout(Box::new(output.clone()))
+    }
+
+    /// Collect everything the WASI program writes to `stderr` in `output`
+    pub fn capture_stderr(&mut self, output: &CapturedOutput) -> &mut Self {
+        self.stderr(Box::new(output.clone()))
+    }
+
+    /// Give the WASI program `input` as its `stdin`
+    pub fn stdin_bytes<T: Into<Vec<u8>>>(&mut self, input: T) -> &mut Self {
+        self.stdin(Box::new(ReplayInput::new(input)))
+    }
+
+    /// Set the filesystem that the preopened host directories are looked up in.
+    ///
+    /// Defaults to [`HostFileSystem`].  In-memory directories don't use it.
+    pub fn filesystem(&mut self, filesystem: Arc<dyn WasiFileSystem>) -> &mut Self {
+        self.filesystem = Some(filesystem);
+
+        self
+    }
+
+    /// Set the network backend used to connect the sockets of the WASI program.
+    ///
+    /// Usage:
+    ///
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use wasmer_wasi::state::{LoopbackNetwork, WasiState, WasiStateCreationError};
+    /// # fn main() -> Result<(), WasiStateCreationError> {
+    /// let addr = "127.0.0.1:8080".parse().unwrap();
+    /// let network = LoopbackNetwork::new();
+    /// network.listen(addr).unwrap();
+    ///
+    /// WasiState::new("program_name")
+    ///    .network(Arc::new(network.clone()))
+    ///    .allow_address(addr)
+    ///    .socket(addr)
+    ///    .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn network(&mut self, network: Arc<dyn WasiNetwork>) -> &mut Self {
+        self.network = Some(network);
+
+        self
+    }
+
+    /// Allow sockets to connect to `addr`
+    pub fn allow_address(&mut self, addr: SocketAddr) -> &mut Self {
+        self.allowed_addrs.push(addr);
+
+        self
+    }
+
+    /// Allow sockets to connect to each of the given addresses
+    pub fn allow_addresses<I>(&mut self, addrs: I) -> &mut Self
+    where
+        I: IntoIterator<Item = SocketAddr>,
+    {
+        self.allowed_addrs.extend(addrs);
+
+        self
+    }
+
+    /// Open a stream socket to `addr` for the WASI program.
+    ///
+    /// Sockets get the file descriptors following the preopened directories,
+    /// in the order they were added.  `addr` must be allowed with
+    /// [`WasiStateBuilder::allow_address`].
+    pub fn socket(&mut self, addr: SocketAddr) -> &mut Self {
+        self.sockets.push(addr);
+
+        self
+    }
+
+    /// Limit the number of bytes the WASI program can write to files.
+    ///
+    /// Growing a file with `fd_allocate` or `fd_filestat_set_size` counts as
+    /// writing.  Writes past the limit fail with `__WASI_EDQUOT`.
+    pub fn max_bytes_written(&mut self, max: u64) -> &mut Self {
+        self.quota.max_bytes_written = Some(max);
+
+        self
+    }
+
+    /// Limit the number of file descriptors that can be open at once.
+    ///
+    /// stdio, the preopened directories and the sockets count towards the
+    /// limit.  Opening past the limit fails with `__WASI_ENFILE`.
+    pub fn max_open_fds(&mut self, max: usize) -> &mut Self {
+        self.quota.max_open_fds = Some(max);
+
+        self
+    }
+
+    /// Limit the number of files, directories and symlinks the WASI program
+    /// can create.  Creating past the limit fails with `__WASI_ENFILE`.
+    pub fn max_created_inodes(&mut self, max: u64) -> &mut Self {
+        self.quota.max_created_inodes = Some(max);
+
+        self
+    }
+
+    /// Replace the host's clocks with `clock`.
+    ///
+    /// Together with [`WasiStateBuilder::random`] and the
+    /// `deterministic-execution` feature of the runtime this makes runs of
+    /// the WASI program reproducible.
+    ///
+    /// Usage:
+    ///
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use wasmer_wasi::state::{SeededRandom, VirtualClock, WasiState, WasiStateCreationError};
+    /// # fn main() -> Result<(), WasiStateCreationError> {
+    /// let clock = VirtualClock::new(1_500_000_000_000_000_000);
+    ///
+    /// WasiState::new("program_name")
+    ///    .clock(Arc::new(clock.clone()))
+    ///    .random(Arc::new(SeededRandom::new(0)))
+    ///    .build()?;
+    ///
+    /// // later, while the program is paused
+    /// clock.advance(1_000_000);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clock(&mut self, clock: Arc<dyn WasiClock>) -> &mut Self {
+        self.clock = Some(clock);
+
+        self
+    }
+
+    /// Replace the host's random number generator with `random`.
+    pub fn random(&mut self, random: Arc<dyn WasiRandom>) -> &mut Self {
+        self.random = Some(random);
+
+        self
+    }
+
     /// Setup the WASI filesystem before running
     // TODO: improve ergonomics on this function
     pub fn setup_fs(
@@ -352,11 +579,39 @@ impl WasiStateBuilder {
             }
         }
 
-        // self.preopens are checked in [`PreopenDirBuilder::build`]
+        // self.preopens are checked in [`PreopenDirBuilder::build`], except for
+        // their existence which depends on the filesystem
+        let filesystem = self
+            .filesystem
+            .clone()
+            .unwrap_or_else(|| Arc::new(HostFileSystem));
+        for preopen in self.preopens.iter() {
+            if preopen.memory.is_none() && filesystem.metadata(&preopen.path).is_err() {
+                return Err(WasiStateCreationError::PreopenedDirectoryNotFound(
+                    preopen.path.clone(),
+                ));
+            }
+            if let Some(OverlayUpper::Directory(upper_dir)) = &preopen.overlay {
+                match filesystem.metadata(upper_dir) {
+                    Ok(ref metadata) if metadata.is_dir() => (),
+                    _ => {
+                        return Err(WasiStateCreationError::PreopenedDirectoryNotFound(
+                            upper_dir.clone(),
+                        ))
+                    }
+                }
+            }
+        }
+
+        for addr in self.sockets.iter() {
+            if !self.allowed_addrs.contains(addr) {
+                return Err(WasiStateCreationError::SocketAddressNotAllowed(*addr));
+            }
+        }
 
         // this deprecation warning only applies to external callers
         #[allow(deprecated)]
-        let mut wasi_fs = WasiFs::new_with_preopen(&self.preopens)
+        let mut wasi_fs = WasiFs::new_with_preopen(&self.preopens, filesystem)
             .map_err(WasiStateCreationError::WasiFsCreationError)?;
         // set up the file system, overriding base files and calling the setup function
         if let Some(stdin_override) = self.stdin_override.take() {
@@ -374,13 +629,26 @@ impl WasiStateBuilder {
                 .swap_file(__WASI_STDERR_FILENO, stderr_override)
                 .map_err(WasiStateCreationError::WasiFsError)?;
         }
+        for addr in self.sockets.iter() {
+            wasi_fs
+                .create_socket_fd(*addr)
+                .map_err(WasiStateCreationError::WasiFsError)?;
+        }
         if let Some(f) = &self.setup_fs_fn {
             f(&mut wasi_fs).map_err(WasiStateCreationError::WasiFsSetupError)?;
         }
+        // the limits only apply to the WASI program
+        wasi_fs.quota = self.quota.clone();
         Ok(WasiState {
             fs: wasi_fs,
+            net: WasiNet {
+                backend: self.network.clone(),
+                allowed_addrs: self.allowed_addrs.clone(),
+            },
             args: self.args.clone(),
             envs: self.envs.clone(),
+            clock: self.clock.clone(),
+            random: self.random.clone(),
         })
     }
 }
@@ -393,6 +661,8 @@ pub struct PreopenDirBuilder {
     read: bool,
     write: bool,
     create: bool,
+    memory: Option<HashMap<PathBuf, Vec<u8>>>,
+    overlay: Option<OverlayUpper>,
 }
 
 /// The built version of `PreopenDirBuilder`
@@ -403,6 +673,18 @@ pub(crate) struct PreopenedDir {
     pub(crate) read: bool,
     pub(crate) write: bool,
     pub(crate) create: bool,
+    /// The initial files of an in-memory directory
+    pub(crate) memory: Option<HashMap<PathBuf, Vec<u8>>>,
+    /// Where the changes to an overlaid directory go
+    pub(crate) overlay: Option<OverlayUpper>,
+}
+
+/// The upper layer of an overlaid preopened directory
+#[derive(Debug, Clone)]
+pub(crate) enum OverlayUpper {
+    /// A scratch directory in the filesystem of the preopened directory
+    Directory(PathBuf),
+    Memory,
 }
 
 impl PreopenDirBuilder {
@@ -422,6 +704,58 @@ impl PreopenDirBuilder {
         self
     }
 
+    /// Make this preopened directory an in-memory directory seeded with
+    /// `files` instead of a directory on the host.
+    ///
+    /// In-memory directories must be given an alias.
+    pub fn in_memory(&mut self, files: HashMap<PathBuf, Vec<u8>>) -> &mut Self {
+        self.memory = Some(files);
+
+        self
+    }
+
+    /// Keep the host directory untouched: the WASI program still sees its
+    /// contents, but everything it writes, creates or removes goes to the
+    /// scratch directory `upper_dir` instead.
+    ///
+    /// Removed entries are recorded in `upper_dir` as empty files named
+    /// `.wh.<name>`.  Use [`WasiFs::overlay_changes`] to get the changes.
+    ///
+    /// Usage:
+    ///
+    /// ```no_run
+    /// # use wasmer_wasi::state::{WasiState, WasiStateCreationError};
+    /// # fn main() -> Result<(), WasiStateCreationError> {
+    /// let state = WasiState::new("program_name")
+    ///    .preopen(|p| {
+    ///        p.directory("src")
+    ///            .read(true)
+    ///            .write(true)
+    ///            .create(true)
+    ///            .overlay("/tmp/src-changes")
+    ///    })?
+    ///    .build()?;
+    /// // ... run the program ...
+    /// let changes = state.fs.overlay_changes("src").unwrap();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn overlay<FilePath>(&mut self, upper_dir: FilePath) -> &mut Self
+    where
+        FilePath: AsRef<Path>,
+    {
+        self.overlay = Some(OverlayUpper::Directory(upper_dir.as_ref().to_path_buf()));
+
+        self
+    }
+
+    /// Like [`PreopenDirBuilder::overlay`] but keep the changes in memory
+    pub fn overlay_in_memory(&mut self) -> &mut Self {
+        self.overlay = Some(OverlayUpper::Memory);
+
+        self
+    }
+
     /// Make this preopened directory appear to the WASI program as `alias`
     pub fn alias(&mut self, alias: &str) -> &mut Self {
         self.alias = Some(alias.to_string());
@@ -461,16 +795,40 @@ impl PreopenDirBuilder {
             return Err(WasiStateCreationError::PreopenedDirectoryError("Preopened directories must have at least one of read, write, create permissions set".to_string()));
         }
 
-        if self.path.is_none() {
-            return Err(WasiStateCreationError::PreopenedDirectoryError(
-                "Preopened directories must point to a host directory".to_string(),
-            ));
-        }
-        let path = self.path.clone().unwrap();
-
-        if !path.exists() {
-            return Err(WasiStateCreationError::PreopenedDirectoryNotFound(path));
-        }
+        let path = if self.memory.is_some() {
+            if self.path.is_some() {
+                return Err(WasiStateCreationError::PreopenedDirectoryError(
+                    "In-memory preopened directories can not point to a host directory".to_string(),
+                ));
+            }
+            if self.alias.is_none() {
+                return Err(WasiStateCreationError::PreopenedDirectoryError(
+                    "In-memory preopened directories must have an alias".to_string(),
+                ));
+            }
+            if self.overlay.is_some() {
+                return Err(WasiStateCreationError::PreopenedDirectoryError(
+                    "In-memory preopened directories can not be overlaid".to_string(),
+                ));
+            }
+            PathBuf::new()
+        } else {
+            if self.path.is_none() {
+                return Err(WasiStateCreationError::PreopenedDirectoryError(
+                    "Preopened directories must point to a host directory".to_string(),
+                ));
+            }
+            let path = self.path.clone().unwrap();
+            if let Some(OverlayUpper::Directory(upper_dir)) = &self.overlay {
+                if upper_dir.starts_with(&path) || path.starts_with(upper_dir) {
+                    return Err(WasiStateCreationError::PreopenedDirectoryError(format!(
+                        "The overlay directory {:?} can not overlap with {:?}",
+                        upper_dir, path
+                    )));
+                }
+            }
+            path
+        };
         if let Some(alias) = &self.alias {
             validate_mapped_dir_alias(alias)?;
         }
@@ -481,6 +839,8 @@ impl PreopenDirBuilder {
             read: self.read,
             write: self.write,
             create: self.create,
+            memory: self.memory.clone(),
+            overlay: self.overlay.clone(),
         })
     }
 }
@@ -523,4 +883,45 @@ mod test {
             _ => assert!(false),
         }
     }
+
+    #[test]
+    fn sockets_must_be_allowed() {
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let output = create_wasi_state("test_prog").socket(addr).build();
+        match output {
+            Err(WasiStateCreationError::SocketAddressNotAllowed(a)) => assert_eq!(a, addr),
+            _ => assert!(false),
+        }
+
+        let state = create_wasi_state("test_prog")
+            .allow_address(addr)
+            .socket(addr)
+            .build()
+            .unwrap();
+        assert!(state.net.is_allowed(&addr));
+    }
+
+    #[test]
+    fn overlay_must_not_overlap() {
+        let output = create_wasi_state("test_prog")
+            .preopen(|p| p.directory("src").read(true).overlay("src/changes"))
+            .map(|_| ());
+        match output {
+            Err(WasiStateCreationError::PreopenedDirectoryError(_)) => assert!(true),
+            _ => assert!(false),
+        }
+
+        let output = create_wasi_state("test_prog")
+            .preopen(|p| {
+                p.in_memory(HashMap::new())
+                    .alias("mem")
+                    .read(true)
+                    .overlay_in_memory()
+            })
+            .map(|_| ());
+        match output {
+            Err(WasiStateCreationError::PreopenedDirectoryError(_)) => assert!(true),
+            _ => assert!(false),
+        }
+    }
 }
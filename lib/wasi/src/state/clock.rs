@@ -0,0 +1,188 @@
+//! Clocks and random number generators that can replace the host's, so that
+//! WASI programs can be run reproducibly.
+
+use crate::syscalls::types::*;
+use std::sync::{Arc, Mutex};
+
+/// A clock used by `clock_time_get` and `clock_res_get` instead of the host's
+/// clocks.
+pub trait WasiClock: std::fmt::Debug + Send + Sync {
+    /// The resolution of the clock `clock_id` in nanoseconds
+    fn res_get(&self, clock_id: __wasi_clockid_t) -> Result<__wasi_timestamp_t, __wasi_errno_t>;
+    /// The time of the clock `clock_id` in nanoseconds
+    fn time_get(
+        &self,
+        clock_id: __wasi_clockid_t,
+        precision: __wasi_timestamp_t,
+    ) -> Result<__wasi_timestamp_t, __wasi_errno_t>;
+
+    /// Wait for `nanoseconds` to pass on this clock, used by `poll_oneoff`.
+    ///
+    /// The default implementation puts the current thread to sleep.
+    fn sleep(&self, nanoseconds: __wasi_timestamp_t) {
+        std::thread::sleep(std::time::Duration::from_nanos(nanoseconds));
+    }
+}
+
+/// A source of random bytes used by `random_get` instead of the host's.
+pub trait WasiRandom: std::fmt::Debug + Send + Sync {
+    /// Fill `buf` with random bytes
+    fn fill(&self, buf: &mut [u8]) -> Result<(), __wasi_errno_t>;
+}
+
+fn check_clock_id(clock_id: __wasi_clockid_t) -> Result<(), __wasi_errno_t> {
+    match clock_id {
+        __WASI_CLOCK_MONOTONIC
+        | __WASI_CLOCK_PROCESS_CPUTIME_ID
+        | __WASI_CLOCK_REALTIME
+        | __WASI_CLOCK_THREAD_CPUTIME_ID => Ok(()),
+        _ => Err(__WASI_EINVAL),
+    }
+}
+
+#[derive(Debug)]
+struct VirtualClockState {
+    now: __wasi_timestamp_t,
+    tick: __wasi_timestamp_t,
+    resolution: __wasi_timestamp_t,
+}
+
+/// A clock that only moves when told to.
+///
+/// All clocks read the same time.  The time starts at a fixed value and is
+/// moved with [`VirtualClock::advance`], or automatically after each read
+/// with [`VirtualClock::with_tick`].  Sleeping in `poll_oneoff` moves the
+/// clock forward instead of waiting.  Clones share the same time, so a clone
+/// can be kept to control the clock given to a [`WasiStateBuilder`].
+///
+/// ```
+/// # use wasmer_wasi::state::{VirtualClock, WasiClock};
+/// # use wasmer_wasi::types::__WASI_CLOCK_MONOTONIC;
+/// let clock = VirtualClock::new(1_000).with_tick(10);
+/// assert_eq!(clock.time_get(__WASI_CLOCK_MONOTONIC, 0), Ok(1_000));
+/// assert_eq!(clock.time_get(__WASI_CLOCK_MONOTONIC, 0), Ok(1_010));
+/// clock.advance(500);
+/// assert_eq!(clock.now(), 1_520);
+/// ```
+///
+/// [`WasiStateBuilder`]: crate::state::WasiStateBuilder
+#[derive(Debug, Clone)]
+pub struct VirtualClock {
+    state: Arc<Mutex<VirtualClockState>>,
+}
+
+impl VirtualClock {
+    /// Create a clock that reads `start` nanoseconds until it is advanced
+    pub fn new(start: __wasi_timestamp_t) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(VirtualClockState {
+                now: start,
+                tick: 0,
+                resolution: 1,
+            })),
+        }
+    }
+
+    /// Advance the clock by `tick` nanoseconds each time it is read
+    pub fn with_tick(self, tick: __wasi_timestamp_t) -> Self {
+        self.state.lock().unwrap().tick = tick;
+        self
+    }
+
+    /// Set the resolution the clock reports, defaults to 1 nanosecond
+    pub fn with_resolution(self, resolution: __wasi_timestamp_t) -> Self {
+        self.state.lock().unwrap().resolution = resolution;
+        self
+    }
+
+    /// Move the clock forward by `nanoseconds`
+    pub fn advance(&self, nanoseconds: __wasi_timestamp_t) {
+        let mut state = self.state.lock().unwrap();
+        state.now = state.now.saturating_add(nanoseconds);
+    }
+
+    /// Set the time of the clock
+    pub fn set(&self, now: __wasi_timestamp_t) {
+        self.state.lock().unwrap().now = now;
+    }
+
+    /// The time the clock will read next
+    pub fn now(&self) -> __wasi_timestamp_t {
+        self.state.lock().unwrap().now
+    }
+}
+
+impl WasiClock for VirtualClock {
+    fn res_get(&self, clock_id: __wasi_clockid_t) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+        check_clock_id(clock_id)?;
+        Ok(self.state.lock().unwrap().resolution)
+    }
+
+    fn time_get(
+        &self,
+        clock_id: __wasi_clockid_t,
+        _precision: __wasi_timestamp_t,
+    ) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+        check_clock_id(clock_id)?;
+        let mut state = self.state.lock().unwrap();
+        let now = state.now;
+        state.now = state.now.saturating_add(state.tick);
+        Ok(now)
+    }
+
+    /// Advances the clock instead of waiting
+    fn sleep(&self, nanoseconds: __wasi_timestamp_t) {
+        self.advance(nanoseconds);
+    }
+}
+
+/// A random number generator that produces the same bytes for the same seed.
+///
+/// This is not cryptographically secure, it is meant for reproducible runs.
+#[derive(Debug)]
+pub struct SeededRandom {
+    state: Mutex<u64>,
+}
+
+impl SeededRandom {
+    /// Create a generator from `seed`
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Mutex::new(seed),
+        }
+    }
+}
+
+impl WasiRandom for SeededRandom {
+    fn fill(&self, buf: &mut [u8]) -> Result<(), __wasi_errno_t> {
+        let mut state = self.state.lock().unwrap();
+        for chunk in buf.chunks_mut(8) {
+            // splitmix64
+            *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = *state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^= z >> 31;
+            let bytes = z.to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn seeded_random_is_reproducible() {
+        let mut first = [0u8; 20];
+        let mut second = [0u8; 20];
+        SeededRandom::new(42).fill(&mut first).unwrap();
+        SeededRandom::new(42).fill(&mut second).unwrap();
+        assert_eq!(first, second);
+
+        SeededRandom::new(43).fill(&mut second).unwrap();
+        assert_ne!(first, second);
+    }
+}
@@ -0,0 +1,647 @@
+//! The filesystem backing the host directories that WASI programs see.
+//!
+//! [`WasiFile`] abstracts files that are already open; [`WasiFileSystem`]
+//! covers everything else WASI does with the paths of preopened directories:
+//! looking up metadata, listing, creating, removing and renaming entries, and
+//! opening files.  [`HostFileSystem`] forwards these to `std::fs`,
+//! [`MemoryFileSystem`] keeps everything in memory.
+
+use crate::state::{HostFile, WasiFile, WasiFsError};
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read, Seek, Write},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// The type of an entry in a [`WasiFileSystem`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasiFileType {
+    Directory,
+    File,
+    Symlink,
+    /// Anything else, like devices or sockets
+    Other,
+}
+
+impl WasiFileType {
+    /// The WASI file type of entries of this type
+    pub fn to_wasi_file_type(self) -> __wasi_filetype_t {
+        match self {
+            WasiFileType::Directory => __WASI_FILETYPE_DIRECTORY,
+            WasiFileType::File => __WASI_FILETYPE_REGULAR_FILE,
+            WasiFileType::Symlink => __WASI_FILETYPE_SYMBOLIC_LINK,
+            WasiFileType::Other => __WASI_FILETYPE_UNKNOWN,
+        }
+    }
+}
+
+impl From<fs::FileType> for WasiFileType {
+    fn from(file_type: fs::FileType) -> Self {
+        if file_type.is_dir() {
+            WasiFileType::Directory
+        } else if file_type.is_file() {
+            WasiFileType::File
+        } else if file_type.is_symlink() {
+            WasiFileType::Symlink
+        } else {
+            WasiFileType::Other
+        }
+    }
+}
+
+/// Metadata of an entry in a [`WasiFileSystem`].
+///
+/// Times are in nanoseconds since the Unix epoch, `0` if unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasiMetadata {
+    pub file_type: WasiFileType,
+    pub len: u64,
+    pub accessed: __wasi_timestamp_t,
+    pub modified: __wasi_timestamp_t,
+    pub created: __wasi_timestamp_t,
+}
+
+impl WasiMetadata {
+    pub fn is_dir(&self) -> bool {
+        self.file_type == WasiFileType::Directory
+    }
+
+    /// The WASI view of this metadata
+    pub fn to_filestat(&self) -> __wasi_filestat_t {
+        __wasi_filestat_t {
+            st_filetype: self.file_type.to_wasi_file_type(),
+            st_size: self.len,
+            st_atim: self.accessed,
+            st_mtim: self.modified,
+            st_ctim: self.created,
+            ..__wasi_filestat_t::default()
+        }
+    }
+}
+
+/// An entry of a directory listed by [`WasiFileSystem::read_dir`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasiDirEntry {
+    pub name: String,
+    pub file_type: WasiFileType,
+}
+
+/// How [`WasiFileSystem::open`] should open a file, like `std::fs::OpenOptions`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WasiOpenOptions {
+    pub read: bool,
+    pub write: bool,
+    pub append: bool,
+    pub truncate: bool,
+    /// Create the file if it does not exist
+    pub create: bool,
+    /// Create the file, failing if it already exists
+    pub create_new: bool,
+}
+
+/// The directory and metadata operations of the filesystem that preopened
+/// host directories live in.
+///
+/// Paths are the `path`s of preopened directories joined with the path of
+/// the entry inside of them, the same paths that would be given to
+/// `std::fs`.  The sandboxing of the WASI program happens before any of
+/// these methods are called.
+pub trait WasiFileSystem: std::fmt::Debug + Send + Sync {
+    /// Get the metadata of `path`, following symlinks
+    fn metadata(&self, path: &Path) -> Result<WasiMetadata, WasiFsError>;
+
+    /// Get the metadata of `path` without following symlinks
+    fn symlink_metadata(&self, path: &Path) -> Result<WasiMetadata, WasiFsError>;
+
+    /// Read the target of the symlink at `path`
+    fn read_link(&self, path: &Path) -> Result<PathBuf, WasiFsError>;
+
+    /// List the entries of the directory at `path`, without `.` and `..`
+    fn read_dir(&self, path: &Path) -> Result<Vec<WasiDirEntry>, WasiFsError>;
+
+    /// Create a directory at `path`; its parent must exist
+    fn create_dir(&self, path: &Path) -> Result<(), WasiFsError>;
+
+    /// Remove the empty directory at `path`
+    fn remove_dir(&self, path: &Path) -> Result<(), WasiFsError>;
+
+    /// Remove the file at `path`
+    fn remove_file(&self, path: &Path) -> Result<(), WasiFsError>;
+
+    /// Move the file or directory at `from` to `to`
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), WasiFsError>;
+
+    /// Open the file at `path`.
+    ///
+    /// The returned file is responsible for unlinking and renaming itself
+    /// while it is open.
+    fn open(
+        &self,
+        path: &Path,
+        options: &WasiOpenOptions,
+    ) -> Result<Box<dyn WasiFile>, WasiFsError>;
+}
+
+/// The default [`WasiFileSystem`], operating on the filesystem of the host
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HostFileSystem;
+
+impl WasiFileSystem for HostFileSystem {
+    fn metadata(&self, path: &Path) -> Result<WasiMetadata, WasiFsError> {
+        host_metadata(fs::metadata(path)?)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<WasiMetadata, WasiFsError> {
+        host_metadata(fs::symlink_metadata(path)?)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, WasiFsError> {
+        fs::read_link(path).map_err(Into::into)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<WasiDirEntry>, WasiFsError> {
+        let mut entries = vec![];
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            entries.push(WasiDirEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                file_type: entry.file_type()?.into(),
+            });
+        }
+        Ok(entries)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        fs::create_dir(path).map_err(Into::into)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        fs::remove_dir(path).map_err(Into::into)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), WasiFsError> {
+        fs::remove_file(path).map_err(Into::into)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), WasiFsError> {
+        fs::rename(from, to).map_err(Into::into)
+    }
+
+    fn open(
+        &self,
+        path: &Path,
+        options: &WasiOpenOptions,
+    ) -> Result<Box<dyn WasiFile>, WasiFsError> {
+        let file = fs::OpenOptions::new()
+            .read(options.read)
+            .write(options.write)
+            .append(options.append)
+            .truncate(options.truncate)
+            .create(options.create)
+            .create_new(options.create_new)
+            .open(path)?;
+        Ok(Box::new(HostFile::new(
+            file,
+            path.to_path_buf(),
+            options.read,
+            options.write,
+            options.append,
+        )))
+    }
+}
+
+fn host_metadata(md: fs::Metadata) -> Result<WasiMetadata, WasiFsError> {
+    let nanos = |time: SystemTime| -> Result<u64, WasiFsError> {
+        time.duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .map_err(|_| WasiFsError::IOError)
+    };
+    Ok(WasiMetadata {
+        file_type: md.file_type().into(),
+        len: md.len(),
+        accessed: nanos(md.accessed()?)?,
+        modified: nanos(md.modified()?)?,
+        // not every platform records creation times
+        created: md.created().ok().and_then(|ct| nanos(ct).ok()).unwrap_or(0),
+    })
+}
+
+/// A [`WasiFileSystem`] that lives entirely in memory.
+///
+/// There are no symlinks and all times are `0`.  Clones share the same
+/// filesystem.
+///
+/// ```
+/// # use wasmer_wasi::state::{MemoryFileSystem, WasiFileSystem, WasiOpenOptions};
+/// # use std::io::{Read, Write};
+/// # use std::path::Path;
+/// let fs = MemoryFileSystem::new();
+/// fs.create_dir(Path::new("dir")).unwrap();
+///
+/// let options = WasiOpenOptions {
+///     write: true,
+///     create: true,
+///     ..WasiOpenOptions::default()
+/// };
+/// let mut file = fs.open(Path::new("dir/file"), &options).unwrap();
+/// file.write_all(b"hello").unwrap();
+/// assert_eq!(fs.metadata(Path::new("dir/file")).unwrap().len, 5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MemoryFileSystem {
+    /// Entries keyed by their normalized path, the root is the empty path
+    nodes: Arc<Mutex<HashMap<PathBuf, MemoryNode>>>,
+}
+
+#[derive(Debug, Clone)]
+enum MemoryNode {
+    Dir,
+    File(Arc<Mutex<Vec<u8>>>),
+}
+
+impl MemoryNode {
+    fn metadata(&self) -> WasiMetadata {
+        let (file_type, len) = match self {
+            MemoryNode::Dir => (WasiFileType::Directory, 0),
+            MemoryNode::File(data) => (WasiFileType::File, data.lock().unwrap().len() as u64),
+        };
+        WasiMetadata {
+            file_type,
+            len,
+            accessed: 0,
+            modified: 0,
+            created: 0,
+        }
+    }
+}
+
+impl Default for MemoryFileSystem {
+    fn default() -> Self {
+        let mut nodes = HashMap::new();
+        nodes.insert(PathBuf::new(), MemoryNode::Dir);
+        Self {
+            nodes: Arc::new(Mutex::new(nodes)),
+        }
+    }
+}
+
+impl MemoryFileSystem {
+    /// Create a filesystem containing only an empty root directory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The key of `path` in `nodes`.  Paths are relative to the root of the
+    /// filesystem whether or not they start with `/`.
+    fn key(path: &Path) -> Result<PathBuf, WasiFsError> {
+        let mut key = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => key.push(name),
+                Component::RootDir | Component::CurDir => (),
+                Component::ParentDir | Component::Prefix(_) => {
+                    return Err(WasiFsError::InvalidInput)
+                }
+            }
+        }
+        Ok(key)
+    }
+
+    /// Check that the parent of `key` is a directory
+    fn check_parent(nodes: &HashMap<PathBuf, MemoryNode>, key: &Path) -> Result<(), WasiFsError> {
+        let parent = key.parent().ok_or(WasiFsError::AlreadyExists)?;
+        match nodes.get(parent) {
+            Some(MemoryNode::Dir) => Ok(()),
+            Some(MemoryNode::File(_)) => Err(WasiFsError::BaseNotDirectory),
+            None => Err(WasiFsError::EntityNotFound),
+        }
+    }
+}
+
+impl WasiFileSystem for MemoryFileSystem {
+    fn metadata(&self, path: &Path) -> Result<WasiMetadata, WasiFsError> {
+        let key = Self::key(path)?;
+        let nodes = self.nodes.lock().unwrap();
+        nodes
+            .get(&key)
+            .map(MemoryNode::metadata)
+            .ok_or(WasiFsError::EntityNotFound)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<WasiMetadata, WasiFsError> {
+        self.metadata(path)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, WasiFsError> {
+        self.metadata(path)?;
+        Err(WasiFsError::InvalidInput)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<WasiDirEntry>, WasiFsError> {
+        let key = Self::key(path)?;
+        let nodes = self.nodes.lock().unwrap();
+        match nodes.get(&key) {
+            Some(MemoryNode::Dir) => (),
+            Some(MemoryNode::File(_)) => return Err(WasiFsError::BaseNotDirectory),
+            None => return Err(WasiFsError::EntityNotFound),
+        }
+        Ok(nodes
+            .iter()
+            .filter(|(entry_key, _)| entry_key.parent() == Some(&key))
+            .map(|(entry_key, node)| WasiDirEntry {
+                name: entry_key
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                file_type: node.metadata().file_type,
+            })
+            .collect())
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        let key = Self::key(path)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        if nodes.contains_key(&key) {
+            return Err(WasiFsError::AlreadyExists);
+        }
+        Self::check_parent(&nodes, &key)?;
+        nodes.insert(key, MemoryNode::Dir);
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        let key = Self::key(path)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        match nodes.get(&key) {
+            // the root can't be removed
+            Some(MemoryNode::Dir) if key.parent().is_some() => (),
+            Some(_) => return Err(WasiFsError::PermissionDenied),
+            None => return Err(WasiFsError::EntityNotFound),
+        }
+        if nodes
+            .keys()
+            .any(|entry_key| entry_key.parent() == Some(&key))
+        {
+            return Err(WasiFsError::UnknownError(__WASI_ENOTEMPTY));
+        }
+        nodes.remove(&key);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), WasiFsError> {
+        let key = Self::key(path)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        match nodes.get(&key) {
+            Some(MemoryNode::File(_)) => {
+                nodes.remove(&key);
+                Ok(())
+            }
+            Some(MemoryNode::Dir) => Err(WasiFsError::NotAFile),
+            None => Err(WasiFsError::EntityNotFound),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), WasiFsError> {
+        let from = Self::key(from)?;
+        let to = Self::key(to)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        Self::check_parent(&nodes, &from)?;
+        Self::check_parent(&nodes, &to)?;
+        match (nodes.get(&from), nodes.get(&to)) {
+            (None, _) => return Err(WasiFsError::EntityNotFound),
+            // files replace files
+            (Some(MemoryNode::File(_)), Some(MemoryNode::File(_))) | (_, None) => (),
+            (Some(_), Some(_)) => return Err(WasiFsError::AlreadyExists),
+        }
+        if to.starts_with(&from) {
+            // a directory can't be moved into itself
+            return Err(WasiFsError::InvalidInput);
+        }
+        let moved: Vec<PathBuf> = nodes
+            .keys()
+            .filter(|key| key.starts_with(&from))
+            .cloned()
+            .collect();
+        for old_key in moved {
+            let node = nodes.remove(&old_key).unwrap();
+            let new_key = to.join(old_key.strip_prefix(&from).unwrap());
+            nodes.insert(new_key, node);
+        }
+        Ok(())
+    }
+
+    fn open(
+        &self,
+        path: &Path,
+        options: &WasiOpenOptions,
+    ) -> Result<Box<dyn WasiFile>, WasiFsError> {
+        let key = Self::key(path)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        let data = match nodes.get(&key) {
+            Some(_) if options.create_new => return Err(WasiFsError::AlreadyExists),
+            Some(MemoryNode::File(data)) => data.clone(),
+            Some(MemoryNode::Dir) => return Err(WasiFsError::NotAFile),
+            None if options.create || options.create_new => {
+                Self::check_parent(&nodes, &key)?;
+                let data = Arc::new(Mutex::new(vec![]));
+                nodes.insert(key.clone(), MemoryNode::File(data.clone()));
+                data
+            }
+            None => return Err(WasiFsError::EntityNotFound),
+        };
+        if options.truncate {
+            data.lock().unwrap().clear();
+        }
+        Ok(Box::new(MemoryFile {
+            data,
+            position: 0,
+            append: options.append,
+            path: key,
+            fs: Some(self.clone()),
+        }))
+    }
+}
+
+/// A file opened from a [`MemoryFileSystem`].
+///
+/// Only the contents of the file are serialized; a deserialized file is no
+/// longer part of a filesystem.
+#[derive(Debug)]
+pub struct MemoryFile {
+    data: Arc<Mutex<Vec<u8>>>,
+    position: u64,
+    append: bool,
+    path: PathBuf,
+    fs: Option<MemoryFileSystem>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MemoryFileState {
+    data: Vec<u8>,
+    position: u64,
+    append: bool,
+    path: PathBuf,
+}
+
+impl Serialize for MemoryFile {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MemoryFileState {
+            data: self.data.lock().unwrap().clone(),
+            position: self.position,
+            append: self.append,
+            path: self.path.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MemoryFile {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = MemoryFileState::deserialize(deserializer)?;
+        Ok(MemoryFile {
+            data: Arc::new(Mutex::new(state.data)),
+            position: state.position,
+            append: state.append,
+            path: state.path,
+            fs: None,
+        })
+    }
+}
+
+impl Read for MemoryFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.data.lock().unwrap();
+        let start = std::cmp::min(self.position as usize, data.len());
+        let amount = std::cmp::min(buf.len(), data.len() - start);
+        buf[..amount].copy_from_slice(&data[start..start + amount]);
+        self.position += amount as u64;
+        Ok(amount)
+    }
+}
+
+impl Seek for MemoryFile {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let len = self.data.lock().unwrap().len() as i64;
+        let position = match pos {
+            io::SeekFrom::Start(offset) => offset as i64,
+            io::SeekFrom::End(offset) => len + offset,
+            io::SeekFrom::Current(offset) => self.position as i64 + offset,
+        };
+        if position < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek to a negative position",
+            ));
+        }
+        self.position = position as u64;
+        Ok(self.position)
+    }
+}
+
+impl Write for MemoryFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = self.data.lock().unwrap();
+        if self.append {
+            self.position = data.len() as u64;
+        }
+        let start = self.position as usize;
+        if data.len() < start + buf.len() {
+            data.resize(start + buf.len(), 0);
+        }
+        data[start..start + buf.len()].copy_from_slice(buf);
+        self.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for MemoryFile {
+    fn last_accessed(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn last_modified(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn created_time(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn size(&self) -> u64 {
+        self.data.lock().unwrap().len() as u64
+    }
+    fn set_len(&mut self, new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        self.data.lock().unwrap().resize(new_size as usize, 0);
+        Ok(())
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        match &self.fs {
+            Some(fs) => fs.remove_file(&self.path),
+            None => Ok(()),
+        }
+    }
+    fn rename_file(&self, new_name: &Path) -> Result<(), WasiFsError> {
+        match &self.fs {
+            Some(fs) => fs.rename(&self.path, new_name),
+            None => Err(WasiFsError::EntityNotFound),
+        }
+    }
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        let len = self.data.lock().unwrap().len() as u64;
+        Ok(len.saturating_sub(self.position) as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn host_file_system_round_trip() {
+        let dir = std::env::temp_dir().join(format!("wasi-host-fs-{}", std::process::id()));
+        let fs = HostFileSystem;
+        fs.create_dir(&dir).unwrap();
+        assert!(fs.metadata(&dir).unwrap().is_dir());
+
+        let file_path = dir.join("file");
+        let options = WasiOpenOptions {
+            read: true,
+            write: true,
+            create_new: true,
+            ..WasiOpenOptions::default()
+        };
+        let mut file = fs.open(&file_path, &options).unwrap();
+        file.write_all(b"hello").unwrap();
+        drop(file);
+        assert_eq!(
+            fs.open(&file_path, &options).unwrap_err(),
+            WasiFsError::AlreadyExists
+        );
+        assert_eq!(fs.metadata(&file_path).unwrap().len, 5);
+
+        let renamed = dir.join("renamed");
+        fs.rename(&file_path, &renamed).unwrap();
+        assert_eq!(
+            fs.read_dir(&dir).unwrap(),
+            vec![WasiDirEntry {
+                name: "renamed".to_string(),
+                file_type: WasiFileType::File,
+            }]
+        );
+        assert_eq!(
+            fs.metadata(&file_path).unwrap_err(),
+            WasiFsError::EntityNotFound
+        );
+
+        fs.remove_file(&renamed).unwrap();
+        fs.remove_dir(&dir).unwrap();
+        assert!(fs.symlink_metadata(&dir).is_err());
+    }
+}
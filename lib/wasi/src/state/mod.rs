@@ -14,9 +14,22 @@
 //! [WASI plugin example](https://github.com/wasmerio/wasmer/blob/master/examples/plugin.rs).
 
 mod builder;
+mod clock;
+mod filesystem;
+mod overlay;
+mod quota;
+mod socket;
+mod tar;
 mod types;
 
 pub use self::builder::*;
+pub use self::clock::*;
+pub use self::filesystem::*;
+pub use self::overlay::OverlayChanges;
+pub use self::overlay::OverlayFile;
+use self::overlay::{Overlay, OverlayFileSystem};
+pub use self::quota::WasiFsQuota;
+pub use self::socket::*;
 pub use self::types::*;
 use crate::syscalls::types::*;
 use generational_arena::Arena;
@@ -28,8 +41,9 @@ use std::{
     cell::Cell,
     fs,
     io::Write,
-    path::{Path, PathBuf},
-    time::SystemTime,
+    net::SocketAddr,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
 };
 use wasmer_runtime_core::vm::Ctx;
 
@@ -97,14 +111,15 @@ pub enum Kind {
         path: PathBuf,
         /// The entries of a directory are lazily filled.
         entries: HashMap<String, Inode>,
+        /// The directory only exists in memory: `path` is empty and `entries`
+        /// is never filled from the host.
+        in_memory: bool,
     },
     /// The same as Dir but without the irrelevant bits
     /// The root is immutable after creation; generally the Kind::Root
     /// branch of whatever code you're writing will be a simpler version of
     /// your Kind::Dir logic
-    Root {
-        entries: HashMap<String, Inode>,
-    },
+    Root { entries: HashMap<String, Inode> },
     /// The first two fields are data _about_ the symlink
     /// the last field is the data _inside_ the symlink
     ///
@@ -119,8 +134,16 @@ pub enum Kind {
         /// the value of the symlink as a relative path
         relative_path: PathBuf,
     },
-    Buffer {
-        buffer: Vec<u8>,
+    /// A file that only exists in memory
+    Buffer { buffer: Vec<u8> },
+    /// A stream socket connected through the [`WasiNet`] of the [`WasiState`]
+    Socket {
+        /// The address that the socket connects to
+        addr: SocketAddr,
+        /// The connected socket.  Sockets are connected on first use and
+        /// are not part of the serialized state.
+        #[serde(skip)]
+        handle: Option<Box<dyn WasiSocket>>,
     },
 }
 
@@ -170,6 +193,23 @@ pub struct WasiFs {
     inode_counter: Cell<u64>,
     /// for fds still open after the file has been deleted
     pub orphan_fds: HashMap<Inode, InodeVal>,
+    /// The filesystem that the host directories live in.
+    ///
+    /// The filesystem is shared and is not part of the serialized state, a
+    /// deserialized [`WasiFs`] uses the [`HostFileSystem`].
+    #[serde(skip, default = "default_backend")]
+    pub backend: Arc<dyn WasiFileSystem>,
+    /// The overlaid preopened directories by name, their changes are made
+    /// through `backend`
+    #[serde(skip)]
+    overlays: HashMap<String, Arc<Overlay>>,
+    /// The limits on what the program can write, open and create
+    #[serde(default)]
+    pub quota: WasiFsQuota,
+}
+
+fn default_backend() -> Arc<dyn WasiFileSystem> {
+    Arc::new(HostFileSystem)
 }
 
 impl WasiFs {
@@ -183,25 +223,23 @@ impl WasiFs {
         preopened_dirs: &[PathBuf],
         mapped_dirs: &[(String, PathBuf)],
     ) -> Result<Self, String> {
-        let (mut wasi_fs, root_inode) = Self::new_init()?;
+        let (mut wasi_fs, root_inode) = Self::new_init(default_backend())?;
 
         debug!("wasi::fs::preopen_dirs");
         for dir in preopened_dirs {
             debug!("Attempting to preopen {}", &dir.to_string_lossy());
             // TODO: think about this
             let default_rights = ALL_RIGHTS;
-            let cur_dir_metadata = dir.metadata().map_err(|e| {
-                format!(
-                    "Could not get metadata for file {:?}: {}",
-                    dir,
-                    e.to_string()
-                )
-            })?;
+            let cur_dir_metadata = wasi_fs
+                .backend
+                .metadata(dir)
+                .map_err(|e| format!("Could not get metadata for file {:?}: {:?}", dir, e))?;
             let kind = if cur_dir_metadata.is_dir() {
                 Kind::Dir {
                     parent: Some(root_inode),
                     path: dir.clone(),
                     entries: Default::default(),
+                    in_memory: false,
                 }
             } else {
                 return Err(format!(
@@ -240,18 +278,16 @@ impl WasiFs {
             debug!("Attempting to open {:?} at {}", real_dir, alias);
             // TODO: think about this
             let default_rights = ALL_RIGHTS;
-            let cur_dir_metadata = real_dir.metadata().map_err(|e| {
-                format!(
-                    "Could not get metadata for file {:?}: {}",
-                    &real_dir,
-                    e.to_string()
-                )
-            })?;
+            let cur_dir_metadata = wasi_fs
+                .backend
+                .metadata(real_dir)
+                .map_err(|e| format!("Could not get metadata for file {:?}: {:?}", &real_dir, e))?;
             let kind = if cur_dir_metadata.is_dir() {
                 Kind::Dir {
                     parent: Some(root_inode),
                     path: real_dir.clone(),
                     entries: Default::default(),
+                    in_memory: false,
                 }
             } else {
                 return Err(format!(
@@ -289,8 +325,40 @@ impl WasiFs {
     }
 
     /// Created for the builder API. like `new` but with more information
-    pub(crate) fn new_with_preopen(preopens: &[PreopenedDir]) -> Result<Self, String> {
-        let (mut wasi_fs, root_inode) = Self::new_init()?;
+    pub(crate) fn new_with_preopen(
+        preopens: &[PreopenedDir],
+        backend: Arc<dyn WasiFileSystem>,
+    ) -> Result<Self, String> {
+        let mut overlays = HashMap::new();
+        for preopen in preopens {
+            let upper = match &preopen.overlay {
+                Some(upper) => upper,
+                None => continue,
+            };
+            let (upper_fs, upper_root) = match upper {
+                OverlayUpper::Directory(dir) => (backend.clone(), dir.clone()),
+                OverlayUpper::Memory => (
+                    Arc::new(MemoryFileSystem::new()) as Arc<dyn WasiFileSystem>,
+                    PathBuf::new(),
+                ),
+            };
+            let overlay = Overlay::new(backend.clone(), preopen.path.clone(), upper_fs, upper_root);
+            let name = match &preopen.alias {
+                Some(alias) => alias.clone(),
+                None => preopen.path.to_string_lossy().into_owned(),
+            };
+            overlays.insert(name, Arc::new(overlay));
+        }
+        let backend = if overlays.is_empty() {
+            backend
+        } else {
+            Arc::new(OverlayFileSystem::new(
+                backend,
+                overlays.values().cloned().collect(),
+            ))
+        };
+        let (mut wasi_fs, root_inode) = Self::new_init(backend)?;
+        wasi_fs.overlays = overlays;
 
         for PreopenedDir {
             path,
@@ -298,6 +366,8 @@ impl WasiFs {
             read,
             write,
             create,
+            memory,
+            ..
         } in preopens
         {
             debug!(
@@ -305,25 +375,32 @@ impl WasiFs {
                 &path.to_string_lossy(),
                 &alias
             );
-            let cur_dir_metadata = path.metadata().map_err(|e| {
-                format!(
-                    "Could not get metadata for file {:?}: {}",
-                    path,
-                    e.to_string()
-                )
-            })?;
-
-            let kind = if cur_dir_metadata.is_dir() {
+            let kind = if memory.is_some() {
                 Kind::Dir {
                     parent: Some(root_inode),
-                    path: path.clone(),
+                    path: PathBuf::new(),
                     entries: Default::default(),
+                    in_memory: true,
                 }
             } else {
-                return Err(format!(
-                    "WASI only supports pre-opened directories right now; found \"{}\"",
-                    &path.to_string_lossy()
-                ));
+                let cur_dir_metadata = wasi_fs
+                    .backend
+                    .metadata(path)
+                    .map_err(|e| format!("Could not get metadata for file {:?}: {:?}", path, e))?;
+
+                if cur_dir_metadata.is_dir() {
+                    Kind::Dir {
+                        parent: Some(root_inode),
+                        path: path.clone(),
+                        entries: Default::default(),
+                        in_memory: false,
+                    }
+                } else {
+                    return Err(format!(
+                        "WASI only supports pre-opened directories right now; found \"{}\"",
+                        &path.to_string_lossy()
+                    ));
+                }
             };
 
             let rights = {
@@ -379,6 +456,15 @@ impl WasiFs {
                     e
                 )
             })?;
+            if let Some(files) = memory {
+                for (file_path, contents) in files {
+                    wasi_fs
+                        .create_memory_file(inode, file_path, contents.clone())
+                        .map_err(|e| {
+                            format!("Could not create in-memory file {:?}: {:?}", file_path, e)
+                        })?;
+                }
+            }
             let fd_flags = {
                 let mut fd_flags = 0;
                 if *read {
@@ -413,7 +499,7 @@ impl WasiFs {
 
     /// Private helper function to init the filesystem, called in `new` and
     /// `new_with_preopen`
-    fn new_init() -> Result<(Self, Inode), String> {
+    fn new_init(backend: Arc<dyn WasiFileSystem>) -> Result<(Self, Inode), String> {
         debug!("Initializing WASI filesystem");
         let inodes = Arena::new();
         let mut wasi_fs = Self {
@@ -424,6 +510,9 @@ impl WasiFs {
             next_fd: Cell::new(3),
             inode_counter: Cell::new(1024),
             orphan_fds: HashMap::new(),
+            backend,
+            overlays: HashMap::new(),
+            quota: WasiFsQuota::default(),
         };
         wasi_fs.create_stdin();
         wasi_fs.create_stdout();
@@ -566,6 +655,7 @@ impl WasiFs {
                         parent: Some(cur_inode),
                         path: PathBuf::from(""),
                         entries: HashMap::new(),
+                        in_memory: false,
                     };
 
                     let inode =
@@ -687,6 +777,128 @@ impl WasiFs {
         Ok(ret)
     }
 
+    /// Create a file containing `contents` at `path` inside of the in-memory
+    /// directory `base_inode`, creating the missing parent directories
+    pub(crate) fn create_memory_file(
+        &mut self,
+        base_inode: Inode,
+        path: &Path,
+        contents: Vec<u8>,
+    ) -> Result<Inode, WasiFsError> {
+        let mut names = vec![];
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => names.push(name.to_string_lossy().into_owned()),
+                Component::CurDir | Component::RootDir => (),
+                Component::ParentDir | Component::Prefix(_) => {
+                    return Err(WasiFsError::InvalidInput)
+                }
+            }
+        }
+        let file_name = names.pop().ok_or(WasiFsError::InvalidInput)?;
+
+        let mut cur_inode = base_inode;
+        for name in names {
+            let existing_entry = match &self.inodes[cur_inode].kind {
+                Kind::Dir {
+                    entries,
+                    in_memory: true,
+                    ..
+                } => entries.get(&name).cloned(),
+                _ => return Err(WasiFsError::BaseNotDirectory),
+            };
+            cur_inode = match existing_entry {
+                Some(entry) => entry,
+                None => {
+                    let kind = Kind::Dir {
+                        parent: Some(cur_inode),
+                        path: PathBuf::new(),
+                        entries: HashMap::new(),
+                        in_memory: true,
+                    };
+                    let inode = self
+                        .create_inode(kind, false, name.clone())
+                        .map_err(WasiFsError::from_wasi_err)?;
+                    if let Kind::Dir { entries, .. } = &mut self.inodes[cur_inode].kind {
+                        entries.insert(name, inode);
+                    }
+                    inode
+                }
+            };
+        }
+
+        match &self.inodes[cur_inode].kind {
+            Kind::Dir {
+                entries,
+                in_memory: true,
+                ..
+            } => {
+                if entries.contains_key(&file_name) {
+                    return Err(WasiFsError::AlreadyExists);
+                }
+            }
+            _ => return Err(WasiFsError::BaseNotDirectory),
+        }
+        let kind = Kind::Buffer { buffer: contents };
+        let inode = self
+            .create_inode(kind, false, file_name.clone())
+            .map_err(WasiFsError::from_wasi_err)?;
+        if let Kind::Dir { entries, .. } = &mut self.inodes[cur_inode].kind {
+            entries.insert(file_name, inode);
+        }
+
+        Ok(inode)
+    }
+
+    /// What the WASI program changed in the overlaid preopened directory
+    /// `alias`, see [`PreopenDirBuilder::overlay`]
+    pub fn overlay_changes(&self, alias: &str) -> Result<OverlayChanges, WasiFsError> {
+        self.overlays
+            .get(alias)
+            .ok_or(WasiFsError::EntityNotFound)?
+            .changes()
+    }
+
+    /// Get the contents of every file in the in-memory directory preopened as
+    /// `alias`, keyed by their path relative to that directory.
+    ///
+    /// This is how the files a WASI program created or changed in memory are
+    /// pulled back out after it has run.
+    pub fn in_memory_files(&self, alias: &str) -> Result<HashMap<PathBuf, Vec<u8>>, WasiFsError> {
+        let root_inode = self
+            .get_fd(VIRTUAL_ROOT_FD)
+            .map_err(WasiFsError::from_wasi_err)?
+            .inode;
+        let dir_inode = match &self.inodes[root_inode].kind {
+            Kind::Root { entries } => *entries.get(alias).ok_or(WasiFsError::EntityNotFound)?,
+            _ => unreachable!("The virtual root is not a Kind::Root"),
+        };
+
+        let mut files = HashMap::new();
+        let mut stack = vec![(dir_inode, PathBuf::new())];
+        while let Some((inode, path)) = stack.pop() {
+            match &self.inodes[inode].kind {
+                Kind::Dir {
+                    entries,
+                    in_memory: true,
+                    ..
+                } => {
+                    for (name, entry) in entries {
+                        stack.push((*entry, path.join(name)));
+                    }
+                }
+                Kind::Buffer { buffer } => {
+                    files.insert(path, buffer.clone());
+                }
+                _ if inode == dir_inode => return Err(WasiFsError::BaseNotDirectory),
+                // symlinks are not files
+                _ => (),
+            }
+        }
+
+        Ok(files)
+    }
+
     /// refresh size from filesystem
     pub(crate) fn filestat_resync_size(
         &mut self,
@@ -703,7 +915,14 @@ impl WasiFs {
                     Err(__WASI_EBADF)
                 }
             }
+            Kind::Buffer { buffer } => {
+                let new_size = buffer.len() as u64;
+                self.inodes[fd.inode].stat.st_size = new_size;
+                Ok(new_size as __wasi_filesize_t)
+            }
             Kind::Dir { .. } | Kind::Root { .. } => Err(__WASI_EISDIR),
+            // sockets don't have a size
+            Kind::Socket { .. } => Ok(0),
             _ => Err(__WASI_EINVAL),
         }
     }
@@ -729,7 +948,7 @@ impl WasiFs {
         follow_symlinks: bool,
     ) -> Result<Inode, __wasi_errno_t> {
         if symlink_count > MAX_SYMLINKS {
-            return Err(__WASI_EMLINK);
+            return Err(__WASI_ELOOP);
         }
 
         let base_dir = self.get_fd(base)?;
@@ -743,14 +962,13 @@ impl WasiFs {
             let last_component = i + 1 == n_components;
             // for each component traverse file structure
             // loading inodes as necessary
-            'symlink_resolution: while symlink_count < MAX_SYMLINKS {
+            'symlink_resolution: loop {
                 match &mut self.inodes[cur_inode].kind {
-                    Kind::Buffer { .. } => unimplemented!("state::get_inode_at_path for buffers"),
                     Kind::Dir {
                         ref mut entries,
                         ref path,
                         ref parent,
-                        ..
+                        in_memory,
                     } => {
                         match component.as_os_str().to_string_lossy().borrow() {
                             ".." => {
@@ -764,33 +982,39 @@ impl WasiFs {
                             "." => continue 'path_iter,
                             _ => (),
                         }
-                        // used for full resolution of symlinks
-                        let mut loop_for_symlink = false;
                         if let Some(entry) =
                             entries.get(component.as_os_str().to_string_lossy().as_ref())
                         {
                             cur_inode = *entry;
+                        } else if *in_memory {
+                            // all entries of in-memory directories are known
+                            return Err(__WASI_ENOENT);
                         } else {
                             let file = {
                                 let mut cd = path.clone();
                                 cd.push(component);
                                 cd
                             };
-                            let metadata = file.symlink_metadata().ok().ok_or(__WASI_EINVAL)?;
-                            let file_type = metadata.file_type();
+                            let metadata = self
+                                .backend
+                                .symlink_metadata(&file)
+                                .ok()
+                                .ok_or(__WASI_EINVAL)?;
+                            let file_type = metadata.file_type;
                             // we want to insert newly opened dirs and files, but not transient symlinks
                             // TODO: explain why (think about this deeply when well rested)
                             let mut should_insert = false;
 
-                            let kind = if file_type.is_dir() {
+                            let kind = if file_type == WasiFileType::Directory {
                                 should_insert = true;
                                 // load DIR
                                 Kind::Dir {
                                     parent: Some(cur_inode),
                                     path: file.clone(),
                                     entries: Default::default(),
+                                    in_memory: false,
                                 }
-                            } else if file_type.is_file() {
+                            } else if file_type == WasiFileType::File {
                                 should_insert = true;
                                 // load file
                                 Kind::File {
@@ -798,17 +1022,13 @@ impl WasiFs {
                                     path: file.clone(),
                                     fd: None,
                                 }
-                            } else if file_type.is_symlink() {
-                                let link_value = file.read_link().ok().ok_or(__WASI_EIO)?;
+                            } else if file_type == WasiFileType::Symlink {
+                                let link_value =
+                                    self.backend.read_link(&file).ok().ok_or(__WASI_EIO)?;
                                 debug!("attempting to decompose path {:?}", link_value);
 
-                                let (pre_open_dir_fd, relative_path) = if link_value.is_relative() {
-                                    self.path_into_pre_open_and_relative_path(&file)?
-                                } else {
-                                    unimplemented!("Absolute symlinks are not yet supported");
-                                };
-                                loop_for_symlink = true;
-                                symlink_count += 1;
+                                let (pre_open_dir_fd, relative_path) =
+                                    self.path_into_pre_open_and_relative_path(&file)?;
                                 Kind::Symlink {
                                     base_po_dir: pre_open_dir_fd,
                                     path_to_symlink: relative_path,
@@ -832,8 +1052,12 @@ impl WasiFs {
                                 }
                             }
                             cur_inode = new_inode;
+                        }
 
-                            if loop_for_symlink && follow_symlinks {
+                        // symlinks in the middle of a path are always followed, the last
+                        // component is only followed if we were asked to
+                        if let Kind::Symlink { .. } = &self.inodes[cur_inode].kind {
+                            if follow_symlinks || !last_component {
                                 debug!("Following symlink to {:?}", cur_inode);
                                 continue 'symlink_resolution;
                             }
@@ -856,7 +1080,7 @@ impl WasiFs {
                             return Err(__WASI_EINVAL);
                         }
                     }
-                    Kind::File { .. } => {
+                    Kind::File { .. } | Kind::Buffer { .. } | Kind::Socket { .. } => {
                         return Err(__WASI_ENOTDIR);
                     }
                     Kind::Symlink {
@@ -864,36 +1088,37 @@ impl WasiFs {
                         path_to_symlink,
                         relative_path,
                     } => {
-                        let new_base_dir = *base_po_dir;
+                        symlink_count += 1;
+                        if symlink_count > MAX_SYMLINKS {
+                            return Err(__WASI_ELOOP);
+                        }
                         // allocate to reborrow mutabily to recur
-                        let new_path = {
-                            /*if let Kind::Root { .. } = self.inodes[base_po_dir].kind {
-                                assert!(false, "symlinks should never be relative to the root");
-                            }*/
+                        let (new_base_dir, new_path) = if relative_path.is_absolute() {
+                            // absolute symlinks must point into a preopened directory
+                            let relative_path = relative_path.clone();
+                            self.path_into_pre_open_and_relative_path(&relative_path)
+                                .map_err(|_| __WASI_ENOTCAPABLE)?
+                        } else {
                             let mut base = path_to_symlink.clone();
                             // remove the symlink file itself from the path, leaving just the path from the base
                             // to the dir containing the symlink
                             base.pop();
                             base.push(relative_path);
-                            base.to_string_lossy().to_string()
+                            (*base_po_dir, base)
                         };
+                        if path_escapes_base(&new_path) {
+                            debug!("Symlink {:?} escapes its preopened directory", new_path);
+                            return Err(__WASI_ENOTCAPABLE);
+                        }
                         debug!("Following symlink recursively");
-                        let symlink_inode = self.get_inode_at_path_inner(
+                        cur_inode = self.get_inode_at_path_inner(
                             new_base_dir,
-                            &new_path,
-                            symlink_count + 1,
-                            follow_symlinks,
+                            &new_path.to_string_lossy(),
+                            symlink_count,
+                            // the target of a symlink we follow is always resolved fully
+                            true,
                         )?;
-                        cur_inode = symlink_inode;
-                        // if we're at the very end and we found a file, then we're done
-                        // TODO: figure out if this should also happen for directories?
-                        if let Kind::File { .. } = &self.inodes[cur_inode].kind {
-                            // check if on last step
-                            if last_component {
-                                break 'symlink_resolution;
-                            }
-                        }
-                        continue 'symlink_resolution;
+                        continue 'path_iter;
                     }
                 }
                 break 'symlink_resolution;
@@ -919,6 +1144,10 @@ impl WasiFs {
         for po_fd in &self.preopen_fds {
             let po_inode = self.fd_map[po_fd].inode;
             let po_path = match &self.inodes[po_inode].kind {
+                // in-memory directories have no host path
+                Kind::Dir {
+                    in_memory: true, ..
+                } => continue,
                 Kind::Dir { path, .. } => &**path,
                 Kind::Root { .. } => Path::new("/"),
                 _ => unreachable!("Preopened FD that's not a directory or the root"),
@@ -1094,9 +1323,10 @@ impl WasiFs {
 
         Ok(__wasi_fdstat_t {
             fs_filetype: match self.inodes[fd.inode].kind {
-                Kind::File { .. } => __WASI_FILETYPE_REGULAR_FILE,
+                Kind::File { .. } | Kind::Buffer { .. } => __WASI_FILETYPE_REGULAR_FILE,
                 Kind::Dir { .. } => __WASI_FILETYPE_DIRECTORY,
                 Kind::Symlink { .. } => __WASI_FILETYPE_SYMBOLIC_LINK,
+                Kind::Socket { .. } => __WASI_FILETYPE_SOCKET_STREAM,
                 _ => __WASI_FILETYPE_UNKNOWN,
             },
             fs_flags: fd.flags,
@@ -1211,6 +1441,7 @@ impl WasiFs {
         open_flags: u16,
         inode: Inode,
     ) -> Result<__wasi_fd_t, __wasi_errno_t> {
+        self.quota.check_open_fds(self.fd_map.len())?;
         let idx = self.next_fd.get();
         self.next_fd.set(idx + 1);
         self.fd_map.insert(
@@ -1337,9 +1568,30 @@ impl WasiFs {
                         ..__wasi_filestat_t::default()
                     })
                 }
-                None => path.metadata().ok()?,
+                None => self.backend.metadata(path).ok()?,
             },
-            Kind::Dir { path, .. } => path.metadata().ok()?,
+            Kind::Dir {
+                in_memory: true, ..
+            } => {
+                return Some(__wasi_filestat_t {
+                    st_filetype: __WASI_FILETYPE_DIRECTORY,
+                    ..__wasi_filestat_t::default()
+                })
+            }
+            Kind::Dir { path, .. } => self.backend.metadata(path).ok()?,
+            Kind::Buffer { buffer } => {
+                return Some(__wasi_filestat_t {
+                    st_filetype: __WASI_FILETYPE_REGULAR_FILE,
+                    st_size: buffer.len() as u64,
+                    ..__wasi_filestat_t::default()
+                })
+            }
+            Kind::Socket { .. } => {
+                return Some(__wasi_filestat_t {
+                    st_filetype: __WASI_FILETYPE_SOCKET_STREAM,
+                    ..__wasi_filestat_t::default()
+                })
+            }
             Kind::Symlink {
                 base_po_dir,
                 path_to_symlink,
@@ -1348,9 +1600,13 @@ impl WasiFs {
                 let base_po_inode = &self.fd_map[base_po_dir].inode;
                 let base_po_inode_v = &self.inodes[*base_po_inode];
                 match &base_po_inode_v.kind {
-                    Kind::Root { .. } => {
-                        path_to_symlink.clone().symlink_metadata().ok()?
+                    Kind::Dir { in_memory: true, .. } => {
+                        return Some(__wasi_filestat_t {
+                            st_filetype: __WASI_FILETYPE_SYMBOLIC_LINK,
+                            ..__wasi_filestat_t::default()
+                        })
                     }
+                    Kind::Root { .. } => self.backend.symlink_metadata(path_to_symlink).ok()?,
                     Kind::Dir { path, .. } => {
                         let mut real_path = path.clone();
                         // PHASE 1: ignore all possible symlinks in `relative_path`
@@ -1360,7 +1616,7 @@ impl WasiFs {
                         // TODO: adjust size of symlink, too
                         //      for all paths adjusted think about this
                         real_path.push(path_to_symlink);
-                        real_path.symlink_metadata().ok()?
+                        self.backend.symlink_metadata(&real_path).ok()?
                     }
                     // if this triggers, there's a bug in the symlink code
                     _ => unreachable!("Symlink pointing to something that's not a directory as its base preopened directory"),
@@ -1368,48 +1624,36 @@ impl WasiFs {
             }
             __ => return None,
         };
-        Some(__wasi_filestat_t {
-            st_filetype: host_file_type_to_wasi_file_type(md.file_type()),
-            st_size: md.len(),
-            st_atim: md
-                .accessed()
-                .ok()?
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .ok()?
-                .as_nanos() as u64,
-            st_mtim: md
-                .modified()
-                .ok()?
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .ok()?
-                .as_nanos() as u64,
-            st_ctim: md
-                .created()
-                .ok()
-                .and_then(|ct| ct.duration_since(SystemTime::UNIX_EPOCH).ok())
-                .map(|ct| ct.as_nanos() as u64)
-                .unwrap_or(0),
-            ..__wasi_filestat_t::default()
-        })
+        Some(md.to_filestat())
     }
 
     /// Closes an open FD, handling all details such as FD being preopen
     pub(crate) fn close_fd(&mut self, fd: __wasi_fd_t) -> Result<(), __wasi_errno_t> {
         let inodeval_mut = self.get_inodeval_mut(fd)?;
         let is_preopened = inodeval_mut.is_preopened;
+        let name = inodeval_mut.name.clone();
 
         match &mut inodeval_mut.kind {
             Kind::File { ref mut handle, .. } => {
                 let mut empty_handle = None;
                 std::mem::swap(handle, &mut empty_handle);
             }
-            Kind::Dir { parent, path, .. } => {
+            Kind::Dir {
+                parent,
+                path,
+                in_memory,
+                ..
+            } => {
                 debug!("Closing dir {:?}", &path);
-                let key = path
-                    .file_name()
-                    .ok_or(__WASI_EINVAL)?
-                    .to_string_lossy()
-                    .to_string();
+                let key = if *in_memory {
+                    // in-memory directories have no host path to take the name from
+                    name
+                } else {
+                    path.file_name()
+                        .ok_or(__WASI_EINVAL)?
+                        .to_string_lossy()
+                        .to_string()
+                };
                 if let Some(p) = parent.clone() {
                     match &mut self.inodes[p].kind {
                         Kind::Dir { entries, .. } | Kind::Root { entries } => {
@@ -1441,12 +1685,40 @@ impl WasiFs {
                     return Err(__WASI_EINVAL);
                 }
             }
+            Kind::Socket { ref mut handle, .. } => {
+                // dropping the handle closes the connection
+                handle.take();
+                self.fd_map.remove(&fd);
+            }
+            Kind::Buffer { .. } => {
+                self.fd_map.remove(&fd);
+            }
             Kind::Root { .. } => return Err(__WASI_EACCES),
-            Kind::Symlink { .. } | Kind::Buffer { .. } => return Err(__WASI_EINVAL),
+            Kind::Symlink { .. } => return Err(__WASI_EINVAL),
         }
 
         Ok(())
     }
+
+    /// Creates an fd for a stream socket connecting to `addr`.  The socket is
+    /// connected when it's first used.
+    pub(crate) fn create_socket_fd(
+        &mut self,
+        addr: SocketAddr,
+    ) -> Result<__wasi_fd_t, WasiFsError> {
+        let kind = Kind::Socket { addr, handle: None };
+        let inode = self
+            .create_inode(kind, false, addr.to_string())
+            .map_err(WasiFsError::from_wasi_err)?;
+        let rights = __WASI_RIGHT_FD_READ
+            | __WASI_RIGHT_FD_WRITE
+            | __WASI_RIGHT_FD_FDSTAT_SET_FLAGS
+            | __WASI_RIGHT_FD_FILESTAT_GET
+            | __WASI_RIGHT_POLL_FD_READWRITE
+            | __WASI_RIGHT_SOCK_SHUTDOWN;
+        self.create_fd(rights, 0, 0, Fd::READ | Fd::WRITE, inode)
+            .map_err(WasiFsError::from_wasi_err)
+    }
 }
 
 /// Top level data type containing all* the state with which WASI can
@@ -1480,8 +1752,19 @@ impl WasiFs {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WasiState {
     pub fs: WasiFs,
+    pub net: WasiNet,
     pub args: Vec<Vec<u8>>,
     pub envs: Vec<Vec<u8>>,
+    /// The clock used instead of the host's clocks, if any.
+    ///
+    /// The clock is shared and is not part of the serialized state.
+    #[serde(skip)]
+    pub clock: Option<Arc<dyn WasiClock>>,
+    /// The random number generator used instead of the host's, if any.
+    ///
+    /// The generator is shared and is not part of the serialized state.
+    #[serde(skip)]
+    pub random: Option<Arc<dyn WasiRandom>>,
 }
 
 impl WasiState {
@@ -1497,20 +1780,61 @@ impl WasiState {
     }
 
     /// Get a WasiState from bytes
+    ///
+    /// The network backend, clock and random number generator are not
+    /// serialized; set `net.backend`, `clock` and `random` again on the
+    /// returned state if they are used.
     pub fn unfreeze(bytes: &[u8]) -> Option<Self> {
         bincode::deserialize(bytes).ok()
     }
+
+    /// Open a stream socket to `addr` and return its fd.
+    ///
+    /// `addr` must be in the allow-list of [`WasiNet`].  The socket is
+    /// connected through the network backend when the WASI program first
+    /// uses it.
+    pub fn open_socket(&mut self, addr: SocketAddr) -> Result<__wasi_fd_t, WasiFsError> {
+        if !self.net.is_allowed(&addr) {
+            return Err(WasiFsError::PermissionDenied);
+        }
+        self.fs.create_socket_fd(addr)
+    }
 }
 
 pub fn host_file_type_to_wasi_file_type(file_type: fs::FileType) -> __wasi_filetype_t {
-    // TODO: handle other file types
-    if file_type.is_dir() {
-        __WASI_FILETYPE_DIRECTORY
-    } else if file_type.is_file() {
-        __WASI_FILETYPE_REGULAR_FILE
-    } else if file_type.is_symlink() {
-        __WASI_FILETYPE_SYMBOLIC_LINK
-    } else {
-        __WASI_FILETYPE_UNKNOWN
+    WasiFileType::from(file_type).to_wasi_file_type()
+}
+
+/// Returns `true` if following the `..` components of `path` would leave the
+/// directory that `path` is relative to
+fn path_escapes_base(path: &Path) -> bool {
+    let mut depth: usize = 0;
+    for component in path.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if depth == 0 {
+                    return true;
+                }
+                depth -= 1;
+            }
+            Component::RootDir | Component::Prefix(_) => return true,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn symlink_targets_must_stay_in_base() {
+        assert!(!path_escapes_base(Path::new("a/b/../c")));
+        assert!(!path_escapes_base(Path::new("./a/..")));
+        assert!(path_escapes_base(Path::new("a/../..")));
+        assert!(path_escapes_base(Path::new("../a")));
+        assert!(path_escapes_base(Path::new("/etc/passwd")));
     }
 }
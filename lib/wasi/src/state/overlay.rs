@@ -0,0 +1,783 @@
+//! Copy-on-write overlays of preopened host directories.
+//!
+//! An overlay shows the WASI program a host directory (the lower layer) but
+//! sends every change to a separate upper layer, leaving the host directory
+//! untouched.  Files are copied to the upper layer the first time they are
+//! written to.  Removing an entry of the lower layer leaves a whiteout in
+//! the upper layer: an empty file named `.wh.<name>` next to where the entry
+//! would be.  A whiteout hides the lower entry and everything below it, so a
+//! directory that is removed and created again starts out empty.
+
+use crate::state::{
+    PollEventSet, WasiDirEntry, WasiFile, WasiFileSystem, WasiFileType, WasiFsError, WasiMetadata,
+    WasiOpenOptions,
+};
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{self, Read, Seek, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// What a WASI program changed in an overlaid directory, see
+/// [`WasiFs::overlay_changes`](crate::state::WasiFs::overlay_changes).
+///
+/// Paths are relative to the overlaid directory.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OverlayChanges {
+    /// The files that were created or written to, with their contents
+    pub written: HashMap<PathBuf, Vec<u8>>,
+    /// The directories that were created
+    pub created_dirs: Vec<PathBuf>,
+    /// The entries of the host directory that were removed
+    pub removed: Vec<PathBuf>,
+}
+
+/// A host directory overlaid with an upper layer
+#[derive(Debug)]
+pub(crate) struct Overlay {
+    lower: Arc<dyn WasiFileSystem>,
+    lower_root: PathBuf,
+    upper: Arc<dyn WasiFileSystem>,
+    upper_root: PathBuf,
+}
+
+impl Overlay {
+    pub(crate) fn new(
+        lower: Arc<dyn WasiFileSystem>,
+        lower_root: PathBuf,
+        upper: Arc<dyn WasiFileSystem>,
+        upper_root: PathBuf,
+    ) -> Self {
+        Self {
+            lower,
+            lower_root,
+            upper,
+            upper_root,
+        }
+    }
+
+    /// The path of `path` relative to the overlaid directory
+    fn relative<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        path.strip_prefix(&self.lower_root).ok()
+    }
+
+    fn lower_path(&self, rel: &Path) -> PathBuf {
+        self.lower_root.join(rel)
+    }
+
+    fn upper_path(&self, rel: &Path) -> PathBuf {
+        self.upper_root.join(rel)
+    }
+
+    fn whiteout_path(&self, rel: &Path) -> Option<PathBuf> {
+        let name = rel.file_name()?;
+        let mut path = self.upper_path(rel);
+        path.set_file_name(format!("{}{}", WHITEOUT_PREFIX, name.to_string_lossy()));
+        Some(path)
+    }
+
+    /// Returns `true` if `rel` or one of its ancestors was removed from the
+    /// lower layer
+    fn is_whited_out(&self, rel: &Path) -> bool {
+        rel.ancestors()
+            .filter_map(|ancestor| self.whiteout_path(ancestor))
+            .any(|whiteout| self.upper.symlink_metadata(&whiteout).is_ok())
+    }
+
+    fn in_upper(&self, rel: &Path) -> Option<WasiMetadata> {
+        self.upper.symlink_metadata(&self.upper_path(rel)).ok()
+    }
+
+    fn in_lower(&self, rel: &Path) -> Option<WasiMetadata> {
+        if self.is_whited_out(rel) {
+            return None;
+        }
+        self.lower.symlink_metadata(&self.lower_path(rel)).ok()
+    }
+
+    /// The metadata of `rel` as the WASI program sees it
+    fn visible(&self, rel: &Path) -> Option<WasiMetadata> {
+        self.in_upper(rel).or_else(|| self.in_lower(rel))
+    }
+
+    /// Create the directory `rel` and its ancestors in the upper layer
+    fn copy_up_dir(&self, rel: &Path) -> Result<(), WasiFsError> {
+        let mut ancestors: Vec<&Path> = rel.ancestors().collect();
+        ancestors.reverse();
+        for ancestor in ancestors {
+            if self.in_upper(ancestor).is_none() {
+                self.upper.create_dir(&self.upper_path(ancestor))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Prepare the parent directory of `rel` for creating `rel` in the upper layer
+    fn prepare_parent(&self, rel: &Path) -> Result<(), WasiFsError> {
+        let name = rel.file_name().ok_or(WasiFsError::AlreadyExists)?;
+        if name.to_string_lossy().starts_with(WHITEOUT_PREFIX) {
+            return Err(WasiFsError::PermissionDenied);
+        }
+        let parent = rel.parent().unwrap_or_else(|| Path::new(""));
+        match self.visible(parent) {
+            Some(ref metadata) if metadata.is_dir() => self.copy_up_dir(parent),
+            Some(_) => Err(WasiFsError::BaseNotDirectory),
+            None => Err(WasiFsError::EntityNotFound),
+        }
+    }
+
+    /// Hide the lower entry at `rel`
+    fn white_out(&self, rel: &Path) -> Result<(), WasiFsError> {
+        let whiteout = self
+            .whiteout_path(rel)
+            .ok_or(WasiFsError::PermissionDenied)?;
+        self.copy_up_dir(rel.parent().unwrap_or_else(|| Path::new("")))?;
+        let options = WasiOpenOptions {
+            write: true,
+            create: true,
+            ..WasiOpenOptions::default()
+        };
+        self.upper.open(&whiteout, &options).map(|_| ())
+    }
+
+    fn metadata(&self, rel: &Path) -> Result<WasiMetadata, WasiFsError> {
+        if self.in_upper(rel).is_some() {
+            self.upper.metadata(&self.upper_path(rel))
+        } else if self.in_lower(rel).is_some() {
+            self.lower.metadata(&self.lower_path(rel))
+        } else {
+            Err(WasiFsError::EntityNotFound)
+        }
+    }
+
+    fn symlink_metadata(&self, rel: &Path) -> Result<WasiMetadata, WasiFsError> {
+        self.visible(rel).ok_or(WasiFsError::EntityNotFound)
+    }
+
+    fn read_link(&self, rel: &Path) -> Result<PathBuf, WasiFsError> {
+        if self.in_upper(rel).is_some() {
+            self.upper.read_link(&self.upper_path(rel))
+        } else if self.in_lower(rel).is_some() {
+            self.lower.read_link(&self.lower_path(rel))
+        } else {
+            Err(WasiFsError::EntityNotFound)
+        }
+    }
+
+    fn read_dir(&self, rel: &Path) -> Result<Vec<WasiDirEntry>, WasiFsError> {
+        let upper = self.in_upper(rel);
+        let lower = self.in_lower(rel);
+        match (&upper, &lower) {
+            (None, None) => return Err(WasiFsError::EntityNotFound),
+            (Some(metadata), _) | (None, Some(metadata)) if !metadata.is_dir() => {
+                return Err(WasiFsError::BaseNotDirectory)
+            }
+            _ => (),
+        }
+
+        let mut entries: HashMap<String, WasiFileType> = HashMap::new();
+        if lower.map(|metadata| metadata.is_dir()).unwrap_or(false) {
+            for entry in self.lower.read_dir(&self.lower_path(rel))? {
+                let whiteout = self.whiteout_path(&rel.join(&entry.name));
+                if whiteout.map_or(false, |path| self.upper.symlink_metadata(&path).is_ok()) {
+                    continue;
+                }
+                entries.insert(entry.name, entry.file_type);
+            }
+        }
+        if upper.is_some() {
+            for entry in self.upper.read_dir(&self.upper_path(rel))? {
+                if !entry.name.starts_with(WHITEOUT_PREFIX) {
+                    entries.insert(entry.name, entry.file_type);
+                }
+            }
+        }
+
+        let mut entries: Vec<WasiDirEntry> = entries
+            .into_iter()
+            .map(|(name, file_type)| WasiDirEntry { name, file_type })
+            .collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
+    fn create_dir(&self, rel: &Path) -> Result<(), WasiFsError> {
+        if self.visible(rel).is_some() {
+            return Err(WasiFsError::AlreadyExists);
+        }
+        self.prepare_parent(rel)?;
+        self.upper.create_dir(&self.upper_path(rel))
+    }
+
+    fn remove_dir(&self, rel: &Path) -> Result<(), WasiFsError> {
+        match self.visible(rel) {
+            Some(ref metadata) if metadata.is_dir() => (),
+            Some(_) => return Err(WasiFsError::BaseNotDirectory),
+            None => return Err(WasiFsError::EntityNotFound),
+        }
+        if rel.file_name().is_none() {
+            return Err(WasiFsError::PermissionDenied);
+        }
+        if !self.read_dir(rel)?.is_empty() {
+            return Err(WasiFsError::UnknownError(__WASI_ENOTEMPTY));
+        }
+        if self.in_upper(rel).is_some() {
+            // only whiteouts are left in the upper directory
+            let upper_path = self.upper_path(rel);
+            for entry in self.upper.read_dir(&upper_path)? {
+                self.upper.remove_file(&upper_path.join(&entry.name))?;
+            }
+            self.upper.remove_dir(&upper_path)?;
+        }
+        if self.in_lower(rel).is_some() {
+            self.white_out(rel)?;
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, rel: &Path) -> Result<(), WasiFsError> {
+        match self.visible(rel) {
+            Some(ref metadata) if metadata.is_dir() => return Err(WasiFsError::NotAFile),
+            Some(_) => (),
+            None => return Err(WasiFsError::EntityNotFound),
+        }
+        if self.in_upper(rel).is_some() {
+            self.upper.remove_file(&self.upper_path(rel))?;
+        }
+        if self.in_lower(rel).is_some() {
+            self.white_out(rel)?;
+        }
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), WasiFsError> {
+        let source = self.visible(from).ok_or(WasiFsError::EntityNotFound)?;
+        let from_lower = self.in_lower(from).is_some();
+        if source.is_dir() {
+            if from_lower {
+                // like overlayfs, directories of the lower layer can't be moved
+                return Err(WasiFsError::UnknownError(__WASI_EXDEV));
+            }
+            if self.visible(to).is_some() {
+                return Err(WasiFsError::AlreadyExists);
+            }
+        } else if self.visible(to).map_or(false, |metadata| metadata.is_dir()) {
+            return Err(WasiFsError::AlreadyExists);
+        }
+        self.prepare_parent(to)?;
+
+        if self.in_upper(from).is_some() {
+            self.upper
+                .rename(&self.upper_path(from), &self.upper_path(to))?;
+        } else {
+            self.copy_up_file(from, to)?;
+        }
+        if from_lower {
+            self.white_out(from)?;
+        }
+        Ok(())
+    }
+
+    /// Copy the lower file at `from` to `to` in the upper layer
+    fn copy_up_file(&self, from: &Path, to: &Path) -> Result<(), WasiFsError> {
+        let read = WasiOpenOptions {
+            read: true,
+            ..WasiOpenOptions::default()
+        };
+        let mut contents = vec![];
+        self.lower
+            .open(&self.lower_path(from), &read)?
+            .read_to_end(&mut contents)?;
+        let write = WasiOpenOptions {
+            write: true,
+            create: true,
+            truncate: true,
+            ..WasiOpenOptions::default()
+        };
+        self.upper
+            .open(&self.upper_path(to), &write)?
+            .write_all(&contents)?;
+        Ok(())
+    }
+
+    fn open(
+        overlay: &Arc<Overlay>,
+        rel: &Path,
+        options: &WasiOpenOptions,
+    ) -> Result<Box<dyn WasiFile>, WasiFsError> {
+        let upper_options = WasiOpenOptions {
+            create_new: false,
+            ..*options
+        };
+        let in_upper = match overlay.visible(rel) {
+            Some(_) if options.create_new => return Err(WasiFsError::AlreadyExists),
+            Some(ref metadata) if metadata.is_dir() => return Err(WasiFsError::NotAFile),
+            Some(_) => overlay.in_upper(rel).is_some(),
+            None if options.create || options.create_new => {
+                overlay.prepare_parent(rel)?;
+                true
+            }
+            None => return Err(WasiFsError::EntityNotFound),
+        };
+
+        let (inner, copied_up) = if in_upper {
+            let upper_options = WasiOpenOptions {
+                create: true,
+                ..upper_options
+            };
+            let file = overlay
+                .upper
+                .open(&overlay.upper_path(rel), &upper_options)?;
+            (file, true)
+        } else if options.truncate {
+            // nothing of the lower file would be kept
+            overlay.prepare_parent(rel)?;
+            let upper_options = WasiOpenOptions {
+                create: true,
+                ..upper_options
+            };
+            let file = overlay
+                .upper
+                .open(&overlay.upper_path(rel), &upper_options)?;
+            (file, true)
+        } else {
+            let read = WasiOpenOptions {
+                read: true,
+                ..WasiOpenOptions::default()
+            };
+            let file = overlay.lower.open(&overlay.lower_path(rel), &read)?;
+            (file, false)
+        };
+
+        Ok(Box::new(OverlayFile {
+            inner,
+            copied_up,
+            path: rel.to_path_buf(),
+            append: options.append,
+            overlay: Some(overlay.clone()),
+        }))
+    }
+
+    /// Everything the upper layer records
+    pub(crate) fn changes(&self) -> Result<OverlayChanges, WasiFsError> {
+        let mut changes = OverlayChanges::default();
+        let mut stack = vec![PathBuf::new()];
+        while let Some(dir) = stack.pop() {
+            for entry in self.upper.read_dir(&self.upper_path(&dir))? {
+                if entry.name.starts_with(WHITEOUT_PREFIX) {
+                    changes
+                        .removed
+                        .push(dir.join(&entry.name[WHITEOUT_PREFIX.len()..]));
+                    continue;
+                }
+                let rel = dir.join(&entry.name);
+                match entry.file_type {
+                    WasiFileType::Directory => {
+                        if self.in_lower(&rel).is_none() {
+                            changes.created_dirs.push(rel.clone());
+                        }
+                        stack.push(rel);
+                    }
+                    WasiFileType::File => {
+                        let read = WasiOpenOptions {
+                            read: true,
+                            ..WasiOpenOptions::default()
+                        };
+                        let mut contents = vec![];
+                        self.upper
+                            .open(&self.upper_path(&rel), &read)?
+                            .read_to_end(&mut contents)?;
+                        changes.written.insert(rel, contents);
+                    }
+                    WasiFileType::Symlink | WasiFileType::Other => (),
+                }
+            }
+        }
+        changes.created_dirs.sort();
+        changes.removed.sort();
+        Ok(changes)
+    }
+}
+
+/// A [`WasiFileSystem`] that sends the paths inside of overlaid directories
+/// to their [`Overlay`] and everything else to `base`
+#[derive(Debug)]
+pub(crate) struct OverlayFileSystem {
+    base: Arc<dyn WasiFileSystem>,
+    overlays: Vec<Arc<Overlay>>,
+}
+
+impl OverlayFileSystem {
+    pub(crate) fn new(base: Arc<dyn WasiFileSystem>, overlays: Vec<Arc<Overlay>>) -> Self {
+        Self { base, overlays }
+    }
+
+    /// The innermost overlay containing `path` and the path relative to it
+    fn overlay_of<'a>(&self, path: &'a Path) -> Option<(&Arc<Overlay>, &'a Path)> {
+        self.overlays
+            .iter()
+            .filter_map(|overlay| overlay.relative(path).map(|rel| (overlay, rel)))
+            .min_by_key(|(_, rel)| rel.components().count())
+    }
+}
+
+impl WasiFileSystem for OverlayFileSystem {
+    fn metadata(&self, path: &Path) -> Result<WasiMetadata, WasiFsError> {
+        match self.overlay_of(path) {
+            Some((overlay, rel)) => overlay.metadata(rel),
+            None => self.base.metadata(path),
+        }
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<WasiMetadata, WasiFsError> {
+        match self.overlay_of(path) {
+            Some((overlay, rel)) => overlay.symlink_metadata(rel),
+            None => self.base.symlink_metadata(path),
+        }
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, WasiFsError> {
+        match self.overlay_of(path) {
+            Some((overlay, rel)) => overlay.read_link(rel),
+            None => self.base.read_link(path),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<WasiDirEntry>, WasiFsError> {
+        match self.overlay_of(path) {
+            Some((overlay, rel)) => overlay.read_dir(rel),
+            None => self.base.read_dir(path),
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        match self.overlay_of(path) {
+            Some((overlay, rel)) => overlay.create_dir(rel),
+            None => self.base.create_dir(path),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<(), WasiFsError> {
+        match self.overlay_of(path) {
+            Some((overlay, rel)) => overlay.remove_dir(rel),
+            None => self.base.remove_dir(path),
+        }
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), WasiFsError> {
+        match self.overlay_of(path) {
+            Some((overlay, rel)) => overlay.remove_file(rel),
+            None => self.base.remove_file(path),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), WasiFsError> {
+        match (self.overlay_of(from), self.overlay_of(to)) {
+            (Some((from_overlay, from_rel)), Some((to_overlay, to_rel)))
+                if Arc::ptr_eq(from_overlay, to_overlay) =>
+            {
+                from_overlay.rename(from_rel, to_rel)
+            }
+            (None, None) => self.base.rename(from, to),
+            _ => Err(WasiFsError::UnknownError(__WASI_EXDEV)),
+        }
+    }
+
+    fn open(
+        &self,
+        path: &Path,
+        options: &WasiOpenOptions,
+    ) -> Result<Box<dyn WasiFile>, WasiFsError> {
+        match self.overlay_of(path) {
+            Some((overlay, rel)) => Overlay::open(overlay, rel, options),
+            None => self.base.open(path, options),
+        }
+    }
+}
+
+/// A file in an overlaid directory.
+///
+/// Reads go to the host file until the file is first written to, at which
+/// point it's copied to the upper layer.  A deserialized file is no longer
+/// part of the overlay and can't be copied up anymore.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OverlayFile {
+    inner: Box<dyn WasiFile>,
+    /// `inner` is the file in the upper layer
+    copied_up: bool,
+    /// The path relative to the overlaid directory
+    path: PathBuf,
+    append: bool,
+    #[serde(skip)]
+    overlay: Option<Arc<Overlay>>,
+}
+
+impl OverlayFile {
+    fn copy_up(&mut self) -> Result<(), WasiFsError> {
+        if self.copied_up {
+            return Ok(());
+        }
+        let overlay = self.overlay.as_ref().ok_or(WasiFsError::PermissionDenied)?;
+        let position = self.inner.seek(io::SeekFrom::Current(0))?;
+        overlay.prepare_parent(&self.path)?;
+        overlay.copy_up_file(&self.path, &self.path)?;
+
+        let options = WasiOpenOptions {
+            read: true,
+            write: true,
+            append: self.append,
+            ..WasiOpenOptions::default()
+        };
+        let mut upper = overlay
+            .upper
+            .open(&overlay.upper_path(&self.path), &options)?;
+        upper.seek(io::SeekFrom::Start(position))?;
+        self.inner = upper;
+        self.copied_up = true;
+        Ok(())
+    }
+}
+
+fn into_io_error(error: WasiFsError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, format!("{:?}", error))
+}
+
+impl Read for OverlayFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for OverlayFile {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl Write for OverlayFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.copy_up().map_err(into_io_error)?;
+        self.inner.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for OverlayFile {
+    fn last_accessed(&self) -> __wasi_timestamp_t {
+        self.inner.last_accessed()
+    }
+    fn last_modified(&self) -> __wasi_timestamp_t {
+        self.inner.last_modified()
+    }
+    fn created_time(&self) -> __wasi_timestamp_t {
+        self.inner.created_time()
+    }
+    fn set_last_accessed(&self, last_accessed: __wasi_timestamp_t) {
+        self.inner.set_last_accessed(last_accessed)
+    }
+    fn set_last_modified(&self, last_modified: __wasi_timestamp_t) {
+        self.inner.set_last_modified(last_modified)
+    }
+    fn set_created_time(&self, created_time: __wasi_timestamp_t) {
+        self.inner.set_created_time(created_time)
+    }
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+    fn set_len(&mut self, new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        self.copy_up()?;
+        self.inner.set_len(new_size)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        let overlay = self.overlay.as_ref().ok_or(WasiFsError::PermissionDenied)?;
+        overlay.remove_file(&self.path)
+    }
+    fn sync_to_disk(&self) -> Result<(), WasiFsError> {
+        self.inner.sync_to_disk()
+    }
+    fn rename_file(&self, new_name: &Path) -> Result<(), WasiFsError> {
+        let overlay = self.overlay.as_ref().ok_or(WasiFsError::PermissionDenied)?;
+        let new_rel = overlay
+            .relative(new_name)
+            .ok_or(WasiFsError::UnknownError(__WASI_EXDEV))?;
+        overlay.rename(&self.path, new_rel)
+    }
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        self.inner.bytes_available()
+    }
+    fn get_raw_fd(&self) -> Option<i32> {
+        self.inner.get_raw_fd()
+    }
+    fn poll_readiness(&self, interest: PollEventSet) -> Result<PollEventSet, WasiFsError> {
+        self.inner.poll_readiness(interest)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::MemoryFileSystem;
+
+    fn write_file(fs: &dyn WasiFileSystem, path: &str, contents: &[u8]) {
+        let options = WasiOpenOptions {
+            write: true,
+            create: true,
+            truncate: true,
+            ..WasiOpenOptions::default()
+        };
+        fs.open(Path::new(path), &options)
+            .unwrap()
+            .write_all(contents)
+            .unwrap();
+    }
+
+    fn read_file(fs: &dyn WasiFileSystem, path: &str) -> Vec<u8> {
+        let options = WasiOpenOptions {
+            read: true,
+            ..WasiOpenOptions::default()
+        };
+        let mut contents = vec![];
+        fs.open(Path::new(path), &options)
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    fn names(fs: &dyn WasiFileSystem, path: &str) -> Vec<String> {
+        let mut names: Vec<String> = fs
+            .read_dir(Path::new(path))
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn setup() -> (MemoryFileSystem, MemoryFileSystem, OverlayFileSystem) {
+        let lower = MemoryFileSystem::new();
+        lower.create_dir(Path::new("src")).unwrap();
+        lower.create_dir(Path::new("src/dir")).unwrap();
+        write_file(&lower, "src/a.txt", b"lower a");
+        write_file(&lower, "src/dir/b.txt", b"lower b");
+        write_file(&lower, "outside.txt", b"outside");
+
+        let upper = MemoryFileSystem::new();
+        let overlay = Overlay::new(
+            Arc::new(lower.clone()),
+            PathBuf::from("src"),
+            Arc::new(upper.clone()),
+            PathBuf::new(),
+        );
+        let fs = OverlayFileSystem::new(Arc::new(lower.clone()), vec![Arc::new(overlay)]);
+        (lower, upper, fs)
+    }
+
+    #[test]
+    fn writes_go_to_the_upper_layer() {
+        let (lower, upper, fs) = setup();
+
+        // reading doesn't copy anything
+        assert_eq!(read_file(&fs, "src/a.txt"), b"lower a");
+        assert!(names(&upper, "").is_empty());
+
+        let options = WasiOpenOptions {
+            read: true,
+            write: true,
+            ..WasiOpenOptions::default()
+        };
+        let mut file = fs.open(Path::new("src/dir/b.txt"), &options).unwrap();
+        let mut buf = [0; 6];
+        file.read_exact(&mut buf).unwrap();
+        file.write_all(b"B").unwrap();
+        drop(file);
+        assert_eq!(read_file(&fs, "src/dir/b.txt"), b"lower B");
+        assert_eq!(read_file(&lower, "src/dir/b.txt"), b"lower b");
+
+        write_file(&fs, "src/new.txt", b"new");
+        fs.create_dir(Path::new("src/new_dir")).unwrap();
+        assert_eq!(
+            names(&fs, "src"),
+            vec!["a.txt", "dir", "new.txt", "new_dir"]
+        );
+        assert_eq!(names(&lower, "src"), vec!["a.txt", "dir"]);
+        assert!(lower.metadata(Path::new("src/new.txt")).is_err());
+
+        // paths outside of the overlay are not affected
+        write_file(&fs, "outside.txt", b"changed");
+        assert_eq!(read_file(&lower, "outside.txt"), b"changed");
+    }
+
+    #[test]
+    fn removals_leave_whiteouts() {
+        let (lower, _upper, fs) = setup();
+
+        fs.remove_file(Path::new("src/a.txt")).unwrap();
+        assert_eq!(
+            fs.metadata(Path::new("src/a.txt")).unwrap_err(),
+            WasiFsError::EntityNotFound
+        );
+        assert_eq!(read_file(&lower, "src/a.txt"), b"lower a");
+
+        assert_eq!(
+            fs.remove_dir(Path::new("src/dir")).unwrap_err(),
+            WasiFsError::UnknownError(__WASI_ENOTEMPTY)
+        );
+        assert_eq!(
+            fs.rename(Path::new("src/dir"), Path::new("src/moved"))
+                .unwrap_err(),
+            WasiFsError::UnknownError(__WASI_EXDEV)
+        );
+        fs.remove_file(Path::new("src/dir/b.txt")).unwrap();
+        fs.remove_dir(Path::new("src/dir")).unwrap();
+        assert!(names(&fs, "src").is_empty());
+
+        // a directory created again doesn't show the old contents
+        fs.create_dir(Path::new("src/dir")).unwrap();
+        assert!(names(&fs, "src/dir").is_empty());
+        assert_eq!(
+            fs.metadata(Path::new("src/dir/b.txt")).unwrap_err(),
+            WasiFsError::EntityNotFound
+        );
+
+        // which makes it a directory of the upper layer
+        fs.rename(Path::new("src/dir"), Path::new("src/moved"))
+            .unwrap();
+        assert_eq!(names(&fs, "src"), vec!["moved"]);
+    }
+
+    #[test]
+    fn changes_can_be_listed() {
+        let (_lower, _upper, fs) = setup();
+
+        write_file(&fs, "src/a.txt", b"upper a");
+        fs.create_dir(Path::new("src/dir/sub")).unwrap();
+        write_file(&fs, "src/dir/sub/c.txt", b"c");
+        fs.rename(Path::new("src/dir/b.txt"), Path::new("src/b.txt"))
+            .unwrap();
+
+        let overlay = &fs.overlays[0];
+        let changes = overlay.changes().unwrap();
+        let mut written = HashMap::new();
+        written.insert(PathBuf::from("a.txt"), b"upper a".to_vec());
+        written.insert(PathBuf::from("b.txt"), b"lower b".to_vec());
+        written.insert(PathBuf::from("dir/sub/c.txt"), b"c".to_vec());
+        assert_eq!(
+            changes,
+            OverlayChanges {
+                written,
+                created_dirs: vec![PathBuf::from("dir/sub")],
+                removed: vec![PathBuf::from("dir/b.txt")],
+            }
+        );
+    }
+}
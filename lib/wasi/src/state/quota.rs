@@ -0,0 +1,104 @@
+//! Limits on the resources a WASI program can use through the filesystem.
+
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+
+/// The limits of a [`WasiFs`](crate::state::WasiFs) and how much of them has
+/// been used.  Set the limits with the [`WasiStateBuilder`](crate::state::WasiStateBuilder).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WasiFsQuota {
+    /// The number of bytes that may be written to files, including the bytes
+    /// files grow by when their size is changed
+    pub max_bytes_written: Option<u64>,
+    /// The number of fds that may be open at the same time, including the
+    /// preopened directories and stdio
+    pub max_open_fds: Option<usize>,
+    /// The number of files, directories and symlinks that may be created
+    pub max_created_inodes: Option<u64>,
+    /// The number of bytes written to files so far
+    pub bytes_written: u64,
+    /// The number of files, directories and symlinks created so far
+    pub created_inodes: u64,
+}
+
+impl WasiFsQuota {
+    /// Account for writing `amount` bytes, fails with `__WASI_EDQUOT` if that
+    /// would exceed the limit
+    pub(crate) fn charge_bytes(&mut self, amount: u64) -> Result<(), __wasi_errno_t> {
+        let bytes_written = self
+            .bytes_written
+            .checked_add(amount)
+            .ok_or(__WASI_EDQUOT)?;
+        if let Some(max) = self.max_bytes_written {
+            if bytes_written > max {
+                debug!("Write of {} bytes denied: byte quota exhausted", amount);
+                return Err(__WASI_EDQUOT);
+            }
+        }
+        self.bytes_written = bytes_written;
+        Ok(())
+    }
+
+    /// Give back `amount` bytes charged with `charge_bytes` which were not
+    /// written after all
+    pub(crate) fn refund_bytes(&mut self, amount: u64) {
+        self.bytes_written = self.bytes_written.saturating_sub(amount);
+    }
+
+    /// Account for creating an inode, fails with `__WASI_ENFILE` if that
+    /// would exceed the limit
+    pub(crate) fn charge_inode(&mut self) -> Result<(), __wasi_errno_t> {
+        if let Some(max) = self.max_created_inodes {
+            if self.created_inodes >= max {
+                debug!("Creation denied: inode quota exhausted");
+                return Err(__WASI_ENFILE);
+            }
+        }
+        self.created_inodes += 1;
+        Ok(())
+    }
+
+    /// Check that another fd can be opened while `open_fds` are open
+    pub(crate) fn check_open_fds(&self, open_fds: usize) -> Result<(), __wasi_errno_t> {
+        match self.max_open_fds {
+            Some(max) if open_fds >= max => {
+                debug!("Open denied: fd quota exhausted");
+                Err(__WASI_ENFILE)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn limits_are_enforced() {
+        let mut quota = WasiFsQuota {
+            max_bytes_written: Some(10),
+            max_open_fds: Some(4),
+            max_created_inodes: Some(1),
+            ..WasiFsQuota::default()
+        };
+        assert_eq!(quota.charge_bytes(6), Ok(()));
+        assert_eq!(quota.charge_bytes(5), Err(__WASI_EDQUOT));
+        assert_eq!(quota.charge_bytes(4), Ok(()));
+        assert_eq!(quota.bytes_written, 10);
+        quota.refund_bytes(3);
+        assert_eq!(quota.charge_bytes(4), Err(__WASI_EDQUOT));
+        assert_eq!(quota.charge_bytes(3), Ok(()));
+
+        assert_eq!(quota.charge_inode(), Ok(()));
+        assert_eq!(quota.charge_inode(), Err(__WASI_ENFILE));
+
+        assert_eq!(quota.check_open_fds(3), Ok(()));
+        assert_eq!(quota.check_open_fds(4), Err(__WASI_ENFILE));
+
+        // no limits by default
+        let mut quota = WasiFsQuota::default();
+        assert_eq!(quota.charge_bytes(u64::max_value() / 2), Ok(()));
+        assert_eq!(quota.charge_inode(), Ok(()));
+    }
+}
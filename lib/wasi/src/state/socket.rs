@@ -0,0 +1,346 @@
+//! Host-side sockets that can be handed to WASI programs.
+//!
+//! WASI does not let a program create sockets on its own: sockets are opened
+//! by the host and given to the program as file descriptors.  The host decides
+//! how sockets are connected by providing a [`WasiNetwork`] and which
+//! addresses may be reached by the allow-list in [`WasiNet`].
+
+use crate::state::WasiFsError;
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream},
+    sync::{Arc, Mutex},
+};
+
+/// A connected stream socket that a WASI program can use through
+/// `sock_recv`, `sock_send` and `sock_shutdown`.
+///
+/// `Read` and `Write` must not block; return
+/// [`io::ErrorKind::WouldBlock`] if the operation can't complete right away.
+pub trait WasiSocket: std::fmt::Debug + Send + Read + Write {
+    /// Read data into `buf` without removing it from the socket
+    fn peek(&mut self, buf: &mut [u8]) -> Result<usize, WasiFsError>;
+
+    /// Shut down the read half, the write half, or both halves of the socket
+    fn shutdown(&mut self, how: Shutdown) -> Result<(), WasiFsError>;
+
+    /// Returns the number of bytes available.  This function must not block
+    fn bytes_available(&self) -> Result<usize, WasiFsError>;
+}
+
+/// A network backend which creates the sockets used by WASI programs.
+pub trait WasiNetwork: std::fmt::Debug + Send + Sync {
+    /// Connect a new stream socket to `addr`
+    fn connect(&self, addr: SocketAddr) -> Result<Box<dyn WasiSocket>, WasiFsError>;
+}
+
+/// The networking configuration of a [`WasiState`](crate::state::WasiState).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WasiNet {
+    /// The backend used to connect sockets.
+    ///
+    /// The backend is shared and is not part of the serialized state.
+    #[serde(skip)]
+    pub backend: Option<Arc<dyn WasiNetwork>>,
+    /// The addresses that sockets may be connected to
+    pub allowed_addrs: Vec<SocketAddr>,
+}
+
+impl WasiNet {
+    /// Returns `true` if sockets may be connected to `addr`
+    pub fn is_allowed(&self, addr: &SocketAddr) -> bool {
+        self.allowed_addrs.contains(addr)
+    }
+
+    /// Connect a socket to `addr` through the backend, checking the allow-list
+    pub(crate) fn connect(&self, addr: SocketAddr) -> Result<Box<dyn WasiSocket>, __wasi_errno_t> {
+        if !self.is_allowed(&addr) {
+            debug!("Connection to {} denied: address is not allowed", addr);
+            return Err(__WASI_ENOTCAPABLE);
+        }
+        let backend = self.backend.as_ref().ok_or(__WASI_ENOTSUP)?;
+        backend.connect(addr).map_err(WasiFsError::into_wasi_err)
+    }
+}
+
+/// A [`WasiNetwork`] connecting real TCP sockets on the host
+#[derive(Debug, Default)]
+pub struct HostNetwork;
+
+impl WasiNetwork for HostNetwork {
+    fn connect(&self, addr: SocketAddr) -> Result<Box<dyn WasiSocket>, WasiFsError> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nonblocking(true)?;
+        Ok(Box::new(HostSocket { inner: stream }))
+    }
+}
+
+/// A thin wrapper around `std::net::TcpStream`
+#[derive(Debug)]
+pub struct HostSocket {
+    pub inner: TcpStream,
+}
+
+impl Read for HostSocket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+impl Write for HostSocket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl WasiSocket for HostSocket {
+    fn peek(&mut self, buf: &mut [u8]) -> Result<usize, WasiFsError> {
+        self.inner.peek(buf).map_err(Into::into)
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<(), WasiFsError> {
+        self.inner.shutdown(how).map_err(Into::into)
+    }
+
+    #[cfg(unix)]
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        use std::os::unix::io::AsRawFd;
+        crate::state::host_file_bytes_available(self.inner.as_raw_fd())
+    }
+
+    #[cfg(not(unix))]
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        // not implemented for non-Unix-like targets yet
+        Err(WasiFsError::UnknownError(__WASI_ENOTSUP))
+    }
+}
+
+/// One direction of a [`LoopbackSocket`] connection
+#[derive(Debug, Default)]
+struct LoopbackChannel {
+    data: VecDeque<u8>,
+    /// Set when either end shuts this direction down
+    closed: bool,
+}
+
+/// A [`WasiNetwork`] that lives entirely in memory.
+///
+/// The host calls [`LoopbackNetwork::listen`] on an address and picks up
+/// connections made to it with [`LoopbackNetwork::accept`].  Connections to
+/// addresses nobody listens on are refused.  Clones share the same network.
+///
+/// ```
+/// # use wasmer_wasi::state::{LoopbackNetwork, WasiNetwork};
+/// # use std::io::{Read, Write};
+/// let network = LoopbackNetwork::new();
+/// let addr = "127.0.0.1:8080".parse().unwrap();
+/// network.listen(addr).unwrap();
+///
+/// let mut client = network.connect(addr).unwrap();
+/// let mut server = network.accept(addr).unwrap().unwrap();
+/// client.write_all(b"ping").unwrap();
+///
+/// let mut buf = [0; 4];
+/// server.read_exact(&mut buf).unwrap();
+/// assert_eq!(&buf, b"ping");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LoopbackNetwork {
+    listeners: Arc<Mutex<HashMap<SocketAddr, VecDeque<LoopbackSocket>>>>,
+}
+
+impl LoopbackNetwork {
+    /// Create an empty network
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start accepting connections made to `addr`
+    pub fn listen(&self, addr: SocketAddr) -> Result<(), WasiFsError> {
+        let mut listeners = self.listeners.lock().unwrap();
+        if listeners.contains_key(&addr) {
+            return Err(WasiFsError::AddressInUse);
+        }
+        listeners.insert(addr, VecDeque::new());
+        Ok(())
+    }
+
+    /// Take the host end of the oldest pending connection made to `addr`.
+    ///
+    /// Returns `Ok(None)` if there is no pending connection.
+    pub fn accept(&self, addr: SocketAddr) -> Result<Option<LoopbackSocket>, WasiFsError> {
+        let mut listeners = self.listeners.lock().unwrap();
+        let pending = listeners
+            .get_mut(&addr)
+            .ok_or(WasiFsError::AddressNotAvailable)?;
+        Ok(pending.pop_front())
+    }
+}
+
+impl WasiNetwork for LoopbackNetwork {
+    fn connect(&self, addr: SocketAddr) -> Result<Box<dyn WasiSocket>, WasiFsError> {
+        let mut listeners = self.listeners.lock().unwrap();
+        let pending = listeners
+            .get_mut(&addr)
+            .ok_or(WasiFsError::ConnectionRefused)?;
+        let (client, server) = LoopbackSocket::pair();
+        pending.push_back(server);
+        Ok(Box::new(client))
+    }
+}
+
+/// One end of an in-memory connection created by [`LoopbackNetwork`]
+#[derive(Debug)]
+pub struct LoopbackSocket {
+    rx: Arc<Mutex<LoopbackChannel>>,
+    tx: Arc<Mutex<LoopbackChannel>>,
+}
+
+impl LoopbackSocket {
+    /// Create two connected sockets
+    pub fn pair() -> (LoopbackSocket, LoopbackSocket) {
+        let a_to_b = Arc::new(Mutex::new(LoopbackChannel::default()));
+        let b_to_a = Arc::new(Mutex::new(LoopbackChannel::default()));
+        (
+            LoopbackSocket {
+                rx: b_to_a.clone(),
+                tx: a_to_b.clone(),
+            },
+            LoopbackSocket {
+                rx: a_to_b,
+                tx: b_to_a,
+            },
+        )
+    }
+
+    fn copy_out(&self, buf: &mut [u8], consume: bool) -> io::Result<usize> {
+        let mut rx = self.rx.lock().unwrap();
+        if rx.data.is_empty() {
+            if rx.closed || buf.is_empty() {
+                return Ok(0);
+            }
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let amount = std::cmp::min(buf.len(), rx.data.len());
+        for (dst, src) in buf.iter_mut().zip(rx.data.iter()) {
+            *dst = *src;
+        }
+        if consume {
+            rx.data.drain(..amount);
+        }
+        Ok(amount)
+    }
+}
+
+impl Read for LoopbackSocket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.copy_out(buf, true)
+    }
+}
+
+impl Write for LoopbackSocket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut tx = self.tx.lock().unwrap();
+        if tx.closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        tx.data.extend(buf.iter());
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl WasiSocket for LoopbackSocket {
+    fn peek(&mut self, buf: &mut [u8]) -> Result<usize, WasiFsError> {
+        self.copy_out(buf, false).map_err(Into::into)
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<(), WasiFsError> {
+        if how != Shutdown::Write {
+            let mut rx = self.rx.lock().unwrap();
+            rx.closed = true;
+            rx.data.clear();
+        }
+        if how != Shutdown::Read {
+            self.tx.lock().unwrap().closed = true;
+        }
+        Ok(())
+    }
+
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        Ok(self.rx.lock().unwrap().data.len())
+    }
+}
+
+impl Drop for LoopbackSocket {
+    fn drop(&mut self) {
+        self.rx.lock().unwrap().closed = true;
+        self.tx.lock().unwrap().closed = true;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn loopback_connection() {
+        let network = LoopbackNetwork::new();
+        let addr: SocketAddr = "10.0.0.1:80".parse().unwrap();
+        assert_eq!(
+            network.connect(addr).unwrap_err(),
+            WasiFsError::ConnectionRefused
+        );
+        network.listen(addr).unwrap();
+
+        let mut client = network.connect(addr).unwrap();
+        let mut server = network.accept(addr).unwrap().unwrap();
+        assert!(network.accept(addr).unwrap().is_none());
+
+        let mut buf = [0; 8];
+        assert_eq!(
+            client.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+
+        server.write_all(b"hello").unwrap();
+        assert_eq!(client.bytes_available().unwrap(), 5);
+        assert_eq!(client.peek(&mut buf).unwrap(), 5);
+        assert_eq!(client.read(&mut buf[..3]).unwrap(), 3);
+        assert_eq!(&buf[..3], b"hel");
+        assert_eq!(client.bytes_available().unwrap(), 2);
+
+        server.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(client.read(&mut buf).unwrap(), 2);
+        assert_eq!(client.read(&mut buf).unwrap(), 0);
+
+        drop(server);
+        assert_eq!(
+            client.write(b"bye").unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+
+    #[test]
+    fn allow_list_is_enforced() {
+        let network = LoopbackNetwork::new();
+        let allowed: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let denied: SocketAddr = "127.0.0.1:2".parse().unwrap();
+        network.listen(allowed).unwrap();
+        network.listen(denied).unwrap();
+
+        let net = WasiNet {
+            backend: Some(Arc::new(network)),
+            allowed_addrs: vec![allowed],
+        };
+        assert!(net.connect(allowed).is_ok());
+        assert_eq!(net.connect(denied).unwrap_err(), __WASI_ENOTCAPABLE);
+    }
+}
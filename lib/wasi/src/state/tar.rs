@@ -0,0 +1,254 @@
+//! A minimal reader for tar archives, used to seed in-memory directories.
+//!
+//! Only regular files are extracted.  Directories are created implicitly from
+//! the paths of the files they contain, everything else (links, devices, ...)
+//! is skipped.  Long names in GNU (`L`) and pax (`x`) headers are supported.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::PathBuf;
+
+const BLOCK_SIZE: usize = 512;
+
+/// Read the regular files of the tar archive `archive`, keyed by their path
+pub(crate) fn read_tar_files<R: Read>(mut archive: R) -> Result<HashMap<PathBuf, Vec<u8>>, String> {
+    let mut files = HashMap::new();
+    let mut long_name: Option<String> = None;
+    let mut header = [0u8; BLOCK_SIZE];
+
+    loop {
+        if !read_block(&mut archive, &mut header)? || header.iter().all(|b| *b == 0) {
+            // the archive ends with two zero blocks, but some writers leave them out
+            break;
+        }
+        if !header_checksum_is_valid(&header) {
+            return Err("Invalid checksum in tar header".to_string());
+        }
+
+        let size = parse_size(&header[124..136])?;
+        // the size comes from the archive, so the data is only allocated as it is read
+        let mut data = Vec::new();
+        archive
+            .by_ref()
+            .take(size as u64)
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Could not read tar entry: {}", e))?;
+        if data.len() != size {
+            return Err("Unexpected end of tar archive".to_string());
+        }
+        let padding = (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE;
+        archive
+            .read_exact(&mut [0; BLOCK_SIZE][..padding])
+            .map_err(|e| format!("Could not read tar entry: {}", e))?;
+
+        match header[156] {
+            // GNU long name of the next entry
+            b'L' => long_name = Some(c_str(&data)),
+            // pax extended header of the next entry
+            b'x' => {
+                if let Some(path) = pax_path(&data)? {
+                    long_name = Some(path);
+                }
+            }
+            // regular file
+            b'0' | b'\0' | b'7' => {
+                let name = match long_name.take() {
+                    Some(name) => name,
+                    None => header_name(&header),
+                };
+                files.insert(PathBuf::from(name), data);
+            }
+            _ => long_name = None,
+        }
+    }
+
+    Ok(files)
+}
+
+/// Fill `block`, returns `false` if the archive ended before the block started
+fn read_block<R: Read>(archive: &mut R, block: &mut [u8; BLOCK_SIZE]) -> Result<bool, String> {
+    let mut filled = 0;
+    while filled < BLOCK_SIZE {
+        match archive.read(&mut block[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err("Unexpected end of tar archive".to_string()),
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(format!("Could not read tar archive: {}", e)),
+        }
+    }
+    Ok(true)
+}
+
+fn header_checksum_is_valid(header: &[u8; BLOCK_SIZE]) -> bool {
+    let expected = match parse_octal(&header[148..156]) {
+        Ok(checksum) => checksum,
+        Err(_) => return false,
+    };
+    // the checksum field itself is counted as spaces
+    let sum: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(i, b)| if i >= 148 && i < 156 { b' ' } else { *b } as u64)
+        .sum();
+    sum == expected
+}
+
+/// The path of the entry, joining the ustar prefix if there is one
+fn header_name(header: &[u8; BLOCK_SIZE]) -> String {
+    let name = c_str(&header[0..100]);
+    if &header[257..262] == b"ustar" {
+        let prefix = c_str(&header[345..500]);
+        if !prefix.is_empty() {
+            return format!("{}/{}", prefix, name);
+        }
+    }
+    name
+}
+
+fn c_str(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn parse_size(field: &[u8]) -> Result<usize, String> {
+    // sizes that don't fit in octal are stored in base-256 with the high bit set
+    let size = if field[0] & 0x80 != 0 {
+        field[1..]
+            .iter()
+            .try_fold(u64::from(field[0] & 0x7f), |acc, b| {
+                acc.checked_mul(256).map(|acc| acc | u64::from(*b))
+            })
+            .ok_or("Entry size in tar header is too large")?
+    } else {
+        parse_octal(field)?
+    };
+    if size > usize::max_value() as u64 {
+        return Err("Entry size in tar header is too large".to_string());
+    }
+    Ok(size as usize)
+}
+
+fn parse_octal(field: &[u8]) -> Result<u64, String> {
+    let digits = c_str(field);
+    let digits = digits.trim_matches(|c| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8)
+        .map_err(|_| format!("Invalid number in tar header: {:?}", digits))
+}
+
+/// Finds the `path` record of a pax extended header.  Records look like
+/// `"<length> <key>=<value>\n"` where `<length>` counts the whole record.
+fn pax_path(mut data: &[u8]) -> Result<Option<String>, String> {
+    while !data.is_empty() {
+        let space = data
+            .iter()
+            .position(|b| *b == b' ')
+            .ok_or("Invalid pax header")?;
+        let len: usize = std::str::from_utf8(&data[..space])
+            .ok()
+            .and_then(|len| len.parse().ok())
+            .ok_or("Invalid pax header")?;
+        if len <= space || len > data.len() {
+            return Err("Invalid pax header".to_string());
+        }
+        let record = &data[space + 1..len];
+        if record.starts_with(b"path=") {
+            let mut value = &record[5..];
+            if value.last() == Some(&b'\n') {
+                value = &value[..value.len() - 1];
+            }
+            return Ok(Some(String::from_utf8_lossy(value).into_owned()));
+        }
+        data = &data[len..];
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(name: &str, typeflag: u8, size: usize) -> [u8; BLOCK_SIZE] {
+        let mut header = [0u8; BLOCK_SIZE];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{:011o}", size);
+        header[124..135].copy_from_slice(size.as_bytes());
+        header[156] = typeflag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        set_checksum(&mut header);
+        header
+    }
+
+    fn set_checksum(header: &mut [u8; BLOCK_SIZE]) {
+        header[148..156].copy_from_slice(b"        ");
+        let sum: u64 = header.iter().map(|b| *b as u64).sum();
+        let sum = format!("{:06o}\0 ", sum);
+        header[148..156].copy_from_slice(sum.as_bytes());
+    }
+
+    fn entry(archive: &mut Vec<u8>, name: &str, typeflag: u8, data: &[u8]) {
+        archive.extend_from_slice(&header(name, typeflag, data.len()));
+        archive.extend_from_slice(data);
+        let padding = (BLOCK_SIZE - data.len() % BLOCK_SIZE) % BLOCK_SIZE;
+        archive.extend(std::iter::repeat(0).take(padding));
+    }
+
+    #[test]
+    fn reads_regular_files() {
+        let mut archive = vec![];
+        entry(&mut archive, "dir/", b'5', b"");
+        entry(&mut archive, "dir/hello.txt", b'0', b"hello world");
+        entry(&mut archive, "link", b'2', b"");
+        entry(
+            &mut archive,
+            "././@LongLink",
+            b'L',
+            b"dir/a-very-long-name\0",
+        );
+        entry(&mut archive, "truncated", b'0', &[7; 600]);
+        archive.extend_from_slice(&[0; 2 * BLOCK_SIZE]);
+
+        let files = read_tar_files(&archive[..]).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[&PathBuf::from("dir/hello.txt")], b"hello world");
+        assert_eq!(files[&PathBuf::from("dir/a-very-long-name")], vec![7; 600]);
+    }
+
+    #[test]
+    fn rejects_corrupt_archives() {
+        let mut archive = vec![];
+        entry(&mut archive, "file", b'0', b"data");
+        archive[0] = b'g';
+        assert!(read_tar_files(&archive[..]).is_err());
+
+        let mut archive = vec![];
+        entry(&mut archive, "file", b'0', b"data");
+        archive.truncate(BLOCK_SIZE + 2);
+        assert!(read_tar_files(&archive[..]).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_entries() {
+        // a base-256 size overflowing a u64
+        let mut header = header("file", b'0', 0);
+        header[124] = 0xff;
+        for b in &mut header[125..136] {
+            *b = 0xff;
+        }
+        set_checksum(&mut header);
+        assert!(read_tar_files(&header[..]).is_err());
+
+        // a size far past the end of the archive
+        let mut archive = vec![];
+        entry(&mut archive, "file", b'0', b"data");
+        archive[124..136].copy_from_slice(&[0x80, 0, 0, 0, 0x10, 0, 0, 0, 0, 0, 0, 0]);
+        let mut header = [0u8; BLOCK_SIZE];
+        header.copy_from_slice(&archive[..BLOCK_SIZE]);
+        set_checksum(&mut header);
+        archive[..BLOCK_SIZE].copy_from_slice(&header);
+        assert!(read_tar_files(&archive[..]).is_err());
+    }
+}
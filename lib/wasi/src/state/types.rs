@@ -4,9 +4,11 @@ use serde::{de, Deserialize, Serialize};
 #[cfg(unix)]
 use std::convert::TryInto;
 use std::{
+    collections::VecDeque,
     fs,
     io::{self, Read, Seek, Write},
     path::PathBuf,
+    sync::{Arc, Condvar, Mutex},
     time::SystemTime,
 };
 
@@ -183,6 +185,22 @@ pub trait WasiFile: std::fmt::Debug + Send + Write + Read + Seek {
     fn get_raw_fd(&self) -> Option<i32> {
         None
     }
+
+    /// Returns the events of `interest` that the file is ready for.  This function must not block
+    ///
+    /// Used by `poll_oneoff`.  The default implementation polls the host fd from
+    /// [`WasiFile::get_raw_fd`], files without one are treated like regular files,
+    /// which are always ready.  Implement this method for files like pipes or devices
+    /// that only become ready at some point.
+    fn poll_readiness(&self, interest: PollEventSet) -> Result<PollEventSet, WasiFsError> {
+        #[cfg(unix)]
+        {
+            if let Some(host_fd) = self.get_raw_fd() {
+                return poll_host_fd(host_fd, interest);
+            }
+        }
+        Ok(interest)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -299,46 +317,24 @@ impl PollEventBuilder {
     }
 }
 
+/// Checks which of the `interest` events the host fd `host_fd` is ready for without blocking
 #[cfg(unix)]
-pub(crate) fn poll(
-    selfs: &[&dyn WasiFile],
-    events: &[PollEventSet],
-    seen_events: &mut [PollEventSet],
-) -> Result<u32, WasiFsError> {
-    if !(selfs.len() == events.len() && events.len() == seen_events.len()) {
-        return Err(WasiFsError::InvalidInput);
-    }
-    let mut fds = selfs
-        .iter()
-        .enumerate()
-        .filter_map(|(i, s)| s.get_raw_fd().map(|rfd| (i, rfd)))
-        .map(|(i, host_fd)| libc::pollfd {
-            fd: host_fd,
-            events: poll_event_set_to_platform_poll_events(events[i]),
-            revents: 0,
-        })
-        .collect::<Vec<_>>();
-    let result = unsafe { libc::poll(fds.as_mut_ptr(), selfs.len() as _, 1) };
+pub(crate) fn poll_host_fd(
+    host_fd: i32,
+    interest: PollEventSet,
+) -> Result<PollEventSet, WasiFsError> {
+    let mut fds = [libc::pollfd {
+        fd: host_fd,
+        events: poll_event_set_to_platform_poll_events(interest),
+        revents: 0,
+    }];
+    let result = unsafe { libc::poll(fds.as_mut_ptr(), 1, 0) };
 
     if result < 0 {
         // TODO: check errno and return value
         return Err(WasiFsError::IOError);
     }
-    // convert result and write back values
-    for (i, fd) in fds.into_iter().enumerate() {
-        seen_events[i] = platform_poll_events_to_pollevent_set(fd.revents);
-    }
-    // unwrap is safe because we check for negative values above
-    Ok(result.try_into().unwrap())
-}
-
-#[cfg(not(unix))]
-pub(crate) fn poll(
-    _selfs: &[&dyn WasiFile],
-    _events: &[PollEventSet],
-    _seen_events: &mut [PollEventSet],
-) -> Result<(), WasiFsError> {
-    unimplemented!("HostFile::poll in WasiFile is not implemented for non-Unix-like targets yet");
+    Ok(platform_poll_events_to_pollevent_set(fds[0].revents))
 }
 
 pub trait WasiPath {}
@@ -610,7 +606,7 @@ impl From<io::Error> for WasiFsError {
 }
 
 #[cfg(unix)]
-fn host_file_bytes_available(host_fd: i32) -> Result<usize, WasiFsError> {
+pub(crate) fn host_file_bytes_available(host_fd: i32) -> Result<usize, WasiFsError> {
     let mut bytes_found = 0 as libc::c_int;
     let result = unsafe { libc::ioctl(host_fd, libc::FIONREAD, &mut bytes_found) };
 
@@ -625,7 +621,7 @@ fn host_file_bytes_available(host_fd: i32) -> Result<usize, WasiFsError> {
 }
 
 #[cfg(not(unix))]
-fn host_file_bytes_available(_raw_fd: i32) -> Result<usize, WasiFsError> {
+pub(crate) fn host_file_bytes_available(_raw_fd: i32) -> Result<usize, WasiFsError> {
     unimplemented!("host_file_bytes_available not yet implemented for non-Unix-like targets.  This probably means the program tried to use wasi::poll_oneoff")
 }
 
@@ -909,6 +905,454 @@ impl WasiFile for Stdin {
     }
 }
 
+#[derive(Debug)]
+struct PipeBuffer {
+    data: VecDeque<u8>,
+    capacity: usize,
+    reader_open: bool,
+    writer_open: bool,
+}
+
+#[derive(Debug)]
+struct PipeShared {
+    buffer: Mutex<PipeBuffer>,
+    changed: Condvar,
+}
+
+/// One end of an in-memory pipe, created with [`Pipe::new`].
+///
+/// The pipe holds at most `capacity` bytes.  Reading from an empty pipe and
+/// writing to a full pipe block until the other end makes progress, unless
+/// the end is non-blocking, in which case they fail with
+/// [`io::ErrorKind::WouldBlock`].  Reading returns end of file once the
+/// writer is dropped and the pipe is empty.  Writing fails with
+/// [`io::ErrorKind::BrokenPipe`] once the reader is dropped.
+///
+/// Use the ends as `stdin`, `stdout` or `stderr` to connect WASI programs.
+/// Run each program on its own thread when the ends block.
+///
+/// ```
+/// # use wasmer_wasi::state::{Pipe, WasiState, WasiStateCreationError};
+/// # fn main() -> Result<(), WasiStateCreationError> {
+/// let (reader, writer) = Pipe::new(4096);
+/// let producer = WasiState::new("producer").stdout(Box::new(writer)).build()?;
+/// let consumer = WasiState::new("consumer").stdin(Box::new(reader)).build()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// A pipe that is serialized keeps the data that was not read yet, but is
+/// no longer connected to the other end when deserialized.
+#[derive(Debug)]
+pub struct Pipe {
+    shared: Arc<PipeShared>,
+    is_reader: bool,
+    nonblocking: bool,
+}
+
+impl Pipe {
+    /// Create a pipe that buffers up to `capacity` bytes, returns the
+    /// `(reader, writer)` ends
+    pub fn new(capacity: usize) -> (Pipe, Pipe) {
+        let shared = Arc::new(PipeShared {
+            buffer: Mutex::new(PipeBuffer {
+                data: VecDeque::new(),
+                capacity: std::cmp::max(capacity, 1),
+                reader_open: true,
+                writer_open: true,
+            }),
+            changed: Condvar::new(),
+        });
+        let reader = Pipe {
+            shared: shared.clone(),
+            is_reader: true,
+            nonblocking: false,
+        };
+        let writer = Pipe {
+            shared,
+            is_reader: false,
+            nonblocking: false,
+        };
+        (reader, writer)
+    }
+
+    /// Make reads and writes on this end fail with
+    /// [`io::ErrorKind::WouldBlock`] instead of blocking
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+    }
+
+    /// Returns `true` if this is the end that is read from
+    pub fn is_reader(&self) -> bool {
+        self.is_reader
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut buffer = self.shared.buffer.lock().unwrap();
+        if self.is_reader {
+            buffer.reader_open = false;
+        } else {
+            buffer.writer_open = false;
+        }
+        self.shared.changed.notify_all();
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PipeState {
+    data: Vec<u8>,
+    capacity: usize,
+    is_reader: bool,
+    nonblocking: bool,
+}
+
+impl Serialize for Pipe {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let buffer = self.shared.buffer.lock().unwrap();
+        PipeState {
+            data: buffer.data.iter().cloned().collect(),
+            capacity: buffer.capacity,
+            is_reader: self.is_reader,
+            nonblocking: self.nonblocking,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pipe {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = PipeState::deserialize(deserializer)?;
+        Ok(Pipe {
+            shared: Arc::new(PipeShared {
+                buffer: Mutex::new(PipeBuffer {
+                    data: state.data.into_iter().collect(),
+                    capacity: state.capacity,
+                    // the other end was not serialized with this one
+                    reader_open: state.is_reader,
+                    writer_open: !state.is_reader,
+                }),
+                changed: Condvar::new(),
+            }),
+            is_reader: state.is_reader,
+            nonblocking: state.nonblocking,
+        })
+    }
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.is_reader {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "can not read from the write end of a pipe",
+            ));
+        }
+        let mut buffer = self.shared.buffer.lock().unwrap();
+        while buffer.data.is_empty() && !buf.is_empty() {
+            if !buffer.writer_open {
+                return Ok(0);
+            }
+            if self.nonblocking {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            buffer = self.shared.changed.wait(buffer).unwrap();
+        }
+        let amount = std::cmp::min(buf.len(), buffer.data.len());
+        for (byte, value) in buf.iter_mut().zip(buffer.data.drain(..amount)) {
+            *byte = value;
+        }
+        self.shared.changed.notify_all();
+        Ok(amount)
+    }
+}
+
+impl Seek for Pipe {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(io::ErrorKind::Other, "can not seek a pipe"))
+    }
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.is_reader {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "can not write to the read end of a pipe",
+            ));
+        }
+        let mut buffer = self.shared.buffer.lock().unwrap();
+        loop {
+            if !buffer.reader_open {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            if buffer.data.len() < buffer.capacity || buf.is_empty() {
+                break;
+            }
+            if self.nonblocking {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            buffer = self.shared.changed.wait(buffer).unwrap();
+        }
+        let amount = std::cmp::min(buf.len(), buffer.capacity - buffer.data.len());
+        buffer.data.extend(&buf[..amount]);
+        self.shared.changed.notify_all();
+        Ok(amount)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for Pipe {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        self.shared.buffer.lock().unwrap().data.len() as u64
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Ok(())
+    }
+
+    /// The number of bytes that can be read from the read end, or written to
+    /// the write end, without blocking
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        let buffer = self.shared.buffer.lock().unwrap();
+        if self.is_reader {
+            Ok(buffer.data.len())
+        } else {
+            Ok(buffer.capacity - buffer.data.len())
+        }
+    }
+
+    fn poll_readiness(&self, interest: PollEventSet) -> Result<PollEventSet, WasiFsError> {
+        let buffer = self.shared.buffer.lock().unwrap();
+        let mut ready = PollEventBuilder::new();
+        if self.is_reader {
+            if interest & PollEvent::PollIn as PollEventSet != 0 && !buffer.data.is_empty() {
+                ready = ready.add(PollEvent::PollIn);
+            }
+            if !buffer.writer_open {
+                ready = ready.add(PollEvent::PollHangUp);
+            }
+        } else {
+            if interest & PollEvent::PollOut as PollEventSet != 0
+                && buffer.data.len() < buffer.capacity
+            {
+                ready = ready.add(PollEvent::PollOut);
+            }
+            if !buffer.reader_open {
+                ready = ready.add(PollEvent::PollHangUp);
+            }
+        }
+        Ok(ready.build())
+    }
+}
+
+/// A file that collects everything written to it, for capturing the
+/// `stdout` or `stderr` of a WASI program.
+///
+/// Clones share the same buffer, so a clone can be kept by the host to read
+/// the output after the program has run.
+///
+/// ```
+/// # use wasmer_wasi::state::{CapturedOutput, WasiState, WasiStateCreationError};
+/// # fn main() -> Result<(), WasiStateCreationError> {
+/// let stdout = CapturedOutput::new();
+/// let state = WasiState::new("program_name")
+///    .capture_stdout(&stdout)
+///    .build()?;
+///
+/// // after running the program
+/// let output = stdout.to_string_lossy();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// A serialized capture keeps its contents but no longer shares them once
+/// deserialized.
+#[derive(Debug, Clone, Default)]
+pub struct CapturedOutput {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CapturedOutput {
+    /// Create an empty capture
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A copy of everything written so far
+    pub fn contents(&self) -> Vec<u8> {
+        self.buffer.lock().unwrap().clone()
+    }
+
+    /// Everything written so far as a string, invalid UTF-8 is replaced
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.buffer.lock().unwrap()).into_owned()
+    }
+
+    /// Take everything written so far, leaving the capture empty
+    pub fn take_contents(&self) -> Vec<u8> {
+        std::mem::replace(&mut *self.buffer.lock().unwrap(), Vec::new())
+    }
+}
+
+impl Serialize for CapturedOutput {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.buffer.lock().unwrap().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CapturedOutput {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(CapturedOutput {
+            buffer: Arc::new(Mutex::new(Vec::deserialize(deserializer)?)),
+        })
+    }
+}
+
+impl Read for CapturedOutput {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not read from captured output",
+        ))
+    }
+}
+impl Seek for CapturedOutput {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not seek captured output",
+        ))
+    }
+}
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for CapturedOutput {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        self.buffer.lock().unwrap().len() as u64
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Ok(())
+    }
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        Ok(0)
+    }
+    fn poll_readiness(&self, interest: PollEventSet) -> Result<PollEventSet, WasiFsError> {
+        // writes always succeed right away
+        Ok(interest & PollEvent::PollOut as PollEventSet)
+    }
+}
+
+/// A file that replays fixed input, for giving a WASI program `stdin`
+/// without the host's.  Reading returns end of file once all the input has
+/// been read.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplayInput {
+    data: Vec<u8>,
+    position: usize,
+}
+
+impl ReplayInput {
+    /// Create a file that replays `data`
+    pub fn new<T: Into<Vec<u8>>>(data: T) -> Self {
+        Self {
+            data: data.into(),
+            position: 0,
+        }
+    }
+}
+
+impl Read for ReplayInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = &self.data[self.position..];
+        let amount = std::cmp::min(buf.len(), remaining.len());
+        buf[..amount].copy_from_slice(&remaining[..amount]);
+        self.position += amount;
+        Ok(amount)
+    }
+}
+impl Seek for ReplayInput {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(io::ErrorKind::Other, "can not seek stdin"))
+    }
+}
+impl Write for ReplayInput {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not write to replayed input",
+        ))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for ReplayInput {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        self.data.len() as u64
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Ok(())
+    }
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        Ok(self.data.len() - self.position)
+    }
+    fn poll_readiness(&self, interest: PollEventSet) -> Result<PollEventSet, WasiFsError> {
+        // reads never block, at the end they return end of file
+        Ok(interest & PollEvent::PollIn as PollEventSet)
+    }
+}
+
 /*
 TODO: Think about using this
 trait WasiFdBacking: std::fmt::Debug {
@@ -918,3 +1362,78 @@ trait WasiFdBacking: std::fmt::Debug {
     fn get_name(&self) -> &str;
 }
 */
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pipe_connects_reader_and_writer() {
+        let (mut reader, mut writer) = Pipe::new(4);
+        assert_eq!(writer.write(b"hello").unwrap(), 4);
+        assert_eq!(reader.bytes_available(), Ok(4));
+        assert_eq!(writer.bytes_available(), Ok(0));
+
+        writer.set_nonblocking(true);
+        assert_eq!(
+            writer.write(b"o").unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+
+        let consumer = std::thread::spawn(move || {
+            let mut out = String::new();
+            reader.read_to_string(&mut out).unwrap();
+            out
+        });
+        writer.set_nonblocking(false);
+        writer.write_all(b"o, world").unwrap();
+        drop(writer);
+        assert_eq!(consumer.join().unwrap(), "hello, world");
+    }
+
+    #[test]
+    fn pipe_reports_closed_ends() {
+        let (mut reader, writer) = Pipe::new(16);
+        reader.set_nonblocking(true);
+        let mut buf = [0; 4];
+        assert_eq!(
+            reader.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+        assert_eq!(
+            reader.poll_readiness(PollEvent::PollIn as PollEventSet),
+            Ok(0)
+        );
+
+        drop(writer);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(
+            reader.poll_readiness(PollEvent::PollIn as PollEventSet),
+            Ok(PollEvent::PollHangUp as PollEventSet)
+        );
+
+        let (reader, mut writer) = Pipe::new(16);
+        drop(reader);
+        assert_eq!(
+            writer.write(b"data").unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+
+    #[test]
+    fn captured_output_is_shared_between_clones() {
+        let capture = CapturedOutput::new();
+        let mut file: Box<dyn WasiFile> = Box::new(capture.clone());
+        file.write_all(b"hello ").unwrap();
+        file.write_all(b"world").unwrap();
+        assert_eq!(capture.to_string_lossy(), "hello world");
+        assert_eq!(capture.take_contents(), b"hello world".to_vec());
+        assert!(capture.contents().is_empty());
+
+        let mut input = ReplayInput::new("abc");
+        let mut out = String::new();
+        input.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "abc");
+        assert_eq!(input.bytes_available(), Ok(0));
+    }
+}
@@ -11,15 +11,15 @@ use self::types::*;
 use crate::{
     ptr::{Array, WasmPtr},
     state::{
-        self, host_file_type_to_wasi_file_type, iterate_poll_events, poll, Fd, HostFile, Inode,
-        InodeVal, Kind, PollEvent, PollEventBuilder, WasiFile, WasiFsError, WasiState,
-        MAX_SYMLINKS,
+        self, iterate_poll_events, Fd, Inode, InodeVal, Kind, PollEvent, PollEventBuilder,
+        PollEventSet, WasiFile, WasiFs, WasiFsError, WasiFsQuota, WasiNet, WasiOpenOptions,
+        WasiSocket, WasiState, MAX_SYMLINKS,
     },
     ExitCode,
 };
 use std::borrow::Borrow;
 use std::cell::Cell;
-use std::convert::{Infallible, TryInto};
+use std::convert::{Infallible, TryFrom, TryInto};
 use std::io::{self, Read, Seek, Write};
 use wasmer_runtime_core::{memory::Memory, vm::Ctx};
 
@@ -49,7 +49,7 @@ fn write_bytes_inner<T: Write>(
         let bytes = iov_inner.buf.deref(memory, 0, iov_inner.buf_len)?;
         write_loc
             .write_all(&bytes.iter().map(|b_cell| b_cell.get()).collect::<Vec<u8>>())
-            .map_err(|_| __WASI_EIO)?;
+            .map_err(|e| WasiFsError::from(e).into_wasi_err())?;
 
         // TODO: handle failure more accurately
         bytes_written += iov_inner.buf_len;
@@ -67,6 +67,91 @@ fn write_bytes<T: Write>(
     result
 }
 
+/// The number of bytes the iovecs ask to write
+fn iovs_total_len(iovs_arr_cell: &[Cell<__wasi_ciovec_t>]) -> u64 {
+    iovs_arr_cell
+        .iter()
+        .map(|iov| iov.get().buf_len as u64)
+        .sum()
+}
+
+/// Writes like `write_bytes`, charging the bytes written to the byte quota
+/// of the filesystem
+///
+/// The whole length of the iovecs is charged before writing, so a write
+/// cannot exceed the quota, and the bytes which were not written are refunded.
+fn write_bytes_charged<T: Write>(
+    quota: &mut WasiFsQuota,
+    write_loc: T,
+    memory: &Memory,
+    iovs_arr_cell: &[Cell<__wasi_ciovec_t>],
+) -> Result<u32, __wasi_errno_t> {
+    let requested = iovs_total_len(iovs_arr_cell);
+    quota.charge_bytes(requested)?;
+    let result = write_bytes(write_loc, memory, iovs_arr_cell);
+    let written = result.as_ref().map(|&n| n as u64).unwrap_or(0);
+    quota.refund_bytes(requested.saturating_sub(written));
+    result
+}
+
+/// The size in-memory files cannot grow past, as the host allocates all of
+/// it, including the zeros filling the gap left by a write past the end
+const MAX_BUFFER_SIZE: u64 = 1 << 32;
+
+/// Writes like `write_bytes_charged` to the in-memory file `buffer` at
+/// `offset`, growing it if the write ends past its end
+///
+/// The zeros filling the gap between the end of the file and `offset` are
+/// charged as well.  Fails with `__WASI_EFBIG` if the file would grow past
+/// `MAX_BUFFER_SIZE`.
+fn write_buffer_charged(
+    quota: &mut WasiFsQuota,
+    buffer: &mut Vec<u8>,
+    offset: u64,
+    memory: &Memory,
+    iovs_arr_cell: &[Cell<__wasi_ciovec_t>],
+) -> Result<u32, __wasi_errno_t> {
+    let end = offset
+        .checked_add(iovs_total_len(iovs_arr_cell))
+        .ok_or(__WASI_EFBIG)?;
+    if end > MAX_BUFFER_SIZE {
+        return Err(__WASI_EFBIG);
+    }
+    let gap = offset.saturating_sub(buffer.len() as u64);
+    quota.charge_bytes(gap)?;
+    let mut cursor = std::io::Cursor::new(&mut *buffer);
+    cursor.set_position(offset);
+    let result = write_bytes_charged(quota, &mut cursor, memory, iovs_arr_cell);
+    if (buffer.len() as u64) < offset {
+        // nothing was written, so the gap was not filled
+        quota.refund_bytes(gap);
+    }
+    result
+}
+
+/// The largest number of bytes `sock_recv` receives at once
+const MAX_SOCK_RECV_CHUNK: usize = 64 * 1024;
+
+/// Copies `bytes` into the memory of the vectors `iovs`, starting `offset`
+/// bytes into them
+fn write_to_iovs(iovs: &[&[Cell<u8>]], mut offset: usize, mut bytes: &[u8]) {
+    for cells in iovs {
+        if bytes.is_empty() {
+            break;
+        }
+        if offset >= cells.len() {
+            offset -= cells.len();
+            continue;
+        }
+        let amount = std::cmp::min(cells.len() - offset, bytes.len());
+        for (cell, &byte) in cells[offset..offset + amount].iter().zip(bytes.iter()) {
+            cell.set(byte);
+        }
+        bytes = &bytes[amount..];
+        offset = 0;
+    }
+}
+
 fn read_bytes<T: Read>(
     mut reader: T,
     memory: &Memory,
@@ -79,11 +164,124 @@ fn read_bytes<T: Read>(
         let bytes = iov_inner.buf.deref(memory, 0, iov_inner.buf_len)?;
         let mut raw_bytes: &mut [u8] =
             unsafe { &mut *(bytes as *const [_] as *mut [_] as *mut [u8]) };
-        bytes_read += reader.read(raw_bytes).map_err(|_| __WASI_EIO)? as u32;
+        let amount = reader
+            .read(raw_bytes)
+            .map_err(|e| WasiFsError::from(e).into_wasi_err())?;
+        bytes_read += amount as u32;
+        // don't wait for more data once some has been read
+        if amount < raw_bytes.len() {
+            break;
+        }
     }
     Ok(bytes_read)
 }
 
+/// Returns the socket stored in `handle`, connecting it to `addr` through the
+/// network backend first if it's not connected yet
+fn connect_socket<'a>(
+    net: &WasiNet,
+    addr: std::net::SocketAddr,
+    handle: &'a mut Option<Box<dyn WasiSocket>>,
+) -> Result<&'a mut Box<dyn WasiSocket>, __wasi_errno_t> {
+    if handle.is_none() {
+        debug!("Connecting socket to {}", addr);
+        *handle = Some(net.connect(addr)?);
+    }
+    Ok(handle.as_mut().unwrap())
+}
+
+/// Looks up the socket behind `fd`, connecting it if needed.  Also checks that
+/// `fd` has all the rights in `rights`.
+fn get_socket<'a>(
+    fs: &'a mut WasiFs,
+    net: &WasiNet,
+    fd: __wasi_fd_t,
+    rights: __wasi_rights_t,
+) -> Result<&'a mut Box<dyn WasiSocket>, __wasi_errno_t> {
+    let fd_entry = fs.get_fd(fd)?;
+    if !has_rights(fd_entry.rights, rights) {
+        return Err(__WASI_EACCES);
+    }
+    let inode = fd_entry.inode;
+    match &mut fs.inodes[inode].kind {
+        Kind::Socket { addr, handle } => connect_socket(net, *addr, handle),
+        _ => Err(__WASI_ENOTSOCK),
+    }
+}
+
+/// Checks which of the `interest` events `fd` is ready for without blocking.
+/// Also returns the number of bytes that can be read from `fd`.
+fn fd_readiness(
+    state: &mut WasiState,
+    fd: __wasi_fd_t,
+    interest: PollEventSet,
+    rights: __wasi_rights_t,
+) -> Result<(PollEventSet, u64), __wasi_errno_t> {
+    let fd_entry = state.fs.get_fd(fd)?;
+    match fd {
+        __WASI_STDIN_FILENO | __WASI_STDOUT_FILENO | __WASI_STDERR_FILENO => (),
+        _ => {
+            if !has_rights(fd_entry.rights, rights | __WASI_RIGHT_POLL_FD_READWRITE) {
+                return Err(__WASI_EACCES);
+            }
+        }
+    }
+    let offset = fd_entry.offset;
+    let inode = fd_entry.inode;
+
+    let wants_read = interest & PollEvent::PollIn as PollEventSet != 0;
+    match &mut state.fs.inodes[inode].kind {
+        Kind::File { handle, .. } => {
+            let handle = handle.as_ref().ok_or(__WASI_EBADF)?;
+            let ready = handle
+                .poll_readiness(interest)
+                .map_err(WasiFsError::into_wasi_err)?;
+            let nbytes = if wants_read && ready & PollEvent::PollIn as PollEventSet != 0 {
+                handle
+                    .bytes_available()
+                    .map_err(WasiFsError::into_wasi_err)? as u64
+            } else {
+                0
+            };
+            Ok((ready, nbytes))
+        }
+        // like regular files, buffers are always ready
+        Kind::Buffer { buffer } => Ok((interest, (buffer.len() as u64).saturating_sub(offset))),
+        Kind::Socket { addr, handle } => {
+            let socket = connect_socket(&state.net, *addr, handle)?;
+            let nbytes = socket
+                .bytes_available()
+                .map_err(WasiFsError::into_wasi_err)? as u64;
+            let mut ready = PollEventBuilder::new();
+            if wants_read && nbytes > 0 {
+                ready = ready.add(PollEvent::PollIn);
+            }
+            if interest & PollEvent::PollOut as PollEventSet != 0 {
+                ready = ready.add(PollEvent::PollOut);
+            }
+            Ok((ready.build(), nbytes))
+        }
+        Kind::Dir { .. } | Kind::Root { .. } | Kind::Symlink { .. } => Err(__WASI_EBADF),
+    }
+}
+
+/// Reads the clock `clock_id` from the clock of `state`, or from the host if
+/// there is none
+fn clock_time(
+    state: &WasiState,
+    clock_id: __wasi_clockid_t,
+    precision: __wasi_timestamp_t,
+) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+    if let Some(clock) = &state.clock {
+        return clock.time_get(clock_id, precision);
+    }
+    let time = Cell::new(0);
+    match platform_clock_time_get(clock_id, precision, &time) {
+        __WASI_ESUCCESS => Ok(time.get()),
+        err => Err(err),
+    }
+}
+
 /// checks that `rights_check_set` is a subset of `rights_set`
 fn has_rights(rights_set: __wasi_rights_t, rights_check_set: __wasi_rights_t) -> bool {
     rights_set | rights_check_set == rights_set
@@ -201,9 +399,13 @@ pub fn clock_res_get(
     resolution: WasmPtr<__wasi_timestamp_t>,
 ) -> __wasi_errno_t {
     debug!("wasi::clock_res_get");
-    let memory = ctx.memory(0);
+    let (memory, state) = get_memory_and_wasi_state(ctx, 0);
 
     let out_addr = wasi_try!(resolution.deref(memory));
+    if let Some(clock) = &state.clock {
+        out_addr.set(wasi_try!(clock.res_get(clock_id)));
+        return __WASI_ESUCCESS;
+    }
     platform_clock_res_get(clock_id, out_addr)
 }
 
@@ -227,10 +429,15 @@ pub fn clock_time_get(
         "wasi::clock_time_get clock_id: {}, precision: {}",
         clock_id, precision
     );
-    let memory = ctx.memory(0);
+    let (memory, state) = get_memory_and_wasi_state(ctx, 0);
 
     let out_addr = wasi_try!(time.deref(memory));
-    let result = platform_clock_time_get(clock_id, precision, out_addr);
+    let result = if let Some(clock) = &state.clock {
+        out_addr.set(wasi_try!(clock.time_get(clock_id, precision)));
+        __WASI_ESUCCESS
+    } else {
+        platform_clock_time_get(clock_id, precision, out_addr)
+    };
     debug!(
         "time: {} => {}",
         wasi_try!(time.deref(memory)).get(),
@@ -342,15 +549,26 @@ pub fn fd_allocate(
     match &mut state.fs.inodes[inode].kind {
         Kind::File { handle, .. } => {
             if let Some(handle) = handle {
+                wasi_try!(state
+                    .fs
+                    .quota
+                    .charge_bytes(new_size.saturating_sub(handle.size())));
                 wasi_try!(handle.set_len(new_size).map_err(WasiFsError::into_wasi_err));
             } else {
                 return __WASI_EBADF;
             }
         }
         Kind::Buffer { buffer } => {
+            if new_size > MAX_BUFFER_SIZE {
+                return __WASI_EFBIG;
+            }
+            wasi_try!(state
+                .fs
+                .quota
+                .charge_bytes(new_size.saturating_sub(buffer.len() as u64)));
             buffer.resize(new_size as usize, 0);
         }
-        Kind::Symlink { .. } => return __WASI_EBADF,
+        Kind::Symlink { .. } | Kind::Socket { .. } => return __WASI_EBADF,
         Kind::Dir { .. } | Kind::Root { .. } => return __WASI_EISDIR,
     }
     state.fs.inodes[inode].stat.st_size = new_size;
@@ -537,15 +755,26 @@ pub fn fd_filestat_set_size(
     match &mut state.fs.inodes[inode].kind {
         Kind::File { handle, .. } => {
             if let Some(handle) = handle {
+                wasi_try!(state
+                    .fs
+                    .quota
+                    .charge_bytes(st_size.saturating_sub(handle.size())));
                 wasi_try!(handle.set_len(st_size).map_err(WasiFsError::into_wasi_err));
             } else {
                 return __WASI_EBADF;
             }
         }
         Kind::Buffer { buffer } => {
+            if st_size > MAX_BUFFER_SIZE {
+                return __WASI_EFBIG;
+            }
+            wasi_try!(state
+                .fs
+                .quota
+                .charge_bytes(st_size.saturating_sub(buffer.len() as u64)));
             buffer.resize(st_size as usize, 0);
         }
-        Kind::Symlink { .. } => return __WASI_EBADF,
+        Kind::Symlink { .. } | Kind::Socket { .. } => return __WASI_EBADF,
         Kind::Dir { .. } | Kind::Root { .. } => return __WASI_EISDIR,
     }
     state.fs.inodes[inode].stat.st_size = st_size;
@@ -693,10 +922,14 @@ pub fn fd_pread(
                     }
                 }
                 Kind::Dir { .. } | Kind::Root { .. } => return __WASI_EISDIR,
-                Kind::Symlink { .. } => unimplemented!("Symlinks in wasi::fd_pread"),
-                Kind::Buffer { buffer } => {
-                    wasi_try!(read_bytes(&buffer[(offset as usize)..], memory, iov_cells))
-                }
+                // `path_open` resolves symlinks, so no fd refers to one
+                Kind::Symlink { .. } => return __WASI_EBADF,
+                Kind::Socket { .. } => return __WASI_ESPIPE,
+                Kind::Buffer { buffer } => wasi_try!(read_bytes(
+                    buffer.get(offset as usize..).unwrap_or(&[]),
+                    memory,
+                    iov_cells
+                )),
             }
         }
     };
@@ -771,7 +1004,9 @@ pub fn fd_prestat_dir_name(
                 __WASI_EOVERFLOW
             }
         }
-        Kind::Symlink { .. } | Kind::Buffer { .. } | Kind::File { .. } => __WASI_ENOTDIR,
+        Kind::Symlink { .. } | Kind::Buffer { .. } | Kind::File { .. } | Kind::Socket { .. } => {
+            __WASI_ENOTDIR
+        }
     }
 }
 
@@ -838,7 +1073,12 @@ pub fn fd_pwrite(
                 Kind::File { handle, .. } => {
                     if let Some(handle) = handle {
                         handle.seek(std::io::SeekFrom::Start(offset as u64));
-                        wasi_try!(write_bytes(handle, memory, iovs_arr_cell))
+                        wasi_try!(write_bytes_charged(
+                            &mut state.fs.quota,
+                            handle,
+                            memory,
+                            iovs_arr_cell
+                        ))
                     } else {
                         return __WASI_EINVAL;
                     }
@@ -847,12 +1087,20 @@ pub fn fd_pwrite(
                     // TODO: verify
                     return __WASI_EISDIR;
                 }
-                Kind::Symlink { .. } => unimplemented!("Symlinks in wasi::fd_pwrite"),
-                Kind::Buffer { buffer } => wasi_try!(write_bytes(
-                    &mut buffer[(offset as usize)..],
-                    memory,
-                    iovs_arr_cell
-                )),
+                // `path_open` resolves symlinks, so no fd refers to one
+                Kind::Symlink { .. } => return __WASI_EBADF,
+                Kind::Socket { .. } => return __WASI_ESPIPE,
+                Kind::Buffer { buffer } => {
+                    let bytes_written = wasi_try!(write_buffer_charged(
+                        &mut state.fs.quota,
+                        buffer,
+                        offset,
+                        memory,
+                        iovs_arr_cell
+                    ));
+                    inode.stat.st_size = buffer.len() as u64;
+                    bytes_written
+                }
             };
 
             bytes_written
@@ -924,10 +1172,17 @@ pub fn fd_read(
                     // TODO: verify
                     return __WASI_EISDIR;
                 }
-                Kind::Symlink { .. } => unimplemented!("Symlinks in wasi::fd_read"),
-                Kind::Buffer { buffer } => {
-                    wasi_try!(read_bytes(&buffer[offset..], memory, iovs_arr_cell))
+                // `path_open` resolves symlinks, so no fd refers to one
+                Kind::Symlink { .. } => return __WASI_EBADF,
+                Kind::Socket { addr, handle } => {
+                    let socket = wasi_try!(connect_socket(&state.net, *addr, handle));
+                    wasi_try!(read_bytes(socket, memory, iovs_arr_cell))
                 }
+                Kind::Buffer { buffer } => wasi_try!(read_bytes(
+                    buffer.get(offset..).unwrap_or(&[]),
+                    memory,
+                    iovs_arr_cell
+                )),
             };
 
             fd_entry.offset += bytes_read as u64;
@@ -976,22 +1231,37 @@ pub fn fd_readdir(
     let mut buf_idx = 0;
 
     let entries: Vec<(String, u8, u64)> = match &state.fs.inodes[working_dir.inode].kind {
+        Kind::Dir {
+            entries,
+            in_memory: true,
+            ..
+        } => {
+            let mut entry_vec: Vec<(String, u8, u64)> = entries
+                .iter()
+                .map(|(name, inode)| {
+                    let entry = &state.fs.inodes[*inode];
+                    (name.clone(), entry.stat.st_filetype, entry.stat.st_ino)
+                })
+                .collect();
+            entry_vec.sort_by(|a, b| a.0.cmp(&b.0));
+            entry_vec
+        }
         Kind::Dir { path, entries, .. } => {
             // TODO: refactor this code
             // we need to support multiple calls,
             // simple and obviously correct implementation for now:
             // maintain consistent order via lexacographic sorting
-            let fs_info = wasi_try!(wasi_try!(std::fs::read_dir(path).map_err(|_| __WASI_EIO))
-                .collect::<Result<Vec<_>, _>>()
-                .map_err(|_| __WASI_EIO));
-            let mut entry_vec = wasi_try!(fs_info
+            let fs_info = wasi_try!(state.fs.backend.read_dir(path).map_err(|_| __WASI_EIO));
+            let mut entry_vec = fs_info
                 .into_iter()
-                .map(|entry| Ok((
-                    entry.file_name().to_string_lossy().to_string(),
-                    host_file_type_to_wasi_file_type(entry.file_type().map_err(|_| __WASI_EIO)?),
-                    0, // TODO: inode
-                )))
-                .collect::<Result<Vec<(String, u8, u64)>, _>>());
+                .map(|entry| {
+                    (
+                        entry.name,
+                        entry.file_type.to_wasi_file_type(),
+                        0, // TODO: inode
+                    )
+                })
+                .collect::<Vec<(String, u8, u64)>>();
             entry_vec.extend(
                 entries
                     .iter()
@@ -1027,7 +1297,9 @@ pub fn fd_readdir(
                 })
                 .collect()
         }
-        Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } => return __WASI_ENOTDIR,
+        Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } | Kind::Socket { .. } => {
+            return __WASI_ENOTDIR
+        }
     };
 
     for (entry_path_str, wasi_file_type, ino) in entries.iter().skip(cookie as usize) {
@@ -1132,13 +1404,13 @@ pub fn fd_seek(
                         return __WASI_EINVAL;
                     }
                 }
-                Kind::Symlink { .. } => {
-                    unimplemented!("wasi::fd_seek not implemented for symlinks")
-                }
+                // `path_open` resolves symlinks, so no fd refers to one
+                Kind::Symlink { .. } => return __WASI_EBADF,
                 Kind::Dir { .. } | Kind::Root { .. } => {
                     // TODO: check this
                     return __WASI_EINVAL;
                 }
+                Kind::Socket { .. } => return __WASI_ESPIPE,
                 Kind::Buffer { .. } => {
                     // seeking buffers probably makes sense
                     // TODO: implement this
@@ -1184,7 +1456,7 @@ pub fn fd_sync(ctx: &mut Ctx, fd: __wasi_fd_t) -> __wasi_errno_t {
             }
         }
         Kind::Root { .. } | Kind::Dir { .. } => return __WASI_EISDIR,
-        Kind::Buffer { .. } | Kind::Symlink { .. } => return __WASI_EINVAL,
+        Kind::Buffer { .. } | Kind::Symlink { .. } | Kind::Socket { .. } => return __WASI_EINVAL,
     }
 
     __WASI_ESUCCESS
@@ -1285,7 +1557,12 @@ pub fn fd_write(
                 Kind::File { handle, .. } => {
                     if let Some(handle) = handle {
                         handle.seek(std::io::SeekFrom::Start(offset as u64));
-                        wasi_try!(write_bytes(handle, memory, iovs_arr_cell))
+                        wasi_try!(write_bytes_charged(
+                            &mut state.fs.quota,
+                            handle,
+                            memory,
+                            iovs_arr_cell
+                        ))
                     } else {
                         return __WASI_EINVAL;
                     }
@@ -1294,9 +1571,26 @@ pub fn fd_write(
                     // TODO: verify
                     return __WASI_EISDIR;
                 }
-                Kind::Symlink { .. } => unimplemented!("Symlinks in wasi::fd_write"),
+                // `path_open` resolves symlinks, so no fd refers to one
+                Kind::Symlink { .. } => return __WASI_EBADF,
+                Kind::Socket { addr, handle } => {
+                    let socket = wasi_try!(connect_socket(&state.net, *addr, handle));
+                    wasi_try!(write_bytes(socket, memory, iovs_arr_cell))
+                }
                 Kind::Buffer { buffer } => {
-                    wasi_try!(write_bytes(&mut buffer[offset..], memory, iovs_arr_cell))
+                    let offset = if fd_entry.flags & __WASI_FDFLAG_APPEND != 0 {
+                        fd_entry.offset = buffer.len() as u64;
+                        buffer.len() as u64
+                    } else {
+                        offset as u64
+                    };
+                    wasi_try!(write_buffer_charged(
+                        &mut state.fs.quota,
+                        buffer,
+                        offset,
+                        memory,
+                        iovs_arr_cell
+                    ))
                 }
             };
 
@@ -1368,6 +1662,7 @@ pub fn path_create_directory(
                 ref mut entries,
                 path,
                 parent,
+                in_memory,
             } => {
                 match comp.borrow() {
                     ".." => {
@@ -1382,18 +1677,33 @@ pub fn path_create_directory(
                 if let Some(child) = entries.get(comp) {
                     cur_dir_inode = *child;
                 } else {
-                    let mut adjusted_path = path.clone();
-                    // TODO: double check this doesn't risk breaking the sandbox
-                    adjusted_path.push(comp);
-                    if adjusted_path.exists() && !adjusted_path.is_dir() {
-                        return __WASI_ENOTDIR;
-                    } else if !adjusted_path.exists() {
-                        wasi_try!(std::fs::create_dir(&adjusted_path).ok(), __WASI_EIO);
-                    }
+                    let in_memory = *in_memory;
+                    let adjusted_path = if in_memory {
+                        wasi_try!(state.fs.quota.charge_inode());
+                        std::path::PathBuf::new()
+                    } else {
+                        let mut adjusted_path = path.clone();
+                        // TODO: double check this doesn't risk breaking the sandbox
+                        adjusted_path.push(comp);
+                        match state.fs.backend.metadata(&adjusted_path) {
+                            Ok(metadata) if !metadata.is_dir() => return __WASI_ENOTDIR,
+                            Ok(_) => (),
+                            Err(_) => {
+                                wasi_try!(state.fs.quota.charge_inode());
+                                wasi_try!(state
+                                    .fs
+                                    .backend
+                                    .create_dir(&adjusted_path)
+                                    .map_err(|_| __WASI_EIO))
+                            }
+                        }
+                        adjusted_path
+                    };
                     let kind = Kind::Dir {
                         parent: Some(cur_dir_inode),
                         path: adjusted_path,
                         entries: Default::default(),
+                        in_memory,
                     };
                     let new_inode = wasi_try!(state.fs.create_inode(kind, false, comp.to_string()));
                     // reborrow to insert
@@ -1455,6 +1765,13 @@ pub fn path_filestat_get(
     ));
     let stat = if state.fs.inodes[file_inode].is_preopened {
         state.fs.inodes[file_inode].stat.clone()
+    } else if let Kind::Dir {
+        in_memory: true, ..
+    }
+    | Kind::Buffer { .. } = &state.fs.inodes[file_inode].kind
+    {
+        // in-memory inodes have no host file to refresh their stat from
+        state.fs.inodes[file_inode].stat.clone()
     } else {
         wasi_try!(state
             .fs
@@ -1631,7 +1948,9 @@ pub fn path_link(
             entries.insert(new_entry_name, source_inode);
         }
         Kind::Root { .. } => return __WASI_EINVAL,
-        Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } => return __WASI_ENOTDIR,
+        Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } | Kind::Socket { .. } => {
+            return __WASI_ENOTDIR
+        }
     }
     state.fs.inodes[source_inode].stat.st_nlink += 1;
 
@@ -1733,11 +2052,10 @@ pub fn path_open(
                     return __WASI_ENOTDIR;
                 }
                 if o_flags & __WASI_O_EXCL != 0 {
-                    if path.exists() {
+                    if state.fs.backend.metadata(path).is_ok() {
                         return __WASI_EEXIST;
                     }
                 }
-                let mut open_options = std::fs::OpenOptions::new();
                 let write_permission = adjusted_rights & __WASI_RIGHT_FD_WRITE != 0;
                 // append, truncate, and create all require the permission to write
                 let (append_permission, truncate_permission, create_permission) =
@@ -1750,13 +2068,15 @@ pub fn path_open(
                     } else {
                         (false, false, false)
                     };
-                let open_options = open_options
-                    .read(true)
+                let open_options = WasiOpenOptions {
+                    read: true,
                     // TODO: ensure these rights are actually valid given parent, etc.
-                    .write(write_permission)
-                    .create(create_permission)
-                    .append(append_permission)
-                    .truncate(truncate_permission);
+                    write: write_permission,
+                    create: create_permission,
+                    append: append_permission,
+                    truncate: truncate_permission,
+                    ..WasiOpenOptions::default()
+                };
                 open_flags |= Fd::READ;
                 if adjusted_rights & __WASI_RIGHT_FD_WRITE != 0 {
                     open_flags |= Fd::WRITE;
@@ -1767,15 +2087,30 @@ pub fn path_open(
                 if o_flags & __WASI_O_TRUNC != 0 {
                     open_flags |= Fd::TRUNCATE;
                 }
-                *handle = Some(Box::new(HostFile::new(
-                    wasi_try!(open_options.open(&path).map_err(|_| __WASI_EIO)),
-                    path.to_path_buf(),
-                    true,
-                    adjusted_rights & __WASI_RIGHT_FD_WRITE != 0,
-                    false,
-                )));
+                *handle = Some(wasi_try!(state
+                    .fs
+                    .backend
+                    .open(path, &open_options)
+                    .map_err(|_| __WASI_EIO)));
+            }
+            Kind::Buffer { buffer } => {
+                if o_flags & __WASI_O_DIRECTORY != 0 {
+                    return __WASI_ENOTDIR;
+                }
+                if o_flags & __WASI_O_EXCL != 0 {
+                    return __WASI_EEXIST;
+                }
+                open_flags |= Fd::READ;
+                if adjusted_rights & __WASI_RIGHT_FD_WRITE != 0 {
+                    open_flags |= Fd::WRITE;
+                    if o_flags & __WASI_O_TRUNC != 0 {
+                        open_flags |= Fd::TRUNCATE;
+                        buffer.clear();
+                    }
+                }
             }
-            Kind::Buffer { .. } => unimplemented!("wasi::path_open for Buffer type files"),
+            // sockets are never entries of a directory
+            Kind::Socket { .. } => return __WASI_ENOTSUP,
             Kind::Dir { .. } | Kind::Root { .. } => {
                 // TODO: adjust these to be correct
                 if o_flags & __WASI_O_EXCL != 0 {
@@ -1784,16 +2119,18 @@ pub fn path_open(
                     }
                 }
             }
-            Kind::Symlink {
-                base_po_dir,
-                path_to_symlink,
-                relative_path,
-            } => {
-                // I think this should return an error (because symlinks should be resolved away by the path traversal)
-                // TODO: investigate this
-                unimplemented!("SYMLINKS IN PATH_OPEN");
+            Kind::Symlink { .. } => {
+                // the path traversal resolves symlinks away unless we were told not to
+                // follow them, in which case opening the symlink itself is an error
+                debug!("Not following symlink at {}", path_string);
+                return __WASI_ELOOP;
             }
         }
+        if let Kind::Buffer { buffer } = &state.fs.inodes[inode].kind {
+            // the buffer may have been truncated
+            let size = buffer.len() as u64;
+            state.fs.inodes[inode].stat.st_size = size;
+        }
         inode
     } else {
         // less-happy path, we have to try to create the file
@@ -1811,47 +2148,56 @@ pub fn path_open(
                 dirflags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0
             ));
             let new_file_host_path = match &state.fs.inodes[parent_inode].kind {
+                // files in in-memory directories are buffers
+                Kind::Dir {
+                    in_memory: true, ..
+                } => None,
                 Kind::Dir { path, .. } => {
                     let mut new_path = path.clone();
                     new_path.push(&new_entity_name);
-                    new_path
+                    Some(new_path)
                 }
                 Kind::Root { .. } => return __WASI_EACCES,
                 _ => return __WASI_EINVAL,
             };
-            // once we got the data we need from the parent, we lookup the host file
-            // todo: extra check that opening with write access is okay
-            let handle = {
-                let mut open_options = std::fs::OpenOptions::new();
-                let open_options = open_options
-                    .read(true)
-                    .append(fs_flags & __WASI_FDFLAG_APPEND != 0)
-                    // TODO: ensure these rights are actually valid given parent, etc.
-                    // write access is required for creating a file
-                    .write(true)
-                    .create_new(true);
-                open_flags |= Fd::READ | Fd::WRITE | Fd::CREATE | Fd::TRUNCATE;
-
-                Some(Box::new(HostFile::new(
-                    wasi_try!(open_options.open(&new_file_host_path).map_err(|e| {
-                        debug!("Error opening file {}", e);
-                        __WASI_EIO
-                    })),
-                    new_file_host_path.clone(),
-                    true,
-                    true,
-                    true,
-                )) as Box<dyn WasiFile>)
-            };
+            // check the limits before anything is created
+            wasi_try!(state.fs.quota.check_open_fds(state.fs.fd_map.len()));
+            wasi_try!(state.fs.quota.charge_inode());
+            open_flags |= Fd::READ | Fd::WRITE | Fd::CREATE | Fd::TRUNCATE;
+
+            let kind = if let Some(new_file_host_path) = new_file_host_path {
+                // once we got the data we need from the parent, we lookup the host file
+                // todo: extra check that opening with write access is okay
+                let handle = {
+                    let open_options = WasiOpenOptions {
+                        read: true,
+                        append: fs_flags & __WASI_FDFLAG_APPEND != 0,
+                        // TODO: ensure these rights are actually valid given parent, etc.
+                        // write access is required for creating a file
+                        write: true,
+                        create_new: true,
+                        ..WasiOpenOptions::default()
+                    };
+
+                    Some(wasi_try!(state
+                        .fs
+                        .backend
+                        .open(&new_file_host_path, &open_options)
+                        .map_err(|e| {
+                            debug!("Error opening file {:?}", e);
+                            __WASI_EIO
+                        })))
+                };
 
-            let new_inode = {
-                let kind = Kind::File {
+                Kind::File {
                     handle,
                     path: new_file_host_path,
                     fd: None,
-                };
-                wasi_try!(state.fs.create_inode(kind, false, new_entity_name.clone()))
+                }
+            } else {
+                Kind::Buffer { buffer: vec![] }
             };
+            let new_inode = wasi_try!(state.fs.create_inode(kind, false, new_entity_name.clone()));
 
             if let Kind::Dir {
                 ref mut entries, ..
@@ -1968,15 +2314,22 @@ pub fn path_remove_directory(
             .get_parent_inode_at_path(fd, std::path::Path::new(path_str), false));
 
     let host_path_to_remove = match &state.fs.inodes[inode].kind {
-        Kind::Dir { entries, path, .. } => {
+        Kind::Dir {
+            entries,
+            path,
+            in_memory,
+            ..
+        } => {
             if !entries.is_empty() {
                 return __WASI_ENOTEMPTY;
+            } else if *in_memory {
+                None
             } else {
-                if wasi_try!(std::fs::read_dir(path).ok(), __WASI_EIO).count() != 0 {
+                if !wasi_try!(state.fs.backend.read_dir(path).ok(), __WASI_EIO).is_empty() {
                     return __WASI_ENOTEMPTY;
                 }
+                Some(path.clone())
             }
-            path.clone()
         }
         Kind::Root { .. } => return __WASI_EACCES,
         _ => return __WASI_ENOTDIR,
@@ -1996,16 +2349,18 @@ pub fn path_remove_directory(
         ),
     }
 
-    if let Err(_) = std::fs::remove_dir(path_str) {
-        // reinsert to prevent FS from being in bad state
-        if let Kind::Dir {
-            ref mut entries, ..
-        } = &mut state.fs.inodes[parent_inode].kind
-        {
-            entries.insert(childs_name, inode);
+    if let Some(host_path) = host_path_to_remove {
+        if let Err(_) = state.fs.backend.remove_dir(&host_path) {
+            // reinsert to prevent FS from being in bad state
+            if let Kind::Dir {
+                ref mut entries, ..
+            } = &mut state.fs.inodes[parent_inode].kind
+            {
+                entries.insert(childs_name, inode);
+            }
+            // TODO: more intelligently return error value by inspecting returned error value
+            return __WASI_EIO;
         }
-        // TODO: more intelligently return error value by inspecting returned error value
-        return __WASI_EIO;
     }
 
     __WASI_ESUCCESS
@@ -2058,6 +2413,17 @@ pub fn path_rename(
     let (target_parent_inode, target_entry_name) =
         wasi_try!(state.fs.get_parent_inode_at_path(new_fd, target_path, true));
 
+    // entries can't be moved between in-memory directories and the host
+    let is_in_memory = |kind: &Kind| match kind {
+        Kind::Dir { in_memory, .. } => *in_memory,
+        _ => false,
+    };
+    if is_in_memory(&state.fs.inodes[source_parent_inode].kind)
+        != is_in_memory(&state.fs.inodes[target_parent_inode].kind)
+    {
+        return __WASI_EXDEV;
+    }
+
     let host_adjusted_target_path = match &state.fs.inodes[target_parent_inode].kind {
         Kind::Dir { entries, path, .. } => {
             if entries.contains_key(&target_entry_name) {
@@ -2070,18 +2436,33 @@ pub fn path_rename(
             out_path
         }
         Kind::Root { .. } => return __WASI_ENOTCAPABLE,
-        Kind::Symlink { .. } | Kind::File { .. } | Kind::Buffer { .. } => {
+        Kind::Symlink { .. } | Kind::File { .. } | Kind::Buffer { .. } | Kind::Socket { .. } => {
             unreachable!("Fatal internal logic error: parent of inode is not a directory")
         }
     };
     let source_entry = match &mut state.fs.inodes[source_parent_inode].kind {
         Kind::Dir { entries, .. } => wasi_try!(entries.remove(&source_entry_name), __WASI_EINVAL),
         Kind::Root { .. } => return __WASI_ENOTCAPABLE,
-        Kind::Symlink { .. } | Kind::File { .. } | Kind::Buffer { .. } => {
+        Kind::Symlink { .. } | Kind::File { .. } | Kind::Buffer { .. } | Kind::Socket { .. } => {
             unreachable!("Fatal internal logic error: parent of inode is not a directory")
         }
     };
 
+    // a directory can't be moved into itself
+    let mut ancestor = Some(target_parent_inode);
+    while let Some(cur_inode) = ancestor {
+        if cur_inode == source_entry {
+            if let Kind::Dir { entries, .. } = &mut state.fs.inodes[source_parent_inode].kind {
+                entries.insert(source_entry_name, source_entry);
+            }
+            return __WASI_EINVAL;
+        }
+        ancestor = match &state.fs.inodes[cur_inode].kind {
+            Kind::Dir { parent, .. } => *parent,
+            _ => None,
+        };
+    }
+
     match &mut state.fs.inodes[source_entry].kind {
         Kind::File {
             handle,
@@ -2092,8 +2473,11 @@ pub fn path_rename(
                 h.rename_file(&host_adjusted_target_path)
                     .map_err(|e| e.into_wasi_err())
             } else {
-                let out =
-                    std::fs::rename(&path, &host_adjusted_target_path).map_err(|_| __WASI_EIO);
+                let out = state
+                    .fs
+                    .backend
+                    .rename(&path, &host_adjusted_target_path)
+                    .map_err(|_| __WASI_EIO);
                 *path = host_adjusted_target_path;
                 out
             };
@@ -2105,12 +2489,19 @@ pub fn path_rename(
                 }
             }
         }
+        Kind::Dir {
+            in_memory: true,
+            parent,
+            ..
+        } => *parent = Some(target_parent_inode),
         Kind::Dir { path, .. } => unimplemented!("wasi::path_rename on Directories"),
         Kind::Buffer { .. } => {}
         Kind::Symlink { .. } => {}
+        Kind::Socket { .. } => unreachable!("Sockets are never entries of a directory"),
         Kind::Root { .. } => unreachable!("The root can not be moved"),
     }
 
+    state.fs.inodes[source_entry].name = target_entry_name.clone();
     if let Kind::Dir { entries, .. } = &mut state.fs.inodes[target_parent_inode].kind {
         let result = entries.insert(target_entry_name, source_entry);
         assert!(
@@ -2169,7 +2560,7 @@ pub fn path_symlink(
             }
         }
         Kind::Root { .. } => return __WASI_ENOTCAPABLE,
-        Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } => {
+        Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } | Kind::Socket { .. } => {
             unreachable!("get_parent_inode_at_path returned something other than a Dir or Root")
         }
     }
@@ -2186,6 +2577,7 @@ pub fn path_symlink(
         relative_path.to_string_lossy()
     );
 
+    wasi_try!(state.fs.quota.charge_inode());
     let kind = Kind::Symlink {
         base_po_dir: fd,
         path_to_symlink: std::path::PathBuf::from(new_path_str),
@@ -2262,22 +2654,23 @@ pub fn path_unlink_file(
                     // File is closed
                     // problem with the abstraction, we can't call unlink because there's no handle
                     // TODO: replace this code
-                    wasi_try!(std::fs::remove_file(path).map_err(|_| __WASI_EIO));
+                    wasi_try!(state.fs.backend.remove_file(path).map_err(|_| __WASI_EIO));
                 }
             }
             Kind::Dir { .. } | Kind::Root { .. } => return __WASI_EISDIR,
             Kind::Symlink { .. } => {
                 // TODO: actually delete real symlinks and do nothing for virtual symlinks
             }
-            _ => unimplemented!("wasi::path_unlink_file for Buffer"),
+            // in-memory files only need to be forgotten
+            Kind::Buffer { .. } => {}
+            Kind::Socket { .. } => unreachable!("Sockets are never entries of a directory"),
         }
         // TODO: test this on Windows and actually make it portable
         // make the file an orphan fd if the fd is still open
-        let fd_is_orphaned = if let Kind::File { handle, .. } = &state.fs.inodes[removed_inode].kind
-        {
-            handle.is_some()
-        } else {
-            false
+        let fd_is_orphaned = match &state.fs.inodes[removed_inode].kind {
+            Kind::File { handle, .. } => handle.is_some(),
+            Kind::Buffer { .. } => state.fs.fd_map.values().any(|fd| fd.inode == removed_inode),
+            _ => false,
         };
         let removed_inode_val = unsafe { state.fs.remove_inode(removed_inode) };
         assert!(
@@ -2296,8 +2689,15 @@ pub fn path_unlink_file(
     __WASI_ESUCCESS
 }
 
+/// How long `poll_oneoff` waits between checks of the fds it is polling
+const POLL_INTERVAL_NS: __wasi_timestamp_t = 1_000_000;
+
 /// ### `poll_oneoff()`
 /// Concurrently poll for a set of events
+///
+/// Blocks until at least one subscription is ready, then reports all the
+/// subscriptions that are ready.  Subscriptions with an invalid fd are
+/// reported right away with the error set.
 /// Inputs:
 /// - `const __wasi_subscription_t *in`
 ///     The events to subscribe to
@@ -2321,178 +2721,122 @@ pub fn poll_oneoff(
 
     let subscription_array = wasi_try!(in_.deref(memory, 0, nsubscriptions));
     let event_array = wasi_try!(out_.deref(memory, 0, nsubscriptions));
-    let mut events_seen = 0;
     let out_ptr = wasi_try!(nevents.deref(memory));
 
-    let mut fds = vec![];
-    let mut clock_subs = vec![];
-    let mut in_events = vec![];
-    let mut total_ns_slept = 0;
-
-    for sub in subscription_array.iter() {
-        let s: WasiSubscription = wasi_try!(sub.get().try_into());
-        let mut peb = PollEventBuilder::new();
-        let mut ns_to_sleep = 0;
-
-        let fd = match s.event_type {
-            EventType::Read(__wasi_subscription_fs_readwrite_t { fd }) => {
-                match fd {
-                    __WASI_STDIN_FILENO | __WASI_STDOUT_FILENO | __WASI_STDERR_FILENO => (),
-                    _ => {
-                        let fd_entry = wasi_try!(state.fs.get_fd(fd));
-                        if !has_rights(fd_entry.rights, __WASI_RIGHT_FD_READ) {
-                            return __WASI_EACCES;
-                        }
-                    }
-                }
-                in_events.push(peb.add(PollEvent::PollIn).build());
-                Some(fd)
-            }
-            EventType::Write(__wasi_subscription_fs_readwrite_t { fd }) => {
-                match fd {
-                    __WASI_STDIN_FILENO | __WASI_STDOUT_FILENO | __WASI_STDERR_FILENO => (),
-                    _ => {
-                        let fd_entry = wasi_try!(state.fs.get_fd(fd));
-
-                        if !has_rights(fd_entry.rights, __WASI_RIGHT_FD_WRITE) {
-                            return __WASI_EACCES;
-                        }
-                    }
-                }
-                in_events.push(peb.add(PollEvent::PollOut).build());
-                Some(fd)
-            }
+    let subscriptions = wasi_try!(subscription_array
+        .iter()
+        .map(|sub| WasiSubscription::try_from(sub.get()))
+        .collect::<Result<Vec<_>, __wasi_errno_t>>());
+    if subscriptions.is_empty() {
+        return __WASI_EINVAL;
+    }
+    let polls_fds = subscriptions.iter().any(|sub| match sub.event_type {
+        EventType::Clock(_) => false,
+        EventType::Read(_) | EventType::Write(_) => true,
+    });
+
+    // relative timeouts are measured from the start of the call
+    let mut deadlines = Vec::with_capacity(subscriptions.len());
+    for sub in subscriptions.iter() {
+        deadlines.push(match sub.event_type {
             EventType::Clock(clock_info) => {
-                if clock_info.clock_id == __WASI_CLOCK_REALTIME {
-                    // this is a hack
-                    // TODO: do this properly
-                    ns_to_sleep = clock_info.timeout;
-                    clock_subs.push(clock_info);
-                    None
+                if clock_info.flags & __WASI_SUBSCRIPTION_CLOCK_ABSTIME != 0 {
+                    Ok(clock_info.timeout)
                 } else {
-                    unimplemented!("Polling not implemented for clocks yet");
+                    clock_time(state, clock_info.clock_id, clock_info.precision)
+                        .map(|now| now.saturating_add(clock_info.timeout))
                 }
             }
-        };
+            EventType::Read(_) | EventType::Write(_) => Ok(0),
+        });
+    }
 
-        if let Some(fd) = fd {
-            let wasi_file_ref: &dyn WasiFile = match fd {
-                __WASI_STDERR_FILENO => wasi_try!(
-                    wasi_try!(state.fs.stderr().map_err(WasiFsError::into_wasi_err)).as_ref(),
-                    __WASI_EBADF
-                )
-                .as_ref(),
-                __WASI_STDIN_FILENO => wasi_try!(
-                    wasi_try!(state.fs.stdin().map_err(WasiFsError::into_wasi_err)).as_ref(),
-                    __WASI_EBADF
-                )
-                .as_ref(),
-                __WASI_STDOUT_FILENO => wasi_try!(
-                    wasi_try!(state.fs.stdout().map_err(WasiFsError::into_wasi_err)).as_ref(),
-                    __WASI_EBADF
-                )
-                .as_ref(),
-                _ => {
-                    let fd_entry = wasi_try!(state.fs.get_fd(fd));
-                    let inode = fd_entry.inode;
-                    if !has_rights(fd_entry.rights, __WASI_RIGHT_POLL_FD_READWRITE) {
-                        return __WASI_EACCES;
+    loop {
+        let mut events = vec![];
+        let mut next_deadline_ns: Option<__wasi_timestamp_t> = None;
+
+        for (sub, deadline) in subscriptions.iter().zip(deadlines.iter()) {
+            let (error, nbytes, flags) = match sub.event_type {
+                EventType::Clock(clock_info) => {
+                    let now = deadline.and_then(|deadline| {
+                        clock_time(state, clock_info.clock_id, clock_info.precision)
+                            .map(|now| (now, deadline))
+                    });
+                    match now {
+                        Ok((now, deadline)) if now < deadline => {
+                            let remaining = deadline - now;
+                            next_deadline_ns =
+                                Some(next_deadline_ns.map_or(remaining, |ns| ns.min(remaining)));
+                            continue;
+                        }
+                        Ok(_) => (__WASI_ESUCCESS, 0, 0),
+                        Err(err) => (err, 0, 0),
                     }
-
-                    match &state.fs.inodes[inode].kind {
-                        Kind::File { handle, .. } => {
-                            if let Some(h) = handle {
-                                h.as_ref()
-                            } else {
-                                return __WASI_EBADF;
+                }
+                EventType::Read(__wasi_subscription_fs_readwrite_t { fd })
+                | EventType::Write(__wasi_subscription_fs_readwrite_t { fd }) => {
+                    let (interest, rights) = match sub.event_type {
+                        EventType::Read(_) => (PollEvent::PollIn, __WASI_RIGHT_FD_READ),
+                        _ => (PollEvent::PollOut, __WASI_RIGHT_FD_WRITE),
+                    };
+                    let interest = PollEventBuilder::new().add(interest).build();
+                    match fd_readiness(state, fd, interest, rights) {
+                        Ok((0, _)) => continue,
+                        Ok((ready, nbytes)) => {
+                            let mut error = __WASI_ESUCCESS;
+                            let mut flags = 0;
+                            for event in iterate_poll_events(ready) {
+                                match event {
+                                    PollEvent::PollError => error = __WASI_EIO,
+                                    PollEvent::PollHangUp => {
+                                        flags = __WASI_EVENT_FD_READWRITE_HANGUP
+                                    }
+                                    PollEvent::PollInvalid => error = __WASI_EBADF,
+                                    PollEvent::PollIn | PollEvent::PollOut => (),
+                                }
                             }
+                            (error, nbytes, flags)
                         }
-                        Kind::Dir { .. }
-                        | Kind::Root { .. }
-                        | Kind::Buffer { .. }
-                        | Kind::Symlink { .. } => {
-                            unimplemented!("polling read on non-files not yet supported")
-                        }
+                        Err(err) => (err, 0, 0),
                     }
                 }
             };
-            fds.push(wasi_file_ref);
-        } else {
-            let remaining_ns = ns_to_sleep as i64 - total_ns_slept as i64;
-            if remaining_ns > 0 {
-                debug!("Sleeping for {} nanoseconds", remaining_ns);
-                let duration = std::time::Duration::from_nanos(remaining_ns as u64);
-                std::thread::sleep(duration);
-                total_ns_slept += remaining_ns;
+            events.push(__wasi_event_t {
+                userdata: sub.user_data,
+                error,
+                type_: sub.event_type.raw_tag(),
+                u: __wasi_event_u {
+                    fd_readwrite: __wasi_event_fd_readwrite_t { nbytes, flags },
+                },
+            });
+        }
+
+        if !events.is_empty() {
+            for (cell, event) in event_array.iter().zip(events.iter()) {
+                cell.set(*event);
             }
+            out_ptr.set(events.len() as u32);
+            return __WASI_ESUCCESS;
         }
-    }
-    let mut seen_events = vec![Default::default(); in_events.len()];
-    wasi_try!(poll(
-        fds.as_slice(),
-        in_events.as_slice(),
-        seen_events.as_mut_slice()
-    )
-    .map_err(|e| e.into_wasi_err()));
-
-    for (i, seen_event) in seen_events.into_iter().enumerate() {
-        let mut flags = 0;
-        let mut error = __WASI_EAGAIN;
-        let mut bytes_available = 0;
-        let event_iter = iterate_poll_events(seen_event);
-        for event in event_iter {
-            match event {
-                PollEvent::PollError => error = __WASI_EIO,
-                PollEvent::PollHangUp => flags = __WASI_EVENT_FD_READWRITE_HANGUP,
-                PollEvent::PollInvalid => error = __WASI_EINVAL,
-                PollEvent::PollIn => {
-                    bytes_available =
-                        wasi_try!(fds[i].bytes_available().map_err(|e| e.into_wasi_err()));
-                    error = __WASI_ESUCCESS;
-                }
-                PollEvent::PollOut => {
-                    bytes_available =
-                        wasi_try!(fds[i].bytes_available().map_err(|e| e.into_wasi_err()));
-                    error = __WASI_ESUCCESS;
+
+        // nothing is ready yet: wait for the next deadline, checking the fds
+        // every now and then
+        match next_deadline_ns {
+            Some(ns) => {
+                let ns = if polls_fds {
+                    ns.min(POLL_INTERVAL_NS)
+                } else {
+                    ns
+                };
+                trace!("Sleeping for {} nanoseconds", ns);
+                match &state.clock {
+                    Some(clock) => clock.sleep(ns),
+                    None => std::thread::sleep(std::time::Duration::from_nanos(ns)),
                 }
             }
+            None => std::thread::sleep(std::time::Duration::from_nanos(POLL_INTERVAL_NS)),
         }
-        let event = __wasi_event_t {
-            userdata: subscription_array[i].get().userdata,
-            error,
-            type_: subscription_array[i].get().type_,
-            u: unsafe {
-                __wasi_event_u {
-                    fd_readwrite: __wasi_event_fd_readwrite_t {
-                        nbytes: bytes_available as u64,
-                        flags,
-                    },
-                }
-            },
-        };
-        event_array[events_seen].set(event);
-        events_seen += 1;
-    }
-    for clock_info in clock_subs {
-        let event = __wasi_event_t {
-            // TOOD: review userdata value
-            userdata: 0,
-            error: __WASI_ESUCCESS,
-            type_: __WASI_EVENTTYPE_CLOCK,
-            u: unsafe {
-                __wasi_event_u {
-                    fd_readwrite: __wasi_event_fd_readwrite_t {
-                        nbytes: 0,
-                        flags: 0,
-                    },
-                }
-            },
-        };
-        event_array[events_seen].set(event);
-        events_seen += 1;
     }
-    out_ptr.set(events_seen as u32);
-    __WASI_ESUCCESS
 }
 
 pub fn proc_exit(ctx: &mut Ctx, code: __wasi_exitcode_t) -> Result<Infallible, ExitCode> {
@@ -2513,15 +2857,16 @@ pub fn proc_raise(ctx: &mut Ctx, sig: __wasi_signal_t) -> __wasi_errno_t {
 ///     The number of bytes that will be written
 pub fn random_get(ctx: &mut Ctx, buf: WasmPtr<u8, Array>, buf_len: u32) -> __wasi_errno_t {
     debug!("wasi::random_get buf_len: {}", buf_len);
-    let memory = ctx.memory(0);
+    let (memory, state) = get_memory_and_wasi_state(ctx, 0);
 
     let buf = wasi_try!(buf.deref(memory, 0, buf_len));
+    let u8_buffer = unsafe { &mut *(buf as *const [_] as *mut [_] as *mut [u8]) };
 
-    let res = unsafe {
-        let u8_buffer = &mut *(buf as *const [_] as *mut [_] as *mut [u8]);
-        getrandom::getrandom(u8_buffer)
-    };
-    match res {
+    if let Some(random) = &state.random {
+        wasi_try!(random.fill(u8_buffer));
+        return __WASI_ESUCCESS;
+    }
+    match getrandom::getrandom(u8_buffer) {
         Ok(()) => __WASI_ESUCCESS,
         Err(_) => __WASI_EIO,
     }
@@ -2535,6 +2880,25 @@ pub fn sched_yield(ctx: &mut Ctx) -> __wasi_errno_t {
     __WASI_ESUCCESS
 }
 
+/// ### `sock_recv()`
+/// Receive a message from a socket
+/// Inputs:
+/// - `__wasi_fd_t sock`
+///     The socket to receive data from
+/// - `const __wasi_iovec_t *ri_data`
+///     Vectors where the received data will be stored
+/// - `u32 ri_data_len`
+///     The number of vectors in `ri_data`
+/// - `__wasi_riflags_t ri_flags`
+///     `__WASI_SOCK_RECV_PEEK` leaves the data in the socket,
+///     `__WASI_SOCK_RECV_WAITALL` keeps receiving until all vectors are
+///     filled or the socket has no more data.  Sockets never block, so
+///     `__WASI_EAGAIN` is returned if there is no data at all
+/// Output:
+/// - `u32 *ro_datalen`
+///     The number of bytes received
+/// - `__wasi_roflags_t *ro_flags`
+///     Message flags
 pub fn sock_recv(
     ctx: &mut Ctx,
     sock: __wasi_fd_t,
@@ -2544,9 +2908,83 @@ pub fn sock_recv(
     ro_datalen: WasmPtr<u32>,
     ro_flags: WasmPtr<__wasi_roflags_t>,
 ) -> __wasi_errno_t {
-    debug!("wasi::sock_recv");
-    unimplemented!("wasi::sock_recv")
+    debug!("wasi::sock_recv: sock={}, ri_flags={}", sock, ri_flags);
+    let (memory, state) = get_memory_and_wasi_state(ctx, 0);
+    let iovs_arr_cell = wasi_try!(ri_data.deref(memory, 0, ri_data_len));
+    let ro_datalen_cell = wasi_try!(ro_datalen.deref(memory));
+    let ro_flags_cell = wasi_try!(ro_flags.deref(memory));
+
+    let socket = wasi_try!(get_socket(
+        &mut state.fs,
+        &state.net,
+        sock,
+        __WASI_RIGHT_FD_READ
+    ));
+
+    // check every vector against the memory before receiving anything
+    let mut iovs = Vec::with_capacity(iovs_arr_cell.len());
+    for iov in iovs_arr_cell {
+        let iov_inner = iov.get();
+        iovs.push(wasi_try!(iov_inner.buf.deref(memory, 0, iov_inner.buf_len)));
+    }
+    let total_len = std::cmp::min(
+        iovs.iter().map(|cells| cells.len()).sum::<usize>(),
+        u32::max_value() as usize,
+    );
+
+    // receive through one bounded buffer so that peeking sees the data from
+    // the start for every vector
+    let peek = ri_flags & __WASI_SOCK_RECV_PEEK != 0;
+    let wait_all = ri_flags & __WASI_SOCK_RECV_WAITALL != 0 && !peek;
+    let mut buffer = vec![0; std::cmp::min(total_len, MAX_SOCK_RECV_CHUNK)];
+    let mut received = 0;
+    loop {
+        let amount = std::cmp::min(total_len - received, buffer.len());
+        if amount == 0 {
+            break;
+        }
+        let result = if peek {
+            socket.peek(&mut buffer[..amount])
+        } else {
+            socket
+                .read(&mut buffer[..amount])
+                .map_err(WasiFsError::from)
+        };
+        let n = match result {
+            Ok(n) => n,
+            // sockets never block, so even with `__WASI_SOCK_RECV_WAITALL`
+            // the data received so far is returned
+            Err(WasiFsError::WouldBlock) if received > 0 => break,
+            Err(e) => return e.into_wasi_err(),
+        };
+        write_to_iovs(&iovs, received, &buffer[..n]);
+        received += n;
+        if n == 0 || !wait_all {
+            break;
+        }
+    }
+
+    ro_datalen_cell.set(received as u32);
+    // stream sockets never truncate messages
+    ro_flags_cell.set(0);
+
+    __WASI_ESUCCESS
 }
+
+/// ### `sock_send()`
+/// Send a message on a socket
+/// Inputs:
+/// - `__wasi_fd_t sock`
+///     The socket to send data on
+/// - `const __wasi_ciovec_t *si_data`
+///     Vectors containing the data to send
+/// - `u32 si_data_len`
+///     The number of vectors in `si_data`
+/// - `__wasi_siflags_t si_flags`
+///     Message flags, currently unused
+/// Output:
+/// - `u32 *so_datalen`
+///     The number of bytes sent
 pub fn sock_send(
     ctx: &mut Ctx,
     sock: __wasi_fd_t,
@@ -2555,10 +2993,70 @@ pub fn sock_send(
     si_flags: __wasi_siflags_t,
     so_datalen: WasmPtr<u32>,
 ) -> __wasi_errno_t {
-    debug!("wasi::sock_send");
-    unimplemented!("wasi::sock_send")
+    debug!("wasi::sock_send: sock={}", sock);
+    let (memory, state) = get_memory_and_wasi_state(ctx, 0);
+    let iovs_arr_cell = wasi_try!(si_data.deref(memory, 0, si_data_len));
+    let so_datalen_cell = wasi_try!(so_datalen.deref(memory));
+
+    let socket = wasi_try!(get_socket(
+        &mut state.fs,
+        &state.net,
+        sock,
+        __WASI_RIGHT_FD_WRITE
+    ));
+
+    // check every vector against the memory before sending anything
+    let mut iovs = Vec::with_capacity(iovs_arr_cell.len());
+    for iov in iovs_arr_cell {
+        let iov_inner = iov.get();
+        iovs.push(wasi_try!(iov_inner.buf.deref(memory, 0, iov_inner.buf_len)));
+    }
+
+    let mut bytes_sent: u32 = 0;
+    for cells in iovs {
+        let buffer = cells.iter().map(|b_cell| b_cell.get()).collect::<Vec<u8>>();
+        let amount = match socket.write(&buffer) {
+            Ok(amount) => amount,
+            Err(e) if bytes_sent == 0 => return WasiFsError::from(e).into_wasi_err(),
+            // report what was sent before the error
+            Err(_) => break,
+        };
+        bytes_sent = bytes_sent.saturating_add(amount as u32);
+        if amount < buffer.len() {
+            break;
+        }
+    }
+
+    so_datalen_cell.set(bytes_sent);
+
+    __WASI_ESUCCESS
 }
+
+/// ### `sock_shutdown()`
+/// Shut down socket send and receive channels
+/// Inputs:
+/// - `__wasi_fd_t sock`
+///     The socket to shut down
+/// - `__wasi_sdflags_t how`
+///     `__WASI_SHUT_RD`, `__WASI_SHUT_WR` or both
 pub fn sock_shutdown(ctx: &mut Ctx, sock: __wasi_fd_t, how: __wasi_sdflags_t) -> __wasi_errno_t {
-    debug!("wasi::sock_shutdown");
-    unimplemented!("wasi::sock_shutdown")
+    debug!("wasi::sock_shutdown: sock={}, how={}", sock, how);
+    let (memory, state) = get_memory_and_wasi_state(ctx, 0);
+
+    let how = match how {
+        __WASI_SHUT_RD => std::net::Shutdown::Read,
+        __WASI_SHUT_WR => std::net::Shutdown::Write,
+        _ if how == __WASI_SHUT_RD | __WASI_SHUT_WR => std::net::Shutdown::Both,
+        _ => return __WASI_EINVAL,
+    };
+
+    let socket = wasi_try!(get_socket(
+        &mut state.fs,
+        &state.net,
+        sock,
+        __WASI_RIGHT_SOCK_SHUTDOWN
+    ));
+    wasi_try!(socket.shutdown(how).map_err(WasiFsError::into_wasi_err));
+
+    __WASI_ESUCCESS
 }
@@ -19,6 +19,7 @@ use std::io::Read;
 use std::path::PathBuf;
 use std::process::exit;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
 use structopt::{clap, StructOpt};
 
@@ -29,6 +30,7 @@ use wasmer_clif_backend::CraneliftCompiler;
 use wasmer_llvm_backend::{
     InkwellMemoryBuffer, InkwellModule, LLVMBackendConfig, LLVMCallbacks, LLVMCompiler,
 };
+use wasmer_middleware_common::profiling::{self, DebugLineMap, ProfileLayout};
 use wasmer_runtime::{
     cache::{Cache as BaseCache, FileSystemCache, WasmHash},
     Backend, Value, VERSION,
@@ -92,6 +94,18 @@ struct PrestandardFeatures {
     #[structopt(long = "enable-threads")]
     threads: bool,
 
+    /// Enable support for the reference types proposal.
+    #[structopt(long = "enable-reference-types")]
+    reference_types: bool,
+
+    /// Enable support for the bulk memory operations proposal.
+    #[structopt(long = "enable-bulk-memory")]
+    bulk_memory: bool,
+
+    /// Enable support for the multi-value proposal (singlepass and LLVM backends only).
+    #[structopt(long = "enable-multi-value")]
+    multi_value: bool,
+
     /// Enable support for all pre-standard proposals.
     #[structopt(long = "enable-all")]
     all: bool,
@@ -108,6 +122,15 @@ impl PrestandardFeatures {
         if self.threads || self.all {
             features.enable_threads();
         }
+        if self.reference_types || self.all {
+            features.enable_reference_types();
+        }
+        if self.bulk_memory || self.all {
+            features.enable_bulk_memory();
+        }
+        if self.multi_value || self.all {
+            features.enable_multi_value();
+        }
         features.enable_sign_extension();
         features.enable_sat_float_to_int();
         features
@@ -118,6 +141,9 @@ impl PrestandardFeatures {
         Features {
             simd: self.simd || self.all,
             threads: self.threads || self.all,
+            reference_types: self.reference_types || self.all,
+            bulk_memory: self.bulk_memory || self.all,
+            multi_value: self.multi_value || self.all,
         }
     }
 }
@@ -229,6 +255,12 @@ struct Run {
     #[structopt(long = "block-trace")]
     block_trace: bool,
 
+    /// Write an execution profile to the given file: an lcov tracefile for `.info` and
+    /// `.lcov` files, folded stacks for `.folded` files and a pprof profile otherwise.
+    /// Requires the singlepass backend.
+    #[structopt(long = "profile", parse(from_os_str))]
+    profile: Option<PathBuf>,
+
     /// The command name is a string that will override the first argument passed
     /// to the wasm program. This is used in wapm to provide nicer output in
     /// help commands and error messages of the running wasm program
@@ -267,6 +299,49 @@ struct Run {
     args: Vec<String>,
 }
 
+/// The profile requested with `--profile`.
+struct ProfileOutput {
+    path: PathBuf,
+    module_name: String,
+    layout: Arc<Mutex<ProfileLayout>>,
+}
+
+impl ProfileOutput {
+    /// Writes the profile of `instance`, mapping the code to source lines with the DWARF
+    /// information of `wasm_binary` when available.
+    fn write(
+        &self,
+        instance: &wasmer_runtime_core::Instance,
+        wasm_binary: &[u8],
+    ) -> Result<(), String> {
+        let profile = profiling::collect(instance, &self.layout.lock().unwrap());
+        let output = match self
+            .path
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("info") | Some("lcov") => {
+                let debug_line = DebugLineMap::from_module(wasm_binary);
+                profile
+                    .to_lcov(debug_line.as_ref(), &self.module_name)
+                    .into_bytes()
+            }
+            Some("folded") => profile.to_folded().into_bytes(),
+            _ => {
+                let debug_line = DebugLineMap::from_module(wasm_binary);
+                profile.to_pprof(debug_line.as_ref(), &self.module_name)
+            }
+        };
+        std::fs::write(&self.path, output).map_err(|err| {
+            format!(
+                "Can't write the profile to {}: {}",
+                self.path.as_os_str().to_string_lossy(),
+                err
+            )
+        })
+    }
+}
+
 impl Run {
     /// Used with the `invoke` argument
     fn parse_args(&self, module: &Module, fn_name: &str) -> Result<Vec<Value>, String> {
@@ -403,7 +478,8 @@ fn execute_wasi(
     env_vars: Vec<(&str, &str)>,
     module: wasmer_runtime_core::Module,
     mapped_dirs: Vec<(String, PathBuf)>,
-    _wasm_binary: &[u8],
+    wasm_binary: &[u8],
+    profile: Option<&ProfileOutput>,
 ) -> Result<(), String> {
     let name = if let Some(cn) = &options.command_name {
         cn.clone()
@@ -436,7 +512,6 @@ fn execute_wasi(
     let mut instance = module
         .instantiate(&import_object)
         .map_err(|e| format!("Can't instantiate WASI module: {:?}", e))?;
-
     let start: wasmer_runtime::Func<(), ()> =
         instance.func("_start").map_err(|e| format!("{:?}", e))?;
 
@@ -448,7 +523,7 @@ fn execute_wasi(
         unsafe {
             run_tiering(
                 module.info(),
-                &_wasm_binary,
+                &wasm_binary,
                 if let Some(ref path) = options.resume {
                     let mut f = File::open(path).unwrap();
                     let mut out: Vec<u8> = vec![];
@@ -473,7 +548,7 @@ fn execute_wasi(
                             (
                                 backend.to_string(),
                                 Box::new(move || {
-                                    get_compiler_by_backend(backend, &options).unwrap()
+                                    get_compiler_by_backend(backend, &options, None).unwrap()
                                 }),
                             )
                         },
@@ -508,8 +583,12 @@ fn execute_wasi(
             let invoke_result = instance
                 .dyn_func(invoke_fn)
                 .map_err(|e| format!("Invoke failed: {:?}", e))?
-                .call(&args)
-                .map_err(|e| format!("Calling invoke fn failed: {:?}", e))?;
+                .call(&args);
+            if let Some(profile) = profile {
+                profile.write(&instance, wasm_binary)?;
+            }
+            let invoke_result =
+                invoke_result.map_err(|e| format!("Calling invoke fn failed: {:?}", e))?;
             println!("{}({:?}) returned {:?}", invoke_fn, args, invoke_result);
             return Ok(());
         } else {
@@ -523,8 +602,12 @@ fn execute_wasi(
             }
         }
 
+        if let Some(profile) = profile {
+            profile.write(&instance, wasm_binary)?;
+        }
+
         if let Err(ref err) = result {
-            if let Some(error_code) = err.0.downcast_ref::<wasmer_wasi::ExitCode>() {
+            if let Some(error_code) = err.downcast_ref::<wasmer_wasi::ExitCode>() {
                 std::process::exit(error_code.code as i32)
             }
             return Err(format!("error: {:?}", err));
@@ -565,7 +648,30 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         return Err("Generating debug information is currently only available with the `cranelift` backend.".to_owned());
     }
 
-    let disable_cache = options.disable_cache;
+    if options.profile.is_some() {
+        #[cfg(feature = "backend-singlepass")]
+        let supported = options.backend == Backend::Singlepass;
+        #[cfg(not(feature = "backend-singlepass"))]
+        let supported = false;
+        if !supported {
+            return Err(
+                "Profiling is currently only available with the `singlepass` backend.".to_owned(),
+            );
+        }
+    }
+    let profile = options.profile.as_ref().map(|path| ProfileOutput {
+        path: path.clone(),
+        module_name: options
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        layout: Arc::new(Mutex::new(ProfileLayout::new())),
+    });
+
+    // Profiled modules are instrumented at compile time, so they can't be loaded from the cache.
+    let disable_cache = options.disable_cache || profile.is_some();
 
     let mapped_dirs = get_mapped_dirs(&options.mapped_dirs[..])?;
     #[cfg(feature = "wasi")]
@@ -667,13 +773,17 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         }
     }
 
-    let compiler: Box<dyn Compiler> = get_compiler_by_backend(options.backend, options)
-        .ok_or_else(|| {
-            format!(
-                "the requested backend, \"{}\", is not enabled",
-                options.backend.to_string()
-            )
-        })?;
+    let compiler: Box<dyn Compiler> = get_compiler_by_backend(
+        options.backend,
+        options,
+        profile.as_ref().map(|profile| Arc::clone(&profile.layout)),
+    )
+    .ok_or_else(|| {
+        format!(
+            "the requested backend, \"{}\", is not enabled",
+            options.backend.to_string()
+        )
+    })?;
 
     #[allow(unused_mut)]
     let mut backend_specific_config = None;
@@ -839,8 +949,7 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         let mut instance = module
             .instantiate(&import_object)
             .map_err(|e| format!("Can't instantiate emscripten module: {:?}", e))?;
-
-        wasmer_emscripten::run_emscripten_instance(
+        let result = wasmer_emscripten::run_emscripten_instance(
             &module,
             &mut instance,
             &mut emscripten_globals,
@@ -852,8 +961,11 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
             options.args.iter().map(|arg| arg.as_str()).collect(),
             options.em_entrypoint.clone(),
             mapped_dirs,
-        )
-        .map_err(|e| format!("{:?}", e))?;
+        );
+        if let Some(profile) = &profile {
+            profile.write(&instance, &wasm_binary)?;
+        }
+        result.map_err(|e| format!("{:?}", e))?;
     } else {
         #[cfg(feature = "wasi")]
         let wasi_version = wasmer_wasi::get_wasi_version(&module, true);
@@ -871,13 +983,13 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                 module,
                 mapped_dirs,
                 &wasm_binary,
+                profile.as_ref(),
             )?;
         } else {
             let import_object = wasmer_runtime_core::import::ImportObject::new();
             let instance = module
                 .instantiate(&import_object)
                 .map_err(|e| format!("Can't instantiate module: {:?}", e))?;
-
             let invoke_fn = match options.invoke.as_ref() {
                 Some(fun) => fun,
                 _ => "main",
@@ -902,8 +1014,7 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
             let result = instance
                 .dyn_func(&invoke_fn)
                 .map_err(|e| format!("{:?}", e))?
-                .call(&args)
-                .map_err(|e| format!("{:?}", e))?;
+                .call(&args);
 
             #[cfg(unix)]
             {
@@ -911,6 +1022,11 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
                     pop_code_version().unwrap();
                 }
             }
+
+            if let Some(profile) = &profile {
+                profile.write(&instance, &wasm_binary)?;
+            }
+            let result = result.map_err(|e| format!("{:?}", e))?;
             println!("{}({:?}) returned {:?}", invoke_fn, args, result);
         }
     }
@@ -1083,7 +1199,11 @@ fn validate(validate: Validate) {
     }
 }
 
-fn get_compiler_by_backend(backend: Backend, _opts: &Run) -> Option<Box<dyn Compiler>> {
+fn get_compiler_by_backend(
+    backend: Backend,
+    _opts: &Run,
+    _profile_layout: Option<Arc<Mutex<ProfileLayout>>>,
+) -> Option<Box<dyn Compiler>> {
     Some(match backend {
         #[cfg(feature = "backend-singlepass")]
         Backend::Singlepass => {
@@ -1092,6 +1212,7 @@ fn get_compiler_by_backend(backend: Backend, _opts: &Run) -> Option<Box<dyn Comp
             use wasmer_singlepass_backend::ModuleCodeGenerator as SinglePassMCG;
 
             let opts = _opts.clone();
+            let profile_layout = _profile_layout;
             let middlewares_gen = move || {
                 let mut middlewares = MiddlewareChain::new();
                 if opts.call_trace {
@@ -1102,6 +1223,10 @@ fn get_compiler_by_backend(backend: Backend, _opts: &Run) -> Option<Box<dyn Comp
                     use wasmer_middleware_common::block_trace::BlockTrace;
                     middlewares.push(BlockTrace::new());
                 }
+                if let Some(ref layout) = profile_layout {
+                    use wasmer_middleware_common::profiling::Profiling;
+                    middlewares.push(Profiling::new(Arc::clone(layout)));
+                }
                 middlewares
             };
 
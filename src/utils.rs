@@ -1,6 +1,9 @@
 //! Utility functions for the WebAssembly module
 
-use wasmer_runtime::{types::Type, Module, Value};
+use wasmer_runtime::{
+    types::{ExternRef, FuncRef, Type},
+    Module, Value,
+};
 use wasmer_runtime_core::{backend::SigRegistry, module::ExportIndex};
 
 /// Detect if a provided binary is a Wasm file
@@ -104,6 +107,27 @@ pub fn parse_args(
                                 )));
                                 None
                             }),
+                        Type::ExternRef => argument
+                            .parse::<u64>()
+                            .map(|v| Some(Value::ExternRef(ExternRef::new(v))))
+                            .unwrap_or_else(|_| {
+                                arg_error = Some(InvokeError::CouldNotParseArg(format!(
+                                    "Failed to parse `{:?}` as an `externref` handle",
+                                    argument
+                                )));
+                                None
+                            }),
+                        Type::FuncRef => {
+                            if argument == "null" {
+                                Some(Value::FuncRef(FuncRef::null()))
+                            } else {
+                                arg_error = Some(InvokeError::CouldNotParseArg(format!(
+                                    "Failed to parse `{:?}` as a `funcref`, only `null` is supported",
+                                    argument
+                                )));
+                                None
+                            }
+                        }
                     } {
                         accumulator.push(value);
 